@@ -13,23 +13,66 @@ use adb_client::server::ADBServer;
 use adb_client::server_device::ADBServerDevice;
 use adb_client::tcp::ADBTcpDevice;
 use adb_client::usb::{ADBDeviceInfo, ADBUSBDevice, find_all_connected_adb_devices};
+use adb_client::{AuthOptions, DirectoryTransferEntry, PullOptions, PushOptions, SyncDirOptions};
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use adb_termios::ADBTermios;
 
 use clap::Parser;
 use handlers::{handle_emulator_commands, handle_host_commands, handle_local_commands};
-use models::{DeviceCommands, LocalCommand, MainCommand, Opts};
+use models::{DeviceCommands, LocalCommand, MainCommand, Opts, PairCommand, TcpCommand};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Write, stdout};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use tabwriter::TabWriter;
 use utils::setup_logger;
 
 use crate::models::{ADBCliError, ADBCliResult};
 
+fn log_transfer_report(report: &[DirectoryTransferEntry]) {
+    let failures = report.iter().filter(|entry| entry.result.is_err()).count();
+    for entry in report {
+        if let Err(e) = &entry.result {
+            log::error!("{}: {e}", entry.remote_path);
+        }
+    }
+    log::info!(
+        "{} entries transferred, {failures} failed",
+        report.len() - failures
+    );
+}
+
+/// Install or uninstall packages, as requested by one of the package commands.
+fn run_install(device: &mut dyn ADBDeviceExt, command: DeviceCommands) -> ADBCliResult<()> {
+    match command {
+        DeviceCommands::Install { path, options } => {
+            log::info!("Starting installation of APK {}...", path.display());
+            device.install_with_options(&path, &options.into())?;
+        }
+        DeviceCommands::InstallMultiple { options, paths } => {
+            log::info!("Starting installation of {} APK files...", paths.len());
+            let paths: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+            device.install_multiple(&paths, &options.into())?;
+        }
+        DeviceCommands::InstallMultiPackage { options, paths } => {
+            log::info!("Starting installation of {} packages...", paths.len());
+            let paths: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+            // Each APK file is a package of its own
+            let packages: Vec<&[&Path]> = paths.iter().map(std::slice::from_ref).collect();
+            device.install_multi_package(&packages, &options.into())?;
+        }
+        DeviceCommands::Uninstall { package, user } => {
+            log::info!("Uninstalling the package {package}...");
+            device.uninstall(&package, user.as_deref())?;
+        }
+        other => unreachable!("{other:?} is not a package command"),
+    }
+
+    Ok(())
+}
+
 fn run_command(mut device: Box<dyn ADBDeviceExt>, command: DeviceCommands) -> ADBCliResult<()> {
     match command {
         DeviceCommands::Shell { commands } => {
@@ -52,11 +95,21 @@ fn run_command(mut device: Box<dyn ADBDeviceExt>, command: DeviceCommands) -> AD
             }
         }
         DeviceCommands::Pull {
+            preserve,
             source,
             destination,
         } => {
-            let mut output = File::create(Path::new(&destination))?;
-            device.pull(&source, &mut output)?;
+            let options = PullOptions {
+                preserve_permissions: preserve,
+                preserve_timestamps: preserve,
+            };
+            // Directory type bits of remote file mode
+            if device.stat(&source)?.file_perm & 0o170_000 == 0o040_000 {
+                let report = device.pull_dir(&source, &destination, options)?;
+                log_transfer_report(&report);
+            } else {
+                device.pull_with_options(&source, &destination, options)?;
+            }
             log::info!("Downloaded {source} as {destination}");
         }
         DeviceCommands::Stat { path } => {
@@ -76,10 +129,30 @@ fn run_command(mut device: Box<dyn ADBDeviceExt>, command: DeviceCommands) -> AD
             device.reboot(reboot_type.into())?;
         }
         DeviceCommands::Push { filename, path } => {
-            let mut input = File::open(Path::new(&filename))?;
-            device.push(&mut input, &path)?;
+            if Path::new(&filename).is_dir() {
+                let report = device.push_dir(&filename, &path)?;
+                log_transfer_report(&report);
+            } else {
+                let mut input = File::open(Path::new(&filename))?;
+                let options = PushOptions::from_metadata(&input.metadata()?);
+                device.push_with_options(&mut input, &path, options)?;
+            }
             log::info!("Uploaded {filename} to {path}");
         }
+        DeviceCommands::Sync {
+            delete,
+            dry_run,
+            local,
+            remote,
+        } => {
+            let options = SyncDirOptions { delete, dry_run };
+            for entry in device.sync_dir(&local, &remote, options)? {
+                match entry.result {
+                    Ok(()) => log::info!("{:?} {}", entry.action, entry.remote_path),
+                    Err(e) => log::error!("{:?} {}: {e}", entry.action, entry.remote_path),
+                }
+            }
+        }
         DeviceCommands::Root => {
             device.root()?;
             log::info!("Restarted adbd as root");
@@ -88,14 +161,10 @@ fn run_command(mut device: Box<dyn ADBDeviceExt>, command: DeviceCommands) -> AD
             let output = device.run_activity(&package, &activity)?;
             std::io::stdout().write_all(&output)?;
         }
-        DeviceCommands::Install { path, user } => {
-            log::info!("Starting installation of APK {}...", path.display());
-            device.install(&path, user.as_deref())?;
-        }
-        DeviceCommands::Uninstall { package, user } => {
-            log::info!("Uninstalling the package {package}...");
-            device.uninstall(&package, user.as_deref())?;
-        }
+        command @ (DeviceCommands::Install { .. }
+        | DeviceCommands::InstallMultiple { .. }
+        | DeviceCommands::InstallMultiPackage { .. }
+        | DeviceCommands::Uninstall { .. }) => run_install(device.as_mut(), command)?,
         DeviceCommands::Framebuffer { path } => {
             device.framebuffer(&path)?;
             log::info!("Successfully dumped framebuffer at path {path}");
@@ -111,6 +180,31 @@ fn run_command(mut device: Box<dyn ADBDeviceExt>, command: DeviceCommands) -> AD
     Ok(())
 }
 
+/// Connect to device over TCP, returning it along with command to run.
+fn connect_tcp(tcp_command: TcpCommand) -> ADBCliResult<(Box<dyn ADBDeviceExt>, DeviceCommands)> {
+    let device = ADBTcpDevice::new_with_options(
+        tcp_command.address,
+        tcp_command.path_to_private_key,
+        &tcp_command.auth.into(),
+        tcp_command.tls.into(),
+    )?;
+    Ok((device.boxed(), tcp_command.commands))
+}
+
+fn pair(pair_command: PairCommand) -> ADBCliResult<()> {
+    let device_guid = ADBTcpDevice::pair_with_tls_options(
+        pair_command.address,
+        &pair_command.code,
+        pair_command.path_to_private_key,
+        &pair_command.tls.into(),
+    )?;
+    log::info!(
+        "Paired device {device_guid} at {}, give `--device-id {device_guid}` when connecting to check its certificate",
+        pair_command.address
+    );
+    Ok(())
+}
+
 fn main() -> ExitCode {
     if let Err(err) = inner_main() {
         log::error!("{err}");
@@ -182,15 +276,18 @@ fn inner_main() -> ADBCliResult<()> {
                 return Ok(());
             }
 
+            let auth_options = AuthOptions::from(usb_command.auth);
             let device = match (usb_command.vendor_id, usb_command.product_id) {
-                (Some(vid), Some(pid)) => match usb_command.path_to_private_key {
-                    Some(pk) => ADBUSBDevice::new_with_custom_private_key(vid, pid, pk)?,
-                    None => ADBUSBDevice::new(vid, pid)?,
-                },
-                (None, None) => match usb_command.path_to_private_key {
-                    Some(pk) => ADBUSBDevice::autodetect_with_custom_private_key(pk)?,
-                    None => ADBUSBDevice::autodetect()?,
-                },
+                (Some(vid), Some(pid)) => ADBUSBDevice::new_with_options(
+                    vid,
+                    pid,
+                    usb_command.path_to_private_key,
+                    &auth_options,
+                )?,
+                (None, None) => ADBUSBDevice::autodetect_with_options(
+                    usb_command.path_to_private_key,
+                    &auth_options,
+                )?,
                 _ => {
                     return Err(ADBCliError::Standard(
                         "cannot specify flags --vendor-id without --product-id or vice versa"
@@ -205,13 +302,8 @@ fn inner_main() -> ADBCliResult<()> {
                 return Err(ADBCliError::Standard("no command specified".into()));
             }
         }
-        MainCommand::Tcp(tcp_command) => {
-            let device = match tcp_command.path_to_private_key {
-                Some(pk) => ADBTcpDevice::new_with_custom_private_key(tcp_command.address, pk)?,
-                None => ADBTcpDevice::new(tcp_command.address)?,
-            };
-            (device.boxed(), tcp_command.commands)
-        }
+        MainCommand::Tcp(tcp_command) => connect_tcp(tcp_command)?,
+        MainCommand::Pair(pair_command) => return pair(pair_command),
         MainCommand::Mdns => {
             let mut service = MDNSDiscoveryService::new()?;
 
@@ -60,6 +60,7 @@ impl From<adb_client::RustADBError> for ADBCliError {
             | RustADBError::RSAError(_)
             | RustADBError::TryFromSliceError(_)
             | RustADBError::RsaPkcs8Error(_)
+            | RustADBError::RsaPkcs1Error(_)
             | RustADBError::CertificateGenerationError(_)
             | RustADBError::TLSError(_)
             | RustADBError::PemCertError(_)
@@ -81,7 +82,14 @@ impl From<adb_client::RustADBError> for ADBCliError {
             | RustADBError::USBDeviceNotFound(_, _)
             | RustADBError::WrongFileExtension(_)
             | RustADBError::AddrParseError(_)
-            | RustADBError::DeviceBusy => Self::Standard(value),
+            | RustADBError::DeviceBusy
+            | RustADBError::Cancelled
+            | RustADBError::UnsupportedCompression(_)
+            | RustADBError::InstallFailed(_, _)
+            | RustADBError::PairingError(_)
+            | RustADBError::DeviceCertificateMismatch(_, _)
+            | RustADBError::Unauthorized(_)
+            | RustADBError::Timeout(_) => Self::Standard(value),
         }
     }
 }
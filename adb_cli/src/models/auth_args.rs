@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use adb_client::{AuthEvent, AuthOptions};
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub struct AuthArgs {
+    /// Seconds given to allow debugging on device when it does not trust our key, 0 waiting forever
+    #[clap(long = "auth-timeout", default_value_t = 10)]
+    auth_timeout: u64,
+    /// Only keep in memory the key generated when none exists at private key path, device asking to authorize it again next time
+    #[clap(long = "no-save-generated-key")]
+    no_save_generated_key: bool,
+}
+
+impl From<AuthArgs> for AuthOptions {
+    fn from(value: AuthArgs) -> Self {
+        let authorization_timeout =
+            (value.auth_timeout != 0).then(|| Duration::from_secs(value.auth_timeout));
+        Self::default()
+            .authorization_timeout(authorization_timeout)
+            .save_generated_key(!value.no_save_generated_key)
+            .observer(|event: &AuthEvent| {
+                if let AuthEvent::WaitingForUserAuthorization { fingerprint } = event {
+                    log::warn!(
+                        "Device is unauthorized: allow debugging on device screen, key fingerprint is {fingerprint}"
+                    );
+                }
+            })
+    }
+}
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
-use super::RebootTypeCommand;
+use super::{InstallArgs, RebootTypeCommand};
 
 #[derive(Parser, Debug)]
 pub enum DeviceCommands {
@@ -11,10 +11,27 @@ pub enum DeviceCommands {
         #[arg(trailing_var_arg = true)]
         commands: Vec<String>,
     },
-    /// Pull a file from device
-    Pull { source: String, destination: String },
-    /// Push a file on device
+    /// Pull a file or a directory from device
+    Pull {
+        /// Preserve file timestamp and mode
+        #[clap(short = 'a')]
+        preserve: bool,
+        source: String,
+        destination: String,
+    },
+    /// Push a file or a directory on device
     Push { filename: String, path: String },
+    /// Push only new or changed files of a local directory on device
+    Sync {
+        /// Delete remote files that do not exist locally
+        #[clap(long)]
+        delete: bool,
+        /// Only list actions that would be performed
+        #[clap(short = 'n', long)]
+        dry_run: bool,
+        local: String,
+        remote: String,
+    },
     /// Stat a file on device
     Stat { path: String },
     /// Stat a file on device with extended information
@@ -35,12 +52,27 @@ pub enum DeviceCommands {
     },
     /// Install an APK on device
     Install {
-        /// User id to install the package for
-        #[clap(short = 'u', long = "user")]
-        user: Option<String>,
+        #[clap(flatten)]
+        options: InstallArgs,
         /// Path to APK file. Extension must be ".apk"
         path: PathBuf,
     },
+    /// Install a single package made of several APKs on device, e.g. a base APK and its splits
+    InstallMultiple {
+        #[clap(flatten)]
+        options: InstallArgs,
+        /// Paths to APK files. Extensions must be ".apk"
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Atomically install several packages on device, one per APK file
+    InstallMultiPackage {
+        #[clap(flatten)]
+        options: InstallArgs,
+        /// Paths to APK files. Extensions must be ".apk"
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
     /// Uninstall a package from the device
     Uninstall {
         /// User id of the package to uninstall
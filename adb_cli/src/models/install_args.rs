@@ -0,0 +1,50 @@
+use adb_client::InstallOptions;
+use clap::Args;
+
+#[derive(Args, Debug)]
+#[expect(clippy::struct_excessive_bools)]
+pub struct InstallArgs {
+    /// User id to install the package for
+    #[clap(short = 'u', long = "user")]
+    user: Option<String>,
+    /// Replace existing application
+    #[clap(short = 'r')]
+    replace: bool,
+    /// Allow version code downgrade
+    #[clap(short = 'd')]
+    downgrade: bool,
+    /// Grant all runtime permissions
+    #[clap(short = 'g')]
+    grant_all_permissions: bool,
+    /// Allow test-only packages
+    #[clap(short = 't')]
+    test_only: bool,
+    /// Override ABI of package native code
+    #[clap(long = "abi")]
+    abi: Option<String>,
+    /// Install package as an instant app
+    #[clap(long = "instant")]
+    instant: bool,
+    /// Do not kill running application when updating it
+    #[clap(long = "dont-kill")]
+    dont_kill: bool,
+}
+
+impl From<InstallArgs> for InstallOptions {
+    fn from(value: InstallArgs) -> Self {
+        let mut options = Self::default()
+            .replace(value.replace)
+            .allow_downgrade(value.downgrade)
+            .grant_all_permissions(value.grant_all_permissions)
+            .allow_test_only(value.test_only)
+            .instant(value.instant)
+            .dont_kill(value.dont_kill);
+        if let Some(user) = value.user {
+            options = options.user(user);
+        }
+        if let Some(abi) = value.abi {
+            options = options.abi(abi);
+        }
+        options
+    }
+}
@@ -1,7 +1,9 @@
 mod adb_cli_error;
+mod auth_args;
 mod device;
 mod emu;
 mod host;
+mod install_args;
 mod local;
 mod opts;
 mod reboot_type;
@@ -9,11 +11,13 @@ mod tcp;
 mod usb;
 
 pub use adb_cli_error::{ADBCliError, ADBCliResult};
+pub use auth_args::AuthArgs;
 pub use device::DeviceCommands;
 pub use emu::{EmuCommand, EmulatorCommand};
 pub use host::{HostCommand, MdnsCommand};
+pub use install_args::InstallArgs;
 pub use local::{ForwardCommand, LocalCommand, LocalDeviceCommand, ReverseCommand};
 pub use opts::{MainCommand, Opts, ServerCommand};
 pub use reboot_type::RebootTypeCommand;
-pub use tcp::TcpCommand;
+pub use tcp::{PairCommand, TcpCommand};
 pub use usb::UsbCommand;
@@ -4,7 +4,7 @@ use clap::{Parser, Subcommand};
 
 use crate::utils;
 
-use super::{EmulatorCommand, HostCommand, LocalCommand, TcpCommand, UsbCommand};
+use super::{EmulatorCommand, HostCommand, LocalCommand, PairCommand, TcpCommand, UsbCommand};
 
 #[derive(Debug, Parser)]
 #[clap(about, long_version = utils::long_version(), author)]
@@ -27,6 +27,8 @@ pub enum MainCommand {
     Usb(UsbCommand),
     /// TCP device related commands
     Tcp(TcpCommand),
+    /// Pair with a TCP device using wireless debugging, without server
+    Pair(PairCommand),
     /// MDNS discovery related commands
     Mdns,
     /// Display various version information
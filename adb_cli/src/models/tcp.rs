@@ -1,8 +1,35 @@
-use clap::Parser;
+use adb_client::tcp::{KnownDevices, TlsOptions};
+use clap::{Args, Parser};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 
-use super::DeviceCommands;
+use super::{AuthArgs, DeviceCommands};
+
+#[derive(Args, Debug)]
+pub struct TlsArgs {
+    /// Path to a custom store of pinned device certificates
+    #[clap(long = "known-devices")]
+    pub known_devices: Option<PathBuf>,
+    /// Identity of device pinning its certificate, i.e. GUID given when pairing or mDNS instance name
+    #[clap(long = "device-id")]
+    pub device_id: Option<String>,
+    /// Log TLS secrets to the file named by SSLKEYLOGFILE, for debugging only
+    #[clap(long = "tls-key-log")]
+    pub key_log: bool,
+}
+
+impl From<TlsArgs> for TlsOptions {
+    fn from(value: TlsArgs) -> Self {
+        let mut options = Self::default().key_log(value.key_log);
+        if let Some(path) = value.known_devices {
+            options = options.known_devices(KnownDevices::new(path));
+        }
+        match value.device_id {
+            Some(device_id) => options.device_id(device_id),
+            None => options,
+        }
+    }
+}
 
 #[derive(Parser, Debug)]
 pub struct TcpCommand {
@@ -10,6 +37,23 @@ pub struct TcpCommand {
     /// Path to a custom private key to use for authentication
     #[clap(short = 'k', long = "private-key")]
     pub path_to_private_key: Option<PathBuf>,
+    #[clap(flatten)]
+    pub auth: AuthArgs,
+    #[clap(flatten)]
+    pub tls: TlsArgs,
     #[clap(subcommand)]
     pub commands: DeviceCommands,
 }
+
+#[derive(Parser, Debug)]
+pub struct PairCommand {
+    /// Pairing address displayed by device, which differs from its connection address
+    pub address: SocketAddr,
+    /// Pairing code displayed by device
+    pub code: String,
+    /// Path to a custom private key, trusted by device once paired
+    #[clap(short = 'k', long = "private-key")]
+    pub path_to_private_key: Option<PathBuf>,
+    #[clap(flatten)]
+    pub tls: TlsArgs,
+}
@@ -3,7 +3,7 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
-use super::DeviceCommands;
+use super::{AuthArgs, DeviceCommands};
 
 const fn parse_hex_id(id: &str) -> Result<u16, ParseIntError> {
     u16::from_str_radix(id, 16)
@@ -20,6 +20,8 @@ pub struct UsbCommand {
     /// Path to a custom private key to use for authentication
     #[clap(short = 'k', long = "private-key")]
     pub path_to_private_key: Option<PathBuf>,
+    #[clap(flatten)]
+    pub auth: AuthArgs,
     /// List all connected Android devices
     #[clap(short = 'l', long = "list")]
     pub list_devices: bool,
@@ -1,3 +1,4 @@
+use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
 
@@ -7,7 +8,11 @@ use {
     std::io::Cursor,
 };
 
-use crate::models::{ADBListItemType, AdbStatResponse, RemountInfo};
+use crate::models::{
+    ADBListItemType, AdbStatResponse, CancellationToken, DirectoryTransferEntry, InstallOptions,
+    ProgressObserver, ProgressReader, ProgressWriter, PullOptions, PushOptions, RemountInfo,
+    SyncActionEntry, SyncDirOptions,
+};
 use crate::{ADBStatExtendedResponse, RebootType, Result};
 
 /// Trait representing all features available on ADB devices.
@@ -20,9 +25,15 @@ pub trait ADBDeviceExt {
         stderr: Option<&mut dyn Write>,
     ) -> Result<Option<u8>>;
 
+    /// Runs `command` on the device, writing all of `input` to its standard input, and returns its whole output once it exits.
+    fn exec_with_input(&mut self, command: &str, input: &mut dyn Read) -> Result<Vec<u8>>;
+
     /// Starts an interactive shell session on the device.
     /// Input data is read from reader and write to writer.
-    fn shell(&mut self, reader: &mut dyn Read, writer: Box<dyn Write + Send>) -> Result<()>;
+    ///
+    /// Returns shell exit status once all of its output has been written, only if device reports it using shell v2.
+    fn shell(&mut self, reader: &mut dyn Read, writer: Box<dyn Write + Send>)
+    -> Result<Option<u8>>;
 
     /// Runs command on the device.
     /// Input data is read from reader and write to writer.
@@ -57,8 +68,114 @@ pub trait ADBDeviceExt {
     /// Pull the remote file pointed to by `source` and write its contents into `output`
     fn pull(&mut self, source: &dyn AsRef<str>, output: &mut dyn Write) -> Result<()>;
 
+    /// Pull the remote file pointed to by `source` into local file `destination`, applying remote metadata as requested by `options`.
+    fn pull_with_options(
+        &mut self,
+        source: &dyn AsRef<str>,
+        destination: &dyn AsRef<Path>,
+        options: PullOptions,
+    ) -> Result<()> {
+        let stat = if options.needs_metadata() {
+            Some(self.stat(source)?)
+        } else {
+            None
+        };
+
+        let mut output = File::create(destination)?;
+        self.pull(source, &mut output)?;
+
+        if let Some(stat) = stat {
+            options.apply(&output, &stat)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pull the remote file pointed to by `source` into `output`, reporting progress to `observer`.
+    ///
+    /// Transfer is aborted with [`RustADBError::Cancelled`](crate::RustADBError::Cancelled) as soon as `cancellation` is cancelled.
+    fn pull_with_progress(
+        &mut self,
+        source: &dyn AsRef<str>,
+        output: &mut dyn Write,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let total = self.stat(source)?.file_size;
+        let mut output = ProgressWriter::new(output, Some(total), observer, cancellation);
+        let result = self.pull(source, &mut output);
+        cancellation.map_result(result)
+    }
+
     /// Push `stream` to `path` on the device.
-    fn push(&mut self, stream: &mut dyn Read, path: &dyn AsRef<str>) -> Result<()>;
+    fn push(&mut self, stream: &mut dyn Read, path: &dyn AsRef<str>) -> Result<()> {
+        self.push_with_options(stream, path, PushOptions::default())
+    }
+
+    /// Push `stream` to `path` on the device, with remote file mode and modification time taken from `options`.
+    fn push_with_options(
+        &mut self,
+        stream: &mut dyn Read,
+        path: &dyn AsRef<str>,
+        options: PushOptions,
+    ) -> Result<()>;
+
+    /// Recursively push local directory `local` into remote directory `remote`.
+    ///
+    /// Remote directories are created as needed, symlinks are recreated on device and files keep their local mode and modification time.
+    /// Returns the outcome of each transferred entry, a failing entry does not stop the transfer.
+    fn push_dir(
+        &mut self,
+        local: &dyn AsRef<Path>,
+        remote: &dyn AsRef<str>,
+    ) -> Result<Vec<DirectoryTransferEntry>> {
+        crate::file_sync::push_dir(self, local.as_ref(), remote.as_ref())
+    }
+
+    /// Recursively pull remote directory `remote` into local directory `local`, applying remote metadata as requested by `options`.
+    ///
+    /// Returns the outcome of each transferred entry, a failing entry does not stop the transfer.
+    fn pull_dir(
+        &mut self,
+        remote: &dyn AsRef<str>,
+        local: &dyn AsRef<Path>,
+        options: PullOptions,
+    ) -> Result<Vec<DirectoryTransferEntry>> {
+        crate::file_sync::pull_dir(self, remote.as_ref(), local.as_ref(), options)
+    }
+
+    /// Bring remote directory `remote` up to date with local directory `local`, like `adb sync` does.
+    ///
+    /// Only files missing on device or whose size or modification time differ are pushed.
+    /// Remote entries missing locally are deleted if `options.delete` is set, and nothing is changed on device if `options.dry_run` is set.
+    /// Returns every planned action with its outcome.
+    fn sync_dir(
+        &mut self,
+        local: &dyn AsRef<Path>,
+        remote: &dyn AsRef<str>,
+        options: SyncDirOptions,
+    ) -> Result<Vec<SyncActionEntry>> {
+        crate::file_sync::sync_dir(self, local.as_ref(), remote.as_ref(), options)
+    }
+
+    /// Push `stream` to `path` on the device, reporting progress to `observer`.
+    ///
+    /// `total` is the size of `stream`, if known, for instance from file metadata.
+    /// Transfer is aborted with [`RustADBError::Cancelled`](crate::RustADBError::Cancelled) as soon as `cancellation` is cancelled,
+    /// in which case device discards the partially sent file.
+    fn push_with_progress(
+        &mut self,
+        stream: &mut dyn Read,
+        path: &dyn AsRef<str>,
+        options: PushOptions,
+        total: Option<u64>,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let mut stream = ProgressReader::new(stream, total, observer, cancellation);
+        let result = self.push_with_options(&mut stream, path, options);
+        cancellation.map_result(result)
+    }
 
     /// List the items in a directory on the device
     fn list(&mut self, path: &dyn AsRef<str>) -> Result<Vec<ADBListItemType>>;
@@ -94,7 +211,94 @@ pub trait ADBDeviceExt {
     }
 
     /// Install an APK pointed to by `apk_path` on device.
-    fn install(&mut self, apk_path: &dyn AsRef<Path>, user: Option<&str>) -> Result<()>;
+    fn install(&mut self, apk_path: &dyn AsRef<Path>, user: Option<&str>) -> Result<()> {
+        self.install_with_progress(apk_path, user, &mut |_, _| {}, &CancellationToken::new())
+    }
+
+    /// Install an APK pointed to by `apk_path` on device, reporting upload progress to `observer`.
+    ///
+    /// Installation is aborted with [`RustADBError::Cancelled`](crate::RustADBError::Cancelled) if `cancellation` is cancelled while APK is being uploaded.
+    fn install_with_progress(
+        &mut self,
+        apk_path: &dyn AsRef<Path>,
+        user: Option<&str>,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        let options = match user {
+            Some(user) => InstallOptions::default().user(user),
+            None => InstallOptions::default(),
+        };
+        crate::package_install::install_file(
+            self,
+            apk_path.as_ref(),
+            &options,
+            observer,
+            cancellation,
+        )
+    }
+
+    /// Install an APK pointed to by `apk_path` on device, using given `options`.
+    fn install_with_options(
+        &mut self,
+        apk_path: &dyn AsRef<Path>,
+        options: &InstallOptions,
+    ) -> Result<()> {
+        crate::package_install::install_file(
+            self,
+            apk_path.as_ref(),
+            options,
+            &mut |_, _| {},
+            &CancellationToken::new(),
+        )
+    }
+
+    /// Install an APK of `size` bytes read from `apk` on device, using given `options`.
+    fn install_from_reader(
+        &mut self,
+        apk: &mut dyn Read,
+        size: u64,
+        options: &InstallOptions,
+    ) -> Result<()> {
+        self.install_from_reader_with_progress(
+            apk,
+            size,
+            options,
+            &mut |_, _| {},
+            &CancellationToken::new(),
+        )
+    }
+
+    /// Install an APK of `size` bytes read from `apk` on device, using given `options` and reporting upload progress to `observer`.
+    ///
+    /// Devices without the `cmd` feature get APK pushed into `/data/local/tmp` and installed using `pm install`.
+    /// Installation is aborted with [`RustADBError::Cancelled`](crate::RustADBError::Cancelled) if `cancellation` is cancelled while APK is being uploaded.
+    fn install_from_reader_with_progress(
+        &mut self,
+        apk: &mut dyn Read,
+        size: u64,
+        options: &InstallOptions,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()>;
+
+    /// Install a single package made of several APKs on device, e.g. a base APK and its splits.
+    ///
+    /// APKs are uploaded into a package manager install session, which is abandoned on failure.
+    fn install_multiple(&mut self, apk_paths: &[&Path], options: &InstallOptions) -> Result<()> {
+        crate::package_install::install_multiple(self, apk_paths, options)
+    }
+
+    /// Atomically install several packages on device, each of them being made of one or more APKs.
+    ///
+    /// Either all packages are installed, or none of them.
+    fn install_multi_package(
+        &mut self,
+        packages: &[&[&Path]],
+        options: &InstallOptions,
+    ) -> Result<()> {
+        crate::package_install::install_multi_package(self, packages, options)
+    }
 
     /// Uninstall the package `package` from device.
     fn uninstall(&mut self, package: &dyn AsRef<str>, user: Option<&str>) -> Result<()>;
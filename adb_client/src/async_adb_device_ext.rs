@@ -0,0 +1,550 @@
+use std::{future::Future, io::ErrorKind, path::Path};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    ADBListItemType, AdbStatResponse, RebootType, Result, RustADBError,
+    file_sync::AsyncADBSyncClient,
+    models::{
+        ADBLocalCommand, HostFeatures, InstallOptions, PushOptions, RemountInfo, ShellChannel,
+    },
+    package_install::{check_install_output, pm_install_command, temporary_apk_path},
+    utils::{check_extension_is_apk, shell_quote},
+};
+
+/// Size of buffer used to read shell standard input, packet header included.
+const STDIN_BUFFER_SIZE: usize = 4096;
+/// Size of buffer used to forward output of interactive sessions.
+const OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Trait representing features available on ADB devices, from an asynchronous runtime.
+///
+/// Asynchronous counterpart of [`ADBDeviceExt`](crate::ADBDeviceExt): no call blocks the runtime, and interactive sessions do not spawn any thread.
+/// Every feature is built on [`open_service`](Self::open_service), file transfers being left uncompressed.
+///
+/// ```rust no_run
+/// use adb_client::{AsyncADBDeviceExt, server::AsyncADBServer};
+///
+/// # async fn run() -> adb_client::Result<()> {
+/// let server = AsyncADBServer::default();
+/// let mut device = server.get_device().await?;
+///
+/// let mut output = Vec::new();
+/// device.shell_command("df -h", Some(&mut output), None).await?;
+/// println!("{}", String::from_utf8_lossy(&output));
+/// # Ok(())
+/// # }
+/// ```
+pub trait AsyncADBDeviceExt: Send {
+    /// Stream connected to a service running on device.
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send;
+
+    /// Open a stream to raw `service` on device, such as `shell:ls` or `tcp:8080`.
+    fn open_service(&mut self, service: &str) -> impl Future<Output = Result<Self::Stream>> + Send;
+
+    /// Lists features supported by device.
+    fn host_features(&mut self) -> impl Future<Output = Result<Vec<HostFeatures>>> + Send;
+
+    /// Runs command in a shell on the device, and write its output and error streams into output.
+    ///
+    /// Exit status of command is returned if device supports shell v2.
+    fn shell_command(
+        &mut self,
+        command: &str,
+        mut stdout: Option<&mut (dyn AsyncWrite + Unpin + Send)>,
+        mut stderr: Option<&mut (dyn AsyncWrite + Unpin + Send)>,
+    ) -> impl Future<Output = Result<Option<u8>>> + Send {
+        async move {
+            if !supports(self, &HostFeatures::ShellV2).await {
+                // Shell v1: raw output, without exit status (for older ADB versions)
+                let mut stream = self
+                    .open_service(
+                        &ADBLocalCommand::ShellCommand(command.to_string(), Vec::new()).to_string(),
+                    )
+                    .await?;
+                match stdout {
+                    Some(stdout) => {
+                        tokio::io::copy(&mut stream, stdout).await?;
+                        stdout.flush().await?;
+                    }
+                    None => {
+                        tokio::io::copy(&mut stream, &mut tokio::io::sink()).await?;
+                    }
+                }
+                return Ok(None);
+            }
+
+            let mut stream = self
+                .open_service(
+                    &ADBLocalCommand::ShellCommand(command.to_string(), vec!["v2".to_string()])
+                        .to_string(),
+                )
+                .await?;
+
+            let mut exit_status = None;
+            while let Some((channel, payload)) = read_shell_packet(&mut stream).await? {
+                match channel {
+                    ShellChannel::Stdout => {
+                        if let Some(stdout) = stdout.as_mut() {
+                            stdout.write_all(&payload).await?;
+                        }
+                    }
+                    ShellChannel::Stderr => {
+                        // first stderr if existing, else a merged output into stdout
+                        if let Some(writer) = stderr.as_mut() {
+                            writer.write_all(&payload).await?;
+                        } else if let Some(writer) = stdout.as_mut() {
+                            writer.write_all(&payload).await?;
+                        }
+                    }
+                    ShellChannel::ExitStatus => exit_status = Some(parse_exit_status(&payload)?),
+                    c => log::debug!("ignoring shell packet received on channel {c:?}"),
+                }
+            }
+
+            if let Some(stdout) = stdout {
+                stdout.flush().await?;
+            }
+            if let Some(stderr) = stderr {
+                stderr.flush().await?;
+            }
+
+            Ok(exit_status)
+        }
+    }
+
+    /// Starts an interactive shell session on the device.
+    /// Input data is read from reader and write to writer, until remote shell exits.
+    fn shell(
+        &mut self,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            if !supports(self, &HostFeatures::ShellV2).await {
+                let stream = self
+                    .open_service(&ADBLocalCommand::Shell.to_string())
+                    .await?;
+                return bidirectional_session(stream, reader, writer, false).await;
+            }
+
+            let stream = self
+                .open_service(&ADBLocalCommand::ShellV2.to_string())
+                .await?;
+            let (mut output, mut input) = tokio::io::split(stream);
+
+            let forward_output = async {
+                while let Some((channel, payload)) = read_shell_packet(&mut output).await? {
+                    match channel {
+                        ShellChannel::Stdout | ShellChannel::Stderr => {
+                            writer.write_all(&payload).await?;
+                            writer.flush().await?;
+                        }
+                        ShellChannel::ExitStatus => {
+                            log::debug!(
+                                "shell exited with status {}",
+                                parse_exit_status(&payload)?
+                            );
+                        }
+                        c => log::debug!("ignoring shell packet received on channel {c:?}"),
+                    }
+                }
+                Ok(())
+            };
+
+            // Closing standard input makes remote shell exit
+            until_output_ends(forward_output, write_shell_stdin(reader, &mut input), true).await
+        }
+    }
+
+    /// Runs command on the device.
+    /// Input data is read from reader and write to writer, until command exits.
+    fn exec(
+        &mut self,
+        command: &str,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let stream = self
+                .open_service(&ADBLocalCommand::Exec(command.to_string()).to_string())
+                .await?;
+            bidirectional_session(stream, reader, writer, true).await
+        }
+    }
+
+    /// Runs `command` on the device, writing all of `input` to its standard input, and returns its whole output once it exits.
+    fn exec_with_input(
+        &mut self,
+        command: &str,
+        input: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send {
+        async move {
+            let mut stream = self
+                .open_service(&ADBLocalCommand::Exec(command.to_string()).to_string())
+                .await?;
+
+            // On failure, dropping stream closes it, which stops command on device side.
+            tokio::io::copy(input, &mut stream).await?;
+            stream.flush().await?;
+
+            let mut output = Vec::new();
+            stream.read_to_end(&mut output).await?;
+            Ok(output)
+        }
+    }
+
+    /// Display the stat information for a remote file using STAT protocol command.
+    fn stat(&mut self, remote_path: &str) -> impl Future<Output = Result<AdbStatResponse>> + Send {
+        async move {
+            let mut client = sync_client(self).await?;
+            let stat = client.stat(remote_path).await?;
+            client.quit().await?;
+            Ok(stat)
+        }
+    }
+
+    /// List the items in a directory on the device
+    fn list(&mut self, path: &str) -> impl Future<Output = Result<Vec<ADBListItemType>>> + Send {
+        async move {
+            let mut client = sync_client(self).await?;
+            let items = client.list(path).await?;
+            client.quit().await?;
+            Ok(items)
+        }
+    }
+
+    /// Pull the remote file pointed to by `source` and write its contents into `output`
+    fn pull(
+        &mut self,
+        source: &str,
+        output: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let mut client = sync_client(self).await?;
+            client.recv(source, output).await?;
+            client.quit().await?;
+            Ok(())
+        }
+    }
+
+    /// Push `stream` to `path` on the device.
+    fn push(
+        &mut self,
+        stream: &mut (dyn AsyncRead + Unpin + Send),
+        path: &str,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.push_with_options(stream, path, PushOptions::default())
+    }
+
+    /// Push `stream` to `path` on the device, with remote file mode and modification time taken from `options`.
+    fn push_with_options(
+        &mut self,
+        stream: &mut (dyn AsyncRead + Unpin + Send),
+        path: &str,
+        options: PushOptions,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let mtime = options.remote_mtime()?;
+            let mut client = sync_client(self).await?;
+            client.send(stream, path, options.mode, mtime).await?;
+            client.quit().await?;
+            Ok(())
+        }
+    }
+
+    /// Reboot the device using given reboot type
+    fn reboot(&mut self, reboot_type: RebootType) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let mut stream = self
+                .open_service(&ADBLocalCommand::Reboot(reboot_type).to_string())
+                .await?;
+            // Device may go away before closing the stream
+            if let Err(e) = stream.read_to_end(&mut Vec::new()).await {
+                log::debug!("connection ended while rebooting: {e}");
+            }
+            Ok(())
+        }
+    }
+
+    /// Remount the device partitions as read-write
+    fn remount(&mut self) -> impl Future<Output = Result<Vec<RemountInfo>>> + Send {
+        async move {
+            let mut stream = self
+                .open_service(&ADBLocalCommand::Remount.to_string())
+                .await?;
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).await?;
+            RemountInfo::from_str_response(String::from_utf8_lossy(&response).trim())
+        }
+    }
+
+    /// Restart adb daemon with root permissions
+    fn root(&mut self) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let mut stream = self
+                .open_service(&ADBLocalCommand::Root.to_string())
+                .await?;
+            // adbd restarts as root, connection may end before stream is closed
+            let mut response = Vec::new();
+            match stream.read_to_end(&mut response).await {
+                Ok(_) => log::debug!("{}", String::from_utf8_lossy(&response).trim()),
+                Err(e) => log::debug!("connection ended while restarting as root: {e}"),
+            }
+            Ok(())
+        }
+    }
+
+    /// Install an APK pointed to by `apk_path` on device.
+    fn install(
+        &mut self,
+        apk_path: &Path,
+        user: Option<&str>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let options = match user {
+            Some(user) => InstallOptions::default().user(user),
+            None => InstallOptions::default(),
+        };
+        async move { self.install_with_options(apk_path, &options).await }
+    }
+
+    /// Install an APK pointed to by `apk_path` on device, using given `options`.
+    fn install_with_options(
+        &mut self,
+        apk_path: &Path,
+        options: &InstallOptions,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            check_extension_is_apk(apk_path)?;
+            let mut apk = tokio::fs::File::open(apk_path).await?;
+            let size = apk.metadata().await?.len();
+
+            self.install_from_reader(&mut apk, size, options).await?;
+
+            log::info!("APK file {} successfully installed", apk_path.display());
+            Ok(())
+        }
+    }
+
+    /// Install an APK of `size` bytes read from `apk` on device, using given `options`.
+    ///
+    /// Devices without the `cmd` feature get APK pushed into `/data/local/tmp` and installed using `pm install`.
+    fn install_from_reader(
+        &mut self,
+        apk: &mut (dyn AsyncRead + Unpin + Send),
+        size: u64,
+        options: &InstallOptions,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            if !supports(self, &HostFeatures::Cmd).await {
+                return install_with_pm(self, apk, options).await;
+            }
+
+            let mut stream = self
+                .open_service(&ADBLocalCommand::Install(size, options.clone()).to_string())
+                .await?;
+
+            // On failure, dropping stream closes it, which aborts installation on device side.
+            tokio::io::copy(apk, &mut stream).await?;
+            stream.flush().await?;
+
+            // Status may be split over several reads, device closes stream once done
+            let mut output = Vec::new();
+            stream.read_to_end(&mut output).await?;
+            check_install_output(&output)
+        }
+    }
+
+    /// Uninstall the package `package` from device.
+    fn uninstall(
+        &mut self,
+        package: &str,
+        user: Option<&str>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let mut stream = self
+                .open_service(
+                    &ADBLocalCommand::Uninstall(package.to_string(), user.map(ToString::to_string))
+                        .to_string(),
+                )
+                .await?;
+            let mut output = Vec::new();
+            stream.read_to_end(&mut output).await?;
+
+            if output.starts_with(b"Success") {
+                log::info!("Package {package} successfully uninstalled");
+                Ok(())
+            } else {
+                Err(RustADBError::ADBRequestFailed(String::from_utf8(output)?))
+            }
+        }
+    }
+}
+
+/// Whether `device` supports `feature`, assuming it does not if its features cannot be listed.
+async fn supports<D: AsyncADBDeviceExt + ?Sized>(device: &mut D, feature: &HostFeatures) -> bool {
+    device
+        .host_features()
+        .await
+        .is_ok_and(|features| features.contains(feature))
+}
+
+/// Open a sync session on `device`, using version 2 requests if supported.
+async fn sync_client<D: AsyncADBDeviceExt + ?Sized>(
+    device: &mut D,
+) -> Result<AsyncADBSyncClient<D::Stream>> {
+    let features = device.host_features().await.unwrap_or_default();
+    let stream = device
+        .open_service(&ADBLocalCommand::Sync.to_string())
+        .await?;
+    Ok(AsyncADBSyncClient::new(stream, &features))
+}
+
+/// Install an APK read from `apk` by pushing it into a temporary file and running `pm install`.
+async fn install_with_pm<D: AsyncADBDeviceExt + ?Sized>(
+    device: &mut D,
+    apk: &mut (dyn AsyncRead + Unpin + Send),
+    options: &InstallOptions,
+) -> Result<()> {
+    let remote_path = temporary_apk_path();
+
+    log::debug!("device does not support cmd, installing from {remote_path}");
+    let result = match device.push(apk, &remote_path).await {
+        Ok(()) => {
+            let mut output = Vec::new();
+            device
+                .shell_command(
+                    &pm_install_command(options, &remote_path),
+                    Some(&mut output),
+                    None,
+                )
+                .await
+                .and_then(|_| check_install_output(&output))
+        }
+        Err(e) => Err(e),
+    };
+
+    // Temporary file is removed whatever the installation outcome
+    let remove_command = format!("rm -f {}", shell_quote(&remote_path));
+    if let Err(e) = device.shell_command(&remove_command, None, None).await {
+        log::warn!("cannot remove {remote_path}: {e}");
+    }
+
+    result
+}
+
+/// Forward `reader` to a raw `stream`, and `stream` output to `writer`.
+///
+/// Returns once device closes `stream`, or once `reader` is exhausted unless `wait_for_output` is set.
+async fn bidirectional_session<S: AsyncRead + AsyncWrite + Unpin + Send>(
+    stream: S,
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+    wait_for_output: bool,
+) -> Result<()> {
+    let (mut output, mut input) = tokio::io::split(stream);
+
+    let forward_output = async {
+        let mut buffer = vec![0; OUTPUT_BUFFER_SIZE];
+        loop {
+            let read = output.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            writer.write_all(&buffer[..read]).await?;
+            writer.flush().await?;
+        }
+    };
+
+    let forward_input = async {
+        match tokio::io::copy(reader, &mut input).await {
+            Ok(_) => Ok(()),
+            // Remote process has already exited
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+            Err(e) => Err(RustADBError::IOError(e)),
+        }
+    };
+
+    until_output_ends(forward_output, forward_input, wait_for_output).await
+}
+
+/// Run both `forward_output` and `forward_input` until the first one ends, or once input is exhausted unless `wait_for_output` is set.
+async fn until_output_ends(
+    forward_output: impl Future<Output = Result<()>>,
+    forward_input: impl Future<Output = Result<()>>,
+    wait_for_output: bool,
+) -> Result<()> {
+    tokio::pin!(forward_output, forward_input);
+
+    tokio::select! {
+        result = &mut forward_output => result,
+        result = &mut forward_input => {
+            result?;
+            if wait_for_output {
+                forward_output.await
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Read next shell v2 packet from `input`, or `None` once stream is over.
+async fn read_shell_packet<R: AsyncRead + Unpin>(
+    input: &mut R,
+) -> Result<Option<(ShellChannel, Vec<u8>)>> {
+    let mut header = [0; ShellChannel::HEADER_SIZE];
+    if let Err(e) = input.read_exact(&mut header).await {
+        return match e.kind() {
+            ErrorKind::UnexpectedEof => Ok(None),
+            _ => Err(RustADBError::IOError(e)),
+        };
+    }
+
+    let channel = ShellChannel::try_from(header[0])?;
+    let payload_size = u32::from_le_bytes(header[1..].try_into()?) as usize;
+
+    let mut payload = vec![0; payload_size];
+    input.read_exact(&mut payload).await?;
+
+    Ok(Some((channel, payload)))
+}
+
+fn parse_exit_status(payload: &[u8]) -> Result<u8> {
+    match payload {
+        [status] => Ok(*status),
+        _ => Err(RustADBError::ADBShellV2ParseError(format!(
+            "Spurious exit status packet with size of {} (should be 1)",
+            payload.len()
+        ))),
+    }
+}
+
+/// Forward `reader` content to device as shell v2 standard input packets, until it is exhausted.
+async fn write_shell_stdin<W: AsyncWrite + Unpin>(
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    writer: &mut W,
+) -> Result<()> {
+    let mut buffer = [0; STDIN_BUFFER_SIZE - ShellChannel::HEADER_SIZE];
+    loop {
+        let (packet, last) = match reader.read(&mut buffer).await {
+            // No more input, let remote process know
+            Ok(0) => (ShellChannel::CloseStdin.packet(&[])?, true),
+            Ok(size) => (ShellChannel::Stdin.packet(&buffer[..size])?, false),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(RustADBError::IOError(e)),
+        };
+
+        if let Err(e) = writer.write_all(&packet).await {
+            return match e.kind() {
+                // Remote shell has already exited
+                ErrorKind::BrokenPipe => Ok(()),
+                _ => Err(RustADBError::IOError(e)),
+            };
+        }
+
+        if last {
+            return Ok(());
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::models::TimedOut;
+
 /// Custom Result type thrown by this crate.
 pub type Result<T> = std::result::Result<T, RustADBError>;
 
@@ -8,7 +10,7 @@ pub type Result<T> = std::result::Result<T, RustADBError>;
 pub enum RustADBError {
     /// Indicates that an error occurred with I/O.
     #[error(transparent)]
-    IOError(#[from] std::io::Error),
+    IOError(std::io::Error),
     /// Indicates that an error occurred during ADB shell v2 parsing.
     #[error("ADB shell v2 parsing error: {0}")]
     ADBShellV2ParseError(String),
@@ -111,6 +113,9 @@ pub enum RustADBError {
     /// An error occurred with PKCS8 data
     #[error("error with pkcs8: {0}")]
     RsaPkcs8Error(#[from] rsa::pkcs8::Error),
+    /// An error occurred with PKCS1 data
+    #[error("error with pkcs1: {0}")]
+    RsaPkcs1Error(#[from] rsa::pkcs1::Error),
     /// Error during certificate generation
     #[error(transparent)]
     CertificateGenerationError(#[from] rcgen::Error),
@@ -143,9 +148,62 @@ pub enum RustADBError {
     /// An error occured while parsing a date
     #[error(transparent)]
     ParseDateError(#[from] chrono::ParseError),
+    /// Requested sync compression is not supported by device or by this build
+    #[error("Unsupported sync compression: {0}")]
+    UnsupportedCompression(crate::models::SyncCompression),
     /// An error occurred while parsing a stat extended response
     #[error("stat response error: {0}")]
     StatResponseError(String),
+    /// Transfer has been cancelled using its cancellation token
+    #[error("transfer cancelled")]
+    Cancelled,
+    /// Package manager refused to install a package, with failure reason and raw package manager output
+    #[error("installation failed ({0}): {1}")]
+    InstallFailed(crate::models::InstallFailureReason, String),
+    /// Wireless pairing with device failed
+    #[error("pairing failed: {0}")]
+    PairingError(String),
+    /// Device with given identity presented a certificate, with given fingerprint, different from the one pinned in known devices
+    #[error(
+        "device {0} presented certificate {1} instead of its pinned one: it may be spoofed, pair it again if it has been reset"
+    )]
+    DeviceCertificateMismatch(String, String),
+    /// Device did not trust our public key, with given fingerprint, before authorization timeout: user did not allow debugging
+    #[error("device is unauthorized: allow debugging with key {0} on device screen")]
+    Unauthorized(String),
+    /// A timeout configured using [`Timeouts`](crate::Timeouts) has been reached, during given phase
+    #[error("{0} timeout reached")]
+    Timeout(crate::models::TimeoutPhase),
+}
+
+impl RustADBError {
+    /// Whether this error is caused by a read or write that timed out.
+    pub(crate) fn is_timeout(&self) -> bool {
+        match self {
+            Self::IOError(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ),
+            #[cfg(feature = "usb")]
+            Self::UsbError(rusb::Error::Timeout) => true,
+            _ => false,
+        }
+    }
+}
+
+impl From<std::io::Error> for RustADBError {
+    fn from(err: std::io::Error) -> Self {
+        // Timeouts are reported through I/O operations, possibly wrapped by readers of this crate
+        let inner = err.get_ref();
+        if let Some(TimedOut(phase)) = inner.and_then(|e| e.downcast_ref::<TimedOut>()) {
+            return Self::Timeout(*phase);
+        }
+        if let Some(Self::Timeout(phase)) = inner.and_then(|e| e.downcast_ref::<Self>()) {
+            return Self::Timeout(*phase);
+        }
+
+        Self::IOError(err)
+    }
 }
 
 impl<T> From<std::sync::PoisonError<T>> for RustADBError {
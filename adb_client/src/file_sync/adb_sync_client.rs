@@ -0,0 +1,254 @@
+use std::io::{BufWriter, Read, Write};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    AdbStatResponse, BinaryDecodable, Result, RustADBError,
+    file_sync::{
+        SYNC_DATA_MAX,
+        sync_data::{SyncDataReader, SyncDataWriter},
+        sync_features::SyncFeatures,
+    },
+    models::{ADBListItem, ADBListItemType, HostFeatures, SyncCommand, SyncCompression},
+};
+
+/// Size of a version 1 directory entry, without its leading identifier.
+pub(super) const DENT_V1_SIZE: usize = 16;
+/// Size of a version 2 directory entry, without its leading identifier.
+pub(super) const DENT_V2_SIZE: usize = AdbStatResponse::V2_SIZE + 4;
+
+/// Client running sync requests over a `stream` already switched to sync mode.
+///
+/// Version 2 of each request is used when supported by device, falling back to version 1 otherwise.
+pub(crate) struct ADBSyncClient<S: Read + Write> {
+    stream: S,
+    features: SyncFeatures,
+}
+
+impl<S: Read + Write> ADBSyncClient<S> {
+    pub(crate) fn new(stream: S, features: &[HostFeatures]) -> Self {
+        Self {
+            stream,
+            features: SyncFeatures::from(features),
+        }
+    }
+
+    /// Stat `path` on the device, without following symlinks.
+    pub(crate) fn stat(&mut self, path: &str) -> Result<AdbStatResponse> {
+        if self.features.stat_v2 {
+            self.send_request(&SyncCommand::LStat2, path)?;
+            self.expect_id(&SyncCommand::LStat2)?;
+
+            let mut data = [0_u8; AdbStatResponse::V2_SIZE];
+            self.stream.read_exact(&mut data)?;
+            AdbStatResponse::decode_v2(&data)
+        } else {
+            self.send_request(&SyncCommand::Stat, path)?;
+            self.expect_id(&SyncCommand::Stat)?;
+
+            let mut data = [0_u8; AdbStatResponse::V1_SIZE];
+            self.stream.read_exact(&mut data)?;
+            AdbStatResponse::decode(&data)
+        }
+    }
+
+    /// List the entries of directory `path` on the device.
+    pub(crate) fn list(&mut self, path: &str) -> Result<Vec<ADBListItemType>> {
+        let (command, entry_size) = if self.features.ls_v2 {
+            (SyncCommand::List2, DENT_V2_SIZE)
+        } else {
+            (SyncCommand::List, DENT_V1_SIZE)
+        };
+        self.send_request(&command, path)?;
+
+        let mut list_items = Vec::new();
+        let mut data = vec![0_u8; entry_size];
+        loop {
+            let id = self.read_id()?;
+            // Listing ends with an empty entry
+            self.stream.read_exact(&mut data)?;
+
+            let Some((mode, size, time)) = decode_entry(id, &data)? else {
+                return Ok(list_items);
+            };
+
+            let name_len = LittleEndian::read_u32(&data[entry_size - 4..]) as usize;
+            let mut name_buf = vec![0_u8; name_len];
+            self.stream.read_exact(&mut name_buf)?;
+            let name = String::from_utf8(name_buf)?;
+
+            list_items.push(list_item(mode, size, time, name));
+        }
+    }
+
+    /// Send `input` content to `path` on the device, creating it with given `mode` and `mtime`.
+    pub(crate) fn send(
+        &mut self,
+        input: &mut dyn Read,
+        path: &str,
+        mode: u32,
+        mtime: u32,
+        compression: SyncCompression,
+    ) -> Result<()> {
+        let compression = self.features.resolve_compression(compression)?;
+
+        if self.features.sendrecv_v2 {
+            self.send_request(&SyncCommand::Send2, path)?;
+
+            let mut setup = Vec::with_capacity(12);
+            setup.extend_from_slice(SyncCommand::Send2.to_string().as_bytes());
+            setup.extend_from_slice(&mode.to_le_bytes());
+            setup.extend_from_slice(&compression.flag().to_le_bytes());
+            self.stream.write_all(&setup)?;
+        } else {
+            // Append the permission flags to the filename
+            self.send_request(&SyncCommand::Send, &format!("{path},0{mode:o}"))?;
+        }
+
+        let mut encoder = compression.encoder(BufWriter::with_capacity(
+            SYNC_DATA_MAX,
+            SyncDataWriter::new(&mut self.stream),
+        ))?;
+        std::io::copy(input, &mut encoder)?;
+        encoder.finish()?;
+
+        // Copy is finished, we can now notify as finished
+        self.send_packet(&SyncCommand::Done, mtime)?;
+
+        self.read_status()
+    }
+
+    /// Receive content of `path` on the device into `output`.
+    pub(crate) fn recv(
+        &mut self,
+        path: &str,
+        output: &mut dyn Write,
+        compression: SyncCompression,
+    ) -> Result<()> {
+        let compression = self.features.resolve_compression(compression)?;
+
+        if self.features.sendrecv_v2 {
+            self.send_request(&SyncCommand::Recv2, path)?;
+            self.send_packet(&SyncCommand::Recv2, compression.flag())?;
+        } else {
+            self.send_request(&SyncCommand::Recv, path)?;
+        }
+
+        let mut reader = SyncDataReader::new(&mut self.stream);
+        std::io::copy(&mut compression.decoder(&mut reader)?, output)?;
+
+        // Decompressor may stop reading right before final DONE packet
+        std::io::copy(&mut reader, &mut std::io::sink())?;
+
+        Ok(())
+    }
+
+    /// End synchronization session, giving back underlying stream.
+    pub(crate) fn quit(mut self) -> Result<S> {
+        self.send_packet(&SyncCommand::Quit, 0)?;
+        Ok(self.stream)
+    }
+
+    /// Send `command` request, with `path` as argument.
+    fn send_request(&mut self, command: &SyncCommand, path: &str) -> Result<()> {
+        self.stream.write_all(&request(command, path)?)?;
+
+        Ok(())
+    }
+
+    /// Send a `command` packet holding a single `arg`.
+    fn send_packet(&mut self, command: &SyncCommand, arg: u32) -> Result<()> {
+        self.stream.write_all(&packet(command, arg))?;
+
+        Ok(())
+    }
+
+    fn read_id(&mut self) -> Result<[u8; 4]> {
+        let mut id = [0_u8; 4];
+        self.stream.read_exact(&mut id)?;
+        Ok(id)
+    }
+
+    fn expect_id(&mut self, command: &SyncCommand) -> Result<()> {
+        let id = self.read_id()?;
+        if id == command.to_string().as_bytes() {
+            Ok(())
+        } else {
+            Err(unknown_response(id))
+        }
+    }
+
+    /// Read final status of a `send` request.
+    fn read_status(&mut self) -> Result<()> {
+        let id = self.read_id()?;
+        let mut length = [0_u8; 4];
+        self.stream.read_exact(&mut length)?;
+
+        match &id {
+            b"OKAY" => Ok(()),
+            b"FAIL" => {
+                // We can keep reading to get further details
+                let mut body = vec![0; LittleEndian::read_u32(&length) as usize];
+                self.stream.read_exact(&mut body)?;
+
+                Err(RustADBError::ADBRequestFailed(String::from_utf8(body)?))
+            }
+            _ => Err(unknown_response(id)),
+        }
+    }
+}
+
+/// Encode `command` request, with `path` as argument.
+pub(super) fn request(command: &SyncCommand, path: &str) -> Result<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(8 + path.len());
+    buffer.extend_from_slice(command.to_string().as_bytes());
+    buffer.extend_from_slice(&u32::try_from(path.len())?.to_le_bytes());
+    buffer.extend_from_slice(path.as_bytes());
+
+    Ok(buffer)
+}
+
+/// Encode a `command` packet holding a single `arg`.
+pub(super) fn packet(command: &SyncCommand, arg: u32) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(8);
+    buffer.extend_from_slice(command.to_string().as_bytes());
+    buffer.extend_from_slice(&arg.to_le_bytes());
+
+    buffer
+}
+
+/// Decode mode, size and modification time of a listing entry identified by `id`, or `None` once listing is over.
+pub(super) fn decode_entry(id: [u8; 4], data: &[u8]) -> Result<Option<(u32, u64, i64)>> {
+    match &id {
+        b"DENT" => Ok(Some((
+            LittleEndian::read_u32(&data[0..4]),
+            u64::from(LittleEndian::read_u32(&data[4..8])),
+            i64::from(LittleEndian::read_u32(&data[8..12])),
+        ))),
+        b"DNT2" => {
+            let stat = AdbStatResponse::decode_v2(&data[..AdbStatResponse::V2_SIZE])?;
+            Ok(Some((stat.file_perm, stat.file_size, stat.mod_time)))
+        }
+        b"DONE" => Ok(None),
+        _ => Err(unknown_response(id)),
+    }
+}
+
+/// Build listed item `name` from its decoded entry.
+pub(super) fn list_item(mode: u32, size: u64, time: i64, name: String) -> ADBListItemType {
+    // First 9 bits are the file permissions
+    let permissions = mode & 0b1_1111_1111;
+
+    let entry = ADBListItem {
+        name,
+        time,
+        permissions,
+        size,
+    };
+
+    ADBListItemType::from_mode_and_entry(mode, entry)
+}
+
+pub(super) fn unknown_response(id: [u8; 4]) -> RustADBError {
+    RustADBError::UnknownResponseType(format!("Unknown response {}", String::from_utf8_lossy(&id)))
+}
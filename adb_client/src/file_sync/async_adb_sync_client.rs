@@ -0,0 +1,260 @@
+use byteorder::{ByteOrder, LittleEndian};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    AdbStatResponse, BinaryDecodable, Result, RustADBError,
+    file_sync::{
+        SYNC_DATA_MAX,
+        adb_sync_client::{
+            DENT_V1_SIZE, DENT_V2_SIZE, decode_entry, list_item, packet, request, unknown_response,
+        },
+        sync_features::SyncFeatures,
+    },
+    models::{ADBListItemType, HostFeatures, SyncCommand, SyncCompression},
+};
+
+/// Asynchronous client running sync requests over a `stream` already switched to sync mode.
+///
+/// Behaves like [`ADBSyncClient`](super::ADBSyncClient), transfers being left uncompressed.
+pub(crate) struct AsyncADBSyncClient<S: AsyncRead + AsyncWrite + Unpin> {
+    stream: S,
+    features: SyncFeatures,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncADBSyncClient<S> {
+    pub(crate) fn new(stream: S, features: &[HostFeatures]) -> Self {
+        Self {
+            stream,
+            features: SyncFeatures::from(features),
+        }
+    }
+
+    /// Stat `path` on the device, without following symlinks.
+    pub(crate) async fn stat(&mut self, path: &str) -> Result<AdbStatResponse> {
+        if self.features.stat_v2 {
+            self.send_request(&SyncCommand::LStat2, path).await?;
+            self.expect_id(&SyncCommand::LStat2).await?;
+
+            let mut data = [0_u8; AdbStatResponse::V2_SIZE];
+            self.stream.read_exact(&mut data).await?;
+            AdbStatResponse::decode_v2(&data)
+        } else {
+            self.send_request(&SyncCommand::Stat, path).await?;
+            self.expect_id(&SyncCommand::Stat).await?;
+
+            let mut data = [0_u8; AdbStatResponse::V1_SIZE];
+            self.stream.read_exact(&mut data).await?;
+            AdbStatResponse::decode(&data)
+        }
+    }
+
+    /// List the entries of directory `path` on the device.
+    pub(crate) async fn list(&mut self, path: &str) -> Result<Vec<ADBListItemType>> {
+        let (command, entry_size) = if self.features.ls_v2 {
+            (SyncCommand::List2, DENT_V2_SIZE)
+        } else {
+            (SyncCommand::List, DENT_V1_SIZE)
+        };
+        self.send_request(&command, path).await?;
+
+        let mut list_items = Vec::new();
+        let mut data = vec![0_u8; entry_size];
+        loop {
+            let id = self.read_id().await?;
+            // Listing ends with an empty entry
+            self.stream.read_exact(&mut data).await?;
+
+            let Some((mode, size, time)) = decode_entry(id, &data)? else {
+                return Ok(list_items);
+            };
+
+            let name_len = LittleEndian::read_u32(&data[entry_size - 4..]) as usize;
+            let mut name_buf = vec![0_u8; name_len];
+            self.stream.read_exact(&mut name_buf).await?;
+            let name = String::from_utf8(name_buf)?;
+
+            list_items.push(list_item(mode, size, time, name));
+        }
+    }
+
+    /// Send `input` content to `path` on the device, creating it with given `mode` and `mtime`.
+    pub(crate) async fn send(
+        &mut self,
+        input: &mut (dyn AsyncRead + Unpin + Send),
+        path: &str,
+        mode: u32,
+        mtime: u32,
+    ) -> Result<()> {
+        if self.features.sendrecv_v2 {
+            self.send_request(&SyncCommand::Send2, path).await?;
+
+            let mut setup = Vec::with_capacity(12);
+            setup.extend_from_slice(SyncCommand::Send2.to_string().as_bytes());
+            setup.extend_from_slice(&mode.to_le_bytes());
+            setup.extend_from_slice(&SyncCompression::None.flag().to_le_bytes());
+            self.stream.write_all(&setup).await?;
+        } else {
+            // Append the permission flags to the filename
+            self.send_request(&SyncCommand::Send, &format!("{path},0{mode:o}"))
+                .await?;
+        }
+
+        let header_size = 8;
+        let mut buffer = vec![0_u8; header_size + SYNC_DATA_MAX];
+        buffer[..4].copy_from_slice(SyncCommand::Data.to_string().as_bytes());
+        loop {
+            let read = input.read(&mut buffer[header_size..]).await?;
+            if read == 0 {
+                break;
+            }
+
+            buffer[4..header_size].copy_from_slice(&u32::try_from(read)?.to_le_bytes());
+            self.stream.write_all(&buffer[..header_size + read]).await?;
+        }
+
+        // Copy is finished, we can now notify as finished
+        self.send_packet(&SyncCommand::Done, mtime).await?;
+
+        self.read_status().await
+    }
+
+    /// Receive content of `path` on the device into `output`.
+    pub(crate) async fn recv(
+        &mut self,
+        path: &str,
+        output: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> Result<()> {
+        if self.features.sendrecv_v2 {
+            self.send_request(&SyncCommand::Recv2, path).await?;
+            self.send_packet(&SyncCommand::Recv2, SyncCompression::None.flag())
+                .await?;
+        } else {
+            self.send_request(&SyncCommand::Recv, path).await?;
+        }
+
+        let mut buffer = vec![0_u8; SYNC_DATA_MAX];
+        loop {
+            let id = self.read_id().await?;
+            let length = self.stream.read_u32_le().await? as usize;
+
+            match &id {
+                b"DATA" => {
+                    let mut remaining = length;
+                    while remaining > 0 {
+                        let chunk = remaining.min(buffer.len());
+                        self.stream.read_exact(&mut buffer[..chunk]).await?;
+                        output.write_all(&buffer[..chunk]).await?;
+                        remaining -= chunk;
+                    }
+                }
+                b"DONE" => break,
+                b"FAIL" => {
+                    let mut body = vec![0; length];
+                    self.stream.read_exact(&mut body).await?;
+
+                    return Err(RustADBError::ADBRequestFailed(String::from_utf8(body)?));
+                }
+                _ => return Err(unknown_response(id)),
+            }
+        }
+
+        output.flush().await?;
+
+        Ok(())
+    }
+
+    /// End synchronization session, giving back underlying stream.
+    pub(crate) async fn quit(mut self) -> Result<S> {
+        self.send_packet(&SyncCommand::Quit, 0).await?;
+        Ok(self.stream)
+    }
+
+    async fn send_request(&mut self, command: &SyncCommand, path: &str) -> Result<()> {
+        self.stream.write_all(&request(command, path)?).await?;
+
+        Ok(())
+    }
+
+    async fn send_packet(&mut self, command: &SyncCommand, arg: u32) -> Result<()> {
+        self.stream.write_all(&packet(command, arg)).await?;
+
+        Ok(())
+    }
+
+    async fn read_id(&mut self) -> Result<[u8; 4]> {
+        let mut id = [0_u8; 4];
+        self.stream.read_exact(&mut id).await?;
+        Ok(id)
+    }
+
+    async fn expect_id(&mut self, command: &SyncCommand) -> Result<()> {
+        let id = self.read_id().await?;
+        if id == command.to_string().as_bytes() {
+            Ok(())
+        } else {
+            Err(unknown_response(id))
+        }
+    }
+
+    /// Read final status of a `send` request.
+    async fn read_status(&mut self) -> Result<()> {
+        let id = self.read_id().await?;
+        let length = self.stream.read_u32_le().await?;
+
+        match &id {
+            b"OKAY" => Ok(()),
+            b"FAIL" => {
+                // We can keep reading to get further details
+                let mut body = vec![0; length as usize];
+                self.stream.read_exact(&mut body).await?;
+
+                Err(RustADBError::ADBRequestFailed(String::from_utf8(body)?))
+            }
+            _ => Err(unknown_response(id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::AsyncADBSyncClient;
+    use crate::file_sync::SYNC_DATA_MAX;
+
+    #[tokio::test]
+    async fn test_send_and_recv() {
+        let content: Vec<u8> = (0..=u8::MAX).cycle().take(2 * SYNC_DATA_MAX + 17).collect();
+        let (client_stream, mut device_stream) = tokio::io::duplex(4 * SYNC_DATA_MAX);
+        let mut client = AsyncADBSyncClient::new(client_stream, &[]);
+
+        // Device answers both requests at once: its replies are buffered by the duplex stream
+        let mut replies = Vec::new();
+        replies.extend_from_slice(b"OKAY\0\0\0\0");
+        for chunk in content.chunks(SYNC_DATA_MAX) {
+            replies.extend_from_slice(b"DATA");
+            replies.extend_from_slice(&u32::try_from(chunk.len()).unwrap().to_le_bytes());
+            replies.extend_from_slice(chunk);
+        }
+        replies.extend_from_slice(b"DONE\0\0\0\0");
+        device_stream.write_all(&replies).await.unwrap();
+
+        client
+            .send(&mut content.as_slice(), "/data/local/tmp/file", 0o644, 0)
+            .await
+            .expect("cannot send");
+
+        let mut received = Vec::new();
+        client
+            .recv("/data/local/tmp/file", &mut received)
+            .await
+            .expect("cannot receive");
+        assert_eq!(received, content);
+
+        drop(client.quit().await.unwrap());
+        let mut requests = Vec::new();
+        device_stream.read_to_end(&mut requests).await.unwrap();
+        assert!(requests.starts_with(b"SEND\x19\0\0\0/data/local/tmp/file,0644DATA"));
+        assert!(requests.ends_with(b"RECV\x14\0\0\0/data/local/tmp/fileQUIT\0\0\0\0"));
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    ADBDeviceExt, Result, RustADBError,
+    file_sync::directory_transfer::{
+        LocalEntry, S_IFDIR, S_IFMT, SHELL_BATCH_SIZE, not_a_directory, push_entry,
+        read_remote_link, remote_join, run_on_remote_paths, walk_local_tree,
+    },
+    models::{ADBListItemType, SyncAction, SyncActionEntry, SyncDirOptions, TransferEntryKind},
+};
+
+/// Metadata of an entry found while walking the remote tree.
+struct RemoteEntry {
+    /// Kind of the entry, `None` for special files
+    kind: Option<TransferEntryKind>,
+    size: u64,
+    time: i64,
+}
+
+/// Seconds elapsed since unix epoch at `time`, negative if before.
+fn unix_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => i64::try_from(elapsed.as_secs()).unwrap_or(i64::MAX),
+        Err(e) => i64::try_from(e.duration().as_secs()).map_or(i64::MIN, |secs| -secs),
+    }
+}
+
+/// Index remote tree below `remote` by path, or return `None` if `remote` does not exist.
+fn walk_remote_tree<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    remote: &str,
+) -> Result<Option<HashMap<String, RemoteEntry>>> {
+    let stat = device.stat(&remote)?;
+    // Device answers with zeroed metadata when path does not exist
+    if stat.file_perm == 0 {
+        return Ok(None);
+    }
+    if stat.file_perm & S_IFMT != S_IFDIR {
+        return Err(not_a_directory(&remote));
+    }
+
+    let mut index = HashMap::new();
+    let mut pending = vec![remote.to_string()];
+    while let Some(remote_dir) = pending.pop() {
+        for item in device.list(&remote_dir)? {
+            let (kind, entry) = match &item {
+                ADBListItemType::File(entry) => (Some(TransferEntryKind::File), entry),
+                ADBListItemType::Directory(entry) => (Some(TransferEntryKind::Directory), entry),
+                ADBListItemType::Symlink(entry) => (Some(TransferEntryKind::Symlink), entry),
+                ADBListItemType::Fifo(entry)
+                | ADBListItemType::CharacterDevice(entry)
+                | ADBListItemType::BlockDevice(entry)
+                | ADBListItemType::Socket(entry)
+                | ADBListItemType::Other(entry) => (None, entry),
+            };
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+
+            let remote_path = remote_join(&remote_dir, &entry.name);
+            if kind == Some(TransferEntryKind::Directory) {
+                pending.push(remote_path.clone());
+            }
+            index.insert(
+                remote_path,
+                RemoteEntry {
+                    kind,
+                    size: entry.size,
+                    time: entry.time,
+                },
+            );
+        }
+    }
+
+    Ok(Some(index))
+}
+
+/// Whether local `entry` differs from `remote` one by size or modification time, or by target for symlinks.
+fn has_changed<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    entry: &LocalEntry,
+    remote: &RemoteEntry,
+) -> Result<bool> {
+    // `adbd` does not apply modification time to symlinks, only their target tells whether they changed
+    if entry.kind == TransferEntryKind::Symlink {
+        let target = std::fs::read_link(&entry.local_path)?;
+        return Ok(target.to_string_lossy() != read_remote_link(device, &entry.remote_path)?);
+    }
+
+    let metadata = std::fs::symlink_metadata(&entry.local_path)?;
+    Ok(metadata.len() != remote.size || unix_time(metadata.modified()?) != remote.time)
+}
+
+fn planned(action: SyncAction, entry: &LocalEntry) -> SyncActionEntry {
+    SyncActionEntry {
+        action,
+        local_path: Some(entry.local_path.clone()),
+        remote_path: entry.remote_path.clone(),
+        kind: entry.kind,
+        result: Ok(()),
+    }
+}
+
+/// Compute actions needed to bring remote directory `remote` up to date with local directory `local`.
+///
+/// Deletions come first, then directories creations and finally pushes. Local entries that cannot be read are reported as failed pushes.
+fn plan<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    local: &Path,
+    remote: &str,
+    options: SyncDirOptions,
+) -> Result<Vec<SyncActionEntry>> {
+    let mut failures = Vec::new();
+    let entries = walk_local_tree(local, remote, &mut failures);
+    let remote_index = walk_remote_tree(device, remote)?;
+    let remote_exists = remote_index.is_some();
+    let remote_index = remote_index.unwrap_or_default();
+
+    let mut report: Vec<SyncActionEntry> = failures
+        .into_iter()
+        .map(|failure| SyncActionEntry {
+            action: SyncAction::Push,
+            local_path: Some(failure.local_path),
+            remote_path: failure.remote_path,
+            kind: failure.kind,
+            result: failure.result,
+        })
+        .collect();
+
+    // Remote paths that must not be deleted
+    let mut kept: HashSet<String> = report
+        .iter()
+        .map(|entry| entry.remote_path.clone())
+        .collect();
+    // Content of unreadable local directories is unknown, nothing below them gets deleted
+    let unreadable: Vec<String> = kept.iter().map(|path| format!("{path}/")).collect();
+
+    let mut directories = Vec::new();
+    let mut pushes = Vec::new();
+    // First entry is the root directory itself
+    for entry in entries.iter().skip(usize::from(remote_exists)) {
+        let remote_entry = remote_index.get(&entry.remote_path);
+        let same_kind = remote_entry.is_some_and(|remote| remote.kind == Some(entry.kind));
+        // A remote entry of another kind is only replaced when deletions are allowed
+        if same_kind || !options.delete {
+            kept.insert(entry.remote_path.clone());
+        }
+
+        match (entry.kind, remote_entry) {
+            (TransferEntryKind::Directory, _) if same_kind => {}
+            (TransferEntryKind::Directory, _) => {
+                directories.push(planned(SyncAction::CreateDirectory, entry));
+            }
+            (_, Some(remote_entry)) if same_kind => {
+                match has_changed(device, entry, remote_entry) {
+                    Ok(false) => {}
+                    Ok(true) => pushes.push(planned(SyncAction::Push, entry)),
+                    Err(e) => report.push(SyncActionEntry {
+                        result: Err(e),
+                        ..planned(SyncAction::Push, entry)
+                    }),
+                }
+            }
+            _ => pushes.push(planned(SyncAction::Push, entry)),
+        }
+    }
+
+    let mut deletions: Vec<SyncActionEntry> = Vec::new();
+    if options.delete {
+        let mut extra: Vec<(&String, &RemoteEntry)> = remote_index
+            .iter()
+            .filter(|(path, _)| !kept.contains(path.as_str()))
+            .filter(|(path, _)| !unreadable.iter().any(|prefix| path.starts_with(prefix)))
+            .collect();
+        // Parents sort before their children
+        extra.sort_by_key(|(path, _)| path.as_str());
+
+        for (path, remote_entry) in extra {
+            // Deleting a directory already deletes its content
+            let covered = deletions.iter().any(|deleted| {
+                path.strip_prefix(deleted.remote_path.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+            });
+            if !covered {
+                deletions.push(SyncActionEntry {
+                    action: SyncAction::Delete,
+                    local_path: None,
+                    remote_path: path.clone(),
+                    kind: remote_entry.kind.unwrap_or(TransferEntryKind::File),
+                    result: Ok(()),
+                });
+            }
+        }
+    }
+
+    report.extend(deletions);
+    report.extend(directories);
+    report.extend(pushes);
+
+    Ok(report)
+}
+
+/// Run shell `command` on remote paths of all `entries`, by batches, storing each batch result into its entries.
+fn run_batched<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    command: &str,
+    entries: &mut [&mut SyncActionEntry],
+) {
+    for batch in entries.chunks_mut(SHELL_BATCH_SIZE) {
+        let paths: Vec<&str> = batch
+            .iter()
+            .map(|entry| entry.remote_path.as_str())
+            .collect();
+        let result = run_on_remote_paths(device, command, &paths);
+        for entry in batch {
+            entry.result = match &result {
+                Ok(()) => Ok(()),
+                Err(e) => Err(RustADBError::ADBRequestFailed(e.to_string())),
+            };
+        }
+    }
+}
+
+/// Bring remote directory `remote` up to date with local directory `local`.
+///
+/// Only entries missing on device, differing by size or modification time, or symlinks with another target are pushed.
+pub(crate) fn sync_dir<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    local: &Path,
+    remote: &str,
+    options: SyncDirOptions,
+) -> Result<Vec<SyncActionEntry>> {
+    if !std::fs::metadata(local)?.is_dir() {
+        return Err(not_a_directory(&local.display()));
+    }
+
+    let mut report = plan(device, local, remote, options)?;
+    if options.dry_run {
+        return Ok(report);
+    }
+
+    // Entries already failed during planning are not performed
+    let mut pending: Vec<&mut SyncActionEntry> = report
+        .iter_mut()
+        .filter(|entry| entry.result.is_ok())
+        .collect();
+
+    let (mut deletions, pending): (Vec<_>, Vec<_>) = pending
+        .drain(..)
+        .partition(|entry| entry.action == SyncAction::Delete);
+    run_batched(device, "rm -rf", &mut deletions);
+
+    let (mut directories, pushes): (Vec<_>, Vec<_>) = pending
+        .into_iter()
+        .partition(|entry| entry.action == SyncAction::CreateDirectory);
+    run_batched(device, "mkdir -p", &mut directories);
+
+    for entry in pushes {
+        let Some(local_path) = &entry.local_path else {
+            continue;
+        };
+        entry.result = push_entry(device, local_path, &entry.remote_path, entry.kind);
+        if let Err(e) = &entry.result {
+            log::warn!("cannot push {}: {e}", local_path.display());
+        }
+    }
+
+    Ok(report)
+}
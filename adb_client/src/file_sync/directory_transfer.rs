@@ -0,0 +1,353 @@
+use std::{
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ADBDeviceExt, Result, RustADBError,
+    models::{
+        ADBListItemType, DirectoryTransferEntry, PullOptions, PushOptions, TransferEntryKind,
+    },
+    utils::shell_quote,
+};
+
+/// File type bits of a symbolic link, as sent to device to have it create a symlink.
+pub(super) const S_IFLNK: u32 = 0o120_000;
+/// File type bits of a directory.
+pub(super) const S_IFDIR: u32 = 0o040_000;
+/// Mask of file type bits.
+pub(super) const S_IFMT: u32 = 0o170_000;
+/// Maximum number of paths given to a single shell command.
+pub(super) const SHELL_BATCH_SIZE: usize = 64;
+
+/// Join `name` to remote directory `directory`.
+pub(super) fn remote_join(directory: &str, name: &str) -> String {
+    format!("{}/{name}", directory.trim_end_matches('/'))
+}
+
+pub(super) fn not_a_directory(path: &dyn std::fmt::Display) -> RustADBError {
+    RustADBError::IOError(std::io::Error::new(
+        ErrorKind::NotADirectory,
+        format!("{path} is not a directory"),
+    ))
+}
+
+/// Entry found while walking a local tree.
+pub(super) struct LocalEntry {
+    pub(super) local_path: PathBuf,
+    pub(super) remote_path: String,
+    pub(super) kind: TransferEntryKind,
+}
+
+/// Walk local tree `local`, listing each entry with its remote counterpart below `remote`.
+///
+/// Directories are always listed before their content. Directories that cannot be read are reported in `failures`.
+pub(super) fn walk_local_tree(
+    local: &Path,
+    remote: &str,
+    failures: &mut Vec<DirectoryTransferEntry>,
+) -> Vec<LocalEntry> {
+    let mut entries = vec![LocalEntry {
+        local_path: local.to_path_buf(),
+        remote_path: remote.to_string(),
+        kind: TransferEntryKind::Directory,
+    }];
+
+    let mut pending = vec![(local.to_path_buf(), remote.to_string())];
+    while let Some((local_dir, remote_dir)) = pending.pop() {
+        let children = std::fs::read_dir(&local_dir).and_then(|read_dir| {
+            let mut children = read_dir
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            children.sort();
+            Ok(children)
+        });
+
+        let children = match children {
+            Ok(children) => children,
+            Err(e) => {
+                failures.push(DirectoryTransferEntry {
+                    local_path: local_dir,
+                    remote_path: remote_dir,
+                    kind: TransferEntryKind::Directory,
+                    result: Err(e.into()),
+                });
+                continue;
+            }
+        };
+
+        for local_path in children {
+            let Some(name) = local_path.file_name() else {
+                continue;
+            };
+            let remote_path = remote_join(&remote_dir, &name.to_string_lossy());
+
+            let kind = match std::fs::symlink_metadata(&local_path) {
+                Ok(metadata) if metadata.is_symlink() => TransferEntryKind::Symlink,
+                Ok(metadata) if metadata.is_dir() => {
+                    pending.push((local_path.clone(), remote_path.clone()));
+                    TransferEntryKind::Directory
+                }
+                Ok(_) => TransferEntryKind::File,
+                Err(e) => {
+                    failures.push(DirectoryTransferEntry {
+                        local_path,
+                        remote_path,
+                        kind: TransferEntryKind::File,
+                        result: Err(e.into()),
+                    });
+                    continue;
+                }
+            };
+
+            entries.push(LocalEntry {
+                local_path,
+                remote_path,
+                kind,
+            });
+        }
+    }
+
+    entries
+}
+
+/// Run shell `command` on the device with all `paths` as arguments, failing on non-zero exit status.
+pub(super) fn run_on_remote_paths<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    command: &str,
+    paths: &[&str],
+) -> Result<()> {
+    let arguments = paths
+        .iter()
+        .map(|path| shell_quote(path))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut output = Vec::new();
+    match device.shell_command(&format!("{command} {arguments}"), Some(&mut output), None)? {
+        Some(0) | None => Ok(()),
+        Some(_) => Err(RustADBError::ADBRequestFailed(
+            String::from_utf8_lossy(&output).trim_end().to_string(),
+        )),
+    }
+}
+
+/// Push local entry `local_path` of given `kind` to `remote_path`.
+pub(super) fn push_entry<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    local_path: &Path,
+    remote_path: &str,
+    kind: TransferEntryKind,
+) -> Result<()> {
+    match kind {
+        TransferEntryKind::File => {
+            let mut input = File::open(local_path)?;
+            let options = PushOptions::from_metadata(&input.metadata()?);
+            device.push_with_options(&mut input, &remote_path, options)
+        }
+        TransferEntryKind::Symlink => {
+            // Device creates a symlink pointing to sent content when mode says so
+            let target = std::fs::read_link(local_path)?;
+            let options = PushOptions {
+                mode: S_IFLNK | 0o777,
+                mtime: std::fs::symlink_metadata(local_path)?.modified().ok(),
+            };
+            device.push_with_options(
+                &mut target.to_string_lossy().as_bytes(),
+                &remote_path,
+                options,
+            )
+        }
+        // Directories are created beforehand
+        TransferEntryKind::Directory => Ok(()),
+    }
+}
+
+/// Push local directory `local` content into remote directory `remote`, creating it if needed.
+pub(crate) fn push_dir<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    local: &Path,
+    remote: &str,
+) -> Result<Vec<DirectoryTransferEntry>> {
+    if !std::fs::metadata(local)?.is_dir() {
+        return Err(not_a_directory(&local.display()));
+    }
+
+    let mut report = Vec::new();
+    let entries = walk_local_tree(local, remote, &mut report);
+
+    // Sync protocol only creates parent directories of sent files, empty ones have to be created explicitly
+    let directories: Vec<&LocalEntry> = entries
+        .iter()
+        .filter(|entry| entry.kind == TransferEntryKind::Directory)
+        .collect();
+    for batch in directories.chunks(SHELL_BATCH_SIZE) {
+        let paths: Vec<&str> = batch
+            .iter()
+            .map(|entry| entry.remote_path.as_str())
+            .collect();
+        let result = run_on_remote_paths(device, "mkdir -p", &paths);
+        for entry in batch {
+            report.push(DirectoryTransferEntry {
+                local_path: entry.local_path.clone(),
+                remote_path: entry.remote_path.clone(),
+                kind: TransferEntryKind::Directory,
+                result: match &result {
+                    Ok(()) => Ok(()),
+                    Err(e) => Err(RustADBError::ADBRequestFailed(e.to_string())),
+                },
+            });
+        }
+    }
+
+    for entry in entries
+        .into_iter()
+        .filter(|entry| entry.kind != TransferEntryKind::Directory)
+    {
+        let result = push_entry(device, &entry.local_path, &entry.remote_path, entry.kind);
+        if let Err(e) = &result {
+            log::warn!("cannot push {}: {e}", entry.local_path.display());
+        }
+        report.push(DirectoryTransferEntry {
+            local_path: entry.local_path,
+            remote_path: entry.remote_path,
+            kind: entry.kind,
+            result,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Target of remote symlink `remote_path`.
+pub(super) fn read_remote_link<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    remote_path: &str,
+) -> Result<String> {
+    let mut output = Vec::new();
+    device.shell_command(
+        &format!("readlink {}", shell_quote(remote_path)),
+        Some(&mut output),
+        None,
+    )?;
+    let target = String::from_utf8(output)?;
+    let target = target.trim_end_matches(['\r', '\n']);
+    if target.is_empty() {
+        return Err(RustADBError::ADBRequestFailed(format!(
+            "cannot read symlink {remote_path}"
+        )));
+    }
+
+    Ok(target.to_string())
+}
+
+/// Recreate remote symlink `remote_path` as local `local_path`.
+fn pull_symlink<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    remote_path: &str,
+    local_path: &Path,
+    options: PullOptions,
+) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let _ = options;
+        let target = read_remote_link(device, remote_path)?;
+
+        // Like regular files, an entry left by a previous pull gets replaced
+        match std::fs::symlink_metadata(local_path) {
+            Ok(metadata) if !metadata.is_dir() => std::fs::remove_file(local_path)?,
+            _ => {}
+        }
+        Ok(std::os::unix::fs::symlink(target, local_path)?)
+    }
+
+    // Symlinks cannot always be created, pull their target content instead
+    #[cfg(not(unix))]
+    device.pull_with_options(&remote_path, &local_path, options)
+}
+
+/// Pull remote directory `remote` content into local directory `local`, creating it if needed.
+pub(crate) fn pull_dir<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    remote: &str,
+    local: &Path,
+    options: PullOptions,
+) -> Result<Vec<DirectoryTransferEntry>> {
+    if device.stat(&remote)?.file_perm & S_IFMT != S_IFDIR {
+        return Err(not_a_directory(&remote));
+    }
+
+    let mut report = vec![DirectoryTransferEntry {
+        local_path: local.to_path_buf(),
+        remote_path: remote.to_string(),
+        kind: TransferEntryKind::Directory,
+        result: std::fs::create_dir_all(local).map_err(RustADBError::from),
+    }];
+    if report[0].result.is_err() {
+        return Ok(report);
+    }
+
+    let mut pending = vec![(remote.to_string(), local.to_path_buf())];
+    while let Some((remote_dir, local_dir)) = pending.pop() {
+        let mut items = match device.list(&remote_dir) {
+            Ok(items) => items,
+            Err(e) => {
+                report.push(DirectoryTransferEntry {
+                    local_path: local_dir,
+                    remote_path: remote_dir,
+                    kind: TransferEntryKind::Directory,
+                    result: Err(e),
+                });
+                continue;
+            }
+        };
+        items.sort();
+
+        for item in items {
+            let (kind, name) = match &item {
+                ADBListItemType::File(entry) => (TransferEntryKind::File, &entry.name),
+                ADBListItemType::Directory(entry) => (TransferEntryKind::Directory, &entry.name),
+                ADBListItemType::Symlink(entry) => (TransferEntryKind::Symlink, &entry.name),
+                other => {
+                    log::debug!("skipping special file {other}");
+                    continue;
+                }
+            };
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            let remote_path = remote_join(&remote_dir, name);
+            let local_path = local_dir.join(name);
+
+            let result = match kind {
+                TransferEntryKind::File => {
+                    device.pull_with_options(&remote_path, &local_path, options)
+                }
+                TransferEntryKind::Directory => {
+                    let result = std::fs::create_dir_all(&local_path).map_err(RustADBError::from);
+                    if result.is_ok() {
+                        pending.push((remote_path.clone(), local_path.clone()));
+                    }
+                    result
+                }
+                TransferEntryKind::Symlink => {
+                    pull_symlink(device, &remote_path, &local_path, options)
+                }
+            };
+
+            if let Err(e) = &result {
+                log::warn!("cannot pull {remote_path}: {e}");
+            }
+            report.push(DirectoryTransferEntry {
+                local_path,
+                remote_path,
+                kind,
+                result,
+            });
+        }
+    }
+
+    Ok(report)
+}
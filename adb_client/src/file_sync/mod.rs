@@ -0,0 +1,21 @@
+//! Client side of the file synchronization protocol, shared by all devices types.
+//!
+//! Version 2 requests are used whenever device advertises them.
+
+mod adb_sync_client;
+#[cfg(feature = "tokio")]
+mod async_adb_sync_client;
+mod directory_sync;
+mod directory_transfer;
+mod sync_compression_codec;
+mod sync_data;
+mod sync_features;
+
+pub(crate) use adb_sync_client::ADBSyncClient;
+#[cfg(feature = "tokio")]
+pub(crate) use async_adb_sync_client::AsyncADBSyncClient;
+pub(crate) use directory_sync::sync_dir;
+pub(crate) use directory_transfer::{pull_dir, push_dir};
+
+/// Maximum size of a `DATA` packet payload, as enforced by `adbd`.
+pub(crate) const SYNC_DATA_MAX: usize = 64 * 1024;
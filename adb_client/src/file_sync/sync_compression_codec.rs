@@ -0,0 +1,147 @@
+use std::io::{self, Read, Write};
+
+use crate::{Result, RustADBError, models::SyncCompression};
+
+/// Compressed stream writer, that must be finished once all data has been written.
+pub(crate) trait FinishWrite: Write {
+    /// Write trailing compressed data and flush underlying writer.
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// Writer forwarding data as is.
+struct Uncompressed<W: Write>(W);
+
+impl<W: Write> Write for Uncompressed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for Uncompressed<W> {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(feature = "brotli")]
+impl<W: Write> FinishWrite for brotli::CompressorWriter<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.into_inner().flush()
+    }
+}
+
+#[cfg(feature = "lz4")]
+impl<W: Write> FinishWrite for lz4_flex::frame::FrameEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        lz4_flex::frame::FrameEncoder::finish(*self)
+            .map_err(io::Error::other)?
+            .flush()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write> FinishWrite for zstd::stream::write::Encoder<'static, W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        zstd::stream::write::Encoder::finish(*self)?.flush()
+    }
+}
+
+/// Brotli quality and window size used by `adb` itself, favoring speed.
+#[cfg(feature = "brotli")]
+const BROTLI_QUALITY: u32 = 1;
+#[cfg(feature = "brotli")]
+const BROTLI_LG_WINDOW_SIZE: u32 = 22;
+#[cfg(feature = "brotli")]
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+impl SyncCompression {
+    /// Flag requesting this compression in `SND2` and `RCV2` requests.
+    pub(crate) const fn flag(self) -> u32 {
+        match self {
+            Self::Any | Self::None => 0,
+            Self::Brotli => 1,
+            Self::Lz4 => 2,
+            Self::Zstd => 4,
+        }
+    }
+
+    /// Wrap `output` to compress data written to it.
+    pub(crate) fn encoder<'a, W: Write + 'a>(self, output: W) -> Result<Box<dyn FinishWrite + 'a>> {
+        match self {
+            Self::None => Ok(Box::new(Uncompressed(output))),
+            #[cfg(feature = "brotli")]
+            Self::Brotli => Ok(Box::new(brotli::CompressorWriter::new(
+                output,
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_LG_WINDOW_SIZE,
+            ))),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Ok(Box::new(lz4_flex::frame::FrameEncoder::new(output))),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Ok(Box::new(zstd::stream::write::Encoder::new(
+                output,
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            )?)),
+            compression => Err(RustADBError::UnsupportedCompression(compression)),
+        }
+    }
+
+    /// Wrap `input` to decompress data read from it.
+    pub(crate) fn decoder<'a, R: Read + 'a>(self, input: R) -> Result<Box<dyn Read + 'a>> {
+        match self {
+            Self::None => Ok(Box::new(input)),
+            #[cfg(feature = "brotli")]
+            Self::Brotli => Ok(Box::new(brotli::Decompressor::new(
+                input,
+                BROTLI_BUFFER_SIZE,
+            ))),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Ok(Box::new(lz4_flex::frame::FrameDecoder::new(input))),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Ok(Box::new(zstd::stream::read::Decoder::new(input)?)),
+            compression => Err(RustADBError::UnsupportedCompression(compression)),
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "brotli", feature = "lz4", feature = "zstd")))]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::models::SyncCompression;
+
+    #[test]
+    fn test_compression_roundtrip() {
+        let content = b"adb sync compression ".repeat(4096);
+
+        for compression in [
+            SyncCompression::None,
+            #[cfg(feature = "brotli")]
+            SyncCompression::Brotli,
+            #[cfg(feature = "lz4")]
+            SyncCompression::Lz4,
+            #[cfg(feature = "zstd")]
+            SyncCompression::Zstd,
+        ] {
+            let mut compressed = Vec::new();
+            let mut encoder = compression
+                .encoder(&mut compressed)
+                .expect("cannot create encoder");
+            encoder.write_all(&content).expect("cannot compress");
+            encoder.finish().expect("cannot finish compression");
+
+            let mut decompressed = Vec::new();
+            compression
+                .decoder(compressed.as_slice())
+                .expect("cannot create decoder")
+                .read_to_end(&mut decompressed)
+                .expect("cannot decompress");
+            assert_eq!(decompressed, content, "{compression} roundtrip failed");
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{file_sync::SYNC_DATA_MAX, models::SyncCommand};
+
+/// Internal structure wrapping a [`std::io::Write`] and hiding underlying protocol logic.
+///
+/// Written data is sent as `DATA` packets, each of them holding at most [`SYNC_DATA_MAX`] bytes.
+pub(crate) struct SyncDataWriter<W: Write> {
+    inner: W,
+    /// Reused between packets to avoid an allocation for each of them.
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> SyncDataWriter<W> {
+    pub const fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for SyncDataWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk = &buf[..buf.len().min(SYNC_DATA_MAX)];
+        let chunk_len = u32::try_from(chunk.len()).map_err(io::Error::other)?;
+
+        self.buffer.clear();
+        self.buffer
+            .extend_from_slice(SyncCommand::Data.to_string().as_bytes());
+        self.buffer.extend_from_slice(&chunk_len.to_le_bytes());
+        self.buffer.extend_from_slice(chunk);
+
+        self.inner.write_all(&self.buffer)?;
+
+        Ok(chunk.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Internal structure wrapping a [`std::io::Read`] and hiding underlying protocol logic.
+///
+/// End of stream is reached when a `DONE` packet is received.
+pub(crate) struct SyncDataReader<R: Read> {
+    inner: R,
+    remaining_data_bytes_to_read: usize,
+    done: bool,
+}
+
+impl<R: Read> SyncDataReader<R> {
+    pub const fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining_data_bytes_to_read: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for SyncDataReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // In case of a "DATA" header, we may not have enough space in `buf` to fill it with "length" bytes coming from device.
+        // `remaining_data_bytes_to_read` represents how many bytes are still left to read before receiving another header.
+        while self.remaining_data_bytes_to_read == 0 {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+
+            let mut header = [0_u8; 4];
+            self.inner.read_exact(&mut header)?;
+            let length = self.inner.read_u32::<LittleEndian>()? as usize;
+
+            match &header {
+                b"DATA" => self.remaining_data_bytes_to_read = length,
+                b"DONE" => self.done = true,
+                b"FAIL" => {
+                    let mut error_msg = vec![0; length];
+                    self.inner.read_exact(&mut error_msg)?;
+
+                    return Err(io::Error::other(format!(
+                        "ADB request failed: {}",
+                        String::from_utf8_lossy(&error_msg)
+                    )));
+                }
+                _ => {
+                    return Err(io::Error::other(format!(
+                        "Unknown response from device {header:#?}"
+                    )));
+                }
+            }
+        }
+
+        // Computing minimum to ensure to stop reading before next header...
+        let data_to_read = self.remaining_data_bytes_to_read.min(buf.len());
+        let effective_read = self.inner.read(&mut buf[..data_to_read])?;
+        if effective_read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining_data_bytes_to_read -= effective_read;
+
+        Ok(effective_read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use super::*;
+
+    #[test]
+    fn test_data_roundtrip() {
+        let content: Vec<u8> = (0..=u8::MAX).cycle().take(3 * SYNC_DATA_MAX + 17).collect();
+
+        let mut packets = Vec::new();
+        SyncDataWriter::new(&mut packets)
+            .write_all(&content)
+            .expect("cannot write");
+        packets.extend_from_slice(b"DONE\0\0\0\0");
+
+        let mut received = Vec::new();
+        SyncDataReader::new(Cursor::new(packets))
+            .read_to_end(&mut received)
+            .expect("cannot read");
+        assert_eq!(received, content);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    Result, RustADBError,
+    models::{HostFeatures, SyncCompression},
+};
+
+/// Sync protocol extensions supported by a device.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SyncFeatures {
+    pub stat_v2: bool,
+    pub ls_v2: bool,
+    pub sendrecv_v2: bool,
+    /// Compressions supported by both device and this build, by order of preference.
+    compressions: Vec<SyncCompression>,
+}
+
+impl From<&[HostFeatures]> for SyncFeatures {
+    fn from(features: &[HostFeatures]) -> Self {
+        let sendrecv_v2 = features.contains(&HostFeatures::SendRecvV2);
+
+        let compressions = [
+            (
+                SyncCompression::Zstd,
+                HostFeatures::SendRecvV2Zstd,
+                cfg!(feature = "zstd"),
+            ),
+            (
+                SyncCompression::Lz4,
+                HostFeatures::SendRecvV2Lz4,
+                cfg!(feature = "lz4"),
+            ),
+            (
+                SyncCompression::Brotli,
+                HostFeatures::SendRecvV2Brotli,
+                cfg!(feature = "brotli"),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, feature, enabled)| sendrecv_v2 && *enabled && features.contains(feature))
+        .map(|(compression, _, _)| compression)
+        .collect();
+
+        Self {
+            stat_v2: features.contains(&HostFeatures::StatV2),
+            ls_v2: features.contains(&HostFeatures::LsV2),
+            sendrecv_v2,
+            compressions,
+        }
+    }
+}
+
+impl SyncFeatures {
+    /// Turn `requested` compression into the concrete algorithm to use.
+    ///
+    /// [`SyncCompression::Any`] never fails, and falls back to [`SyncCompression::None`].
+    pub(crate) fn resolve_compression(
+        &self,
+        requested: SyncCompression,
+    ) -> Result<SyncCompression> {
+        match requested {
+            SyncCompression::Any => Ok(self
+                .compressions
+                .first()
+                .copied()
+                .unwrap_or(SyncCompression::None)),
+            SyncCompression::None => Ok(SyncCompression::None),
+            compression if self.compressions.contains(&compression) => Ok(compression),
+            compression => Err(RustADBError::UnsupportedCompression(compression)),
+        }
+    }
+}
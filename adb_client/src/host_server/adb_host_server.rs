@@ -0,0 +1,145 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener},
+    path::PathBuf,
+    sync::Arc,
+    thread::JoinHandle,
+};
+
+use crate::{
+    Result,
+    host_server::{
+        bridged_device::BridgedDevice,
+        host_connection::{HostConnection, HostServerState},
+    },
+    tcp::ADBTcpDevice,
+};
+
+/// Port ADB servers listen on by default.
+const DEFAULT_SERVER_PORT: u16 = 5037;
+
+/// ADB server implemented in Rust, replacing the one started by `adb start-server`.
+///
+/// It speaks the smart socket protocol to its clients, e.g. `adb` command line tool, Android Studio or [`ADBServer`](crate::server::ADBServer),
+/// and bridges device services they open to devices connected using this crate.
+/// Devices are either added before starting server, or connected over TCP by clients using `adb connect`.
+#[derive(Debug)]
+pub struct ADBHostServer {
+    address: SocketAddrV4,
+    private_key_path: Option<PathBuf>,
+    devices: Vec<(String, bool, Box<dyn BridgedDevice>)>,
+}
+
+impl Default for ADBHostServer {
+    fn default() -> Self {
+        Self::new(SocketAddrV4::new(Ipv4Addr::LOCALHOST, DEFAULT_SERVER_PORT))
+    }
+}
+
+impl ADBHostServer {
+    /// Instantiates a new [`ADBHostServer`], listening on `address` once started.
+    ///
+    /// Default one listens on `127.0.0.1:5037`, as `adb` does.
+    #[must_use]
+    pub const fn new(address: SocketAddrV4) -> Self {
+        Self {
+            address,
+            private_key_path: None,
+            devices: Vec::new(),
+        }
+    }
+
+    /// Set private key used to authenticate devices connected by clients, e.g. using `adb connect`.
+    ///
+    /// Defaults to `~/.android/adbkey`.
+    pub fn set_private_key_path(&mut self, private_key_path: PathBuf) {
+        self.private_key_path = Some(private_key_path);
+    }
+
+    /// Serve `device`, listed as `serial` to clients.
+    pub fn add_tcp_device(&mut self, serial: impl Into<String>, device: ADBTcpDevice) {
+        self.devices
+            .push((serial.into(), false, Box::new(device.into_inner())));
+    }
+
+    /// Serve `device`, listed as `serial` to clients.
+    #[cfg(feature = "usb")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "usb")))]
+    pub fn add_usb_device(&mut self, serial: impl Into<String>, device: crate::usb::ADBUSBDevice) {
+        self.devices
+            .push((serial.into(), true, Box::new(device.into_inner())));
+    }
+
+    /// Start serving clients in background, until server is killed (`adb kill-server`) or returned handle is dropped.
+    pub fn start(self) -> Result<ADBHostServerHandle> {
+        let listener = TcpListener::bind(self.address)?;
+        let address = match listener.local_addr()? {
+            SocketAddr::V4(address) => address,
+            SocketAddr::V6(address) => SocketAddrV4::new(Ipv4Addr::LOCALHOST, address.port()),
+        };
+
+        let state = Arc::new(HostServerState::new(address, self.private_key_path));
+        for (serial, usb, device) in self.devices {
+            state.add_device(serial, usb, device);
+        }
+
+        let accepting_state = state.clone();
+        let thread = std::thread::spawn(move || accept_connections(&listener, &accepting_state));
+
+        log::info!("ADB server listening on {address}");
+        Ok(ADBHostServerHandle {
+            address,
+            state,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn accept_connections(listener: &TcpListener, state: &Arc<HostServerState>) {
+    for stream in listener.incoming() {
+        if state.is_stopped() {
+            break;
+        }
+
+        match stream {
+            Ok(stream) => {
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = HostConnection::new(stream, state).serve() {
+                        log::debug!("client connection ended: {e}");
+                    }
+                });
+            }
+            Err(e) => log::warn!("cannot accept client connection: {e}"),
+        }
+    }
+    log::info!("ADB server stopped");
+}
+
+/// Handle on a running [`ADBHostServer`], stopping it once dropped.
+#[derive(Debug)]
+pub struct ADBHostServerHandle {
+    address: SocketAddrV4,
+    state: Arc<HostServerState>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ADBHostServerHandle {
+    /// Address server listens on, e.g. to be used with [`ADBServer::new`](crate::server::ADBServer::new).
+    #[must_use]
+    pub const fn address(&self) -> SocketAddrV4 {
+        self.address
+    }
+
+    /// Block until server is killed by a client, e.g. using `adb kill-server`.
+    pub fn wait(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ADBHostServerHandle {
+    fn drop(&mut self) {
+        self.state.stop();
+    }
+}
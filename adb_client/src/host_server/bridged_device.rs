@@ -0,0 +1,132 @@
+use std::{fmt::Debug, io::Write, net::TcpStream};
+
+use crate::{
+    Result,
+    message_devices::{
+        adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
+        forward_listener::bridge_connection,
+    },
+    models::{ADBLocalCommand, DeviceBanner, ForwardRule, Timeouts},
+};
+
+/// Direct device whose services are bridged to clients of an [`ADBHostServer`](super::ADBHostServer), whatever its transport.
+pub(crate) trait BridgedDevice: Debug + Send {
+    /// Banner sent by device when connection has been established.
+    fn banner(&self) -> &DeviceBanner;
+
+    /// Whether connection to device is still up.
+    fn is_connected(&self) -> bool;
+
+    /// Clone sharing the same connection, used to serve a single client connection.
+    fn detached(&self) -> Box<dyn BridgedDevice>;
+
+    /// Open `service` on device, then answer `OKAY` on `stream` and copy data both ways until one side closes.
+    ///
+    /// Nothing is written on `stream` if service cannot be opened.
+    fn bridge(&mut self, service: &str, stream: TcpStream) -> Result<()>;
+
+    fn forward(&mut self, remote: String, local: &str) -> Result<String>;
+
+    fn forward_remove(&mut self, local: &str) -> Result<()>;
+
+    fn forward_remove_all(&mut self) -> Result<()>;
+
+    fn forward_list(&self) -> Result<Vec<ForwardRule>>;
+}
+
+impl<T: ADBMessageTransport + Debug> BridgedDevice for ADBMessageDevice<T> {
+    fn banner(&self) -> &DeviceBanner {
+        self.banner()
+    }
+
+    fn is_connected(&self) -> bool {
+        self.is_connected()
+    }
+
+    fn detached(&self) -> Box<dyn BridgedDevice> {
+        // Bridged sessions last as long as client keeps them open
+        let mut device = self.detached_clone();
+        device.set_timeouts(Timeouts::none().connect(self.timeouts().connect_value()));
+        Box::new(device)
+    }
+
+    fn bridge(&mut self, service: &str, mut stream: TcpStream) -> Result<()> {
+        let session = self.open_session(&ADBLocalCommand::Service(service.to_string()))?;
+        stream.write_all(b"OKAY")?;
+        bridge_connection(&session, stream, service);
+        Ok(())
+    }
+
+    fn forward(&mut self, remote: String, local: &str) -> Result<String> {
+        self.forward(remote, local)
+    }
+
+    fn forward_remove(&mut self, local: &str) -> Result<()> {
+        self.forward_remove(local)
+    }
+
+    fn forward_remove_all(&mut self) -> Result<()> {
+        self.forward_remove_all()
+    }
+
+    fn forward_list(&self) -> Result<Vec<ForwardRule>> {
+        self.forward_list()
+    }
+}
+
+/// Device served by an [`ADBHostServer`](super::ADBHostServer).
+#[derive(Debug)]
+pub(crate) struct HostedDevice {
+    pub(crate) serial: String,
+    pub(crate) transport_id: u64,
+    /// Whether device is connected over USB, else over TCP.
+    pub(crate) usb: bool,
+    pub(crate) device: Box<dyn BridgedDevice>,
+}
+
+impl HostedDevice {
+    /// State reported to clients, e.g. `device`, `recovery` or `offline`.
+    pub(crate) fn state(&self) -> &str {
+        if self.device.is_connected() {
+            &self.device.banner().connection_type
+        } else {
+            "offline"
+        }
+    }
+
+    /// Whether device is connected using `transport`, as named in `wait-for-<transport>-<state>` requests.
+    pub(crate) fn uses_transport(&self, transport: &str) -> bool {
+        match transport {
+            "usb" => self.usb,
+            "local" => !self.usb,
+            _ => true,
+        }
+    }
+
+    /// Line describing device in `devices` lists, with details if `long` is set.
+    pub(crate) fn describe(&self, long: bool) -> String {
+        if !long {
+            return format!("{}\t{}\n", self.serial, self.state());
+        }
+
+        let banner = self.device.banner();
+        let details: String = [
+            ("product", &banner.product_name),
+            ("model", &banner.model),
+            ("device", &banner.device),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| {
+            // Values are single words, as with adb
+            let value = value.as_ref()?.replace(char::is_whitespace, "_");
+            Some(format!(" {key}:{value}"))
+        })
+        .collect();
+        format!(
+            "{}\t{}{details} transport_id:{}\n",
+            self.serial,
+            self.state(),
+            self.transport_id
+        )
+    }
+}
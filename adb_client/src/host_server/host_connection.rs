@@ -0,0 +1,562 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{Ipv4Addr, SocketAddrV4, TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{
+    Result, RustADBError,
+    host_server::{
+        bridged_device::{BridgedDevice, HostedDevice},
+        host_request::{DeviceRequest, DeviceSelector, HostRequest},
+    },
+    message_devices::models::AuthOptions,
+    tcp::{ADBTcpDevice, TlsOptions},
+};
+
+/// Version of the smart socket protocol reported by `host:version`. Clients of another version restart their server.
+const ADB_SERVER_VERSION: u32 = 41;
+/// Features supported by server itself, every device service being bridged as is.
+const HOST_FEATURES: &str = "shell_v2,cmd,stat_v2,ls_v2,fixed_push_mkdir,apex,abb,fixed_push_symlink_timestamp,abb_exec,remount_shell,track_app,sendrecv_v2,sendrecv_v2_brotli,sendrecv_v2_lz4,sendrecv_v2_zstd,sendrecv_v2_dry_run_send";
+/// Port used by `host:connect` requests not specifying any.
+const DEFAULT_DEVICE_PORT: u16 = 5555;
+/// Interval at which device states are checked for clients tracking or waiting for devices.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// State of a running [`ADBHostServer`](super::ADBHostServer), shared between client connections.
+#[derive(Debug)]
+pub(crate) struct HostServerState {
+    /// Address server listens on.
+    address: SocketAddrV4,
+    devices: Mutex<Vec<HostedDevice>>,
+    next_transport_id: AtomicU64,
+    /// Private key used to authenticate devices connected by clients.
+    private_key_path: Option<PathBuf>,
+    stopped: AtomicBool,
+}
+
+impl HostServerState {
+    pub(crate) fn new(address: SocketAddrV4, private_key_path: Option<PathBuf>) -> Self {
+        Self {
+            address,
+            devices: Mutex::default(),
+            next_transport_id: AtomicU64::new(1),
+            private_key_path,
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    /// Serve `device` under `serial`, assigning it a new transport id.
+    pub(crate) fn add_device(&self, serial: String, usb: bool, device: Box<dyn BridgedDevice>) {
+        let transport_id = self.next_transport_id.fetch_add(1, Ordering::Relaxed);
+        log::info!("serving device {serial} with transport id {transport_id}");
+        lock(&self.devices).push(HostedDevice {
+            serial,
+            transport_id,
+            usb,
+            device,
+        });
+    }
+
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+
+    /// Stop accepting client connections.
+    pub(crate) fn stop(&self) {
+        if self.stopped.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        // Wake up listening thread, blocked on accept
+        let ip = if self.address.ip().is_unspecified() {
+            Ipv4Addr::LOCALHOST
+        } else {
+            *self.address.ip()
+        };
+        let _ = TcpStream::connect((ip, self.address.port()));
+    }
+
+    /// Device list, one device per line.
+    fn list_devices(&self, long: bool) -> String {
+        lock(&self.devices)
+            .iter()
+            .map(|device| device.describe(long))
+            .collect()
+    }
+}
+
+/// What to do once a request has been answered.
+enum Next {
+    Continue,
+    Close,
+}
+
+/// Connection of a client to an [`ADBHostServer`](super::ADBHostServer).
+pub(crate) struct HostConnection {
+    stream: TcpStream,
+    state: Arc<HostServerState>,
+    /// Transport id of device selected by a transport request, if any.
+    transport_id: Option<u64>,
+}
+
+impl HostConnection {
+    pub(crate) const fn new(stream: TcpStream, state: Arc<HostServerState>) -> Self {
+        Self {
+            stream,
+            state,
+            transport_id: None,
+        }
+    }
+
+    /// Answer requests sent by client, until it closes connection or a device service is opened.
+    pub(crate) fn serve(mut self) -> Result<()> {
+        while let Some(request) = self.read_request()? {
+            log::debug!("received request {request}");
+            if let Next::Close = self.handle(HostRequest::parse(&request))? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read next request, or `None` once client closed connection.
+    fn read_request(&mut self) -> Result<Option<String>> {
+        let mut length = [0_u8; 4];
+        match self.stream.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let length = usize::from_str_radix(std::str::from_utf8(&length)?, 16)
+            .map_err(|_| RustADBError::ConversionError)?;
+        let mut request = vec![0_u8; length];
+        self.stream.read_exact(&mut request)?;
+        Ok(Some(String::from_utf8(request)?))
+    }
+
+    fn handle(&mut self, request: HostRequest) -> Result<Next> {
+        match request {
+            HostRequest::Version => self.okay(&format!("{ADB_SERVER_VERSION:04x}")),
+            HostRequest::Kill => {
+                self.stream.write_all(b"OKAY")?;
+                log::info!("server killed by client");
+                self.state.stop();
+                Ok(Next::Close)
+            }
+            HostRequest::HostFeatures => self.okay(HOST_FEATURES),
+            HostRequest::Devices { long } => self.okay(&self.state.list_devices(long)),
+            HostRequest::TrackDevices { long } => self.track_devices(long),
+            HostRequest::Connect(address) => {
+                let message = self.connect(&address);
+                self.okay(&message)
+            }
+            HostRequest::Disconnect(address) => self.disconnect(address.as_deref()),
+            HostRequest::Transport { selector, reply_id } => {
+                let transport_id =
+                    match self.with_device(&selector, true, |device| device.transport_id) {
+                        Ok(transport_id) => transport_id,
+                        Err(error) => return self.fail(&error),
+                    };
+
+                self.transport_id = Some(transport_id);
+                self.stream.write_all(b"OKAY")?;
+                if reply_id {
+                    self.stream.write_all(&transport_id.to_le_bytes())?;
+                }
+                Ok(Next::Continue)
+            }
+            HostRequest::Device(selector, request) => self.handle_device(&selector, request),
+            HostRequest::Service(service) => self.open_service(&service),
+            HostRequest::Unknown(request) => {
+                log::debug!("unknown host service {request}");
+                self.fail("unknown host service")
+            }
+        }
+    }
+
+    fn handle_device(&mut self, selector: &DeviceSelector, request: DeviceRequest) -> Result<Next> {
+        let result = match request {
+            DeviceRequest::Features => self.with_device(selector, true, |device| {
+                device.device.banner().features.join(",")
+            }),
+            DeviceRequest::GetState => {
+                self.with_device(selector, false, |device| device.state().to_string())
+            }
+            DeviceRequest::GetSerialNo => {
+                self.with_device(selector, false, |device| device.serial.clone())
+            }
+            DeviceRequest::GetDevPath => {
+                self.with_device(selector, false, |_| "unknown".to_string())
+            }
+            DeviceRequest::Forward {
+                local,
+                remote,
+                no_rebind,
+            } => return self.forward(selector, &local, remote, no_rebind),
+            DeviceRequest::KillForward(local) => {
+                let removed = self.with_devices(selector, |devices| {
+                    devices
+                        .iter_mut()
+                        .any(|device| device.device.forward_remove(&local).is_ok())
+                });
+                return match removed {
+                    Ok(true) => self.forward_okay(None),
+                    Ok(false) => self.fail(&format!("listener '{local}' not found")),
+                    Err(error) => self.fail(&error),
+                };
+            }
+            DeviceRequest::KillForwardAll => {
+                let result = self.with_devices(selector, |devices| {
+                    devices
+                        .iter_mut()
+                        .try_for_each(|device| device.device.forward_remove_all())
+                });
+                return match result {
+                    Ok(Ok(())) => self.forward_okay(None),
+                    Ok(Err(e)) => self.fail(&e.to_string()),
+                    Err(error) => self.fail(&error),
+                };
+            }
+            DeviceRequest::ListForward => self.with_devices(selector, |devices| {
+                devices
+                    .iter()
+                    .flat_map(|device| {
+                        device
+                            .device
+                            .forward_list()
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|rule| {
+                                format!("{} {} {}\n", device.serial, rule.local, rule.remote)
+                            })
+                    })
+                    .collect()
+            }),
+            DeviceRequest::WaitFor { transport, state } => {
+                return self.wait_for(selector, &transport, &state);
+            }
+        };
+
+        match result {
+            Ok(body) => self.okay(&body),
+            Err(error) => self.fail(&error),
+        }
+    }
+
+    /// Run `operation` on device chosen by `selector`, checking it is connected if `online` is set.
+    ///
+    /// Error returned is the message sent to client.
+    fn with_device<R>(
+        &self,
+        selector: &DeviceSelector,
+        online: bool,
+        operation: impl FnOnce(&mut HostedDevice) -> R,
+    ) -> std::result::Result<R, String> {
+        let mut devices = lock(&self.state.devices);
+        let index = self.select(&devices, selector)?;
+        let device = &mut devices[index];
+        if online && !device.device.is_connected() {
+            return Err("device offline".to_string());
+        }
+
+        Ok(operation(device))
+    }
+
+    /// Run `operation` on device chosen by `selector`, or on every device if none has been selected on this connection.
+    fn with_devices<R>(
+        &self,
+        selector: &DeviceSelector,
+        operation: impl FnOnce(&mut [HostedDevice]) -> R,
+    ) -> std::result::Result<R, String> {
+        let mut devices = lock(&self.state.devices);
+        if *selector == DeviceSelector::Default && self.transport_id.is_none() {
+            return Ok(operation(&mut devices));
+        }
+
+        let index = self.select(&devices, selector)?;
+        Ok(operation(&mut devices[index..=index]))
+    }
+
+    /// Index of the device chosen by `selector` among `devices`, or error message reported by adb.
+    fn select(
+        &self,
+        devices: &[HostedDevice],
+        selector: &DeviceSelector,
+    ) -> std::result::Result<usize, String> {
+        let selector = match (selector, self.transport_id) {
+            (DeviceSelector::Default, Some(transport_id)) => {
+                &DeviceSelector::TransportId(transport_id)
+            }
+            (DeviceSelector::Default, None) => &DeviceSelector::Any,
+            (selector, _) => selector,
+        };
+
+        let (transport, none, many) = match selector {
+            DeviceSelector::Serial(serial) => {
+                return devices
+                    .iter()
+                    .position(|device| device.serial == *serial)
+                    .ok_or_else(|| format!("device '{serial}' not found"));
+            }
+            DeviceSelector::TransportId(transport_id) => {
+                return devices
+                    .iter()
+                    .position(|device| device.transport_id == *transport_id)
+                    .ok_or_else(|| format!("no device with transport id '{transport_id}'"));
+            }
+            DeviceSelector::Usb => ("usb", "no devices found", "more than one device"),
+            DeviceSelector::Local => ("local", "no emulators found", "more than one emulator"),
+            DeviceSelector::Any | DeviceSelector::Default => (
+                "any",
+                "no devices/emulators found",
+                "more than one device/emulator",
+            ),
+        };
+
+        let mut matching = devices
+            .iter()
+            .enumerate()
+            .filter(|(_, device)| device.uses_transport(transport));
+        match (matching.next(), matching.next()) {
+            (Some((index, _)), None) => Ok(index),
+            (None, _) => Err(none.to_string()),
+            (Some(_), Some(_)) => Err(many.to_string()),
+        }
+    }
+
+    /// Open `service` on selected device, then bridge this connection to it until one side closes.
+    fn open_service(&mut self, service: &str) -> Result<Next> {
+        let device = self.with_device(&DeviceSelector::Default, true, |device| {
+            device.device.detached()
+        });
+        let mut device = match device {
+            Ok(device) => device,
+            Err(error) => return self.fail(&error),
+        };
+
+        if let Err(e) = device.bridge(service, self.stream.try_clone()?) {
+            log::debug!("cannot open service {service} on device: {e}");
+            return self.fail("closed");
+        }
+
+        Ok(Next::Close)
+    }
+
+    fn forward(
+        &mut self,
+        selector: &DeviceSelector,
+        local: &str,
+        remote: String,
+        no_rebind: bool,
+    ) -> Result<Next> {
+        let result = self.with_device(selector, true, |device| {
+            if no_rebind
+                && device
+                    .device
+                    .forward_list()?
+                    .iter()
+                    .any(|rule| rule.local == local)
+            {
+                return Err(RustADBError::ADBRequestFailed(
+                    "cannot rebind existing socket".to_string(),
+                ));
+            }
+
+            device.device.forward(remote, local)
+        });
+
+        match result {
+            // Actual port is only reported when client let server pick it
+            Ok(Ok(bound)) if local == "tcp:0" => {
+                let port = bound.trim_start_matches("tcp:").to_string();
+                self.forward_okay(Some(&port))
+            }
+            Ok(Ok(_)) => self.forward_okay(None),
+            Ok(Err(e)) => {
+                let error = match e {
+                    RustADBError::ADBRequestFailed(error) => error,
+                    e => e.to_string(),
+                };
+                self.fail(&error)
+            }
+            Err(error) => self.fail(&error),
+        }
+    }
+
+    /// Answer a forward request, first `OKAY` acknowledging transport and second one the request itself.
+    fn forward_okay(&mut self, port: Option<&str>) -> Result<Next> {
+        self.stream.write_all(b"OKAYOKAY")?;
+        if let Some(port) = port {
+            self.write_hex_length_prefixed(port)?;
+        }
+        Ok(Next::Continue)
+    }
+
+    /// Connect to device listening on `address`, returning message reported to client.
+    fn connect(&self, address: &str) -> String {
+        let serial = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{address}:{DEFAULT_DEVICE_PORT}")
+        };
+        if lock(&self.state.devices)
+            .iter()
+            .any(|device| device.serial == serial)
+        {
+            return format!("already connected to {serial}");
+        }
+
+        let device = serial
+            .to_socket_addrs()
+            .map_err(RustADBError::from)
+            .and_then(|mut addresses| {
+                addresses.next().ok_or_else(|| {
+                    RustADBError::ADBRequestFailed(format!("cannot resolve {serial}"))
+                })
+            })
+            .and_then(|socket_address| {
+                ADBTcpDevice::new_with_options(
+                    socket_address,
+                    self.state.private_key_path.clone(),
+                    &AuthOptions::default(),
+                    TlsOptions::default(),
+                )
+            });
+
+        match device {
+            Ok(device) => {
+                self.state
+                    .add_device(serial.clone(), false, Box::new(device.into_inner()));
+                format!("connected to {serial}")
+            }
+            Err(e) => format!("failed to connect to {serial}: {e}"),
+        }
+    }
+
+    /// Stop serving device connected on `address`, or every device connected over TCP.
+    fn disconnect(&mut self, address: Option<&str>) -> Result<Next> {
+        let mut devices = lock(&self.state.devices);
+        let Some(address) = address else {
+            devices.retain(|device| device.usb);
+            drop(devices);
+            return self.okay("disconnected everything");
+        };
+
+        let serial = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{address}:{DEFAULT_DEVICE_PORT}")
+        };
+        let count = devices.len();
+        devices.retain(|device| device.serial != serial);
+        let removed = devices.len() != count;
+        drop(devices);
+
+        if removed {
+            self.okay(&format!("disconnected {serial}"))
+        } else {
+            self.fail(&format!("no such device '{serial}'"))
+        }
+    }
+
+    /// Send device list, then again each time it changes, until client closes connection.
+    fn track_devices(&mut self, long: bool) -> Result<Next> {
+        self.stream.write_all(b"OKAY")?;
+
+        let mut previous = None;
+        while !self.state.is_stopped() {
+            let devices = self.state.list_devices(long);
+            if previous.as_ref() != Some(&devices) {
+                self.write_hex_length_prefixed(&devices)?;
+                previous = Some(devices);
+            }
+
+            if self.wait_for_close()? {
+                break;
+            }
+        }
+
+        Ok(Next::Close)
+    }
+
+    /// Answer once a device chosen by `selector` and connected using `transport` is in `state`, as with `adb wait-for-device`.
+    fn wait_for(
+        &mut self,
+        selector: &DeviceSelector,
+        transport: &str,
+        state: &str,
+    ) -> Result<Next> {
+        // First answer acknowledges request, second one tells device is in expected state
+        self.stream.write_all(b"OKAY")?;
+
+        while !self.state.is_stopped() {
+            let matching = self.with_device(selector, false, |device| {
+                device.uses_transport(transport)
+                    && ((state == "any" && device.device.is_connected()) || device.state() == state)
+            });
+            let reached = match (state, matching) {
+                ("disconnect", Ok(true)) => false,
+                ("disconnect", _) | (_, Ok(true)) => true,
+                _ => false,
+            };
+            if reached {
+                self.stream.write_all(b"OKAY")?;
+                return Ok(Next::Close);
+            }
+
+            if self.wait_for_close()? {
+                break;
+            }
+        }
+
+        Ok(Next::Close)
+    }
+
+    /// Wait for at most [`POLL_INTERVAL`], returning whether client closed connection meanwhile.
+    fn wait_for_close(&mut self) -> Result<bool> {
+        self.stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        let mut buffer = [0_u8; 256];
+        let result = match self.stream.read(&mut buffer) {
+            Ok(0) => Ok(true),
+            // Nothing is expected from client, input is ignored
+            Ok(_) => Ok(false),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(false),
+            Err(e) => Err(e.into()),
+        };
+        self.stream.set_read_timeout(None)?;
+        result
+    }
+
+    fn write_hex_length_prefixed(&mut self, body: &str) -> Result<()> {
+        self.stream
+            .write_all(format!("{:04x}{body}", body.len()).as_bytes())?;
+        Ok(())
+    }
+
+    /// Answer `OKAY`, followed by `body`.
+    fn okay(&mut self, body: &str) -> Result<Next> {
+        self.stream.write_all(b"OKAY")?;
+        self.write_hex_length_prefixed(body)?;
+        Ok(Next::Continue)
+    }
+
+    /// Answer `FAIL` with `message`, then close connection as adb does.
+    fn fail(&mut self, message: &str) -> Result<Next> {
+        self.stream.write_all(b"FAIL")?;
+        self.write_hex_length_prefixed(message)?;
+        Ok(Next::Close)
+    }
+}
+
+/// Lock `mutex`, even if a thread panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,305 @@
+/// Requests scoped to a device, as in `host-serial:<serial>:<request>`.
+const DEVICE_REQUESTS: [&str; 9] = [
+    "features",
+    "get-state",
+    "get-serialno",
+    "get-devpath",
+    "forward:",
+    "killforward:",
+    "killforward-all",
+    "list-forward",
+    "wait-for-",
+];
+
+/// Device a request applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum DeviceSelector {
+    /// Device selected by a previous transport request on the same connection, else the only one.
+    Default,
+    /// Only device.
+    Any,
+    /// Only device connected over USB.
+    Usb,
+    /// Only device connected over TCP.
+    Local,
+    Serial(String),
+    TransportId(u64),
+}
+
+/// Request sent by a client to an ADB server, e.g. `adb` command line tool.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum HostRequest {
+    Version,
+    Kill,
+    /// Features supported by server itself.
+    HostFeatures,
+    Devices {
+        long: bool,
+    },
+    TrackDevices {
+        long: bool,
+    },
+    Connect(String),
+    /// Disconnect given device, or all of them.
+    Disconnect(Option<String>),
+    /// Switch connection to selected device, replying with its transport id if `reply_id` is set.
+    Transport {
+        selector: DeviceSelector,
+        reply_id: bool,
+    },
+    Device(DeviceSelector, DeviceRequest),
+    /// Service opened on device selected by a previous transport request, e.g. `shell:ls` or `sync:`.
+    Service(String),
+    Unknown(String),
+}
+
+/// Request applying to a single device, or to every device for `list-forward`.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum DeviceRequest {
+    Features,
+    GetState,
+    GetSerialNo,
+    GetDevPath,
+    Forward {
+        local: String,
+        remote: String,
+        no_rebind: bool,
+    },
+    KillForward(String),
+    KillForwardAll,
+    ListForward,
+    /// Wait for a device connected using `transport` (`any`, `usb` or `local`) to be in `state`.
+    WaitFor {
+        transport: String,
+        state: String,
+    },
+}
+
+impl HostRequest {
+    /// Parse `request`, as sent after its 4 hexadecimal digits length.
+    pub(crate) fn parse(request: &str) -> Self {
+        let Some((prefix, rest)) = request.split_once(':') else {
+            return Self::Service(request.to_string());
+        };
+
+        let selector = match prefix {
+            "host" => return Self::parse_host(rest),
+            "host-usb" => DeviceSelector::Usb,
+            "host-local" => DeviceSelector::Local,
+            "host-serial" => {
+                // Serial may itself contain colons, e.g. `192.168.0.10:5555`
+                let Some((serial, rest)) = split_device_request(rest) else {
+                    return Self::Unknown(request.to_string());
+                };
+                return Self::parse_device(DeviceSelector::Serial(serial.to_string()), rest);
+            }
+            "host-transport-id" => {
+                let Some((id, rest)) = rest.split_once(':') else {
+                    return Self::Unknown(request.to_string());
+                };
+                let Ok(id) = id.parse() else {
+                    return Self::Unknown(request.to_string());
+                };
+                return Self::parse_device(DeviceSelector::TransportId(id), rest);
+            }
+            _ => return Self::Service(request.to_string()),
+        };
+
+        Self::parse_device(selector, rest)
+    }
+
+    /// Parse a `host:` request, device requests applying to default device.
+    fn parse_host(request: &str) -> Self {
+        match request {
+            "version" => return Self::Version,
+            "kill" => return Self::Kill,
+            "host-features" => return Self::HostFeatures,
+            "devices" => return Self::Devices { long: false },
+            "devices-l" => return Self::Devices { long: true },
+            "track-devices" => return Self::TrackDevices { long: false },
+            "track-devices-l" => return Self::TrackDevices { long: true },
+            "disconnect:" => return Self::Disconnect(None),
+            _ => {}
+        }
+
+        if let Some(address) = request.strip_prefix("connect:") {
+            return Self::Connect(address.to_string());
+        }
+        if let Some(address) = request.strip_prefix("disconnect:") {
+            return Self::Disconnect(Some(address.to_string()));
+        }
+        if let Some(selector) = request
+            .strip_prefix("transport")
+            .and_then(parse_transport_selector)
+        {
+            return Self::Transport {
+                selector,
+                reply_id: false,
+            };
+        }
+        if let Some(selector) = request
+            .strip_prefix("tport:")
+            .and_then(parse_tport_selector)
+        {
+            return Self::Transport {
+                selector,
+                reply_id: true,
+            };
+        }
+
+        Self::parse_device(DeviceSelector::Default, request)
+    }
+
+    fn parse_device(selector: DeviceSelector, request: &str) -> Self {
+        let device_request = match request {
+            "features" => DeviceRequest::Features,
+            "get-state" => DeviceRequest::GetState,
+            "get-serialno" => DeviceRequest::GetSerialNo,
+            "get-devpath" => DeviceRequest::GetDevPath,
+            "killforward-all" => DeviceRequest::KillForwardAll,
+            "list-forward" => DeviceRequest::ListForward,
+            _ => {
+                if let Some(local) = request.strip_prefix("killforward:") {
+                    DeviceRequest::KillForward(local.to_string())
+                } else if let Some(rule) = request.strip_prefix("forward:") {
+                    let (rule, no_rebind) = match rule.strip_prefix("norebind:") {
+                        Some(rule) => (rule, true),
+                        None => (rule, false),
+                    };
+                    let Some((local, remote)) = rule.split_once(';') else {
+                        return Self::Unknown(request.to_string());
+                    };
+                    DeviceRequest::Forward {
+                        local: local.to_string(),
+                        remote: remote.to_string(),
+                        no_rebind,
+                    }
+                } else if let Some((transport, state)) = request
+                    .strip_prefix("wait-for-")
+                    .and_then(|wait| wait.split_once('-'))
+                {
+                    DeviceRequest::WaitFor {
+                        transport: transport.to_string(),
+                        state: state.to_string(),
+                    }
+                } else {
+                    return Self::Unknown(request.to_string());
+                }
+            }
+        };
+
+        Self::Device(selector, device_request)
+    }
+}
+
+/// Split `request` into serial and device request, at first colon followed by a known device request.
+fn split_device_request(request: &str) -> Option<(&str, &str)> {
+    request.match_indices(':').find_map(|(index, _)| {
+        let rest = &request[index + 1..];
+        DEVICE_REQUESTS
+            .iter()
+            .any(|device_request| rest.starts_with(device_request))
+            .then(|| (&request[..index], rest))
+    })
+}
+
+/// Parse what follows `host:transport`, e.g. `:<serial>` or `-any`.
+fn parse_transport_selector(selector: &str) -> Option<DeviceSelector> {
+    match selector {
+        "-any" => Some(DeviceSelector::Any),
+        "-usb" => Some(DeviceSelector::Usb),
+        "-local" => Some(DeviceSelector::Local),
+        _ => {
+            if let Some(serial) = selector.strip_prefix(':') {
+                return Some(DeviceSelector::Serial(serial.to_string()));
+            }
+            selector
+                .strip_prefix("-id:")
+                .and_then(|id| id.parse().ok())
+                .map(DeviceSelector::TransportId)
+        }
+    }
+}
+
+/// Parse what follows `host:tport:`, e.g. `serial:<serial>` or `any`.
+fn parse_tport_selector(selector: &str) -> Option<DeviceSelector> {
+    match selector {
+        "any" => Some(DeviceSelector::Any),
+        "usb" => Some(DeviceSelector::Usb),
+        "local" => Some(DeviceSelector::Local),
+        _ => {
+            if let Some(serial) = selector.strip_prefix("serial:") {
+                return Some(DeviceSelector::Serial(serial.to_string()));
+            }
+            selector
+                .strip_prefix("id:")
+                .and_then(|id| id.parse().ok())
+                .map(DeviceSelector::TransportId)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceRequest, DeviceSelector, HostRequest};
+
+    #[test]
+    fn test_parse_host_request() {
+        assert_eq!(HostRequest::parse("host:version"), HostRequest::Version);
+        assert_eq!(
+            HostRequest::parse("host:devices-l"),
+            HostRequest::Devices { long: true }
+        );
+        assert_eq!(
+            HostRequest::parse("host:connect:192.168.0.10:5555"),
+            HostRequest::Connect("192.168.0.10:5555".to_string())
+        );
+        assert_eq!(
+            HostRequest::parse("host:tport:serial:emulator-5554"),
+            HostRequest::Transport {
+                selector: DeviceSelector::Serial("emulator-5554".to_string()),
+                reply_id: true
+            }
+        );
+        assert_eq!(
+            HostRequest::parse("host:transport-id:3"),
+            HostRequest::Transport {
+                selector: DeviceSelector::TransportId(3),
+                reply_id: false
+            }
+        );
+        assert_eq!(
+            HostRequest::parse("host:features"),
+            HostRequest::Device(DeviceSelector::Default, DeviceRequest::Features)
+        );
+        assert_eq!(
+            HostRequest::parse("host-serial:192.168.0.10:5555:forward:norebind:tcp:0;tcp:80"),
+            HostRequest::Device(
+                DeviceSelector::Serial("192.168.0.10:5555".to_string()),
+                DeviceRequest::Forward {
+                    local: "tcp:0".to_string(),
+                    remote: "tcp:80".to_string(),
+                    no_rebind: true
+                }
+            )
+        );
+        assert_eq!(
+            HostRequest::parse("host:wait-for-usb-device"),
+            HostRequest::Device(
+                DeviceSelector::Default,
+                DeviceRequest::WaitFor {
+                    transport: "usb".to_string(),
+                    state: "device".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            HostRequest::parse("shell,v2,raw:id"),
+            HostRequest::Service("shell,v2,raw:id".to_string())
+        );
+        assert_eq!(
+            HostRequest::parse("host:unknown"),
+            HostRequest::Unknown("unknown".to_string())
+        );
+    }
+}
@@ -0,0 +1,127 @@
+#![doc = include_str!("./README.md")]
+
+mod adb_host_server;
+mod bridged_device;
+mod host_connection;
+mod host_request;
+
+pub use adb_host_server::{ADBHostServer, ADBHostServerHandle};
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+        path::PathBuf,
+    };
+
+    use super::ADBHostServer;
+    use crate::{
+        ADBDeviceExt,
+        message_devices::{
+            models::{ADBRsaKey, AuthOptions},
+            test_support::{FakeCommand, FakeDevice},
+        },
+        server::{ADBServer, DeviceState},
+        tcp::{ADBTcpDevice, TlsOptions},
+    };
+
+    /// Path of a private key file holding test key, unique to `test`.
+    fn private_key_path(test: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("adb_client_host_{test}_{}.pem", std::process::id()));
+        std::fs::write(&path, ADBRsaKey::TEST_PRIVATE_KEY).expect("cannot write private key");
+        path
+    }
+
+    fn ipv4(address: SocketAddr) -> SocketAddrV4 {
+        let SocketAddr::V4(address) = address else {
+            panic!("fake device listens on IPv4 loopback");
+        };
+        address
+    }
+
+    #[test]
+    fn test_host_server() {
+        let private_key_path = private_key_path("server");
+        let fake = FakeDevice::default()
+            .model("Pixel_7")
+            .command("id -u", FakeCommand::new("2000\n"))
+            .command("false", FakeCommand::default().exit_code(1))
+            .directory("/sdcard")
+            .start()
+            .unwrap();
+        let other = FakeDevice::default().start().unwrap();
+        let device = ADBTcpDevice::new_with_options(
+            fake.address(),
+            Some(private_key_path.clone()),
+            &AuthOptions::default(),
+            TlsOptions::default(),
+        )
+        .unwrap();
+
+        let mut host_server = ADBHostServer::new(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+        host_server.set_private_key_path(private_key_path.clone());
+        host_server.add_tcp_device("pixel", device);
+        let handle = host_server.start().unwrap();
+        let mut server = ADBServer::new(handle.address());
+        server.set_auto_start(false);
+
+        assert_eq!(server.version().unwrap().to_string(), "1.0.41");
+        let devices: Vec<_> = server
+            .devices()
+            .unwrap()
+            .into_iter()
+            .map(|device| (device.identifier, device.state))
+            .collect();
+        assert_eq!(devices, [("pixel".to_string(), DeviceState::Device)]);
+        let devices_long = server.devices_long().unwrap();
+        assert_eq!(devices_long[0].model, "Pixel_7");
+        assert_eq!(devices_long[0].transport_id, 1);
+
+        let mut device = server.get_device_by_name("pixel").unwrap();
+        let mut stdout = Vec::new();
+        assert_eq!(
+            device
+                .shell_command(&"id -u", Some(&mut stdout), None)
+                .unwrap(),
+            Some(0)
+        );
+        assert_eq!(stdout, b"2000\n");
+        assert_eq!(device.shell_command(&"false", None, None).unwrap(), Some(1));
+
+        device
+            .push(&mut b"content".as_slice(), "/sdcard/file.txt")
+            .unwrap();
+        assert_eq!(
+            fake.file("/sdcard/file.txt").as_deref(),
+            Some(b"content".as_slice())
+        );
+        let mut pulled = Vec::new();
+        device.pull(&"/sdcard/file.txt", &mut pulled).unwrap();
+        assert_eq!(pulled, b"content");
+
+        device
+            .forward("tcp:80".to_string(), "tcp:0".to_string())
+            .unwrap();
+        device.forward_remove_all().unwrap();
+
+        // Devices connected by clients are served as well
+        let other_address = ipv4(other.address());
+        server.connect_device(other_address).unwrap();
+        assert_eq!(server.devices().unwrap().len(), 2);
+        let mut connected = server
+            .get_device_by_name(&other_address.to_string())
+            .unwrap();
+        assert_eq!(
+            connected.shell_command(&"id", None, None).unwrap(),
+            Some(127)
+        );
+        drop(connected);
+        server.disconnect_device(other_address).unwrap();
+        assert_eq!(server.devices().unwrap().len(), 1);
+
+        server.kill().unwrap();
+        handle.wait();
+        let _ = std::fs::remove_file(private_key_path);
+    }
+}
@@ -12,11 +12,17 @@
 
 mod adb_device_ext;
 mod adb_transport;
+#[cfg(feature = "tokio")]
+mod async_adb_device_ext;
 /// Emulator-related definitions
 pub mod emulator;
 mod error;
+mod file_sync;
+/// ADB server implemented in Rust, bridging its clients to devices connected using this crate
+pub mod host_server;
 mod message_devices;
 mod models;
+mod package_install;
 
 /// Server-related definitions
 pub mod server;
@@ -32,9 +38,15 @@ pub mod mdns;
 
 pub use adb_device_ext::ADBDeviceExt;
 use adb_transport::ADBTransport;
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub use async_adb_device_ext::AsyncADBDeviceExt;
 pub use error::{Result, RustADBError};
 pub use message_devices::*;
 pub use models::{
     ADBListItem, ADBListItemType, ADBStatExtendedResponse, ADBStatMapping, AdbStatResponse,
-    HostFeatures, RebootType, RemountInfo,
+    CancellationToken, DeviceBanner, DirectoryTransferEntry, ForwardRule, HostFeatures,
+    InstallFailureReason, InstallOptions, ProgressObserver, PullOptions, PushOptions, RebootType,
+    RemountInfo, ShellWindowSize, SyncAction, SyncActionEntry, SyncCompression, SyncDirOptions,
+    TimeoutPhase, Timeouts, TransferEntryKind,
 };
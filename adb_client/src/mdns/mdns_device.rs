@@ -21,6 +21,16 @@ pub struct MDNSDevice {
 }
 
 impl MDNSDevice {
+    /// Return the identity of this device, i.e. the instance part of its fullname, under which its TLS certificate is pinned.
+    ///
+    /// See [`TlsOptions::device_id`](crate::tcp::TlsOptions::device_id).
+    #[must_use]
+    pub fn device_id(&self) -> &str {
+        self.fullname
+            .split_once('.')
+            .map_or(self.fullname.as_str(), |(instance, _)| instance)
+    }
+
     /// Return all adresses linked to this device
     #[must_use]
     pub fn addresses(&self) -> HashSet<IpAddr> {
@@ -0,0 +1,511 @@
+use std::{
+    collections::HashMap,
+    net::TcpStream,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, Sender, channel},
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use rand::RngExt;
+
+use crate::{
+    Result, RustADBError,
+    message_devices::{
+        adb_message_transport::ADBMessageTransport,
+        adb_session::ADBSession,
+        adb_transport_message::ADBTransportMessage,
+        forward_listener::{bridge_connection, parse_local_endpoint},
+        message_commands::MessageCommand,
+        models::{ADBConnectionSettings, INITIAL_DELAYED_ACK_BYTES},
+    },
+    models::Deadline,
+};
+
+/// Interval at which the reading thread checks if it has been asked to stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sending side of the queues feeding a session, either blocking or asynchronous.
+pub(crate) trait SessionSender: Sized {
+    /// Receiving side matching this sender.
+    type Receiver;
+
+    /// Create a new queue.
+    fn channel() -> (Self, Self::Receiver);
+
+    /// Queue `message`, dropping it if session is gone.
+    fn send_message(&self, message: ADBTransportMessage);
+}
+
+impl SessionSender for Sender<ADBTransportMessage> {
+    type Receiver = Receiver<ADBTransportMessage>;
+
+    fn channel() -> (Self, Self::Receiver) {
+        channel()
+    }
+
+    fn send_message(&self, message: ADBTransportMessage) {
+        let _ = self.send(message);
+    }
+}
+
+/// Receiving side of a session registered on an [`ADBRoutingTable`].
+pub(crate) struct SessionReceivers<R = Receiver<ADBTransportMessage>> {
+    /// `WRTE` and `CLSE` messages addressed to this session.
+    pub messages: R,
+    /// `OKAY` messages addressed to this session. A `CLSE` is also delivered here to wake up pending writers.
+    pub acks: R,
+}
+
+#[derive(Debug)]
+struct SessionRoute<S> {
+    messages: S,
+    acks: S,
+}
+
+/// Table mapping each opened session `local_id` to its message queues.
+///
+/// Shared by blocking and asynchronous devices, which only differ by the queues `S` feeding sessions.
+#[derive(Debug)]
+pub(crate) struct ADBRoutingTable<S = Sender<ADBTransportMessage>> {
+    routes: Mutex<HashMap<u32, SessionRoute<S>>>,
+    running: AtomicBool,
+    /// Connection settings negotiated with device, applying to every session.
+    settings: ADBConnectionSettings,
+    /// Reverse forward rules, mapping remote endpoints on device to the local endpoints device may open.
+    reverse_rules: Mutex<HashMap<String, String>>,
+}
+
+impl<S: SessionSender> ADBRoutingTable<S> {
+    /// Table of a running connection, using `settings` negotiated with device.
+    pub(crate) fn new(settings: ADBConnectionSettings) -> Self {
+        Self {
+            routes: Mutex::default(),
+            running: AtomicBool::new(true),
+            settings,
+            reverse_rules: Mutex::default(),
+        }
+    }
+
+    /// Allocate a new unique `local_id` and register its queues.
+    pub(crate) fn register(&self) -> Result<(u32, SessionReceivers<S::Receiver>)> {
+        if !self.is_running() {
+            return Err(connection_closed());
+        }
+
+        let mut routes = self.routes.lock()?;
+
+        let mut rng = rand::rng();
+        let local_id = loop {
+            let local_id: u32 = rng.random();
+            // 0 is reserved by the protocol to represent "no id"
+            if local_id != 0 && !routes.contains_key(&local_id) {
+                break local_id;
+            }
+        };
+
+        let (messages_tx, messages) = S::channel();
+        let (acks_tx, acks) = S::channel();
+        routes.insert(
+            local_id,
+            SessionRoute {
+                messages: messages_tx,
+                acks: acks_tx,
+            },
+        );
+
+        Ok((local_id, SessionReceivers { messages, acks }))
+    }
+
+    /// Remove the queues registered for `local_id`.
+    ///
+    /// Returns `false` if they were already removed, e.g. because device closed the session.
+    pub(crate) fn unregister(&self, local_id: u32) -> bool {
+        self.routes
+            .lock()
+            .is_ok_and(|mut routes| routes.remove(&local_id).is_some())
+    }
+
+    /// Connection settings negotiated with device.
+    pub(crate) const fn settings(&self) -> &ADBConnectionSettings {
+        &self.settings
+    }
+
+    /// Allow device to open streams to `local`, as requested by reverse forward rule set on `remote`.
+    pub(crate) fn add_reverse_rule(&self, remote: String, local: String) -> Result<()> {
+        self.reverse_rules.lock()?.insert(remote, local);
+        Ok(())
+    }
+
+    /// Remove reverse forward rule set on `remote`, or all of them if `None`.
+    pub(crate) fn remove_reverse_rules(&self, remote: Option<&str>) -> Result<()> {
+        let mut reverse_rules = self.reverse_rules.lock()?;
+        match remote {
+            Some(remote) => {
+                reverse_rules.remove(remote);
+            }
+            None => reverse_rules.clear(),
+        }
+        Ok(())
+    }
+
+    /// Reverse forward rules, as `(remote, local)` pairs.
+    pub(crate) fn reverse_rules(&self) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .reverse_rules
+            .lock()?
+            .iter()
+            .map(|(remote, local)| (remote.clone(), local.clone()))
+            .collect())
+    }
+
+    /// Whether device is allowed to open a stream to `local`.
+    fn is_reverse_target(&self, local: &str) -> bool {
+        self.reverse_rules
+            .lock()
+            .is_ok_and(|reverse_rules| reverse_rules.values().any(|target| target == local))
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// Route `message` to the session it is addressed to.
+    ///
+    /// Returns the message back if no session can handle it.
+    pub(crate) fn dispatch(
+        &self,
+        message: ADBTransportMessage,
+    ) -> Result<Option<ADBTransportMessage>> {
+        // Device always uses its own id as arg0, and our id as arg1
+        let local_id = message.header().arg1();
+        let mut routes = self.routes.lock()?;
+
+        match message.header().command() {
+            MessageCommand::Okay => match routes.get(&local_id) {
+                Some(route) => {
+                    route.acks.send_message(message);
+                    Ok(None)
+                }
+                None => Ok(Some(message)),
+            },
+            MessageCommand::Write => match routes.get(&local_id) {
+                Some(route) => {
+                    route.messages.send_message(message);
+                    Ok(None)
+                }
+                None => Ok(Some(message)),
+            },
+            MessageCommand::Clse => match routes.remove(&local_id) {
+                Some(route) => {
+                    route.acks.send_message(message.clone());
+                    route.messages.send_message(message);
+                    Ok(None)
+                }
+                None => Ok(Some(message)),
+            },
+            _ => Ok(Some(message)),
+        }
+    }
+
+    /// Mark the connection as closed, and drop all registered queues to wake up their readers.
+    pub(crate) fn close(&self) {
+        self.running.store(false, Ordering::Release);
+        if let Ok(mut routes) = self.routes.lock() {
+            routes.clear();
+        }
+    }
+}
+
+/// Background reader dispatching every received [`ADBTransportMessage`] to the session it belongs to.
+///
+/// Stops the reading thread and disconnects the underlying transport when dropped.
+#[derive(Debug)]
+pub(crate) struct ADBDemultiplexer<T: ADBMessageTransport> {
+    /// Only used to disconnect, behind a lock so that the demultiplexer can be shared between threads.
+    transport: Mutex<T>,
+    routing_table: Arc<ADBRoutingTable>,
+    /// Idle timeout applied by the reading thread once a message started, shared with it.
+    read_timeout: Arc<Mutex<Option<Duration>>>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl<T: ADBMessageTransport> ADBDemultiplexer<T> {
+    /// Spawn the reading thread on an already connected `transport`, using `settings` negotiated with device.
+    ///
+    /// Once a message started, its end is awaited at most `read_timeout`, `None` waiting forever.
+    pub(crate) fn start(
+        transport: T,
+        settings: ADBConnectionSettings,
+        read_timeout: Option<Duration>,
+    ) -> Self {
+        let routing_table = Arc::new(ADBRoutingTable::new(settings));
+        let read_timeout = Arc::new(Mutex::new(read_timeout));
+
+        let reader = {
+            let transport = transport.clone();
+            let routing_table = routing_table.clone();
+            let read_timeout = read_timeout.clone();
+            std::thread::spawn(move || Self::read_loop(transport, &routing_table, &read_timeout))
+        };
+
+        Self {
+            transport: Mutex::new(transport),
+            routing_table,
+            read_timeout,
+            reader: Some(reader),
+        }
+    }
+
+    /// Set idle timeout awaiting the end of a started message, applied from next message on.
+    pub(crate) fn set_read_timeout(&self, read_timeout: Option<Duration>) {
+        if let Ok(mut current) = self.read_timeout.lock() {
+            *current = read_timeout;
+        }
+    }
+
+    pub(crate) fn routing_table(&self) -> Arc<ADBRoutingTable> {
+        self.routing_table.clone()
+    }
+
+    /// Whether connection is still up, i.e. reading thread did not stop.
+    pub(crate) fn is_running(&self) -> bool {
+        self.routing_table.is_running()
+    }
+
+    fn read_loop(
+        mut transport: T,
+        routing_table: &Arc<ADBRoutingTable>,
+        read_timeout: &Mutex<Option<Duration>>,
+    ) {
+        while routing_table.is_running() {
+            let idle_timeout = read_timeout.lock().map_or(None, |timeout| *timeout);
+            let message = match transport.poll_message(POLL_INTERVAL, idle_timeout) {
+                Ok(message) => message,
+                Err(e) if is_timeout(&e) => continue,
+                Err(e) => {
+                    if routing_table.is_running() {
+                        log::error!("error while reading from device, closing connection: {e}");
+                    }
+                    break;
+                }
+            };
+
+            match routing_table.dispatch(message) {
+                Ok(None) => {}
+                Ok(Some(unhandled)) => {
+                    Self::handle_unrouted_message(&mut transport, routing_table, &unhandled);
+                }
+                Err(e) => {
+                    log::error!("cannot dispatch message: {e}");
+                    break;
+                }
+            }
+        }
+
+        routing_table.close();
+    }
+
+    fn handle_unrouted_message(
+        transport: &mut T,
+        routing_table: &Arc<ADBRoutingTable>,
+        message: &ADBTransportMessage,
+    ) {
+        let header = message.header();
+        match header.command() {
+            MessageCommand::Write => {
+                // Data for a session we do not know (anymore), ask device to close it
+                log::debug!(
+                    "received WRTE for unknown local_id {}, closing it",
+                    header.arg1()
+                );
+                Self::send_close(transport, header.arg1(), header.arg0());
+            }
+            MessageCommand::Open => Self::handle_open(transport, routing_table, message),
+            c => log::trace!(
+                "dropping {c} message (arg0={}, arg1={})",
+                header.arg0(),
+                header.arg1()
+            ),
+        }
+    }
+
+    /// Accept a stream opened by device if it targets a reverse forward rule, connecting it to its local endpoint.
+    fn handle_open(
+        transport: &mut T,
+        routing_table: &Arc<ADBRoutingTable>,
+        message: &ADBTransportMessage,
+    ) {
+        let remote_id = message.header().arg0();
+        // With delayed ACK, device tells how many bytes we may send before waiting for its acknowledgements
+        let delayed_ack = routing_table.settings().delayed_ack;
+        let send_window = delayed_ack.then(|| message.header().arg1());
+        let destination = String::from_utf8_lossy(message.payload())
+            .trim_end_matches('\0')
+            .to_string();
+
+        // Device may only open streams to endpoints of reverse rules
+        if !routing_table.is_reverse_target(&destination) {
+            log::debug!(
+                "refusing OPEN request from device to {destination} (remote_id {remote_id})"
+            );
+            Self::send_close(transport, 0, remote_id);
+            return;
+        }
+
+        let (local_id, receivers) = match routing_table.register() {
+            Ok(registered) => registered,
+            Err(e) => {
+                log::error!("cannot accept OPEN request from device: {e}");
+                Self::send_close(transport, 0, remote_id);
+                return;
+            }
+        };
+        let session = ADBSession::new(
+            transport.clone(),
+            local_id,
+            remote_id,
+            receivers,
+            routing_table.clone(),
+            send_window,
+            // Reverse connections last as long as both ends keep them open
+            Deadline::UNBOUNDED,
+        );
+
+        // Connecting may take time, do not hold other sessions meanwhile
+        std::thread::spawn(move || {
+            let stream = parse_local_endpoint(&destination)
+                .and_then(|address| Ok(TcpStream::connect(address)?));
+            let mut session = session;
+            let accepted = stream.and_then(|stream| {
+                let receive_window = if delayed_ack {
+                    INITIAL_DELAYED_ACK_BYTES.to_le_bytes().to_vec()
+                } else {
+                    Vec::new()
+                };
+                session.write_message(ADBTransportMessage::try_new(
+                    MessageCommand::Okay,
+                    local_id,
+                    remote_id,
+                    &receive_window,
+                )?)?;
+                Ok(stream)
+            });
+
+            match accepted {
+                Ok(stream) => bridge_connection(&session, stream, &destination),
+                Err(e) => {
+                    log::warn!("cannot connect device stream to {destination}: {e}");
+                    let _ = session.close();
+                }
+            }
+        });
+    }
+
+    fn send_close(transport: &mut T, local_id: u32, remote_id: u32) {
+        let message = ADBTransportMessage::try_new(MessageCommand::Clse, local_id, remote_id, &[]);
+        if let Err(e) = message.and_then(|message| transport.write_message(message)) {
+            log::error!("error while sending CLSE message: {e}");
+        }
+    }
+}
+
+impl<T: ADBMessageTransport> Drop for ADBDemultiplexer<T> {
+    fn drop(&mut self) {
+        self.routing_table.close();
+
+        // Best effort here
+        if let Ok(transport) = self.transport.get_mut() {
+            let _ = transport.disconnect();
+        }
+
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// Error returned to sessions once the underlying connection is gone.
+pub(crate) fn connection_closed() -> RustADBError {
+    RustADBError::IOError(std::io::Error::new(
+        std::io::ErrorKind::ConnectionAborted,
+        "connection to device has been closed",
+    ))
+}
+
+/// Whether `error` only reports that no message arrived in time.
+fn is_timeout(error: &RustADBError) -> bool {
+    match error {
+        RustADBError::IOError(e) => matches!(
+            e.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        ),
+        #[cfg(feature = "usb")]
+        RustADBError::UsbError(rusb::Error::Timeout) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running_table() -> ADBRoutingTable {
+        ADBRoutingTable::new(ADBConnectionSettings::default())
+    }
+
+    fn message(command: MessageCommand, local_id: u32) -> ADBTransportMessage {
+        ADBTransportMessage::try_new(command, 42, local_id, b"data").expect("cannot build message")
+    }
+
+    #[test]
+    fn test_dispatch_to_session_queues() {
+        let routing_table = running_table();
+        let (local_id, receivers) = routing_table.register().expect("cannot register");
+
+        for command in [MessageCommand::Okay, MessageCommand::Write] {
+            assert!(
+                routing_table
+                    .dispatch(message(command, local_id))
+                    .expect("cannot dispatch")
+                    .is_none()
+            );
+        }
+        assert_eq!(
+            receivers.acks.try_recv().map(|m| m.header().command()),
+            Ok(MessageCommand::Okay)
+        );
+        assert_eq!(
+            receivers.messages.try_recv().map(|m| m.header().command()),
+            Ok(MessageCommand::Write)
+        );
+
+        // Unknown sessions are left to the caller
+        let unrouted = routing_table
+            .dispatch(message(MessageCommand::Write, local_id.wrapping_add(1)))
+            .expect("cannot dispatch");
+        assert!(unrouted.is_some());
+    }
+
+    #[test]
+    fn test_close_unregisters_session() {
+        let routing_table = running_table();
+        let (local_id, receivers) = routing_table.register().expect("cannot register");
+
+        assert!(
+            routing_table
+                .dispatch(message(MessageCommand::Clse, local_id))
+                .expect("cannot dispatch")
+                .is_none()
+        );
+        assert!(receivers.acks.try_recv().is_ok());
+        assert!(receivers.messages.try_recv().is_ok());
+        assert!(!routing_table.unregister(local_id));
+
+        routing_table.close();
+        assert!(routing_table.register().is_err());
+    }
+}
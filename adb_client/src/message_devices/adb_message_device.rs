@@ -1,68 +1,283 @@
-use rand::RngExt;
-use std::{path::Path, time::Duration};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::Path,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
 
 use crate::{
     Result, RustADBError,
     message_devices::{
+        adb_demultiplexer::{self, ADBDemultiplexer, ADBRoutingTable},
         adb_message_transport::ADBMessageTransport,
-        adb_session::ADBSession,
-        adb_transport_message::{
-            ADBTransportMessage, AUTH_RSAPUBLICKEY, AUTH_SIGNATURE, AUTH_TOKEN,
+        adb_session::{ADBSession, read_acked_bytes},
+        adb_transport_message::ADBTransportMessage,
+        auth_handshake::{AuthHandshake, AuthStep},
+        forward_listener::ForwardListener,
+        message_commands::MessageCommand,
+        models::{
+            ADB_VERSION, ADBConnectionSettings, ADBRsaKey, AuthOptions, DELAYED_ACK_FEATURE,
+            INITIAL_DELAYED_ACK_BYTES, MAX_PAYLOAD, ReconnectPolicy, read_adb_private_key,
+            read_vendor_keys,
         },
-        message_commands::{MessageCommand, MessageSubcommand},
-        models::{ADBRsaKey, read_adb_private_key},
-        utils::BinaryEncodable,
     },
-    models::ADBLocalCommand,
+    models::{
+        ADBLocalCommand, Deadline, DeviceBanner, HostFeatures, SyncCompression, TimeoutPhase,
+        Timeouts, timeout_error,
+    },
 };
 
+/// Read timeout used while waiting forever for user to allow debugging.
+const AUTHORIZATION_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Generic structure representing an ADB device reachable over an [`ADBMessageTransport`].
 /// Structure is totally agnostic over which transport is truly used.
-#[derive(Debug)]
+///
+/// Received messages are dispatched to their session by a background demultiplexer,
+/// so that multiple sessions can be used at the same time.
+/// Cloning this structure shares the same underlying connection.
+///
+/// Once connection dropped, e.g. because `adbd` restarted, it is established again following [`ReconnectPolicy`].
+/// The first clone noticing it reconnects, for all of them.
+#[derive(Debug, Clone)]
 pub struct ADBMessageDevice<T: ADBMessageTransport> {
+    /// Transport used to connect to device.
     transport: T,
+    /// Connection to device, shared between clones so that one of them reconnecting serves all of them.
+    connection: Arc<Mutex<Option<Arc<DeviceConnection<T>>>>>,
+    /// Connection [`banner`](Self::banner) and [`features`](Self::features) have been read from.
+    banner_connection: Weak<DeviceConnection<T>>,
+    banner: DeviceBanner,
+    features: Vec<HostFeatures>,
+    sync_compression: SyncCompression,
+    /// Active forward rules, indexed by local endpoint. Shared between clones, and stopped once all of them are dropped.
+    forwards: Arc<Mutex<HashMap<String, ForwardListener>>>,
+    /// Keys tried when authenticating, kept to authenticate again when reconnecting.
+    private_keys: Arc<[ADBRsaKey]>,
+    auth_options: AuthOptions,
+    reconnect_policy: ReconnectPolicy,
+    timeouts: Timeouts,
+}
+
+/// Connection established with device, shared by all clones of an [`ADBMessageDevice`].
+#[derive(Debug)]
+struct DeviceConnection<T: ADBMessageTransport> {
+    /// Connected transport, sending messages to device. Behind a lock so that the connection can be shared between threads.
+    transport: Mutex<T>,
+    demultiplexer: ADBDemultiplexer<T>,
+    banner: DeviceBanner,
 }
 
 impl<T: ADBMessageTransport> ADBMessageDevice<T> {
-    /// Instantiate a new [`ADBMessageTransport`]
-    pub fn new<P: AsRef<Path>>(transport: T, adb_private_key_path: P) -> Result<Self> {
-        let private_key = if let Some(private_key) = read_adb_private_key(&adb_private_key_path)? {
-            private_key
-        } else {
-            log::warn!(
-                "No private key found at path {}. Generating a new random.",
-                adb_private_key_path.as_ref().display()
-            );
-            ADBRsaKey::new_random()?
+    /// Instantiate a new [`ADBMessageDevice`] authenticating with already loaded `private_keys`.
+    pub(crate) fn new_with_private_keys(
+        transport: T,
+        private_keys: Vec<ADBRsaKey>,
+        auth_options: &AuthOptions,
+    ) -> Result<Self> {
+        let mut message_device = Self {
+            transport,
+            connection: Arc::default(),
+            banner_connection: Weak::new(),
+            banner: DeviceBanner::default(),
+            features: Vec::new(),
+            sync_compression: SyncCompression::default(),
+            forwards: Arc::default(),
+            private_keys: private_keys.into(),
+            auth_options: auth_options.clone(),
+            reconnect_policy: ReconnectPolicy::default(),
+            timeouts: Timeouts::default(),
         };
-
-        let mut message_device = Self { transport };
-        message_device.connect(&private_key)?;
+        let connection = message_device.establish(T::connect)?;
+        *message_device.connection.lock()? = Some(connection);
 
         Ok(message_device)
     }
 
+    /// Connect transport using `connect`, and run handshake before dispatching received messages to sessions.
+    fn establish(&mut self, connect: fn(&mut T) -> Result<()>) -> Result<Arc<DeviceConnection<T>>> {
+        self.transport
+            .set_connect_timeout(self.timeouts.connect_value());
+        let (banner, settings) = match connect(&mut self.transport).and_then(|()| self.handshake())
+        {
+            Ok(negotiated) => negotiated,
+            Err(e) => {
+                // Best effort here
+                let _ = self.transport.disconnect();
+                return Err(e);
+            }
+        };
+
+        // Handshake is over, every following message belongs to a session
+        let connection = Arc::new(DeviceConnection {
+            transport: Mutex::new(self.transport.clone()),
+            demultiplexer: ADBDemultiplexer::start(
+                self.transport.clone(),
+                settings,
+                self.timeouts.read_value(),
+            ),
+            banner,
+        });
+        self.read_banner(&connection);
+
+        Ok(connection)
+    }
+
+    /// Read banner of `connection`, if not done yet.
+    fn read_banner(&mut self, connection: &Arc<DeviceConnection<T>>) {
+        if !Weak::ptr_eq(&self.banner_connection, &Arc::downgrade(connection)) {
+            self.banner = connection.banner.clone();
+            self.features = self.banner.host_features();
+            self.banner_connection = Arc::downgrade(connection);
+        }
+    }
+
+    /// Current connection to device.
+    fn connection(&self) -> Result<Arc<DeviceConnection<T>>> {
+        self.connection
+            .lock()?
+            .clone()
+            .ok_or(RustADBError::IOError(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "not connected",
+            )))
+    }
+
+    /// Whether connection to device is still up.
+    pub(crate) fn is_connected(&self) -> bool {
+        self.connection.lock().is_ok_and(|connection| {
+            connection
+                .as_ref()
+                .is_some_and(|connection| connection.demultiplexer.is_running())
+        })
+    }
+
+    /// Set policy applied when connection to device drops.
+    pub(crate) const fn set_reconnect_policy(&mut self, reconnect_policy: ReconnectPolicy) {
+        self.reconnect_policy = reconnect_policy;
+    }
+
+    /// Timeouts applied to each operation run on device.
+    pub(crate) const fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Set timeouts applied to each operation run on device, and when connecting again to it.
+    ///
+    /// Read timeout also bounds how long the current connection, shared with clones, awaits the end of a started message.
+    pub(crate) fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+        if let Ok(connection) = self.connection() {
+            connection
+                .demultiplexer
+                .set_read_timeout(timeouts.read_value());
+        }
+    }
+
+    /// Connect again to device, trying until `timeout` elapsed.
+    ///
+    /// Clones waiting meanwhile for the same connection to be established again reuse it.
+    fn reconnect(&mut self, timeout: Duration) -> Result<()> {
+        let shared_connection = self.connection.clone();
+        let mut shared_connection = shared_connection.lock()?;
+        if let Some(connection) = shared_connection.as_ref()
+            && connection.demultiplexer.is_running()
+        {
+            log::debug!("device already reconnected by a clone");
+            self.read_banner(connection);
+            return Ok(());
+        }
+        // Stop reading from previous connection
+        *shared_connection = None;
+
+        let deadline = Instant::now() + timeout;
+        let retry_interval = self.reconnect_policy.retry_interval_value();
+        loop {
+            match self.establish(T::reconnect) {
+                Ok(connection) => {
+                    log::info!("reconnected to device");
+                    *shared_connection = Some(connection);
+                    return Ok(());
+                }
+                Err(e) if Instant::now() + retry_interval >= deadline => return Err(e),
+                Err(e) => {
+                    log::debug!("cannot reconnect to device yet: {e}");
+                    std::thread::sleep(retry_interval);
+                }
+            }
+        }
+    }
+
+    /// Wait for device to be reachable, connecting to it again if connection dropped, for at most `timeout`.
+    pub(crate) fn wait_for_device(&mut self, timeout: Duration) -> Result<()> {
+        if self.is_connected() {
+            return Ok(());
+        }
+
+        self.reconnect(timeout)
+    }
+
+    /// Whether `error` is caused by connection to device having dropped.
+    fn is_disconnection(&self, error: &RustADBError) -> bool {
+        if !self.is_connected() {
+            return true;
+        }
+
+        match error {
+            RustADBError::IOError(e) => matches!(
+                e.kind(),
+                ErrorKind::ConnectionAborted
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::NotConnected
+            ),
+            #[cfg(feature = "usb")]
+            RustADBError::UsbError(rusb::Error::NoDevice | rusb::Error::Io | rusb::Error::Pipe) => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Run idempotent `operation`, running it again once reconnected if connection dropped meanwhile.
+    pub(crate) fn retry_on_disconnection<R>(
+        &mut self,
+        mut operation: impl FnMut(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        match operation(self) {
+            Err(e) if self.reconnect_policy.is_enabled() && self.is_disconnection(&e) => {
+                log::info!("connection to device dropped ({e}), reconnecting before retrying");
+                self.reconnect(self.reconnect_policy.timeout_value())?;
+                operation(self)
+            }
+            result => result,
+        }
+    }
+
     pub(crate) const fn get_transport_mut(&mut self) -> &mut T {
         &mut self.transport
     }
 
-    /// Send initial connect
-    fn connect(&mut self, private_key: &ADBRsaKey) -> Result<()> {
-        self.get_transport_mut().connect()?;
+    /// Routing table of messages received from device.
+    pub(crate) fn routing_table(&self) -> Result<Arc<ADBRoutingTable>> {
+        Ok(self.connection()?.demultiplexer.routing_table())
+    }
 
-        let message = ADBTransportMessage::try_new(
-            MessageCommand::Cnxn,
-            0x0100_0000,
-            1_048_576,
-            format!("host::{}\0", env!("CARGO_PKG_NAME")).as_bytes(),
-        )?;
+    /// Send initial connect on a freshly connected transport, authenticating if needed.
+    ///
+    /// Returns banner sent by device, and connection settings negotiated with it.
+    fn handshake(&mut self) -> Result<(DeviceBanner, ADBConnectionSettings)> {
+        let private_keys = self.private_keys.clone();
+        let auth_options = self.auth_options.clone();
 
-        self.get_transport_mut().write_message(message)?;
+        self.get_transport_mut().write_message(connect_message()?)?;
 
-        let message = self.get_transport_mut().read_message()?;
+        let message = self.read_handshake_message()?;
 
         // Check if a client is requesting a secure connection and upgrade it if necessary
-        match message.header().command() {
+        let connection_message = match message.header().command() {
             MessageCommand::Stls => {
                 self.get_transport_mut()
                     .write_message(ADBTransportMessage::try_new(
@@ -73,82 +288,140 @@ impl<T: ADBMessageTransport> ADBMessageDevice<T> {
                     )?)?;
                 self.get_transport_mut().upgrade_connection()?;
                 log::debug!("Connection successfully upgraded from TCP to TLS");
-                Ok(())
+                self.read_handshake_message()?
             }
             MessageCommand::Cnxn => {
                 log::debug!("Unencrypted connection established");
-                Ok(())
+                message
             }
             MessageCommand::Auth => {
                 log::debug!("Authentication required");
-                self.auth_handshake(message, private_key)
+                self.auth_handshake(message, &private_keys, &auth_options)?
             }
-            _ => Err(crate::RustADBError::WrongResponseReceived(
-                "Expected CNXN, STLS or AUTH command".to_string(),
-                message.header().command().to_string(),
-            )),
+            _ => {
+                return Err(crate::RustADBError::WrongResponseReceived(
+                    "Expected CNXN, STLS or AUTH command".to_string(),
+                    message.header().command().to_string(),
+                ));
+            }
+        };
+
+        connection_message.assert_command(MessageCommand::Cnxn)?;
+        let (version, max_payload) = (
+            connection_message.header().arg0(),
+            connection_message.header().arg1(),
+        );
+        let device_infos = String::from_utf8(connection_message.into_payload())?;
+        log::debug!("received device info: {device_infos}");
+        let banner: DeviceBanner = device_infos.parse()?;
+        let settings = ADBConnectionSettings::negotiate(version, max_payload, &banner)?;
+        log::debug!("negotiated connection settings: {settings:?}");
+        self.get_transport_mut()
+            .set_protocol_version(settings.version);
+
+        Ok((banner, settings))
+    }
+
+    /// Read next message sent by device during handshake, within connect timeout.
+    fn read_handshake_message(&mut self) -> Result<ADBTransportMessage> {
+        match self.timeouts.connect_value() {
+            Some(timeout) => self
+                .transport
+                .read_message_with_timeout(timeout)
+                .map_err(|e| {
+                    if e.is_timeout() {
+                        timeout_error(TimeoutPhase::Connect).into()
+                    } else {
+                        e
+                    }
+                }),
+            None => self.transport.read_message(),
         }
     }
 
+    /// Authenticate against device, returning its `CNXN` message on success.
+    ///
+    /// See [`AuthHandshake`] for keys tried, and when user is asked to allow debugging.
     fn auth_handshake(
         &mut self,
-        message: ADBTransportMessage,
-        private_key: &ADBRsaKey,
-    ) -> Result<()> {
-        match message.header().command() {
-            MessageCommand::Auth => {
-                log::debug!("Authentication required");
-            }
-            _ => return Ok(()),
-        }
-
-        // At this point, we should have received an AUTH message with arg0 == 1
-        let auth_message = match message.header().arg0() {
-            AUTH_TOKEN => message,
-            v => {
-                return Err(RustADBError::ADBRequestFailed(format!(
-                    "Received AUTH message with type != 1 ({v})"
-                )));
+        mut message: ADBTransportMessage,
+        private_keys: &[ADBRsaKey],
+        auth_options: &AuthOptions,
+    ) -> Result<ADBTransportMessage> {
+        let mut auth = AuthHandshake::new(private_keys, auth_options);
+        let (public_key_message, fingerprint) = loop {
+            match auth.next(message)? {
+                AuthStep::Send(request) => {
+                    self.transport.write_message(request)?;
+                    message = self.read_handshake_message()?;
+                }
+                AuthStep::WaitForUser(request, fingerprint) => break (request, fingerprint),
+                AuthStep::Connected(message) => return Ok(message),
             }
         };
 
-        let sign = private_key.sign(auth_message.into_payload())?;
+        self.transport.write_message(public_key_message)?;
+
+        let deadline = auth_options
+            .authorization_timeout_value()
+            .map(|timeout| Instant::now() + timeout);
+        let response = loop {
+            let read_timeout = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => AUTHORIZATION_POLL_INTERVAL,
+            };
+            if read_timeout.is_zero() {
+                return Err(RustADBError::Unauthorized(fingerprint));
+            }
 
-        let message = ADBTransportMessage::try_new(MessageCommand::Auth, AUTH_SIGNATURE, 0, &sign)?;
+            match self.transport.read_message_with_timeout(read_timeout) {
+                Ok(response) => break response,
+                Err(e) if e.is_timeout() => {}
+                Err(e) => return Err(e),
+            }
+        };
 
-        self.transport.write_message(message)?;
+        auth.authorized(response)
+    }
 
-        let received_response = self.transport.read_message()?;
+    /// Active forward rules of this device.
+    pub(crate) fn forwards(&self) -> &Mutex<HashMap<String, ForwardListener>> {
+        &self.forwards
+    }
 
-        if received_response.header().command() == MessageCommand::Cnxn {
-            log::info!(
-                "Authentication OK, device info {}",
-                String::from_utf8(received_response.into_payload())?
-            );
-            return Ok(());
+    /// Clone sharing the same connection, but not its forward rules.
+    ///
+    /// Used by background tasks, that must not keep forward rules alive by themselves.
+    pub(crate) fn detached_clone(&self) -> Self {
+        Self {
+            forwards: Arc::default(),
+            ..self.clone()
         }
+    }
 
-        let mut pubkey = private_key.android_pubkey_encode()?.into_bytes();
-        pubkey.push(b'\0');
+    /// Compression used by file transfers.
+    pub(crate) const fn sync_compression(&self) -> SyncCompression {
+        self.sync_compression
+    }
 
-        let message =
-            ADBTransportMessage::try_new(MessageCommand::Auth, AUTH_RSAPUBLICKEY, 0, &pubkey)?;
+    /// Set compression used by file transfers, when device supports sync version 2.
+    pub(crate) const fn set_sync_compression(&mut self, compression: SyncCompression) {
+        self.sync_compression = compression;
+    }
 
-        self.transport.write_message(message)?;
+    /// Banner sent by connected device when connection has been established.
+    pub(crate) const fn banner(&self) -> &DeviceBanner {
+        &self.banner
+    }
 
-        let response = self
-            .transport
-            .read_message_with_timeout(Duration::from_secs(10))
-            .and_then(|message| {
-                message.assert_command(MessageCommand::Cnxn)?;
-                Ok(message)
-            })?;
+    /// Features advertised by connected device, and known by this crate.
+    pub(crate) fn features(&self) -> &[HostFeatures] {
+        &self.features
+    }
 
-        log::info!(
-            "Authentication OK, device info {}",
-            String::from_utf8(response.into_payload())?
-        );
-        Ok(())
+    /// Whether connected device advertised given feature.
+    pub(crate) fn has_feature(&self, feature: &HostFeatures) -> bool {
+        self.features.contains(feature)
     }
 
     pub(crate) fn open_synchronization_session(&mut self) -> Result<ADBSession<T>> {
@@ -156,57 +429,122 @@ impl<T: ADBMessageTransport> ADBMessageDevice<T> {
     }
 
     pub(crate) fn open_session(&mut self, cmd: &ADBLocalCommand) -> Result<ADBSession<T>> {
-        let mut rng = rand::rng();
-        let local_id: u32 = rng.random();
+        if !self.reconnect_policy.is_enabled() {
+            return self.try_open_session(cmd);
+        }
+
+        if !self.is_connected() {
+            log::info!("connection to device dropped, reconnecting");
+            self.reconnect(self.reconnect_policy.timeout_value())?;
+        }
+
+        match self.try_open_session(cmd) {
+            // Device did not accept the session, service could not have been started yet
+            Err(e) if self.is_disconnection(&e) => {
+                log::info!("connection to device dropped ({e}), reconnecting");
+                self.reconnect(self.reconnect_policy.timeout_value())?;
+                self.try_open_session(cmd)
+            }
+            result => result,
+        }
+    }
 
+    fn try_open_session(&mut self, cmd: &ADBLocalCommand) -> Result<ADBSession<T>> {
+        let connection = self.connection()?;
+        // Connection may have been established again by a clone
+        self.read_banner(&connection);
+        let mut transport = connection.transport.lock()?.clone();
+        let routing_table = connection.demultiplexer.routing_table();
+        let delayed_ack = routing_table.settings().delayed_ack;
+        let (local_id, receivers) = routing_table.register()?;
+
+        // With delayed ACK, we tell device how many bytes it may send before waiting for our acknowledgements
+        let receive_window = if delayed_ack {
+            INITIAL_DELAYED_ACK_BYTES
+        } else {
+            0
+        };
         let message = ADBTransportMessage::try_new(
             MessageCommand::Open,
             local_id, // Our 'local-id'
-            0,
+            receive_window,
             cmd.to_string().as_bytes(),
         )?;
-        self.transport.write_message(message)?;
 
-        let response = self.transport.read_message()?;
+        // Deadline of the operation run on this session starts as soon as it is opened
+        let deadline = Deadline::start(&self.timeouts);
+        let response = transport
+            .write_message(message)
+            .and_then(|()| deadline.recv(&receivers.acks, adb_demultiplexer::connection_closed))
+            .inspect_err(|_| {
+                routing_table.unregister(local_id);
+            })?;
 
         if response.header().command() != MessageCommand::Okay {
+            routing_table.unregister(local_id);
             return Err(RustADBError::ADBRequestFailed(format!(
                 "Open session failed: got {} in response instead of OKAY",
                 response.header().command()
             )));
         }
 
-        if response.header().arg1() != local_id {
-            return Err(RustADBError::ADBRequestFailed(format!(
-                "Open session failed: responses used {} for our local_id instead of {local_id}",
-                response.header().arg1()
-            )));
-        }
+        // With delayed ACK, device tells how many bytes we may send before waiting for its acknowledgements
+        let send_window = if delayed_ack {
+            Some(read_acked_bytes(&response)?)
+        } else {
+            None
+        };
 
         Ok(ADBSession::new(
-            self.transport.clone(),
+            transport,
             local_id,
             response.header().arg0(),
+            receivers,
+            routing_table,
+            send_window,
+            deadline,
         ))
     }
+}
 
-    pub(crate) fn end_transaction(&mut self, session: &mut ADBSession<T>) -> Result<()> {
-        let quit_buffer = MessageSubcommand::Quit.with_arg(0u32);
-        session.send_and_expect_okay(ADBTransportMessage::try_new(
-            MessageCommand::Write,
-            session.local_id(),
-            session.remote_id(),
-            &quit_buffer.encode(),
-        )?)?;
+/// Load private key at `adb_private_key_path`, followed by keys from `ADB_VENDOR_KEYS`.
+///
+/// A new private key is generated if missing, only saved at `adb_private_key_path` if `auth_options` ask for it.
+pub(crate) fn load_private_keys<P: AsRef<Path>>(
+    adb_private_key_path: P,
+    auth_options: &AuthOptions,
+) -> Result<Vec<ADBRsaKey>> {
+    let private_key = if let Some(private_key) = read_adb_private_key(&adb_private_key_path)? {
+        private_key
+    } else {
+        let private_key = ADBRsaKey::new_random()?;
+        if auth_options.save_generated_key_value() {
+            log::info!(
+                "No private key found at path {}. Saving a new one there.",
+                adb_private_key_path.as_ref().display()
+            );
+            private_key.save(&adb_private_key_path)?;
+        } else {
+            log::warn!(
+                "No private key found at path {}. Using a new one kept in memory, which device will ask to authorize again at next connection.",
+                adb_private_key_path.as_ref().display()
+            );
+        }
+        private_key
+    };
+    // Like official client, keys from `ADB_VENDOR_KEYS` are tried after default one
+    let mut private_keys = vec![private_key];
+    private_keys.extend(read_vendor_keys());
 
-        let _discard_close = self.transport.read_message()?;
-        Ok(())
-    }
+    Ok(private_keys)
 }
 
-impl<T: ADBMessageTransport> Drop for ADBMessageDevice<T> {
-    fn drop(&mut self) {
-        // Best effort here
-        let _ = self.get_transport_mut().disconnect();
-    }
+/// Initial `CNXN` message, advertising host capabilities.
+pub(crate) fn connect_message() -> Result<ADBTransportMessage> {
+    ADBTransportMessage::try_new(
+        MessageCommand::Cnxn,
+        ADB_VERSION,
+        MAX_PAYLOAD,
+        format!("host::features={DELAYED_ACK_FEATURE}\0").as_bytes(),
+    )
 }
@@ -3,12 +3,12 @@ use crate::{
     message_devices::{
         adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
     },
-    models::{AdbStatResponse, RemountInfo},
-};
-use std::{
-    io::{Read, Write},
-    path::Path,
+    models::{
+        AdbStatResponse, CancellationToken, InstallOptions, ProgressObserver, PushOptions,
+        RemountInfo,
+    },
 };
+use std::io::{Read, Write};
 
 impl<T: ADBMessageTransport> ADBDeviceExt for ADBMessageDevice<T> {
     #[inline]
@@ -22,8 +22,12 @@ impl<T: ADBMessageTransport> ADBDeviceExt for ADBMessageDevice<T> {
     }
 
     #[inline]
-    fn shell(&mut self, reader: &mut dyn Read, writer: Box<dyn Write + Send>) -> Result<()> {
-        self.shell(reader, writer)
+    fn shell(
+        &mut self,
+        reader: &mut dyn Read,
+        writer: Box<dyn Write + Send>,
+    ) -> Result<Option<u8>> {
+        self.shell_with_window_size(reader, writer, None)
     }
 
     #[inline]
@@ -36,6 +40,11 @@ impl<T: ADBMessageTransport> ADBDeviceExt for ADBMessageDevice<T> {
         self.exec(command, reader, writer)
     }
 
+    #[inline]
+    fn exec_with_input(&mut self, command: &str, input: &mut dyn Read) -> Result<Vec<u8>> {
+        self.exec_with_input(command, input)
+    }
+
     #[inline]
     fn stat(&mut self, remote_path: &dyn AsRef<str>) -> Result<AdbStatResponse> {
         self.stat(remote_path)
@@ -47,8 +56,13 @@ impl<T: ADBMessageTransport> ADBDeviceExt for ADBMessageDevice<T> {
     }
 
     #[inline]
-    fn push(&mut self, stream: &mut dyn Read, path: &dyn AsRef<str>) -> Result<()> {
-        self.push(stream, path)
+    fn push_with_options(
+        &mut self,
+        stream: &mut dyn Read,
+        path: &dyn AsRef<str>,
+        options: PushOptions,
+    ) -> Result<()> {
+        self.push_with_options(stream, path, options)
     }
 
     #[inline]
@@ -67,8 +81,15 @@ impl<T: ADBMessageTransport> ADBDeviceExt for ADBMessageDevice<T> {
     }
 
     #[inline]
-    fn install(&mut self, apk_path: &dyn AsRef<Path>, user: Option<&str>) -> Result<()> {
-        self.install(apk_path, user)
+    fn install_from_reader_with_progress(
+        &mut self,
+        apk: &mut dyn Read,
+        size: u64,
+        options: &InstallOptions,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        self.install_from_reader_with_progress(apk, size, options, observer, cancellation)
     }
 
     #[inline]
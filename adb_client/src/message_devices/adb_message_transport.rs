@@ -17,9 +17,36 @@ pub trait ADBMessageTransport: ADBTransport + Clone + Send + 'static {
         Ok(())
     }
 
+    /// Connect again to device once previous connection dropped.
+    ///
+    /// Transports reaching a device which may have changed meanwhile, e.g. enumerated again on USB bus, locate it first.
+    fn reconnect(&mut self) -> Result<()> {
+        self.connect()
+    }
+
+    /// Set longest time given to establish connection to device, `None` waiting as long as operating system allows.
+    ///
+    /// Transports whose connection cannot wait forever, e.g. over USB, ignore it.
+    fn set_connect_timeout(&mut self, _timeout: Option<Duration>) {}
+
+    /// Set protocol version negotiated with device on current connection, telling whether payload checksums are used.
+    fn set_protocol_version(&mut self, version: u32);
+
     /// Read a message using given timeout on the underlying transport
     fn read_message_with_timeout(&mut self, read_timeout: Duration) -> Result<ADBTransportMessage>;
 
+    /// Read a message, waiting at most `poll_timeout` for it to start, a timeout being reported if none did.
+    ///
+    /// Transports which cannot resume a read interrupted by a timeout, e.g. over USB, then read the rest of the message
+    /// waiting at most `read_timeout` for each part of it, `None` waiting forever.
+    fn poll_message(
+        &mut self,
+        poll_timeout: Duration,
+        _read_timeout: Option<Duration>,
+    ) -> Result<ADBTransportMessage> {
+        self.read_message_with_timeout(poll_timeout)
+    }
+
     /// Read data to underlying connection, using default timeout
     fn read_message(&mut self) -> Result<ADBTransportMessage> {
         self.read_message_with_timeout(DEFAULT_READ_TIMEOUT)
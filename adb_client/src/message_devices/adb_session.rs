@@ -1,211 +1,249 @@
 use std::{
-    io::{Cursor, Read, Seek},
-    time::Duration,
+    io::ErrorKind,
+    sync::{Arc, Mutex, mpsc::Receiver},
 };
 
-use byteorder::ReadBytesExt;
-
 use crate::{
-    AdbStatResponse, BinaryDecodable, Result, RustADBError,
+    Result, RustADBError,
     message_devices::{
+        adb_demultiplexer::{ADBRoutingTable, SessionReceivers, connection_closed},
         adb_message_transport::ADBMessageTransport,
         adb_transport_message::ADBTransportMessage,
-        message_commands::{MessageCommand, MessageSubcommand},
-        utils::BinaryEncodable,
+        message_commands::MessageCommand,
     },
+    models::Deadline,
 };
 
-const BUFFER_SIZE: usize = 65535;
+/// State shared between all clones of an [`ADBSession`].
+///
+/// Session is unregistered and closed when the last clone is dropped.
+#[derive(Debug)]
+struct ADBSessionInner<T: ADBMessageTransport> {
+    transport: Mutex<T>,
+    local_id: u32,
+    remote_id: u32,
+    messages: Mutex<Receiver<ADBTransportMessage>>,
+    acks: Mutex<Receiver<ADBTransportMessage>>,
+    /// Held while sending data. With delayed ACK, holds the amount of bytes device still accepts before acknowledging them,
+    /// otherwise `None` and a single `WRTE` may be in flight.
+    send_window: Mutex<Option<i64>>,
+    /// Whether received `WRTE` messages are acknowledged by amount of bytes.
+    delayed_ack: bool,
+    /// Maximum payload of a single message.
+    max_payload: usize,
+    routing_table: Arc<ADBRoutingTable>,
+    /// Timeouts bounding every wait for device on this session.
+    deadline: Deadline,
+}
+
+impl<T: ADBMessageTransport> Drop for ADBSessionInner<T> {
+    fn drop(&mut self) {
+        // Nothing to do if device already closed its side
+        if !self.routing_table.unregister(self.local_id) {
+            return;
+        }
+
+        // Best effort here
+        if let Ok(transport) = self.transport.get_mut()
+            && let Ok(message) = ADBTransportMessage::try_new(
+                MessageCommand::Clse,
+                self.local_id,
+                self.remote_id,
+                &[],
+            )
+        {
+            let _ = transport.write_message(message);
+        }
+    }
+}
 
 /// Represent a session between an `ADBDevice` and remote `adbd`.
+///
+/// Each session has its own message queues, fed by the device demultiplexer.
+/// Cloned sessions share the same stream, allowing to read and write from different threads.
 #[derive(Debug)]
 pub struct ADBSession<T: ADBMessageTransport> {
     transport: T,
-    local_id: u32,
-    remote_id: u32,
+    inner: Arc<ADBSessionInner<T>>,
+}
+
+impl<T: ADBMessageTransport> Clone for ADBSession<T> {
+    fn clone(&self) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            inner: self.inner.clone(),
+        }
+    }
 }
 
 impl<T: ADBMessageTransport> ADBSession<T> {
-    pub const fn new(transport: T, local_id: u32, remote_id: u32) -> Self {
+    pub(crate) fn new(
+        transport: T,
+        local_id: u32,
+        remote_id: u32,
+        receivers: SessionReceivers,
+        routing_table: Arc<ADBRoutingTable>,
+        send_window: Option<u32>,
+        deadline: Deadline,
+    ) -> Self {
+        let max_payload = routing_table.settings().max_payload;
         Self {
-            transport,
-            local_id,
-            remote_id,
+            transport: transport.clone(),
+            inner: Arc::new(ADBSessionInner {
+                transport: Mutex::new(transport),
+                local_id,
+                remote_id,
+                messages: Mutex::new(receivers.messages),
+                acks: Mutex::new(receivers.acks),
+                send_window: Mutex::new(send_window.map(i64::from)),
+                delayed_ack: send_window.is_some(),
+                max_payload,
+                routing_table,
+                deadline,
+            }),
         }
     }
 
-    pub const fn get_transport_mut(&mut self) -> &mut T {
-        &mut self.transport
+    pub fn local_id(&self) -> u32 {
+        self.inner.local_id
     }
 
-    pub const fn local_id(&self) -> u32 {
-        self.local_id
+    pub fn remote_id(&self) -> u32 {
+        self.inner.remote_id
     }
 
-    pub const fn remote_id(&self) -> u32 {
-        self.remote_id
+    /// Write a message on the underlying transport
+    pub(crate) fn write_message(&mut self, message: ADBTransportMessage) -> Result<()> {
+        self.transport.write_message(message)
+    }
+
+    /// Read next `WRTE` or `CLSE` message sent to this session.
+    pub(crate) fn read_message(&self) -> Result<ADBTransportMessage> {
+        self.inner
+            .deadline
+            .recv(&*self.inner.messages.lock()?, connection_closed)
     }
 
     /// Receive a message and acknowledge it by replying with an `OKAY` command
     pub(crate) fn recv_and_reply_okay(&mut self) -> Result<ADBTransportMessage> {
-        let message = self.transport.read_message()?;
-        self.transport.write_message(ADBTransportMessage::try_new(
-            MessageCommand::Okay,
-            self.local_id,
-            self.remote_id,
-            &[],
-        )?)?;
+        let message = self.read_message()?;
+        if message.header().command() == MessageCommand::Write {
+            // With delayed ACK, device is told how many bytes have been consumed
+            let acked_bytes = if self.inner.delayed_ack {
+                u32::try_from(message.payload().len())?
+                    .to_le_bytes()
+                    .to_vec()
+            } else {
+                Vec::new()
+            };
+            self.write_message(ADBTransportMessage::try_new(
+                MessageCommand::Okay,
+                self.local_id(),
+                self.remote_id(),
+                &acked_bytes,
+            )?)?;
+        }
         Ok(message)
     }
 
-    /// Expect a message with an `OKAY` command after sending a message.
-    pub(crate) fn send_and_expect_okay(
-        &mut self,
-        message: ADBTransportMessage,
-    ) -> Result<ADBTransportMessage> {
-        self.transport.write_message(message)?;
-
-        self.transport.read_message().and_then(|message| {
-            message.assert_command(MessageCommand::Okay)?;
-            Ok(message)
-        })
-    }
-
-    pub(crate) fn recv_file<W: std::io::Write>(
-        &mut self,
-        mut output: W,
-    ) -> std::result::Result<(), RustADBError> {
-        let mut len: Option<u64> = None;
-        loop {
-            let payload = self.recv_and_reply_okay()?.into_payload();
-            let mut rdr = Cursor::new(&payload);
-            while rdr.position() != payload.len() as u64 {
-                match len.take() {
-                    Some(0) | None => {
-                        rdr.seek_relative(4)?;
-                        len.replace(u64::from(rdr.read_u32::<byteorder::LittleEndian>()?));
-                    }
-                    Some(length) => {
-                        let remaining_bytes = payload.len() as u64 - rdr.position();
-                        if length < remaining_bytes {
-                            std::io::copy(&mut rdr.by_ref().take(length), &mut output)?;
-                        } else {
-                            std::io::copy(&mut rdr.take(remaining_bytes), &mut output)?;
-                            len.replace(length - remaining_bytes);
-                            // this payload is now exhausted
-                            break;
-                        }
-                    }
-                }
+    /// Send `data` to device in `WRTE` messages of at most the negotiated payload size.
+    ///
+    /// Without delayed ACK, each message is acknowledged before sending the next one.
+    /// Otherwise messages are sent as long as device accepts more bytes, and acknowledgements are processed on the way.
+    /// Fails with an [`ErrorKind::BrokenPipe`] error if device closed this session.
+    pub(crate) fn write_data(&mut self, data: &[u8]) -> Result<()> {
+        let inner = self.inner.clone();
+        let mut send_window = inner.send_window.lock()?;
+        let acks = inner.acks.lock()?;
+
+        for chunk in data.chunks(inner.max_payload) {
+            let message = ADBTransportMessage::try_new(
+                MessageCommand::Write,
+                self.local_id(),
+                self.remote_id(),
+                chunk,
+            )?;
+
+            let Some(window) = send_window.as_mut() else {
+                self.write_message(message)?;
+                let ack = inner.deadline.recv(&acks, connection_closed)?;
+                check_ack(&ack)?;
+                continue;
+            };
+
+            // Process acknowledgements already received, then wait until device accepts more bytes
+            while let Ok(ack) = acks.try_recv() {
+                *window += i64::from(read_acked_bytes(&ack)?);
             }
-            if Cursor::new(&payload[(payload.len() - 8)..(payload.len() - 4)])
-                .read_u32::<byteorder::LittleEndian>()?
-                == MessageSubcommand::Done as u32
-            {
-                break;
+            while *window <= 0 {
+                let ack = inner.deadline.recv(&acks, connection_closed)?;
+                *window += i64::from(read_acked_bytes(&ack)?);
             }
+
+            self.write_message(message)?;
+            *window -= i64::try_from(chunk.len())?;
         }
+
         Ok(())
     }
 
-    pub(crate) fn push_file<R: std::io::Read>(&mut self, mut reader: R) -> Result<()> {
-        let mut buffer = vec![0; BUFFER_SIZE].into_boxed_slice();
-        let amount_read = reader.read(&mut buffer)?;
-        let subcommand_data = MessageSubcommand::Data.with_arg(u32::try_from(amount_read)?);
-
-        let mut serialized_message = subcommand_data.encode();
-        serialized_message.append(&mut buffer[..amount_read].to_vec());
-
-        let message = ADBTransportMessage::try_new(
-            MessageCommand::Write,
-            self.local_id(),
-            self.remote_id(),
-            &serialized_message,
-        )?;
-
-        self.send_and_expect_okay(message)?;
-
+    /// Read every `WRTE` message until device closes this session, and return their concatenated payloads.
+    pub(crate) fn read_until_close(&mut self) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
         loop {
-            let mut buffer = vec![0; BUFFER_SIZE].into_boxed_slice();
-
-            match reader.read(&mut buffer) {
-                Ok(0) => {
-                    // Currently file mtime is not forwarded
-                    let subcommand_data = MessageSubcommand::Done.with_arg(0);
-
-                    let message = ADBTransportMessage::try_new(
-                        MessageCommand::Write,
-                        self.local_id(),
-                        self.remote_id(),
-                        &subcommand_data.encode(),
-                    )?;
-
-                    self.send_and_expect_okay(message)?;
-
-                    // Command should end with a Write => Okay
-                    let received = self.transport.read_message()?;
-                    match received.header().command() {
-                        MessageCommand::Write => return Ok(()),
-                        c => {
-                            return Err(RustADBError::ADBRequestFailed(format!(
-                                "Wrong command received {c}"
-                            )));
-                        }
-                    }
-                }
-                Ok(size) => {
-                    let subcommand_data = MessageSubcommand::Data.with_arg(u32::try_from(size)?);
-
-                    let mut serialized_message = subcommand_data.encode();
-                    serialized_message.append(&mut buffer[..size].to_vec());
-
-                    let message = ADBTransportMessage::try_new(
-                        MessageCommand::Write,
-                        self.local_id(),
-                        self.remote_id(),
-                        &serialized_message,
-                    )?;
-
-                    self.send_and_expect_okay(message)?;
-                }
-                Err(e) => {
-                    return Err(RustADBError::IOError(e));
-                }
+            let message = self.recv_and_reply_okay()?;
+            if message.header().command() == MessageCommand::Clse {
+                return Ok(output);
             }
+            output.extend_from_slice(message.payload());
         }
     }
 
-    pub(crate) fn stat_with_explicit_ids(&mut self, remote_path: &str) -> Result<AdbStatResponse> {
-        let stat_buffer = MessageSubcommand::Stat.with_arg(u32::try_from(remote_path.len())?);
-        let message = ADBTransportMessage::try_new(
-            MessageCommand::Write,
-            self.local_id(),
-            self.remote_id(),
-            &stat_buffer.encode(),
-        )?;
-        self.send_and_expect_okay(message)?;
-        self.send_and_expect_okay(ADBTransportMessage::try_new(
-            MessageCommand::Write,
-            self.local_id(),
-            self.remote_id(),
-            remote_path.as_bytes(),
-        )?)?;
-
-        let response = self.transport.read_message()?;
-        // Skip first 4 bytes as this is the literal "STAT".
-        // Interesting part starts right after
-
-        AdbStatResponse::decode(&response.into_payload()[4..])
+    /// Close this session from our side, waking up any pending reader.
+    ///
+    /// Does nothing if session has already been closed, by either side.
+    pub(crate) fn close(&self) -> Result<()> {
+        if !self.inner.routing_table.unregister(self.local_id()) {
+            return Ok(());
+        }
+
+        self.transport
+            .clone()
+            .write_message(ADBTransportMessage::try_new(
+                MessageCommand::Clse,
+                self.local_id(),
+                self.remote_id(),
+                &[],
+            )?)
+    }
+
+    /// Expect device to close this session.
+    pub(crate) fn expect_close(&self) -> Result<()> {
+        self.read_message()?.assert_command(MessageCommand::Clse)
     }
 }
 
-impl<T: ADBMessageTransport> Drop for ADBSession<T> {
-    fn drop(&mut self) {
-        // some devices will repeat the trailing CLSE command to ensure
-        // the client has acknowledged it. Read them quickly if present.
-        while let Ok(_discard_close_message) = self
-            .transport
-            .read_message_with_timeout(Duration::from_millis(20))
-        {}
+/// Check that `ack` received after a `WRTE` message is an `OKAY`.
+fn check_ack(ack: &ADBTransportMessage) -> Result<()> {
+    match ack.header().command() {
+        MessageCommand::Okay => Ok(()),
+        MessageCommand::Clse => Err(RustADBError::IOError(ErrorKind::BrokenPipe.into())),
+        c => Err(RustADBError::WrongResponseReceived(
+            c.to_string(),
+            MessageCommand::Okay.to_string(),
+        )),
     }
 }
+
+/// Read the amount of bytes acknowledged by an `OKAY` message, when delayed ACK is used.
+pub(crate) fn read_acked_bytes(ack: &ADBTransportMessage) -> Result<u32> {
+    check_ack(ack)?;
+    let acked_bytes = ack.payload().as_slice().try_into().map_err(|_| {
+        RustADBError::ADBRequestFailed(format!(
+            "invalid OKAY payload of {} bytes, expected acknowledged bytes",
+            ack.payload().len()
+        ))
+    })?;
+
+    Ok(u32::from_le_bytes(acked_bytes))
+}
@@ -1,3 +1,8 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+};
+
 use byteorder::{ByteOrder, LittleEndian};
 
 use crate::{
@@ -9,13 +14,16 @@ pub const AUTH_TOKEN: u32 = 1;
 pub const AUTH_SIGNATURE: u32 = 2;
 pub const AUTH_RSAPUBLICKEY: u32 = 3;
 
-#[derive(Debug)]
+/// First protocol version where payload checksums are neither computed nor checked anymore.
+pub const ADB_VERSION_SKIP_CHECKSUM: u32 = 0x0100_0001;
+
+#[derive(Debug, Clone)]
 pub struct ADBTransportMessage {
     header: ADBTransportMessageHeader,
     payload: Vec<u8>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[repr(C)]
 pub struct ADBTransportMessageHeader {
     command: MessageCommand, /* command identifier constant      */
@@ -33,7 +41,8 @@ impl ADBTransportMessageHeader {
             arg0,
             arg1,
             data_length: u32::try_from(data.len())?,
-            data_crc32: Self::compute_crc32(data),
+            // Only computed when sent, if peer needs it
+            data_crc32: 0,
             magic: Self::compute_magic(command),
         })
     }
@@ -121,9 +130,17 @@ impl ADBTransportMessage {
         Self { header, payload }
     }
 
-    pub fn check_message_integrity(&self) -> bool {
+    /// Check message magic, and payload checksum if `check_crc32` is set.
+    pub fn check_message_integrity(&self, check_crc32: bool) -> bool {
         ADBTransportMessageHeader::compute_magic(self.header.command) == self.header.magic
-            && ADBTransportMessageHeader::compute_crc32(&self.payload) == self.header.data_crc32
+            && (!check_crc32
+                || ADBTransportMessageHeader::compute_crc32(&self.payload)
+                    == self.header.data_crc32)
+    }
+
+    /// Fill payload checksum in message header.
+    pub(crate) fn compute_checksum(&mut self) {
+        self.header.data_crc32 = ADBTransportMessageHeader::compute_crc32(&self.payload);
     }
 
     pub fn assert_command(&self, expected_command: MessageCommand) -> Result<()> {
@@ -158,3 +175,68 @@ impl TryFrom<[u8; 24]> for ADBTransportMessageHeader {
         Self::decode(&value)
     }
 }
+
+/// Tells whether payload checksums are used on a connection, depending on the negotiated protocol version.
+///
+/// Shared between clones of a transport. Until a version has been negotiated, checksums are computed
+/// for outgoing messages but not checked on incoming ones, as peer may already have stopped computing them.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChecksumPolicy {
+    /// Negotiated protocol version, 0 if not negotiated yet.
+    protocol_version: Arc<AtomicU32>,
+}
+
+impl ChecksumPolicy {
+    pub(crate) fn set_protocol_version(&self, version: u32) {
+        self.protocol_version.store(version, Ordering::Release);
+    }
+
+    /// Fill checksum of a message about to be sent, if peer needs it.
+    pub(crate) fn prepare_outgoing(&self, message: &mut ADBTransportMessage) {
+        if self.protocol_version.load(Ordering::Acquire) < ADB_VERSION_SKIP_CHECKSUM {
+            message.compute_checksum();
+        }
+    }
+
+    /// Check integrity of a received message, its payload checksum only being checked if peer computes it.
+    pub(crate) fn check_incoming(&self, message: &ADBTransportMessage) -> Result<()> {
+        let version = self.protocol_version.load(Ordering::Acquire);
+        let check_crc32 = version != 0 && version < ADB_VERSION_SKIP_CHECKSUM;
+
+        if message.check_message_integrity(check_crc32) {
+            return Ok(());
+        }
+        Err(RustADBError::InvalidIntegrity(
+            ADBTransportMessageHeader::compute_crc32(message.payload()),
+            message.header().data_crc32(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ADB_VERSION_SKIP_CHECKSUM, ADBTransportMessage, ChecksumPolicy};
+    use crate::message_devices::message_commands::MessageCommand;
+
+    #[test]
+    fn test_checksum_policy() {
+        let policy = ChecksumPolicy::default();
+        let mut message =
+            ADBTransportMessage::try_new(MessageCommand::Write, 1, 2, b"payload").unwrap();
+        // Peer may not compute checksums anymore, it is not known yet
+        assert!(policy.check_incoming(&message).is_ok());
+
+        policy.set_protocol_version(ADB_VERSION_SKIP_CHECKSUM - 1);
+        assert!(policy.check_incoming(&message).is_err());
+        policy.prepare_outgoing(&mut message);
+        assert_ne!(message.header().data_crc32(), 0);
+        assert!(policy.check_incoming(&message).is_ok());
+
+        policy.set_protocol_version(ADB_VERSION_SKIP_CHECKSUM);
+        let mut message =
+            ADBTransportMessage::try_new(MessageCommand::Write, 1, 2, b"payload").unwrap();
+        policy.prepare_outgoing(&mut message);
+        assert_eq!(message.header().data_crc32(), 0);
+        assert!(policy.check_incoming(&message).is_ok());
+    }
+}
@@ -0,0 +1,313 @@
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc::{UnboundedReceiver, UnboundedSender, WeakUnboundedSender, unbounded_channel},
+    task::JoinHandle,
+};
+
+use crate::{
+    Result, RustADBError,
+    message_devices::{
+        adb_demultiplexer::{ADBRoutingTable, SessionReceivers, SessionSender, connection_closed},
+        adb_session::read_acked_bytes,
+        adb_transport_message::{ADBTransportMessage, ADBTransportMessageHeader, ChecksumPolicy},
+        async_adb_session::AsyncADBSession,
+        auth_handshake::{AuthHandshake, AuthStep},
+        message_commands::MessageCommand,
+        models::{ADBConnectionSettings, ADBRsaKey, AuthOptions, INITIAL_DELAYED_ACK_BYTES},
+    },
+    models::{DeviceBanner, HostFeatures},
+};
+
+impl SessionSender for UnboundedSender<ADBTransportMessage> {
+    type Receiver = UnboundedReceiver<ADBTransportMessage>;
+
+    fn channel() -> (Self, Self::Receiver) {
+        unbounded_channel()
+    }
+
+    fn send_message(&self, message: ADBTransportMessage) {
+        let _ = self.send(message);
+    }
+}
+
+/// Table mapping each opened session `local_id` to its message queues, fed by the reading task.
+pub(crate) type AsyncRoutingTable = ADBRoutingTable<UnboundedSender<ADBTransportMessage>>;
+
+/// Receiving side of a session registered on an [`AsyncRoutingTable`].
+pub(crate) type AsyncSessionReceivers = SessionReceivers<UnboundedReceiver<ADBTransportMessage>>;
+
+/// ADB device reachable over an asynchronous stream.
+///
+/// Once connected, a reading task dispatches received messages to their session,
+/// and a writing task sends messages queued by sessions. Both stop when the device and all of its sessions are dropped.
+/// Cloning this structure shares the same underlying connection.
+#[derive(Debug, Clone)]
+pub(crate) struct AsyncADBMessageDevice {
+    outgoing: UnboundedSender<ADBTransportMessage>,
+    routing_table: Arc<AsyncRoutingTable>,
+    banner: DeviceBanner,
+    features: Vec<HostFeatures>,
+}
+
+impl AsyncADBMessageDevice {
+    /// Start reading and writing tasks on `stream`, once connection has been accepted by device with `connection_message`.
+    pub(crate) fn start<S: AsyncRead + AsyncWrite + Send + 'static>(
+        stream: S,
+        connection_message: ADBTransportMessage,
+        checksum: ChecksumPolicy,
+    ) -> Result<Self> {
+        connection_message.assert_command(MessageCommand::Cnxn)?;
+        let (version, max_payload) = (
+            connection_message.header().arg0(),
+            connection_message.header().arg1(),
+        );
+        let device_infos = String::from_utf8(connection_message.into_payload())?;
+        log::debug!("received device info: {device_infos}");
+        let banner: DeviceBanner = device_infos.parse()?;
+        let settings = ADBConnectionSettings::negotiate(version, max_payload, &banner)?;
+        log::debug!("negotiated connection settings: {settings:?}");
+        checksum.set_protocol_version(settings.version);
+
+        let routing_table = Arc::new(AsyncRoutingTable::new(settings));
+        let (outgoing, outgoing_rx) = unbounded_channel();
+
+        let (reader, writer) = tokio::io::split(stream);
+        let reader = tokio::spawn(read_loop(
+            reader,
+            checksum.clone(),
+            routing_table.clone(),
+            outgoing.downgrade(),
+        ));
+        tokio::spawn(write_loop(
+            writer,
+            checksum,
+            outgoing_rx,
+            routing_table.clone(),
+            reader,
+        ));
+
+        Ok(Self {
+            outgoing,
+            routing_table,
+            features: banner.host_features(),
+            banner,
+        })
+    }
+
+    /// Banner sent by connected device when connection has been established.
+    pub(crate) const fn banner(&self) -> &DeviceBanner {
+        &self.banner
+    }
+
+    /// Features advertised by connected device, and known by this crate.
+    pub(crate) fn features(&self) -> &[HostFeatures] {
+        &self.features
+    }
+
+    /// Open a session running `service` on device.
+    pub(crate) async fn open_session(&self, service: &str) -> Result<AsyncADBSession> {
+        let (local_id, mut receivers) = self.routing_table.register()?;
+        let delayed_ack = self.routing_table.settings().delayed_ack;
+
+        // With delayed ACK, we tell device how many bytes it may send before waiting for our acknowledgements
+        let receive_window = if delayed_ack {
+            INITIAL_DELAYED_ACK_BYTES
+        } else {
+            0
+        };
+        let message = ADBTransportMessage::try_new(
+            MessageCommand::Open,
+            local_id,
+            receive_window,
+            service.as_bytes(),
+        )?;
+
+        let response = match self.outgoing.send(message) {
+            Ok(()) => receivers.acks.recv().await,
+            Err(_) => None,
+        };
+        let Some(response) = response else {
+            self.routing_table.unregister(local_id);
+            return Err(connection_closed());
+        };
+
+        if response.header().command() != MessageCommand::Okay {
+            self.routing_table.unregister(local_id);
+            return Err(RustADBError::ADBRequestFailed(format!(
+                "Open session failed: got {} in response instead of OKAY",
+                response.header().command()
+            )));
+        }
+
+        // With delayed ACK, device tells how many bytes we may send before waiting for its acknowledgements
+        let send_window = if delayed_ack {
+            Some(read_acked_bytes(&response)?)
+        } else {
+            None
+        };
+
+        Ok(AsyncADBSession::new(
+            self.outgoing.clone(),
+            local_id,
+            response.header().arg0(),
+            receivers,
+            self.routing_table.clone(),
+            send_window,
+        ))
+    }
+}
+
+/// Authenticate against device, `message` being its answer to our `CNXN` message, and return its `CNXN` message on success.
+///
+/// Same as [`ADBMessageDevice`](super::adb_message_device::ADBMessageDevice), see [`AuthHandshake`] for keys tried.
+pub(crate) async fn authenticate<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    mut message: ADBTransportMessage,
+    private_keys: &[ADBRsaKey],
+    auth_options: &AuthOptions,
+    checksum: &ChecksumPolicy,
+) -> Result<ADBTransportMessage> {
+    match message.header().command() {
+        MessageCommand::Cnxn => {
+            log::debug!("Unencrypted connection established");
+            return Ok(message);
+        }
+        MessageCommand::Auth => log::debug!("Authentication required"),
+        command => {
+            return Err(RustADBError::WrongResponseReceived(
+                "Expected CNXN, STLS or AUTH command".to_string(),
+                command.to_string(),
+            ));
+        }
+    }
+
+    let mut auth = AuthHandshake::new(private_keys, auth_options);
+    let (public_key_message, fingerprint) = loop {
+        match auth.next(message)? {
+            AuthStep::Send(request) => {
+                write_message(stream, request, checksum).await?;
+                message = read_message(stream, checksum).await?;
+            }
+            AuthStep::WaitForUser(request, fingerprint) => break (request, fingerprint),
+            AuthStep::Connected(message) => return Ok(message),
+        }
+    };
+
+    write_message(stream, public_key_message, checksum).await?;
+
+    let response = match auth_options.authorization_timeout_value() {
+        Some(timeout) => tokio::time::timeout(timeout, read_message(stream, checksum))
+            .await
+            .map_err(|_| RustADBError::Unauthorized(fingerprint))??,
+        None => read_message(stream, checksum).await?,
+    };
+
+    auth.authorized(response)
+}
+
+/// Read a whole message from `stream`.
+pub(crate) async fn read_message<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    checksum: &ChecksumPolicy,
+) -> Result<ADBTransportMessage> {
+    let mut data = [0; 24];
+    stream.read_exact(&mut data).await?;
+
+    let header = ADBTransportMessageHeader::try_from(data)?;
+
+    let mut payload = vec![0_u8; header.data_length() as usize];
+    stream.read_exact(&mut payload).await?;
+
+    let message = ADBTransportMessage::from_header_and_payload(header, payload);
+    checksum.check_incoming(&message)?;
+
+    Ok(message)
+}
+
+/// Write a whole message to `stream`.
+pub(crate) async fn write_message<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    mut message: ADBTransportMessage,
+    checksum: &ChecksumPolicy,
+) -> Result<()> {
+    checksum.prepare_outgoing(&mut message);
+
+    let mut message_bytes = message.header().as_bytes();
+    message_bytes.extend_from_slice(message.payload());
+    stream.write_all(&message_bytes).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+async fn read_loop<R: AsyncRead + Unpin>(
+    mut reader: R,
+    checksum: ChecksumPolicy,
+    routing_table: Arc<AsyncRoutingTable>,
+    outgoing: WeakUnboundedSender<ADBTransportMessage>,
+) {
+    loop {
+        let message = match read_message(&mut reader, &checksum).await {
+            Ok(message) => message,
+            Err(e) => {
+                log::debug!("error while reading from device, closing connection: {e}");
+                break;
+            }
+        };
+
+        let unhandled = match routing_table.dispatch(message) {
+            Ok(None) => continue,
+            Ok(Some(unhandled)) => unhandled,
+            Err(e) => {
+                log::error!("cannot dispatch message: {e}");
+                break;
+            }
+        };
+
+        let header = unhandled.header();
+        let (local_id, remote_id) = match header.command() {
+            // Data for a session we do not know (anymore), ask device to close it
+            MessageCommand::Write => (header.arg1(), header.arg0()),
+            // Device may not open streams to us, reverse forwarding is not supported
+            MessageCommand::Open => (0, header.arg0()),
+            c => {
+                log::trace!(
+                    "dropping {c} message (arg0={}, arg1={})",
+                    header.arg0(),
+                    header.arg1()
+                );
+                continue;
+            }
+        };
+        if let Some(outgoing) = outgoing.upgrade()
+            && let Ok(message) =
+                ADBTransportMessage::try_new(MessageCommand::Clse, local_id, remote_id, &[])
+        {
+            let _ = outgoing.send(message);
+        }
+    }
+
+    routing_table.close();
+}
+
+async fn write_loop<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    checksum: ChecksumPolicy,
+    mut outgoing: UnboundedReceiver<ADBTransportMessage>,
+    routing_table: Arc<AsyncRoutingTable>,
+    reader: JoinHandle<()>,
+) {
+    while let Some(message) = outgoing.recv().await {
+        if let Err(e) = write_message(&mut writer, message, &checksum).await {
+            log::debug!("error while writing to device, closing connection: {e}");
+            break;
+        }
+    }
+
+    // Either device and all of its sessions are gone, or connection is broken
+    routing_table.close();
+    let _ = writer.shutdown().await;
+    reader.abort();
+}
@@ -0,0 +1,230 @@
+use std::{
+    io::{self, ErrorKind},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+};
+
+use crate::{
+    RustADBError,
+    message_devices::{
+        adb_session::read_acked_bytes,
+        adb_transport_message::ADBTransportMessage,
+        async_adb_message_device::{AsyncRoutingTable, AsyncSessionReceivers},
+        message_commands::MessageCommand,
+    },
+};
+
+/// Session between an asynchronous device and remote `adbd`, running a single service.
+///
+/// Data sent by service is read using [`AsyncRead`], and data is sent to it using [`AsyncWrite`].
+/// ADB streams cannot be half-closed: shutting down the writing side, or dropping the session, closes it in both directions.
+#[derive(Debug)]
+pub struct AsyncADBSession {
+    local_id: u32,
+    remote_id: u32,
+    outgoing: UnboundedSender<ADBTransportMessage>,
+    messages: UnboundedReceiver<ADBTransportMessage>,
+    acks: UnboundedReceiver<ADBTransportMessage>,
+    routing_table: Arc<AsyncRoutingTable>,
+    /// Maximum payload of a single message.
+    max_payload: usize,
+    /// Payload of the last received `WRTE` message, and how much of it has already been read.
+    read_buffer: Vec<u8>,
+    read_offset: usize,
+    /// Whether every message sent by device before closing this session has been read.
+    read_closed: bool,
+    /// Whether device closed this session, as seen by writers.
+    write_closed: bool,
+    /// With delayed ACK, amount of bytes device still accepts before acknowledging them.
+    /// Otherwise `None` and a single `WRTE` may be in flight.
+    send_window: Option<i64>,
+    /// Without delayed ACK, whether last `WRTE` sent has not been acknowledged yet.
+    awaiting_ack: bool,
+}
+
+impl AsyncADBSession {
+    pub(crate) fn new(
+        outgoing: UnboundedSender<ADBTransportMessage>,
+        local_id: u32,
+        remote_id: u32,
+        receivers: AsyncSessionReceivers,
+        routing_table: Arc<AsyncRoutingTable>,
+        send_window: Option<u32>,
+    ) -> Self {
+        let max_payload = routing_table.settings().max_payload;
+        Self {
+            local_id,
+            remote_id,
+            outgoing,
+            messages: receivers.messages,
+            acks: receivers.acks,
+            routing_table,
+            max_payload,
+            read_buffer: Vec::new(),
+            read_offset: 0,
+            read_closed: false,
+            write_closed: false,
+            send_window: send_window.map(i64::from),
+            awaiting_ack: false,
+        }
+    }
+
+    /// Queue a message addressed to this session on device side.
+    fn send(&self, command: MessageCommand, payload: &[u8]) -> io::Result<()> {
+        let message = ADBTransportMessage::try_new(command, self.local_id, self.remote_id, payload)
+            .map_err(into_io_error)?;
+        self.outgoing
+            .send(message)
+            .map_err(|_| io::Error::new(ErrorKind::ConnectionAborted, "connection closed"))
+    }
+
+    /// Process acknowledgements received so far, returning `Pending` if device does not accept more data yet.
+    fn poll_capacity(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.write_closed {
+                return Poll::Ready(Err(ErrorKind::BrokenPipe.into()));
+            }
+
+            let has_capacity = match self.send_window {
+                Some(window) => window > 0,
+                None => !self.awaiting_ack,
+            };
+
+            let ack = match self.acks.poll_recv(cx) {
+                Poll::Ready(Some(ack)) => ack,
+                Poll::Ready(None) => {
+                    return Poll::Ready(Err(io::Error::new(
+                        ErrorKind::ConnectionAborted,
+                        "connection closed",
+                    )));
+                }
+                Poll::Pending if has_capacity => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if ack.header().command() == MessageCommand::Clse {
+                self.write_closed = true;
+                continue;
+            }
+            match self.send_window.as_mut() {
+                Some(window) => {
+                    *window += i64::from(read_acked_bytes(&ack).map_err(into_io_error)?);
+                }
+                None => self.awaiting_ack = false,
+            }
+        }
+    }
+}
+
+impl AsyncRead for AsyncADBSession {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            if self.read_offset < self.read_buffer.len() {
+                let available = &self.read_buffer[self.read_offset..];
+                let size = available.len().min(buf.remaining());
+                buf.put_slice(&available[..size]);
+                self.read_offset += size;
+                return Poll::Ready(Ok(()));
+            }
+
+            if self.read_closed {
+                return Poll::Ready(Ok(()));
+            }
+
+            let Some(message) = ready!(self.messages.poll_recv(cx)) else {
+                return Poll::Ready(Err(io::Error::new(
+                    ErrorKind::ConnectionAborted,
+                    "connection closed",
+                )));
+            };
+
+            match message.header().command() {
+                MessageCommand::Write => {
+                    // With delayed ACK, device is told how many bytes have been consumed
+                    let acked_bytes = if self.send_window.is_some() {
+                        u32::try_from(message.payload().len())
+                            .map_err(io::Error::other)?
+                            .to_le_bytes()
+                            .to_vec()
+                    } else {
+                        Vec::new()
+                    };
+                    self.send(MessageCommand::Okay, &acked_bytes)?;
+
+                    self.read_buffer = message.into_payload();
+                    self.read_offset = 0;
+                }
+                MessageCommand::Clse => self.read_closed = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncADBSession {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_capacity(cx))?;
+
+        let chunk = &buf[..buf.len().min(self.max_payload)];
+        self.send(MessageCommand::Write, chunk)?;
+        match self.send_window.as_mut() {
+            Some(window) => *window -= i64::try_from(chunk.len()).map_err(io::Error::other)?,
+            None => self.awaiting_ack = true,
+        }
+
+        Poll::Ready(Ok(chunk.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Without delayed ACK, data is flushed once device acknowledged it
+        while self.awaiting_ack {
+            ready!(self.poll_capacity(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.write_closed {
+            ready!(self.as_mut().poll_flush(cx))?;
+        }
+
+        if self.routing_table.unregister(self.local_id) {
+            self.send(MessageCommand::Clse, &[])?;
+        }
+        self.read_closed = true;
+        self.write_closed = true;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for AsyncADBSession {
+    fn drop(&mut self) {
+        // Nothing to do if session has already been closed, by either side
+        if self.routing_table.unregister(self.local_id) {
+            // Best effort here
+            let _ = self.send(MessageCommand::Clse, &[]);
+        }
+    }
+}
+
+fn into_io_error(error: RustADBError) -> io::Error {
+    match error {
+        RustADBError::IOError(e) => e,
+        e => io::Error::other(e),
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{
+    Result, RustADBError,
+    message_devices::{
+        adb_transport_message::{
+            ADBTransportMessage, AUTH_RSAPUBLICKEY, AUTH_SIGNATURE, AUTH_TOKEN,
+        },
+        message_commands::MessageCommand,
+        models::{ADBRsaKey, AuthEvent, AuthOptions},
+    },
+};
+
+/// Next step of an [`AuthHandshake`], to be performed by the device driving it.
+#[derive(Debug)]
+pub(crate) enum AuthStep {
+    /// Send message to device, and give its answer to [`AuthHandshake::next`].
+    Send(ADBTransportMessage),
+    /// Send message to device, then wait within authorization timeout for user to allow debugging with key of given fingerprint,
+    /// and give device answer to [`AuthHandshake::authorized`].
+    WaitForUser(ADBTransportMessage, String),
+    /// Device accepted connection, with given `CNXN` message.
+    Connected(ADBTransportMessage),
+}
+
+/// Authentication against device, independent of how messages are exchanged with it.
+///
+/// Token sent by device is signed with each of `private_keys` in turn, device sending a new token after each rejected signature.
+/// If none is trusted, public key of first one is sent, device asking user whether to trust it within authorization timeout.
+pub(crate) struct AuthHandshake<'a> {
+    private_keys: &'a [ADBRsaKey],
+    auth_options: &'a AuthOptions,
+    tried_keys: usize,
+}
+
+impl<'a> AuthHandshake<'a> {
+    pub(crate) const fn new(private_keys: &'a [ADBRsaKey], auth_options: &'a AuthOptions) -> Self {
+        Self {
+            private_keys,
+            auth_options,
+            tried_keys: 0,
+        }
+    }
+
+    /// Step to perform after receiving `message` from device.
+    pub(crate) fn next(&mut self, message: ADBTransportMessage) -> Result<AuthStep> {
+        if message.header().command() == MessageCommand::Cnxn {
+            log::info!("Authentication OK");
+            return Ok(AuthStep::Connected(message));
+        }
+
+        if let Some(private_key) = self.private_keys.get(self.tried_keys) {
+            log::debug!("signing authentication token with key #{}", self.tried_keys);
+            self.tried_keys += 1;
+            return Ok(AuthStep::Send(signature_message(private_key, message)?));
+        }
+
+        // Device is still waiting for an authentication
+        auth_token(message)?;
+        let private_key = self
+            .private_keys
+            .first()
+            .ok_or_else(|| RustADBError::ADBRequestFailed("no private key available".into()))?;
+
+        let fingerprint = private_key.fingerprint()?;
+        log::info!("Waiting for user to allow debugging with key {fingerprint} on device");
+        self.auth_options
+            .notify(&AuthEvent::WaitingForUserAuthorization {
+                fingerprint: fingerprint.clone(),
+            });
+
+        Ok(AuthStep::WaitForUser(
+            public_key_message(private_key)?,
+            fingerprint,
+        ))
+    }
+
+    /// Check `response` sent by device once user has been asked to allow debugging, returning it on success.
+    pub(crate) fn authorized(&self, response: ADBTransportMessage) -> Result<ADBTransportMessage> {
+        response.assert_command(MessageCommand::Cnxn)?;
+
+        log::info!("Authentication OK");
+        self.auth_options.notify(&AuthEvent::Authorized);
+        Ok(response)
+    }
+}
+
+/// `AUTH` message answering token request `message` with its signature by `private_key`.
+fn signature_message(
+    private_key: &ADBRsaKey,
+    message: ADBTransportMessage,
+) -> Result<ADBTransportMessage> {
+    let sign = private_key.sign(auth_token(message)?)?;
+    ADBTransportMessage::try_new(MessageCommand::Auth, AUTH_SIGNATURE, 0, &sign)
+}
+
+/// `AUTH` message sending public key of `private_key`, for device to ask user whether to trust it.
+fn public_key_message(private_key: &ADBRsaKey) -> Result<ADBTransportMessage> {
+    let mut pubkey = private_key.android_pubkey_encode()?.into_bytes();
+    pubkey.push(b'\0');
+
+    ADBTransportMessage::try_new(MessageCommand::Auth, AUTH_RSAPUBLICKEY, 0, &pubkey)
+}
+
+/// Token to sign carried by `message`, which must be an `AUTH` token request.
+fn auth_token(message: ADBTransportMessage) -> Result<Vec<u8>> {
+    match (message.header().command(), message.header().arg0()) {
+        (MessageCommand::Auth, AUTH_TOKEN) => Ok(message.into_payload()),
+        (MessageCommand::Auth, v) => Err(RustADBError::ADBRequestFailed(format!(
+            "Received AUTH message with type != 1 ({v})"
+        ))),
+        (command, _) => Err(RustADBError::WrongResponseReceived(
+            "Expected AUTH or CNXN command".to_string(),
+            command.to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthHandshake, AuthStep};
+    use crate::message_devices::{
+        adb_transport_message::{
+            ADBTransportMessage, AUTH_RSAPUBLICKEY, AUTH_SIGNATURE, AUTH_TOKEN,
+        },
+        message_commands::MessageCommand,
+        models::{ADBRsaKey, AuthOptions},
+    };
+
+    fn token() -> ADBTransportMessage {
+        ADBTransportMessage::try_new(MessageCommand::Auth, AUTH_TOKEN, 0, &[0x42; 20])
+            .expect("cannot build message")
+    }
+
+    #[test]
+    fn test_auth_handshake_steps() {
+        let private_key =
+            ADBRsaKey::new_from_pkcs8(ADBRsaKey::TEST_PRIVATE_KEY).expect("cannot load key");
+        let private_keys = [private_key.clone(), private_key.clone()];
+        let auth_options = AuthOptions::default();
+        let mut auth = AuthHandshake::new(&private_keys, &auth_options);
+
+        // Each key signs a token in turn, before public key of first one is sent
+        for _ in &private_keys {
+            match auth.next(token()).expect("cannot sign token") {
+                AuthStep::Send(message) => assert_eq!(message.header().arg0(), AUTH_SIGNATURE),
+                step => panic!("unexpected step: {step:?}"),
+            }
+        }
+        match auth.next(token()).expect("cannot send public key") {
+            AuthStep::WaitForUser(message, fingerprint) => {
+                assert_eq!(message.header().arg0(), AUTH_RSAPUBLICKEY);
+                assert_eq!(fingerprint, private_key.fingerprint().unwrap());
+            }
+            step => panic!("unexpected step: {step:?}"),
+        }
+
+        let connection =
+            ADBTransportMessage::try_new(MessageCommand::Cnxn, 0, 0, b"device::").unwrap();
+        assert!(auth.authorized(connection.clone()).is_ok());
+        assert!(auth.authorized(token()).is_err());
+        assert!(matches!(
+            AuthHandshake::new(&private_keys, &auth_options).next(connection),
+            Ok(AuthStep::Connected(_))
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{
+    Result, RustADBError,
+    message_devices::{
+        adb_message_device::ADBMessageDevice,
+        adb_message_transport::ADBMessageTransport,
+        forward_listener::{ForwardListener, parse_local_endpoint},
+    },
+    models::{ForwardRule, Timeouts},
+};
+
+impl<T: ADBMessageTransport> ADBMessageDevice<T> {
+    /// Forward connections accepted on `local` to `remote` service on device.
+    ///
+    /// Returns the local endpoint actually used, which differs from `local` when port 0 has been requested.
+    pub(crate) fn forward(&mut self, remote: String, local: &str) -> Result<String> {
+        let address = parse_local_endpoint(local)?;
+
+        let mut forwards = self.forwards().lock()?;
+        // Existing rule is replaced, as adb does
+        if forwards.remove(local).is_some() {
+            log::debug!("replacing forward rule on {local}");
+        }
+
+        // Forwarded connections last as long as both ends keep them open
+        let mut device = self.detached_clone();
+        device.set_timeouts(Timeouts::none().connect(self.timeouts().connect_value()));
+        let listener = ForwardListener::start(device, address, remote)?;
+        let local = format!("tcp:{}", listener.address().port());
+        forwards.insert(local.clone(), listener);
+
+        Ok(local)
+    }
+
+    /// Remove forward rule set on `local`.
+    pub(crate) fn forward_remove(&mut self, local: &str) -> Result<()> {
+        match self.forwards().lock()?.remove(local) {
+            Some(_) => Ok(()),
+            None => Err(RustADBError::ADBRequestFailed(format!(
+                "listener '{local}' not found"
+            ))),
+        }
+    }
+
+    /// Remove all forward rules.
+    pub(crate) fn forward_remove_all(&mut self) -> Result<()> {
+        self.forwards().lock()?.clear();
+        Ok(())
+    }
+
+    /// List active forward rules.
+    pub(crate) fn forward_list(&self) -> Result<Vec<ForwardRule>> {
+        let mut rules: Vec<ForwardRule> = self
+            .forwards()
+            .lock()?
+            .iter()
+            .map(|(local, listener)| ForwardRule {
+                local: local.clone(),
+                remote: listener.remote().to_string(),
+            })
+            .collect();
+        rules.sort_by(|a, b| a.local.cmp(&b.local));
+
+        Ok(rules)
+    }
+}
@@ -7,13 +7,16 @@ use crate::{
     Result, RustADBError,
     message_devices::{
         adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
-        message_commands::MessageCommand,
     },
     models::{ADBLocalCommand, FrameBufferInfoV1, FrameBufferInfoV2},
 };
 
 impl<T: ADBMessageTransport> ADBMessageDevice<T> {
     pub(crate) fn framebuffer_inner(&mut self) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+        self.retry_on_disconnection(Self::capture_framebuffer)
+    }
+
+    fn capture_framebuffer(&mut self) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
         let mut session = self.open_session(&ADBLocalCommand::FrameBuffer)?;
 
         let response = session.recv_and_reply_okay()?;
@@ -92,10 +95,7 @@ impl<T: ADBMessageTransport> ADBMessageDevice<T> {
             v => return Err(RustADBError::UnimplementedFramebufferImageVersion(v)),
         };
 
-        session
-            .get_transport_mut()
-            .read_message()
-            .and_then(|message| message.assert_command(MessageCommand::Clse))?;
+        session.expect_close()?;
 
         Ok(img)
     }
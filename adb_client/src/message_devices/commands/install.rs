@@ -1,50 +1,40 @@
-use std::{fs::File, path::Path};
+use std::io::Read;
 
 use crate::{
     Result,
     message_devices::{
         adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
-        commands::utils::MessageWriter, message_commands::MessageCommand,
+        commands::utils::MessageWriter,
     },
-    models::ADBLocalCommand,
-    utils::check_extension_is_apk,
+    models::{
+        ADBLocalCommand, CancellationToken, HostFeatures, InstallOptions, ProgressObserver,
+        ProgressReader,
+    },
+    package_install::{check_install_output, install_with_pm},
 };
 
 impl<T: ADBMessageTransport> ADBMessageDevice<T> {
-    pub(crate) fn install(&mut self, apk_path: &dyn AsRef<Path>, user: Option<&str>) -> Result<()> {
-        let mut apk_file = File::open(apk_path)?;
-
-        check_extension_is_apk(apk_path)?;
-
-        let file_size = apk_file.metadata()?.len();
-
-        let mut session = self.open_session(&ADBLocalCommand::Install(
-            file_size,
-            user.map(ToString::to_string),
-        ))?;
-
-        {
-            // Read data from apk_file and write it to the underlying session
-            let mut writer = MessageWriter::new(&mut session);
-            std::io::copy(&mut apk_file, &mut writer)?;
+    pub(crate) fn install_from_reader_with_progress(
+        &mut self,
+        apk: &mut dyn Read,
+        size: u64,
+        options: &InstallOptions,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        if !self.has_feature(&HostFeatures::Cmd) {
+            return install_with_pm(self, apk, size, options, observer, cancellation);
         }
 
-        let final_status = session.get_transport_mut().read_message()?;
+        let mut session = self.open_session(&ADBLocalCommand::Install(size, options.clone()))?;
 
-        match final_status.into_payload().as_slice() {
-            b"Success\n" => {
-                log::info!(
-                    "APK file {} successfully installed",
-                    apk_path.as_ref().display()
-                );
-                self.get_transport_mut()
-                    .read_message()?
-                    .assert_command(MessageCommand::Clse)?;
-                Ok(())
-            }
-            d => Err(crate::RustADBError::ADBRequestFailed(String::from_utf8(
-                d.to_vec(),
-            )?)),
-        }
+        // Read data from apk and write it to the underlying session.
+        // On failure, dropping session closes it, which aborts installation on device side.
+        let mut reader = ProgressReader::new(apk, Some(size), observer, cancellation);
+        let mut writer = MessageWriter::new(session.clone());
+        cancellation.map_result(std::io::copy(&mut reader, &mut writer).map_err(Into::into))?;
+
+        // Status may be split over several messages, device closes session once done
+        check_install_output(&session.read_until_close()?)
     }
 }
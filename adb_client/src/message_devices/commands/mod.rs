@@ -1,14 +1,17 @@
+mod forward;
 mod install;
 mod list;
 mod pull;
 mod push;
 mod reboot;
 mod remount;
+mod reverse;
 mod root;
 mod shell;
 mod stat;
+mod sync;
 mod uninstall;
-mod utils;
+pub(crate) mod utils;
 mod verity;
 
 #[cfg(feature = "framebuffer")]
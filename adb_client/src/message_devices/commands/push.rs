@@ -3,34 +3,22 @@ use std::io::Read;
 use crate::{
     Result,
     message_devices::{
-        adb_message_device::ADBMessageDevice,
-        adb_message_transport::ADBMessageTransport,
-        adb_transport_message::ADBTransportMessage,
-        message_commands::{MessageCommand, MessageSubcommand},
-        utils::BinaryEncodable,
+        adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
     },
+    models::PushOptions,
 };
 
 impl<T: ADBMessageTransport> ADBMessageDevice<T> {
-    pub(crate) fn push<R: Read, A: AsRef<str>>(&mut self, stream: R, path: A) -> Result<()> {
-        let mut session = self.open_synchronization_session()?;
-
-        let path_header = format!("{},0777", path.as_ref());
-
-        let send_buffer = MessageSubcommand::Send.with_arg(u32::try_from(path_header.len())?);
-        let mut send_buffer = send_buffer.encode();
-        send_buffer.append(&mut path_header.as_bytes().to_vec());
-
-        session.send_and_expect_okay(ADBTransportMessage::try_new(
-            MessageCommand::Write,
-            session.local_id(),
-            session.remote_id(),
-            &send_buffer,
-        )?)?;
-
-        session.push_file(stream)?;
-        self.end_transaction(&mut session)?;
-
-        Ok(())
+    pub(crate) fn push_with_options<R: Read, A: AsRef<str>>(
+        &mut self,
+        mut stream: R,
+        path: A,
+        options: PushOptions,
+    ) -> Result<()> {
+        let mtime = options.remote_mtime()?;
+        let compression = self.sync_compression();
+        self.with_sync_client(|client| {
+            client.send(&mut stream, path.as_ref(), options.mode, mtime, compression)
+        })
     }
 }
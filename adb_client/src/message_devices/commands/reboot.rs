@@ -2,17 +2,19 @@ use crate::{
     RebootType, Result,
     message_devices::{
         adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
-        message_commands::MessageCommand,
     },
     models::ADBLocalCommand,
 };
 
 impl<T: ADBMessageTransport> ADBMessageDevice<T> {
     pub(crate) fn reboot(&mut self, reboot_type: RebootType) -> Result<()> {
-        self.open_session(&ADBLocalCommand::Reboot(reboot_type))?;
+        let mut session = self.open_session(&ADBLocalCommand::Reboot(reboot_type))?;
 
-        self.get_transport_mut()
-            .read_message()
-            .and_then(|message| message.assert_command(MessageCommand::Okay))
+        // Device may go away before closing the session
+        if let Err(e) = session.read_until_close() {
+            log::debug!("connection ended while rebooting: {e}");
+        }
+
+        Ok(())
     }
 }
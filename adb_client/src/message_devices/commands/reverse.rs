@@ -0,0 +1,128 @@
+use crate::{
+    Result, RustADBError,
+    message_devices::{
+        adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
+        forward_listener::parse_local_endpoint,
+    },
+    models::{ADBLocalCommand, ForwardRule},
+};
+
+/// Read a 4 hex digits length-prefixed string at the beginning of `data`.
+fn read_length_prefixed(data: &[u8]) -> Result<String> {
+    let (length, value) = data
+        .split_at_checked(4)
+        .ok_or_else(|| RustADBError::ADBRequestFailed("truncated reverse reply".to_string()))?;
+    let length = usize::from_str_radix(std::str::from_utf8(length)?, 16)?;
+    let value = value
+        .get(..length)
+        .ok_or_else(|| RustADBError::ADBRequestFailed("truncated reverse reply".to_string()))?;
+
+    Ok(String::from_utf8(value.to_vec())?)
+}
+
+/// Parse reply of a `reverse:` service: `OKAY`, optionally followed by a length-prefixed value,
+/// or `FAIL` followed by a length-prefixed error message.
+fn parse_reverse_reply(mut reply: &[u8]) -> Result<Option<String>> {
+    if let Some(message) = reply.strip_prefix(b"FAIL") {
+        return Err(RustADBError::ADBRequestFailed(read_length_prefixed(
+            message,
+        )?));
+    }
+
+    if !reply.starts_with(b"OKAY") {
+        return Err(RustADBError::ADBRequestFailed(format!(
+            "unexpected reverse reply: {}",
+            String::from_utf8_lossy(reply)
+        )));
+    }
+    // Some versions of adbd acknowledge twice
+    while let Some(rest) = reply.strip_prefix(b"OKAY") {
+        reply = rest;
+    }
+
+    if reply.is_empty() {
+        return Ok(None);
+    }
+    read_length_prefixed(reply).map(Some)
+}
+
+impl<T: ADBMessageTransport> ADBMessageDevice<T> {
+    /// Run `reverse:` service `command` and return its optional reply value.
+    fn reverse_request(&mut self, command: &ADBLocalCommand) -> Result<Option<String>> {
+        let mut session = self.open_session(command)?;
+        parse_reverse_reply(&session.read_until_close()?)
+    }
+
+    /// Ask device to forward connections accepted on `remote` to `local` endpoint on this host.
+    ///
+    /// Returns the remote endpoint actually used, which differs from `remote` when port 0 has been requested.
+    pub(crate) fn reverse(&mut self, remote: String, local: &str) -> Result<String> {
+        // Only endpoints this host is able to connect to are accepted
+        parse_local_endpoint(local)?;
+
+        let routing_table = self.routing_table()?;
+        // Rule is allowed beforehand, as device may open streams as soon as it is installed
+        routing_table.add_reverse_rule(remote.clone(), local.to_string())?;
+
+        let reply =
+            self.reverse_request(&ADBLocalCommand::Reverse(remote.clone(), local.to_string()));
+        let resolved_port = match reply {
+            Ok(resolved_port) => resolved_port,
+            Err(e) => {
+                routing_table.remove_reverse_rules(Some(&remote))?;
+                return Err(e);
+            }
+        };
+
+        match resolved_port {
+            Some(port) if remote == "tcp:0" => {
+                let resolved = format!("tcp:{port}");
+                routing_table.remove_reverse_rules(Some(&remote))?;
+                routing_table.add_reverse_rule(resolved.clone(), local.to_string())?;
+                Ok(resolved)
+            }
+            _ => Ok(remote),
+        }
+    }
+
+    /// Remove reverse forward rule set on `remote`.
+    pub(crate) fn reverse_remove(&mut self, remote: &str) -> Result<()> {
+        self.reverse_request(&ADBLocalCommand::ReverseRemove(remote.to_string()))?;
+        self.routing_table()?.remove_reverse_rules(Some(remote))
+    }
+
+    /// Remove all reverse forward rules.
+    pub(crate) fn reverse_remove_all(&mut self) -> Result<()> {
+        self.reverse_request(&ADBLocalCommand::ReverseRemoveAll)?;
+        self.routing_table()?.remove_reverse_rules(None)
+    }
+
+    /// List reverse forward rules set from this host.
+    pub(crate) fn reverse_list(&self) -> Result<Vec<ForwardRule>> {
+        let mut rules: Vec<ForwardRule> = self
+            .routing_table()?
+            .reverse_rules()?
+            .into_iter()
+            .map(|(remote, local)| ForwardRule { local, remote })
+            .collect();
+        rules.sort_by(|a, b| a.remote.cmp(&b.remote));
+
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_reverse_reply;
+
+    #[test]
+    fn test_parse_reverse_reply() {
+        assert_eq!(parse_reverse_reply(b"OKAY").unwrap(), None);
+        assert_eq!(
+            parse_reverse_reply(b"OKAYOKAY000540123").unwrap(),
+            Some("40123".to_string())
+        );
+        assert!(parse_reverse_reply(b"FAIL0005nope!").is_err());
+        assert!(parse_reverse_reply(b"OKAY00").is_err());
+    }
+}
@@ -2,17 +2,20 @@ use crate::{
     Result,
     message_devices::{
         adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
-        message_commands::MessageCommand,
     },
     models::ADBLocalCommand,
 };
 
 impl<T: ADBMessageTransport> ADBMessageDevice<T> {
     pub(crate) fn root(&mut self) -> Result<()> {
-        self.open_session(&ADBLocalCommand::Root)?;
+        let mut session = self.open_session(&ADBLocalCommand::Root)?;
 
-        self.get_transport_mut()
-            .read_message()
-            .and_then(|message| message.assert_command(MessageCommand::Okay))
+        // adbd restarts as root, connection may end before session is closed
+        match session.read_until_close() {
+            Ok(response) => log::debug!("{}", String::from_utf8_lossy(&response).trim()),
+            Err(e) => log::debug!("connection ended while restarting as root: {e}"),
+        }
+
+        Ok(())
     }
 }
@@ -1,22 +1,47 @@
-use std::io::{ErrorKind, Read, Write};
+use std::{
+    io::{ErrorKind, Read, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError},
+    },
+    time::Duration,
+};
 
-use crate::models::ADBLocalCommand;
+use crate::models::{ADBLocalCommand, HostFeatures, ShellChannel, ShellWindowSize};
 use crate::{
     Result, RustADBError,
     message_devices::{
-        adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
-        adb_transport_message::ADBTransportMessage, commands::utils::ShellMessageWriter,
+        adb_message_device::ADBMessageDevice,
+        adb_message_transport::ADBMessageTransport,
+        commands::utils::{MessageReader, MessageWriter, ShellMessageWriter},
         message_commands::MessageCommand,
     },
 };
 
+/// Size of buffer used to read shell standard input, packet header included.
+const STDIN_BUFFER_SIZE: usize = 4096;
+
 impl<T: ADBMessageTransport> ADBMessageDevice<T> {
     /// Runs 'command' in a shell on the device, and write its output and error streams into output.
     pub(crate) fn shell_command(
+        &mut self,
+        command: &dyn AsRef<str>,
+        stdout: Option<&mut dyn Write>,
+        stderr: Option<&mut dyn Write>,
+    ) -> Result<Option<u8>> {
+        if self.has_feature(&HostFeatures::ShellV2) {
+            self.shell_command_v2(command, stdout, stderr)
+        } else {
+            self.shell_command_v1(command, stdout)
+        }
+    }
+
+    /// Shell v1: raw output, without exit status (for older ADB versions)
+    fn shell_command_v1(
         &mut self,
         command: &dyn AsRef<str>,
         mut stdout: Option<&mut dyn Write>,
-        _stderr: Option<&mut dyn Write>,
     ) -> Result<Option<u8>> {
         let mut session = self.open_session(&ADBLocalCommand::ShellCommand(
             command.as_ref().to_string(),
@@ -37,14 +62,127 @@ impl<T: ADBMessageTransport> ADBMessageDevice<T> {
         Ok(None)
     }
 
+    /// Shell v2: with protocol packets (for newer ADB versions)
+    fn shell_command_v2(
+        &mut self,
+        command: &dyn AsRef<str>,
+        mut stdout: Option<&mut dyn Write>,
+        mut stderr: Option<&mut dyn Write>,
+    ) -> Result<Option<u8>> {
+        let session = self.open_session(&ADBLocalCommand::ShellCommand(
+            command.as_ref().to_string(),
+            vec!["v2".to_string()],
+        ))?;
+
+        let mut input = MessageReader::new(session);
+        let mut exit_status = None;
+        while let Some((channel, payload)) = read_shell_packet(&mut input)? {
+            match channel {
+                ShellChannel::Stdout => {
+                    if let Some(stdout) = stdout.as_mut() {
+                        stdout.write_all(&payload)?;
+                    }
+                }
+                ShellChannel::Stderr => {
+                    // first stderr if existing, else a merged output into stdout
+                    if let Some(writer) = stderr.as_mut() {
+                        writer.write_all(&payload)?;
+                    } else if let Some(writer) = stdout.as_mut() {
+                        writer.write_all(&payload)?;
+                    }
+                }
+                ShellChannel::ExitStatus => exit_status = Some(parse_exit_status(&payload)?),
+                c => log::debug!("ignoring shell packet received on channel {c:?}"),
+            }
+        }
+
+        Ok(exit_status)
+    }
+
     /// Starts an interactive shell session on the device.
     /// Input data is read from [reader] and write to [writer].
-    pub(crate) fn shell(
+    ///
+    /// If device supports shell v2, each size received from `window_sizes` is forwarded to the remote terminal,
+    /// and shell exit status is returned.
+    pub(crate) fn shell_with_window_size(
         &mut self,
         reader: &mut dyn Read,
         writer: Box<dyn Write + Send>,
-    ) -> Result<()> {
-        self.bidirectional_session(&ADBLocalCommand::Shell, reader, writer)
+        window_sizes: Option<Receiver<ShellWindowSize>>,
+    ) -> Result<Option<u8>> {
+        if self.has_feature(&HostFeatures::ShellV2) {
+            self.shell_v2(reader, writer, window_sizes)
+        } else {
+            self.bidirectional_session(&ADBLocalCommand::Shell, reader, writer)?;
+            Ok(None)
+        }
+    }
+
+    /// Once input is over, waits for shell to exit and all of its output to be written.
+    fn shell_v2(
+        &mut self,
+        reader: &mut dyn Read,
+        mut writer: Box<dyn Write + Send>,
+        window_sizes: Option<Receiver<ShellWindowSize>>,
+    ) -> Result<Option<u8>> {
+        let session = self.open_session(&ADBLocalCommand::ShellV2)?;
+
+        let reader_session = session.clone();
+
+        // Reading thread, reads response from adbd until session is closed
+        let output = std::thread::spawn(move || -> Result<Option<u8>> {
+            let mut input = MessageReader::new(reader_session);
+            let mut exit_status = None;
+            while let Some((channel, payload)) = read_shell_packet(&mut input)? {
+                match channel {
+                    ShellChannel::Stdout | ShellChannel::Stderr => {
+                        writer.write_all(&payload)?;
+                        writer.flush()?;
+                    }
+                    ShellChannel::ExitStatus => exit_status = Some(parse_exit_status(&payload)?),
+                    c => log::debug!("ignoring shell packet received on channel {c:?}"),
+                }
+            }
+            Ok(exit_status)
+        });
+
+        let stdin_closed = Arc::new(AtomicBool::new(false));
+
+        // Resizing thread, forwards window size changes to adbd
+        if let Some(window_sizes) = window_sizes {
+            let mut resize_writer = ShellMessageWriter::new(session.clone());
+            let stdin_closed = stdin_closed.clone();
+            std::thread::spawn(move || -> Result<()> {
+                while !stdin_closed.load(Ordering::Acquire) {
+                    match window_sizes.recv_timeout(Duration::from_millis(100)) {
+                        Ok(window_size) => {
+                            let payload = format!("{window_size}\0");
+                            resize_writer.write_all(
+                                &ShellChannel::WindowSizeChange.packet(payload.as_bytes())?,
+                            )?;
+                        }
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                Ok(())
+            });
+        }
+
+        let mut shell_writer = ShellMessageWriter::new(session.clone());
+        let input_result = write_shell_stdin(reader, &mut shell_writer);
+        stdin_closed.store(true, Ordering::Release);
+
+        if let Err(e) = input_result {
+            // Closing session stops reading thread, shell not getting any more input
+            let _ = session.close();
+            let _ = output.join();
+            return Err(e);
+        }
+
+        output
+            .join()
+            .map_err(|_| RustADBError::ADBRequestFailed("shell output thread panicked".into()))?
     }
 
     /// Runs `command` on the device.
@@ -58,6 +196,20 @@ impl<T: ADBMessageTransport> ADBMessageDevice<T> {
         self.bidirectional_session(&ADBLocalCommand::Exec(command.to_string()), reader, writer)
     }
 
+    /// Runs `command`, writing all of `input` to its standard input, and returns its whole output once it exits.
+    pub(crate) fn exec_with_input(
+        &mut self,
+        command: &str,
+        input: &mut dyn Read,
+    ) -> Result<Vec<u8>> {
+        let mut session = self.open_session(&ADBLocalCommand::Exec(command.to_string()))?;
+
+        // On failure, dropping session closes it, which stops command on device side.
+        std::io::copy(input, &mut MessageWriter::new(session.clone()))?;
+
+        session.read_until_close()
+    }
+
     /// Starts an bidirectional(interactive) session. This can be a shell or an exec session.
     fn bidirectional_session(
         &mut self,
@@ -67,34 +219,26 @@ impl<T: ADBMessageTransport> ADBMessageDevice<T> {
     ) -> Result<()> {
         let session = self.open_session(local_command)?;
 
-        let local_id = session.local_id();
-        let remote_id = session.remote_id();
-
-        let mut transport = self.get_transport_mut().clone();
+        let mut reader_session = session.clone();
 
         // Reading thread, reads response from adbd
         std::thread::spawn(move || -> Result<()> {
             loop {
-                let message = transport.read_message()?;
-
                 // Acknowledge for more data
-                let response =
-                    ADBTransportMessage::try_new(MessageCommand::Okay, local_id, remote_id, &[])?;
-                transport.write_message(response)?;
+                let message = reader_session.recv_and_reply_okay()?;
 
                 match message.header().command() {
                     MessageCommand::Write => {
                         writer.write_all(&message.into_payload())?;
                         writer.flush()?;
                     }
-                    MessageCommand::Okay => {}
+                    MessageCommand::Clse => return Ok(()),
                     _ => return Err(RustADBError::ADBShellNotSupported),
                 }
             }
         });
 
-        let transport = self.get_transport_mut().clone();
-        let mut shell_writer = ShellMessageWriter::new(transport, local_id, remote_id);
+        let mut shell_writer = ShellMessageWriter::new(session);
 
         // Read from given reader (that could be stdin e.g), and write content to device adbd
         if let Err(e) = std::io::copy(&mut reader, &mut shell_writer) {
@@ -107,3 +251,58 @@ impl<T: ADBMessageTransport> ADBMessageDevice<T> {
         Ok(())
     }
 }
+
+/// Read next shell v2 packet from `input`, or `None` once stream is over.
+fn read_shell_packet(input: &mut dyn Read) -> Result<Option<(ShellChannel, Vec<u8>)>> {
+    let mut header = [0; ShellChannel::HEADER_SIZE];
+    if let Err(e) = input.read_exact(&mut header) {
+        return match e.kind() {
+            ErrorKind::UnexpectedEof => Ok(None),
+            _ => Err(e.into()),
+        };
+    }
+
+    let channel = ShellChannel::try_from(header[0])?;
+    let payload_size = u32::from_le_bytes(header[1..].try_into()?) as usize;
+
+    let mut payload = vec![0; payload_size];
+    input.read_exact(&mut payload)?;
+
+    Ok(Some((channel, payload)))
+}
+
+fn parse_exit_status(payload: &[u8]) -> Result<u8> {
+    match payload {
+        [status] => Ok(*status),
+        _ => Err(RustADBError::ADBShellV2ParseError(format!(
+            "Spurious exit status packet with size of {} (should be 1)",
+            payload.len()
+        ))),
+    }
+}
+
+/// Forward `reader` content to device as shell v2 standard input packets, until it is exhausted.
+fn write_shell_stdin(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+    let mut buffer = [0; STDIN_BUFFER_SIZE - ShellChannel::HEADER_SIZE];
+    loop {
+        let (packet, last) = match reader.read(&mut buffer) {
+            // No more input, let remote process know
+            Ok(0) => (ShellChannel::CloseStdin.packet(&[])?, true),
+            Ok(size) => (ShellChannel::Stdin.packet(&buffer[..size])?, false),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(RustADBError::IOError(e)),
+        };
+
+        if let Err(e) = writer.write_all(&packet) {
+            return match e.kind() {
+                // Remote shell has already exited
+                ErrorKind::BrokenPipe => Ok(()),
+                _ => Err(RustADBError::IOError(e)),
+            };
+        }
+
+        if last {
+            return Ok(());
+        }
+    }
+}
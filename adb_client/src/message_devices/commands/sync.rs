@@ -0,0 +1,27 @@
+use crate::{
+    Result,
+    file_sync::ADBSyncClient,
+    message_devices::{
+        adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
+        commands::utils::MessageStream,
+    },
+};
+
+impl<T: ADBMessageTransport> ADBMessageDevice<T> {
+    /// Run `operation` on a new synchronization session, ending it properly afterwards.
+    pub(crate) fn with_sync_client<R>(
+        &mut self,
+        operation: impl FnOnce(&mut ADBSyncClient<MessageStream<T>>) -> Result<R>,
+    ) -> Result<R> {
+        let session = self.open_synchronization_session()?;
+        let mut client = ADBSyncClient::new(MessageStream::new(session), self.features());
+
+        let output = operation(&mut client)?;
+
+        // Device closes session once it received QUIT
+        let mut stream = client.quit()?;
+        std::io::copy(&mut stream, &mut std::io::sink())?;
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::{Read, Result};
+
+use crate::message_devices::{
+    adb_message_transport::ADBMessageTransport, adb_session::ADBSession,
+    message_commands::MessageCommand,
+};
+
+/// [`Read`] trait implementation to hide underlying ADB protocol read logic.
+///
+/// Each received message is acknowledged. End of stream is reached once device closes the session.
+pub struct MessageReader<T: ADBMessageTransport> {
+    session: ADBSession<T>,
+    payload: Vec<u8>,
+    position: usize,
+    closed: bool,
+}
+
+impl<T: ADBMessageTransport> MessageReader<T> {
+    pub const fn new(session: ADBSession<T>) -> Self {
+        Self {
+            session,
+            payload: Vec::new(),
+            position: 0,
+            closed: false,
+        }
+    }
+}
+
+impl<T: ADBMessageTransport> Read for MessageReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.position == self.payload.len() {
+            if self.closed {
+                return Ok(0);
+            }
+
+            let message = self
+                .session
+                .recv_and_reply_okay()
+                .map_err(std::io::Error::other)?;
+            if message.header().command() == MessageCommand::Clse {
+                self.closed = true;
+            } else {
+                self.payload = message.into_payload();
+                self.position = 0;
+            }
+        }
+
+        let available = &self.payload[self.position..];
+        let amount = available.len().min(buf.len());
+        buf[..amount].copy_from_slice(&available[..amount]);
+        self.position += amount;
+
+        Ok(amount)
+    }
+}
@@ -0,0 +1,38 @@
+use std::io::{Read, Result, Write};
+
+use crate::message_devices::{
+    adb_message_transport::ADBMessageTransport,
+    adb_session::ADBSession,
+    commands::utils::{MessageReader, MessageWriter},
+};
+
+/// [`Read`] and [`Write`] traits implementation over a single session, for request / response protocols.
+pub struct MessageStream<T: ADBMessageTransport> {
+    reader: MessageReader<T>,
+    writer: MessageWriter<T>,
+}
+
+impl<T: ADBMessageTransport> MessageStream<T> {
+    pub fn new(session: ADBSession<T>) -> Self {
+        Self {
+            reader: MessageReader::new(session.clone()),
+            writer: MessageWriter::new(session),
+        }
+    }
+}
+
+impl<T: ADBMessageTransport> Read for MessageStream<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl<T: ADBMessageTransport> Write for MessageStream<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
@@ -1,4 +1,8 @@
+mod message_reader;
+mod message_stream;
 mod message_writer;
 mod shell_message_writer;
+pub use message_reader::MessageReader;
+pub use message_stream::MessageStream;
 pub use message_writer::MessageWriter;
 pub use shell_message_writer::ShellMessageWriter;
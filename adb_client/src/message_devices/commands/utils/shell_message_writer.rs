@@ -1,36 +1,30 @@
-use std::io::Write;
+use std::io::{ErrorKind, Write};
 
-use crate::message_devices::{
-    adb_message_transport::ADBMessageTransport, adb_transport_message::ADBTransportMessage,
-    message_commands::MessageCommand,
+use crate::{
+    RustADBError,
+    message_devices::{adb_message_transport::ADBMessageTransport, adb_session::ADBSession},
 };
 
 /// [`Write`] trait implementation to hide underlying ADB protocol write logic for shell commands.
+///
+/// Returns a [`ErrorKind::BrokenPipe`] error once device has closed the session.
 pub struct ShellMessageWriter<T: ADBMessageTransport> {
-    transport: T,
-    local_id: u32,
-    remote_id: u32,
+    session: ADBSession<T>,
 }
 
 impl<T: ADBMessageTransport> ShellMessageWriter<T> {
-    pub const fn new(transport: T, local_id: u32, remote_id: u32) -> Self {
-        Self {
-            transport,
-            local_id,
-            remote_id,
-        }
+    pub const fn new(session: ADBSession<T>) -> Self {
+        Self { session }
     }
 }
 
 impl<T: ADBMessageTransport> Write for ShellMessageWriter<T> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let message =
-            ADBTransportMessage::try_new(MessageCommand::Write, self.local_id, self.remote_id, buf)
-                .map_err(std::io::Error::other)?;
-        self.transport
-            .write_message(message)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        Ok(buf.len())
+        match self.session.write_data(buf) {
+            Ok(()) => Ok(buf.len()),
+            Err(RustADBError::IOError(e)) if e.kind() == ErrorKind::BrokenPipe => Err(e),
+            Err(e) => Err(std::io::Error::new(ErrorKind::BrokenPipe, e)),
+        }
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
@@ -2,25 +2,26 @@ use crate::{
     Result,
     message_devices::{
         adb_message_device::ADBMessageDevice, adb_message_transport::ADBMessageTransport,
-        message_commands::MessageCommand,
     },
     models::ADBLocalCommand,
 };
 
 impl<T: ADBMessageTransport> ADBMessageDevice<T> {
     pub(crate) fn enable_verity(&mut self) -> Result<()> {
-        self.open_session(&ADBLocalCommand::EnableVerity)?;
+        let mut session = self.open_session(&ADBLocalCommand::EnableVerity)?;
 
-        self.get_transport_mut()
-            .read_message()
-            .and_then(|message| message.assert_command(MessageCommand::Okay))
+        let response = session.read_until_close()?;
+        log::debug!("{}", String::from_utf8_lossy(&response).trim());
+
+        Ok(())
     }
 
     pub(crate) fn disable_verity(&mut self) -> Result<()> {
-        self.open_session(&ADBLocalCommand::DisableVerity)?;
+        let mut session = self.open_session(&ADBLocalCommand::DisableVerity)?;
+
+        let response = session.read_until_close()?;
+        log::debug!("{}", String::from_utf8_lossy(&response).trim());
 
-        self.get_transport_mut()
-            .read_message()
-            .and_then(|message| message.assert_command(MessageCommand::Okay))
+        Ok(())
     }
 }
@@ -0,0 +1,144 @@
+use std::{
+    net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use crate::{
+    Result, RustADBError,
+    message_devices::{
+        adb_message_device::ADBMessageDevice,
+        adb_message_transport::ADBMessageTransport,
+        adb_session::ADBSession,
+        commands::utils::{MessageReader, MessageWriter},
+    },
+    models::ADBLocalCommand,
+};
+
+/// Local TCP listener forwarding each accepted connection to a service on device.
+///
+/// Listener is stopped when dropped. Already accepted connections are left untouched.
+#[derive(Debug)]
+pub(crate) struct ForwardListener {
+    address: SocketAddr,
+    remote: String,
+    stopped: Arc<AtomicBool>,
+}
+
+impl ForwardListener {
+    /// Listen on `address`, opening a session to `remote` service on `device` for each accepted connection.
+    pub(crate) fn start<T: ADBMessageTransport>(
+        device: ADBMessageDevice<T>,
+        address: SocketAddr,
+        remote: String,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        {
+            let remote = remote.clone();
+            let stopped = stopped.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::Acquire) {
+                        break;
+                    }
+
+                    match stream {
+                        Ok(stream) => {
+                            let device = device.clone();
+                            let remote = remote.clone();
+                            std::thread::spawn(move || forward_connection(device, stream, &remote));
+                        }
+                        Err(e) => log::warn!("cannot accept connection on {address}: {e}"),
+                    }
+                }
+                log::debug!("stopped forwarding {address} to {remote}");
+            });
+        }
+
+        log::debug!("forwarding {address} to {remote}");
+        Ok(Self {
+            address,
+            remote,
+            stopped,
+        })
+    }
+
+    /// Address this listener is bound to.
+    pub(crate) const fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Service on device connections are forwarded to.
+    pub(crate) fn remote(&self) -> &str {
+        &self.remote
+    }
+}
+
+impl Drop for ForwardListener {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+        // Wake up listening thread, blocked on accept
+        let _ = TcpStream::connect(self.address);
+    }
+}
+
+/// Open a session to `remote` service on `device`, and forward local `stream` to it.
+fn forward_connection<T: ADBMessageTransport>(
+    mut device: ADBMessageDevice<T>,
+    stream: TcpStream,
+    remote: &str,
+) {
+    match device.open_session(&ADBLocalCommand::Service(remote.to_string())) {
+        Ok(session) => bridge_connection(&session, stream, remote),
+        Err(e) => log::warn!("cannot connect to {remote} on device: {e}"),
+    }
+}
+
+/// Copy data both ways between local `stream` and `session`, until one side closes.
+///
+/// `peer` only describes the other end of the connection in logs.
+pub(crate) fn bridge_connection<T: ADBMessageTransport>(
+    session: &ADBSession<T>,
+    stream: TcpStream,
+    peer: &str,
+) {
+    let mut local_writer = match stream.try_clone() {
+        Ok(local_writer) => local_writer,
+        Err(e) => {
+            log::warn!("cannot forward connection to {peer}: {e}");
+            let _ = session.close();
+            return;
+        }
+    };
+    let mut device_reader = MessageReader::new(session.clone());
+    std::thread::spawn(move || {
+        let _ = std::io::copy(&mut device_reader, &mut local_writer);
+        // Device closed its side, or session has been closed locally
+        let _ = local_writer.shutdown(Shutdown::Both);
+    });
+
+    let mut local_reader = stream;
+    let mut device_writer = MessageWriter::new(session.clone());
+    if let Err(e) = std::io::copy(&mut local_reader, &mut device_writer) {
+        log::debug!("connection forwarded to {peer} ended: {e}");
+    }
+
+    // Best effort here
+    let _ = session.close();
+}
+
+/// Parse a `tcp:<port>` local endpoint into its localhost address, other kinds of endpoints being unsupported.
+pub(crate) fn parse_local_endpoint(local: &str) -> Result<SocketAddr> {
+    local
+        .strip_prefix("tcp:")
+        .and_then(|port| port.parse::<u16>().ok())
+        .map(|port| SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        .ok_or_else(|| {
+            RustADBError::ADBRequestFailed(format!("unsupported local endpoint: {local}"))
+        })
+}
@@ -6,14 +6,30 @@ pub mod usb;
 /// Device reachable over TCP related definition
 pub mod tcp;
 
-mod adb_message_device;
+/// Fake device to test code using this crate without any device
+#[cfg(any(test, feature = "test-support"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-support")))]
+pub mod test_support;
+
+mod adb_demultiplexer;
+pub(crate) mod adb_message_device;
 mod adb_message_device_commands;
-mod adb_message_transport;
+pub(crate) mod adb_message_transport;
 mod adb_session;
 mod adb_transport_message;
+#[cfg(feature = "tokio")]
+mod async_adb_message_device;
+#[cfg(feature = "tokio")]
+mod async_adb_session;
+mod auth_handshake;
 mod commands;
+pub(crate) mod forward_listener;
 mod message_commands;
-mod models;
+pub(crate) mod models;
 mod utils;
 
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub use async_adb_session::AsyncADBSession;
+pub use models::{ADBRsaKey, AuthEvent, AuthObserver, AuthOptions, ReconnectPolicy};
 pub use utils::BinaryDecodable;
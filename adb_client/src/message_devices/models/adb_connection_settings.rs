@@ -0,0 +1,73 @@
+use crate::{Result, models::DeviceBanner};
+
+/// Oldest protocol version, where every message payload has a checksum.
+const ADB_VERSION_MIN: u32 = 0x0100_0000;
+/// Protocol version advertised by this host.
+pub const ADB_VERSION: u32 = 0x0100_0001;
+/// Maximum payload size advertised by this host.
+pub const MAX_PAYLOAD: u32 = 1024 * 1024;
+/// Maximum payload size of devices using [`ADB_VERSION_MIN`].
+const MAX_PAYLOAD_V1: u32 = 4 * 1024;
+/// Amount of bytes a peer may send on a session before waiting for acknowledgements, when delayed ACK is used.
+pub const INITIAL_DELAYED_ACK_BYTES: u32 = 32 * 1024 * 1024;
+/// Feature advertised by both peers when they support delayed ACK.
+pub const DELAYED_ACK_FEATURE: &str = "delayed_ack";
+
+/// Connection parameters negotiated with device during `CNXN` exchange.
+#[derive(Clone, Copy, Debug)]
+pub struct ADBConnectionSettings {
+    /// Protocol version used by both peers.
+    pub version: u32,
+    /// Maximum size of a message payload accepted by both peers.
+    pub max_payload: usize,
+    /// Whether `WRTE` messages are acknowledged by amount of bytes, allowing many of them in flight.
+    pub delayed_ack: bool,
+}
+
+impl ADBConnectionSettings {
+    /// Negotiate settings from `version` and `max_payload` sent by device in its `CNXN` message, along with its `banner`.
+    pub fn negotiate(version: u32, max_payload: u32, banner: &DeviceBanner) -> Result<Self> {
+        let version = version.min(ADB_VERSION);
+        let max_payload = max_payload.clamp(MAX_PAYLOAD_V1, MAX_PAYLOAD);
+
+        Ok(Self {
+            version,
+            max_payload: usize::try_from(max_payload)?,
+            delayed_ack: version >= ADB_VERSION && banner.has_feature(DELAYED_ACK_FEATURE),
+        })
+    }
+}
+
+impl Default for ADBConnectionSettings {
+    fn default() -> Self {
+        Self {
+            version: ADB_VERSION_MIN,
+            max_payload: MAX_PAYLOAD_V1 as usize,
+            delayed_ack: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ADB_VERSION, ADB_VERSION_MIN, ADBConnectionSettings, MAX_PAYLOAD};
+    use crate::models::DeviceBanner;
+
+    #[test]
+    fn test_negotiate_connection_settings() {
+        let banner: DeviceBanner = "device::features=shell_v2,delayed_ack".parse().unwrap();
+
+        let settings = ADBConnectionSettings::negotiate(ADB_VERSION, 256 * 1024, &banner).unwrap();
+        assert!(settings.delayed_ack);
+        assert_eq!(settings.max_payload, 256 * 1024);
+
+        let settings = ADBConnectionSettings::negotiate(ADB_VERSION_MIN, 4096, &banner).unwrap();
+        assert!(!settings.delayed_ack);
+        assert_eq!(settings.max_payload, 4096);
+
+        let banner: DeviceBanner = "device::features=shell_v2".parse().unwrap();
+        let settings = ADBConnectionSettings::negotiate(ADB_VERSION, u32::MAX, &banner).unwrap();
+        assert!(!settings.delayed_ack);
+        assert_eq!(settings.max_payload, MAX_PAYLOAD as usize);
+    }
+}
@@ -0,0 +1,102 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+/// Default time given to user to allow USB debugging on device.
+const DEFAULT_AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Authentication state changes reported to an [`AuthObserver`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthEvent {
+    /// Device does not trust any of our keys, and asks user whether to allow debugging with the key of given fingerprint,
+    /// formatted like Android does, e.g. `17:9D:69:...`.
+    WaitingForUserAuthorization {
+        /// Fingerprint of the public key displayed by device.
+        fingerprint: String,
+    },
+    /// User allowed debugging, connection is established.
+    Authorized,
+}
+
+/// Observer notified while authenticating against a device.
+///
+/// Implemented for any `Fn(&AuthEvent)` closure that can be shared between threads.
+pub trait AuthObserver: Send + Sync {
+    /// Called each time authentication state changes.
+    fn on_auth_event(&self, event: &AuthEvent);
+}
+
+impl<F: Fn(&AuthEvent) + Send + Sync> AuthObserver for F {
+    fn on_auth_event(&self, event: &AuthEvent) {
+        self(event);
+    }
+}
+
+/// Options of authentication against devices reached over USB or TCP.
+///
+/// Options are set using builder methods, e.g. `AuthOptions::default().authorization_timeout(None)`.
+#[derive(Clone)]
+pub struct AuthOptions {
+    authorization_timeout: Option<Duration>,
+    observer: Option<Arc<dyn AuthObserver>>,
+    save_generated_key: bool,
+}
+
+impl Default for AuthOptions {
+    fn default() -> Self {
+        Self {
+            authorization_timeout: Some(DEFAULT_AUTHORIZATION_TIMEOUT),
+            observer: None,
+            save_generated_key: true,
+        }
+    }
+}
+
+impl Debug for AuthOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthOptions")
+            .field("authorization_timeout", &self.authorization_timeout)
+            .field("observer", &self.observer.is_some())
+            .field("save_generated_key", &self.save_generated_key)
+            .finish()
+    }
+}
+
+impl AuthOptions {
+    /// Time given to user to allow debugging on device when it does not trust any of our keys, `None` waiting forever.
+    ///
+    /// Defaults to 10 seconds.
+    #[must_use]
+    pub const fn authorization_timeout(mut self, authorization_timeout: Option<Duration>) -> Self {
+        self.authorization_timeout = authorization_timeout;
+        self
+    }
+
+    /// Notify `observer` of authentication state changes, e.g. to tell user to look at device screen.
+    #[must_use]
+    pub fn observer<O: AuthObserver + 'static>(mut self, observer: O) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Save the key generated when none exists at private key path, for device to keep trusting it at next connections.
+    ///
+    /// Defaults to `true`, like official client. Otherwise generated key is only kept in memory, hence authorized again on device at each connection.
+    #[must_use]
+    pub const fn save_generated_key(mut self, save_generated_key: bool) -> Self {
+        self.save_generated_key = save_generated_key;
+        self
+    }
+
+    pub(crate) const fn authorization_timeout_value(&self) -> Option<Duration> {
+        self.authorization_timeout
+    }
+
+    pub(crate) const fn save_generated_key_value(&self) -> bool {
+        self.save_generated_key
+    }
+
+    pub(crate) fn notify(&self, event: &AuthEvent) {
+        if let Some(observer) = &self.observer {
+            observer.on_auth_event(event);
+        }
+    }
+}
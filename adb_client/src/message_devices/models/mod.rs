@@ -1,4 +1,14 @@
+mod adb_connection_settings;
 mod adb_rsa_key;
+mod auth_options;
+mod reconnect_policy;
 
+pub use adb_connection_settings::{
+    ADB_VERSION, ADBConnectionSettings, DELAYED_ACK_FEATURE, INITIAL_DELAYED_ACK_BYTES, MAX_PAYLOAD,
+};
 pub use adb_rsa_key::ADBRsaKey;
-pub use adb_rsa_key::read_adb_private_key;
+#[cfg(any(test, feature = "test-support"))]
+pub(crate) use adb_rsa_key::verify_android_signature;
+pub use adb_rsa_key::{read_adb_private_key, read_vendor_keys};
+pub use auth_options::{AuthEvent, AuthObserver, AuthOptions};
+pub use reconnect_policy::ReconnectPolicy;
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+/// Default time spent trying to reach device again once its connection dropped.
+const DEFAULT_RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default delay between two reconnection attempts.
+const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Policy applied when connection to a device reached over USB or TCP drops, e.g. because `adbd` restarted as root.
+///
+/// Connection is established again before running next operation, and idempotent operations interrupted by the
+/// disconnection are run again. Policy is set using builder methods, e.g. `ReconnectPolicy::default().timeout(Duration::from_secs(5))`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    enabled: bool,
+    timeout: Duration,
+    retry_interval: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout: DEFAULT_RECONNECT_TIMEOUT,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Never reconnect: once connection dropped, every operation fails until device is instantiated again.
+    #[must_use]
+    pub const fn disabled() -> Self {
+        Self {
+            enabled: false,
+            timeout: Duration::ZERO,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+        }
+    }
+
+    /// Time spent trying to reach device again before giving up.
+    ///
+    /// Defaults to 30 seconds.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Delay between two reconnection attempts.
+    ///
+    /// Defaults to 500 milliseconds.
+    #[must_use]
+    pub const fn retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    pub(crate) const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) const fn timeout_value(&self) -> Duration {
+        self.timeout
+    }
+
+    pub(crate) const fn retry_interval_value(&self) -> Duration {
+        self.retry_interval
+    }
+}
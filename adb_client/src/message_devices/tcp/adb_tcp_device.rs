@@ -1,15 +1,23 @@
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
 use std::{io::Read, net::SocketAddr};
 
-use crate::message_devices::adb_message_device::ADBMessageDevice;
-use crate::models::RemountInfo;
+use crate::message_devices::adb_message_device::{ADBMessageDevice, load_private_keys};
+use crate::message_devices::models::{AuthOptions, ReconnectPolicy};
+use crate::models::{
+    CancellationToken, DeviceBanner, ForwardRule, InstallOptions, ProgressObserver, PushOptions,
+    RemountInfo, ShellWindowSize, SyncCompression, Timeouts,
+};
+use crate::tcp::TlsOptions;
+use crate::tcp::pairing::pair;
 use crate::tcp::tcp_transport::TcpTransport;
 use crate::utils::get_default_adb_key_path;
 use crate::{ADBDeviceExt, ADBListItemType, Result};
 
 /// Represent a device reached and available over TCP.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ADBTcpDevice {
     inner: ADBMessageDevice<TcpTransport>,
 }
@@ -25,13 +33,197 @@ impl ADBTcpDevice {
         address: A,
         private_key_path: P,
     ) -> Result<Self> {
+        Self::new_with_options_inner(
+            address,
+            private_key_path,
+            &AuthOptions::default(),
+            TlsOptions::default(),
+        )
+    }
+
+    /// Instantiate a new [`ADBTcpDevice`] using given authentication and TLS options, and an optional private key path.
+    ///
+    /// Certificate presented by device is checked against the one pinned for [`TlsOptions::device_id`], see [`KnownDevices`](crate::tcp::KnownDevices).
+    pub fn new_with_options<A: Into<SocketAddr>>(
+        address: A,
+        private_key_path: Option<PathBuf>,
+        auth_options: &AuthOptions,
+        tls_options: TlsOptions,
+    ) -> Result<Self> {
+        let private_key_path = match private_key_path {
+            Some(private_key_path) => private_key_path,
+            None => get_default_adb_key_path()?,
+        };
+
+        Self::new_with_options_inner(address, &private_key_path, auth_options, tls_options)
+    }
+
+    fn new_with_options_inner<P: AsRef<Path>, A: Into<SocketAddr>>(
+        address: A,
+        private_key_path: P,
+        auth_options: &AuthOptions,
+        tls_options: TlsOptions,
+    ) -> Result<Self> {
+        // Key authenticating TLS connection is the one authenticating with `AUTH` messages
+        let private_keys = load_private_keys(private_key_path, auth_options)?;
+        let transport = TcpTransport::new(address, private_keys[0].clone(), tls_options);
+
         Ok(Self {
-            inner: ADBMessageDevice::new(
-                TcpTransport::new(address, &private_key_path),
-                private_key_path,
-            )?,
+            inner: ADBMessageDevice::new_with_private_keys(transport, private_keys, auth_options)?,
         })
     }
+
+    /// Pair with a device waiting for wireless debugging pairing on `address`, using `code` displayed by device, and return device GUID.
+    ///
+    /// Once paired, the device accepts connections authenticated with the default private key, which is created if needed.
+    /// Pairing port differs from the port used to connect to device afterwards.
+    /// Returned GUID identifies device certificate in known devices, using [`TlsOptions::device_id`].
+    pub fn pair<A: Into<SocketAddr>>(address: A, code: &str) -> Result<String> {
+        Self::pair_with_custom_private_key(address, code, get_default_adb_key_path()?)
+    }
+
+    /// Pair with a device waiting for wireless debugging pairing on `address`, trusting key at a custom private key path.
+    pub fn pair_with_custom_private_key<P: AsRef<Path>, A: Into<SocketAddr>>(
+        address: A,
+        code: &str,
+        private_key_path: P,
+    ) -> Result<String> {
+        pair(
+            address.into(),
+            code,
+            private_key_path.as_ref(),
+            &TlsOptions::default(),
+        )
+    }
+
+    /// Pair with a device waiting for wireless debugging pairing on `address`, using given TLS options and an optional private key path.
+    ///
+    /// Certificate pinned for returned device GUID in known devices of `tls_options` is forgotten, next connection pinning the one then presented.
+    pub fn pair_with_tls_options<A: Into<SocketAddr>>(
+        address: A,
+        code: &str,
+        private_key_path: Option<PathBuf>,
+        tls_options: &TlsOptions,
+    ) -> Result<String> {
+        let private_key_path = match private_key_path {
+            Some(private_key_path) => private_key_path,
+            None => get_default_adb_key_path()?,
+        };
+
+        pair(address.into(), code, &private_key_path, tls_options)
+    }
+
+    /// Starts an interactive shell session on the device, forwarding each size received from `window_sizes` to the remote terminal.
+    /// Input data is read from reader and write to writer.
+    ///
+    /// Window sizes are only forwarded, and shell exit status returned, if device supports shell v2.
+    pub fn shell_with_window_size(
+        &mut self,
+        reader: &mut dyn Read,
+        writer: Box<dyn Write + Send>,
+        window_sizes: Receiver<ShellWindowSize>,
+    ) -> Result<Option<u8>> {
+        self.inner
+            .shell_with_window_size(reader, writer, Some(window_sizes))
+    }
+
+    /// Banner sent by device when connection has been established, describing it and its features.
+    #[must_use]
+    pub const fn banner(&self) -> &DeviceBanner {
+        self.inner.banner()
+    }
+
+    /// Set compression used by file transfers, when device supports sync version 2.
+    ///
+    /// Defaults to [`SyncCompression::Any`], using the best algorithm supported by both device and this build.
+    pub const fn set_sync_compression(&mut self, compression: SyncCompression) {
+        self.inner.set_sync_compression(compression);
+    }
+
+    /// Set policy applied when connection to device drops, e.g. after [`root`](ADBDeviceExt::root) restarted `adbd`.
+    ///
+    /// Defaults to reconnecting for up to 30 seconds before next operation, see [`ReconnectPolicy`].
+    pub const fn set_reconnect_policy(&mut self, reconnect_policy: ReconnectPolicy) {
+        self.inner.set_reconnect_policy(reconnect_policy);
+    }
+
+    /// Set default timeouts applied to each operation run on this device, and when connecting again to it.
+    ///
+    /// Connection defaults to a 10 seconds timeout, handshake included, operations waiting as long as needed. See [`Timeouts`].
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.inner.set_timeouts(timeouts);
+    }
+
+    /// Run `operation` on this device using `timeouts` instead of its defaults, restored afterwards.
+    pub fn with_timeouts<R>(
+        &mut self,
+        timeouts: Timeouts,
+        operation: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        let defaults = self.inner.timeouts();
+        self.inner.set_timeouts(timeouts);
+        let result = operation(self);
+        self.inner.set_timeouts(defaults);
+        result
+    }
+
+    /// Wait for device to be reachable for at most `timeout`, connecting to it again if connection dropped, e.g. after a reboot.
+    ///
+    /// Returns immediately if connection is still up.
+    pub fn wait_for_device(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.wait_for_device(timeout)
+    }
+
+    /// Forward connections accepted on `local` to `remote` service on device, e.g. `tcp:8080` or `localabstract:name`.
+    ///
+    /// Only `tcp:<port>` local endpoints are supported, listening on localhost. Port 0 picks a free port.
+    /// Returns the local endpoint actually used. Forwarding stops once this device is dropped.
+    pub fn forward(&mut self, remote: String, local: &str) -> Result<String> {
+        self.inner.forward(remote, local)
+    }
+
+    /// Remove a previously applied forward rule by its local endpoint.
+    pub fn forward_remove(&mut self, local: &str) -> Result<()> {
+        self.inner.forward_remove(local)
+    }
+
+    /// Remove all previously applied forward rules
+    pub fn forward_remove_all(&mut self) -> Result<()> {
+        self.inner.forward_remove_all()
+    }
+
+    /// List active forward rules
+    pub fn forward_list(&self) -> Result<Vec<ForwardRule>> {
+        self.inner.forward_list()
+    }
+
+    /// Ask device to forward connections accepted on `remote` to `local` endpoint on this host, e.g. `tcp:8080`.
+    ///
+    /// Only `tcp:<port>` local endpoints are supported. Remote port 0 lets device pick a free port.
+    /// Returns the remote endpoint actually used.
+    pub fn reverse(&mut self, remote: String, local: &str) -> Result<String> {
+        self.inner.reverse(remote, local)
+    }
+
+    /// Remove a previously applied reverse rule by its remote endpoint.
+    pub fn reverse_remove(&mut self, remote: &str) -> Result<()> {
+        self.inner.reverse_remove(remote)
+    }
+
+    /// Remove all previously applied reverse rules
+    pub fn reverse_remove_all(&mut self) -> Result<()> {
+        self.inner.reverse_remove_all()
+    }
+
+    /// List reverse rules applied from this host
+    pub fn reverse_list(&self) -> Result<Vec<ForwardRule>> {
+        self.inner.reverse_list()
+    }
+
+    /// Underlying message device, e.g. to be served by an [`ADBHostServer`](crate::host_server::ADBHostServer).
+    pub(crate) fn into_inner(self) -> ADBMessageDevice<TcpTransport> {
+        self.inner
+    }
 }
 
 impl ADBDeviceExt for ADBTcpDevice {
@@ -46,8 +238,12 @@ impl ADBDeviceExt for ADBTcpDevice {
     }
 
     #[inline]
-    fn shell(&mut self, reader: &mut dyn Read, writer: Box<dyn Write + Send>) -> Result<()> {
-        self.inner.shell(reader, writer)
+    fn shell(
+        &mut self,
+        reader: &mut dyn Read,
+        writer: Box<dyn Write + Send>,
+    ) -> Result<Option<u8>> {
+        self.inner.shell_with_window_size(reader, writer, None)
     }
 
     #[inline]
@@ -61,8 +257,13 @@ impl ADBDeviceExt for ADBTcpDevice {
     }
 
     #[inline]
-    fn push(&mut self, stream: &mut dyn Read, path: &dyn AsRef<str>) -> Result<()> {
-        self.inner.push(stream, path)
+    fn push_with_options(
+        &mut self,
+        stream: &mut dyn Read,
+        path: &dyn AsRef<str>,
+        options: PushOptions,
+    ) -> Result<()> {
+        self.inner.push_with_options(stream, path, options)
     }
 
     #[inline]
@@ -81,8 +282,16 @@ impl ADBDeviceExt for ADBTcpDevice {
     }
 
     #[inline]
-    fn install(&mut self, apk_path: &dyn AsRef<Path>, user: Option<&str>) -> Result<()> {
-        self.inner.install(apk_path, user)
+    fn install_from_reader_with_progress(
+        &mut self,
+        apk: &mut dyn Read,
+        size: u64,
+        options: &InstallOptions,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        self.inner
+            .install_from_reader_with_progress(apk, size, options, observer, cancellation)
     }
 
     #[inline]
@@ -120,4 +329,9 @@ impl ADBDeviceExt for ADBTcpDevice {
     ) -> Result<()> {
         self.inner.exec(command, reader, writer)
     }
+
+    #[inline]
+    fn exec_with_input(&mut self, command: &str, input: &mut dyn Read) -> Result<Vec<u8>> {
+        self.inner.exec_with_input(command, input)
+    }
 }
@@ -0,0 +1,154 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustls::pki_types::ServerName;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use crate::message_devices::adb_message_device::{connect_message, load_private_keys};
+use crate::message_devices::adb_transport_message::{ADBTransportMessage, ChecksumPolicy};
+use crate::message_devices::async_adb_message_device::{
+    AsyncADBMessageDevice, authenticate, read_message, write_message,
+};
+use crate::message_devices::async_adb_session::AsyncADBSession;
+use crate::message_devices::message_commands::MessageCommand;
+use crate::message_devices::models::AuthOptions;
+use crate::models::{DeviceBanner, HostFeatures};
+use crate::tcp::TlsOptions;
+use crate::tcp::tcp_transport::{CertificatePinning, tls_client_config};
+use crate::utils::get_default_adb_key_path;
+use crate::{AsyncADBDeviceExt, Result, RustADBError};
+
+/// Represent a device reached and available over TCP, from an asynchronous runtime.
+///
+/// Asynchronous counterpart of [`ADBTcpDevice`](super::ADBTcpDevice), features being provided by [`AsyncADBDeviceExt`].
+/// Cloning this structure shares the same underlying connection, which is closed once all clones and their sessions are dropped.
+#[derive(Debug, Clone)]
+pub struct AsyncADBTcpDevice {
+    inner: AsyncADBMessageDevice,
+}
+
+impl AsyncADBTcpDevice {
+    /// Instantiate a new [`AsyncADBTcpDevice`]
+    pub async fn new<A: Into<SocketAddr>>(address: A) -> Result<Self> {
+        Self::new_with_custom_private_key(address, get_default_adb_key_path()?).await
+    }
+
+    /// Instantiate a new [`AsyncADBTcpDevice`] using a custom private key path
+    pub async fn new_with_custom_private_key<P: AsRef<Path>, A: Into<SocketAddr>>(
+        address: A,
+        private_key_path: P,
+    ) -> Result<Self> {
+        Self::new_with_options_inner(
+            address.into(),
+            private_key_path.as_ref(),
+            &AuthOptions::default(),
+            &TlsOptions::default(),
+        )
+        .await
+    }
+
+    /// Instantiate a new [`AsyncADBTcpDevice`] using given authentication and TLS options, and an optional private key path.
+    ///
+    /// Certificate presented by device is checked against the one pinned for [`TlsOptions::device_id`], see [`KnownDevices`](crate::tcp::KnownDevices).
+    pub async fn new_with_options<A: Into<SocketAddr>>(
+        address: A,
+        private_key_path: Option<PathBuf>,
+        auth_options: &AuthOptions,
+        tls_options: TlsOptions,
+    ) -> Result<Self> {
+        let private_key_path = match private_key_path {
+            Some(private_key_path) => private_key_path,
+            None => get_default_adb_key_path()?,
+        };
+
+        Self::new_with_options_inner(
+            address.into(),
+            &private_key_path,
+            auth_options,
+            &tls_options,
+        )
+        .await
+    }
+
+    async fn new_with_options_inner(
+        address: SocketAddr,
+        private_key_path: &Path,
+        auth_options: &AuthOptions,
+        tls_options: &TlsOptions,
+    ) -> Result<Self> {
+        // Loading keys reads files, and may generate one
+        let private_keys = {
+            let private_key_path = private_key_path.to_path_buf();
+            let auth_options = auth_options.clone();
+            tokio::task::spawn_blocking(move || load_private_keys(private_key_path, &auth_options))
+                .await
+                .map_err(|e| RustADBError::ADBRequestFailed(format!("cannot load keys: {e}")))??
+        };
+        let checksum = ChecksumPolicy::default();
+
+        let mut stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        write_message(&mut stream, connect_message()?, &checksum).await?;
+        let message = read_message(&mut stream, &checksum).await?;
+
+        // Check if a client is requesting a secure connection and upgrade it if necessary
+        if message.header().command() != MessageCommand::Stls {
+            let connection_message =
+                authenticate(&mut stream, message, &private_keys, auth_options, &checksum).await?;
+            return Ok(Self {
+                inner: AsyncADBMessageDevice::start(stream, connection_message, checksum)?,
+            });
+        }
+
+        write_message(
+            &mut stream,
+            ADBTransportMessage::try_new(MessageCommand::Stls, 1, 0, &[])?,
+            &checksum,
+        )
+        .await?;
+
+        let private_key = private_keys
+            .first()
+            .ok_or_else(|| RustADBError::ADBRequestFailed("no private key available".into()))?;
+        let pinning = CertificatePinning::new(address, tls_options)?;
+        let connector = TlsConnector::from(Arc::new(tls_client_config(
+            private_key,
+            pinning.verifier(),
+            tls_options,
+        )?));
+        let mut stream = connector
+            .connect(ServerName::from(address.ip()), stream)
+            .await
+            .map_err(|e| pinning.handshake_error(e))?;
+        pinning.finish()?;
+        log::debug!("Connection successfully upgraded from TCP to TLS");
+
+        let message = read_message(&mut stream, &checksum).await?;
+        let connection_message =
+            authenticate(&mut stream, message, &private_keys, auth_options, &checksum).await?;
+
+        Ok(Self {
+            inner: AsyncADBMessageDevice::start(stream, connection_message, checksum)?,
+        })
+    }
+
+    /// Banner sent by device when connection has been established, describing it and its features.
+    #[must_use]
+    pub const fn banner(&self) -> &DeviceBanner {
+        self.inner.banner()
+    }
+}
+
+impl AsyncADBDeviceExt for AsyncADBTcpDevice {
+    type Stream = AsyncADBSession;
+
+    async fn open_service(&mut self, service: &str) -> Result<AsyncADBSession> {
+        self.inner.open_session(service).await
+    }
+
+    async fn host_features(&mut self) -> Result<Vec<HostFeatures>> {
+        Ok(self.inner.features().to_vec())
+    }
+}
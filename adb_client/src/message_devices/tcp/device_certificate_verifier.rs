@@ -0,0 +1,103 @@
+use std::sync::Mutex;
+
+use rustls::{
+    CertificateError, DigitallySignedStruct, SignatureScheme,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{
+        WebPkiSupportedAlgorithms, ring::default_provider, verify_tls12_signature,
+        verify_tls13_signature,
+    },
+    pki_types::{CertificateDer, ServerName, UnixTime},
+};
+
+use crate::tcp::known_devices::public_key_fingerprint;
+
+/// Verify certificate presented by device against the pinned one.
+///
+/// Devices use self-signed certificates, hence only their public key fingerprint is checked.
+/// Any certificate is accepted if none is pinned, its fingerprint being available once handshake is over.
+/// Handshake signatures are always verified, proving that device owns presented certificate.
+#[derive(Debug)]
+pub(crate) struct DeviceCertificateVerifier {
+    pinned_fingerprint: Option<String>,
+    presented_fingerprint: Mutex<Option<String>>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl DeviceCertificateVerifier {
+    pub(crate) fn new(pinned_fingerprint: Option<String>) -> Self {
+        Self {
+            pinned_fingerprint,
+            presented_fingerprint: Mutex::new(None),
+            algorithms: default_provider().signature_verification_algorithms,
+        }
+    }
+
+    /// Fingerprint of certificate presented by device, if any.
+    pub(crate) fn presented_fingerprint(&self) -> Option<String> {
+        self.presented_fingerprint
+            .lock()
+            .ok()
+            .and_then(|fingerprint| fingerprint.clone())
+    }
+
+    /// Fingerprint of certificate presented by device, if it has been rejected.
+    pub(crate) fn rejected_fingerprint(&self) -> Option<String> {
+        let pinned_fingerprint = self.pinned_fingerprint.as_ref()?;
+        self.presented_fingerprint()
+            .filter(|fingerprint| fingerprint != pinned_fingerprint)
+    }
+}
+
+impl ServerCertVerifier for DeviceCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = public_key_fingerprint(end_entity).ok_or(
+            rustls::Error::InvalidCertificate(CertificateError::BadEncoding),
+        )?;
+        let matches = self
+            .pinned_fingerprint
+            .as_ref()
+            .is_none_or(|pinned_fingerprint| *pinned_fingerprint == fingerprint);
+
+        if let Ok(mut presented_fingerprint) = self.presented_fingerprint.lock() {
+            *presented_fingerprint = Some(fingerprint);
+        }
+
+        if matches {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    fmt::Write,
+    fs::{create_dir_all, read_to_string, write},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use ring::digest::{SHA256, digest};
+use rustls::pki_types::CertificateDer;
+use webpki::EndEntityCert;
+
+use crate::{Result, RustADBError, utils::get_default_known_devices_path};
+
+/// Store of certificates presented by devices reached over TLS, pinned by device identity.
+///
+/// Identity is the GUID returned by pairing, which is also the instance name advertised by device over mDNS, e.g. `adb-<serial>-<suffix>`.
+/// It is given using [`TlsOptions::device_id`](crate::tcp::TlsOptions::device_id), as addresses and ports of a device change each time wireless debugging is enabled.
+///
+/// First connection to a device trusts whatever certificate it presents, and pins it. Any later connection presenting another one is rejected.
+/// Only certificate public key is pinned, as `adbd` signs a new certificate for each connection.
+/// Pairing again with a device forgets its pinned certificate, e.g. once it has been reset.
+///
+/// Store is a text file holding one `<device identity> <SHA-256 fingerprint of certificate public key>` line per device.
+#[derive(Clone, Debug)]
+pub struct KnownDevices {
+    path: PathBuf,
+}
+
+impl KnownDevices {
+    /// Use store located at `path`, created when pinning a first certificate.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Use default store, located next to default ADB keys.
+    pub fn new_default() -> Result<Self> {
+        Ok(Self::new(get_default_known_devices_path()?))
+    }
+
+    /// Path of this store.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// List known devices, with fingerprint of their pinned certificate.
+    pub fn list(&self) -> Result<Vec<(String, String)>> {
+        let content = match read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut devices = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once(' ') {
+                Some((device, fingerprint)) => {
+                    devices.push((device.into(), fingerprint.trim().into()));
+                }
+                None => log::warn!(
+                    "ignoring invalid line in known devices {}: {line}",
+                    self.path.display()
+                ),
+            }
+        }
+
+        Ok(devices)
+    }
+
+    /// Fingerprint of certificate pinned for `device`, if known.
+    pub fn fingerprint(&self, device: &str) -> Result<Option<String>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .find_map(|(known_device, fingerprint)| {
+                (known_device == device).then_some(fingerprint)
+            }))
+    }
+
+    /// Forget certificate pinned for `device`, returning whether it was known.
+    ///
+    /// Next certificate presented by this device gets pinned.
+    pub fn forget(&self, device: &str) -> Result<bool> {
+        let mut devices = self.list()?;
+        let count = devices.len();
+        devices.retain(|(known_device, _)| *known_device != device);
+        if devices.len() == count {
+            return Ok(false);
+        }
+
+        self.save(&devices)?;
+        Ok(true)
+    }
+
+    /// Pin certificate with `fingerprint` for `device`, replacing any previous one.
+    pub(crate) fn pin(&self, device: &str, fingerprint: &str) -> Result<()> {
+        if device.is_empty() || device.contains(char::is_whitespace) {
+            return Err(RustADBError::ADBRequestFailed(format!(
+                "invalid device identity: {device:?}"
+            )));
+        }
+
+        let mut devices = self.list()?;
+        devices.retain(|(known_device, _)| known_device != device);
+        devices.push((device.into(), fingerprint.to_string()));
+
+        self.save(&devices)
+    }
+
+    fn save(&self, devices: &[(String, String)]) -> Result<()> {
+        let mut content = String::new();
+        for (device, fingerprint) in devices {
+            let _ = writeln!(content, "{device} {fingerprint}");
+        }
+
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+        Ok(write(&self.path, content)?)
+    }
+}
+
+/// Fingerprint of `certificate` public key, as stored in [`KnownDevices`].
+pub(crate) fn public_key_fingerprint(certificate: &CertificateDer<'_>) -> Option<String> {
+    let certificate = EndEntityCert::try_from(certificate).ok()?;
+    let fingerprint = digest(&SHA256, certificate.subject_public_key_info().as_ref())
+        .as_ref()
+        .iter()
+        .fold(String::new(), |mut fingerprint, byte| {
+            let _ = write!(fingerprint, "{byte:02x}");
+            fingerprint
+        });
+
+    Some(fingerprint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KnownDevices;
+
+    #[test]
+    fn test_known_devices() {
+        let path = std::env::temp_dir().join(format!(
+            "adb_client_known_devices_test_{}",
+            std::process::id()
+        ));
+        let known_devices = KnownDevices::new(&path);
+        let (first, second) = ("adb-R5CT1234-AbCdEf", "adb-emulator-5554-GhIjKl");
+
+        assert_eq!(known_devices.fingerprint(first).unwrap(), None);
+        known_devices.pin(first, "aa").unwrap();
+        known_devices.pin(second, "bb").unwrap();
+        known_devices.pin(first, "cc").unwrap();
+        assert_eq!(
+            known_devices.list().unwrap(),
+            vec![(second.into(), "bb".into()), (first.into(), "cc".into())]
+        );
+        assert!(known_devices.pin("adb device", "dd").is_err());
+        assert!(known_devices.pin("", "dd").is_err());
+
+        assert!(known_devices.forget(second).unwrap());
+        assert!(!known_devices.forget(second).unwrap());
+        assert_eq!(known_devices.fingerprint(second).unwrap(), None);
+        assert_eq!(known_devices.fingerprint(first).unwrap(), Some("cc".into()));
+
+        let _ = std::fs::remove_file(path);
+    }
+}
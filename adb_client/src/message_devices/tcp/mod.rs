@@ -1,6 +1,17 @@
 #![doc = include_str!("./README.md")]
 
 mod adb_tcp_device;
+#[cfg(feature = "tokio")]
+mod async_adb_tcp_device;
+mod device_certificate_verifier;
+mod known_devices;
+mod pairing;
 mod tcp_transport;
+mod tls_options;
 
 pub use adb_tcp_device::ADBTcpDevice;
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub use async_adb_tcp_device::AsyncADBTcpDevice;
+pub use known_devices::KnownDevices;
+pub use tls_options::TlsOptions;
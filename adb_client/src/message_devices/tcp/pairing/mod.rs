@@ -0,0 +1,5 @@
+mod pairing_cipher;
+mod pairing_connection;
+mod spake2;
+
+pub(crate) use pairing_connection::pair;
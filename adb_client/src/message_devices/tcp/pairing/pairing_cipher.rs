@@ -0,0 +1,69 @@
+use ring::{
+    aead::{AES_128_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    hkdf::{HKDF_SHA256, Salt},
+};
+
+use crate::{Result, RustADBError};
+
+/// Information used to derive encryption key from SPAKE2 key.
+const HKDF_INFO: &[u8] = b"adb pairing_auth aes-128-gcm key";
+
+/// AES-128-GCM cipher protecting peer information exchanged while pairing.
+///
+/// Nonces are made of a per-direction message counter.
+#[derive(Debug)]
+pub(crate) struct PairingCipher {
+    key: LessSafeKey,
+    encrypt_sequence: u64,
+    decrypt_sequence: u64,
+}
+
+impl PairingCipher {
+    /// Derive a cipher from `key_material` agreed on with SPAKE2.
+    pub(crate) fn new(key_material: &[u8]) -> Result<Self> {
+        let key: UnboundKey = Salt::new(HKDF_SHA256, &[])
+            .extract(key_material)
+            .expand(&[HKDF_INFO], &AES_128_GCM)
+            .map_err(|_| RustADBError::PairingError("cannot derive encryption key".into()))?
+            .into();
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            encrypt_sequence: 0,
+            decrypt_sequence: 0,
+        })
+    }
+
+    fn nonce(sequence: u64) -> Nonce {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..8].copy_from_slice(&sequence.to_le_bytes());
+        Nonce::assume_unique_for_key(nonce)
+    }
+
+    pub(crate) fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut data = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(Self::nonce(self.encrypt_sequence), Aad::empty(), &mut data)
+            .map_err(|_| RustADBError::PairingError("cannot encrypt peer information".into()))?;
+        self.encrypt_sequence += 1;
+
+        Ok(data)
+    }
+
+    pub(crate) fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let mut data = ciphertext.to_vec();
+        let plaintext_len = self
+            .key
+            .open_in_place(Self::nonce(self.decrypt_sequence), Aad::empty(), &mut data)
+            .map_err(|_| {
+                RustADBError::PairingError(
+                    "cannot decrypt peer information, pairing code is likely wrong".into(),
+                )
+            })?
+            .len();
+        data.truncate(plaintext_len);
+        self.decrypt_sequence += 1;
+
+        Ok(data)
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    path::Path,
+    sync::Arc,
+};
+
+use rustls::{ClientConnection, StreamOwned};
+
+use super::{
+    pairing_cipher::PairingCipher,
+    spake2::{Spake2, Spake2Role},
+};
+use crate::{
+    Result, RustADBError,
+    message_devices::models::ADBRsaKey,
+    tcp::{
+        TlsOptions, device_certificate_verifier::DeviceCertificateVerifier,
+        tcp_transport::tls_client_config,
+    },
+};
+
+/// Name of pairing client in SPAKE2 exchange, NUL terminator included.
+const CLIENT_NAME: &[u8] = b"adb pair client\0";
+/// Name of pairing server in SPAKE2 exchange, NUL terminator included.
+const SERVER_NAME: &[u8] = b"adb pair server\0";
+
+/// Label of keying material exported from TLS session, NUL terminator included.
+const EXPORTED_KEY_LABEL: &[u8] = b"adb-label\0";
+const EXPORTED_KEY_SIZE: usize = 64;
+
+const PAIRING_PACKET_VERSION: u8 = 1;
+const PAIRING_PACKET_HEADER_SIZE: usize = 6;
+const PACKET_TYPE_SPAKE2_MESSAGE: u8 = 0;
+const PACKET_TYPE_PEER_INFO: u8 = 1;
+
+const MAX_PEER_INFO_SIZE: usize = 8192;
+const MAX_PAYLOAD_SIZE: usize = 2 * MAX_PEER_INFO_SIZE;
+const PEER_INFO_RSA_PUBLIC_KEY: u8 = 0;
+const PEER_INFO_DEVICE_GUID: u8 = 1;
+
+/// Information exchanged by peers once they agreed on a key: our public key, and device GUID.
+#[derive(Debug, PartialEq, Eq)]
+struct PeerInfo {
+    info_type: u8,
+    data: Vec<u8>,
+}
+
+impl PeerInfo {
+    /// Fixed-size encoding, `data` being padded with zeros.
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.data.len() >= MAX_PEER_INFO_SIZE - 1 {
+            return Err(RustADBError::PairingError(format!(
+                "peer information too large: {} bytes",
+                self.data.len()
+            )));
+        }
+
+        let mut bytes = vec![0u8; MAX_PEER_INFO_SIZE];
+        bytes[0] = self.info_type;
+        bytes[1..=self.data.len()].copy_from_slice(&self.data);
+        Ok(bytes)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some((info_type, data)) = bytes.split_first() else {
+            return Err(RustADBError::PairingError("empty peer information".into()));
+        };
+        if bytes.len() != MAX_PEER_INFO_SIZE {
+            return Err(RustADBError::PairingError(format!(
+                "invalid peer information size: {}",
+                bytes.len()
+            )));
+        }
+
+        let data_len = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+        Ok(Self {
+            info_type: *info_type,
+            data: data[..data_len].to_vec(),
+        })
+    }
+}
+
+/// Pair with device listening for pairing requests on `address`, using pairing `code` displayed by device, and return device GUID.
+///
+/// Public key matching private key at `private_key_path` gets trusted by device.
+/// A new private key is generated there if none exists yet.
+///
+/// Pairing certificate is not checked, as the exchange fails if TLS session is not the one established by device.
+/// It is not pinned either, as `adbd` presents another certificate once connected.
+/// Certificate pinned for device GUID in known devices is forgotten instead, next connection pinning the one then presented.
+pub(crate) fn pair(
+    address: SocketAddr,
+    code: &str,
+    private_key_path: &Path,
+    tls_options: &TlsOptions,
+) -> Result<String> {
+    let known_devices = tls_options.known_devices_store()?;
+    let private_key = ADBRsaKey::load_or_generate(private_key_path)?;
+    let public_key = private_key.android_pubkey_encode()?;
+
+    let connection = ClientConnection::new(
+        Arc::new(tls_client_config(
+            &private_key,
+            Arc::new(DeviceCertificateVerifier::new(None)),
+            tls_options,
+        )?),
+        address.ip().into(),
+    )?;
+    let mut stream = StreamOwned::new(connection, TcpStream::connect(address)?);
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+
+    // Binding pairing code to this TLS session prevents relaying the exchange
+    let mut password = code.as_bytes().to_vec();
+    password.extend_from_slice(&stream.conn.export_keying_material(
+        [0u8; EXPORTED_KEY_SIZE],
+        EXPORTED_KEY_LABEL,
+        None,
+    )?);
+
+    let our_info = PeerInfo {
+        info_type: PEER_INFO_RSA_PUBLIC_KEY,
+        data: public_key.into_bytes(),
+    };
+    let device_info = exchange(&mut stream, Spake2Role::Alice, &password, &our_info)?;
+    if device_info.info_type != PEER_INFO_DEVICE_GUID {
+        return Err(RustADBError::PairingError(format!(
+            "unexpected peer information type: {}",
+            device_info.info_type
+        )));
+    }
+
+    let device_guid = String::from_utf8(device_info.data)?;
+    log::info!("successfully paired with device {device_guid}");
+    if known_devices.forget(&device_guid)? {
+        log::info!("forgot certificate pinned for device {device_guid}, paired again");
+    }
+
+    Ok(device_guid)
+}
+
+/// Agree on a key with SPAKE2 using `password`, then exchange peer information encrypted with it.
+fn exchange<S: Read + Write>(
+    stream: &mut S,
+    role: Spake2Role,
+    password: &[u8],
+    our_info: &PeerInfo,
+) -> Result<PeerInfo> {
+    let (our_name, their_name) = match role {
+        Spake2Role::Alice => (CLIENT_NAME, SERVER_NAME),
+        Spake2Role::Bob => (SERVER_NAME, CLIENT_NAME),
+    };
+    let spake2 = Spake2::new(role, our_name, their_name, password)?;
+
+    write_packet(stream, PACKET_TYPE_SPAKE2_MESSAGE, spake2.message())?;
+    let their_message = read_packet(stream, PACKET_TYPE_SPAKE2_MESSAGE)?;
+    let mut cipher = PairingCipher::new(&spake2.process_message(&their_message)?)?;
+
+    write_packet(
+        stream,
+        PACKET_TYPE_PEER_INFO,
+        &cipher.encrypt(&our_info.to_bytes()?)?,
+    )?;
+    // Peer closes connection if it cannot decrypt our information
+    let their_info = read_packet(stream, PACKET_TYPE_PEER_INFO).map_err(|e| {
+        RustADBError::PairingError(format!(
+            "peer did not answer, pairing code is likely wrong: {e}"
+        ))
+    })?;
+
+    PeerInfo::from_bytes(&cipher.decrypt(&their_info)?)
+}
+
+fn write_packet<S: Write>(stream: &mut S, packet_type: u8, payload: &[u8]) -> Result<()> {
+    let mut packet = Vec::with_capacity(PAIRING_PACKET_HEADER_SIZE + payload.len());
+    packet.push(PAIRING_PACKET_VERSION);
+    packet.push(packet_type);
+    packet.extend_from_slice(&u32::try_from(payload.len())?.to_be_bytes());
+    packet.extend_from_slice(payload);
+
+    stream.write_all(&packet)?;
+    Ok(stream.flush()?)
+}
+
+fn read_packet<S: Read>(stream: &mut S, expected_type: u8) -> Result<Vec<u8>> {
+    let mut header = [0u8; PAIRING_PACKET_HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+
+    let [version, packet_type, size @ ..] = header;
+    if version != PAIRING_PACKET_VERSION {
+        return Err(RustADBError::PairingError(format!(
+            "unsupported pairing packet version: {version}"
+        )));
+    }
+    if packet_type != expected_type {
+        return Err(RustADBError::PairingError(format!(
+            "unexpected pairing packet type {packet_type}, expected {expected_type}"
+        )));
+    }
+    let size = usize::try_from(u32::from_be_bytes(size))?;
+    if size == 0 || size > MAX_PAYLOAD_SIZE {
+        return Err(RustADBError::PairingError(format!(
+            "invalid pairing packet size: {size}"
+        )));
+    }
+
+    let mut payload = vec![0u8; size];
+    stream.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{SocketAddr, TcpListener},
+        path::PathBuf,
+        sync::Arc,
+        thread::JoinHandle,
+    };
+
+    use rcgen::{CertificateParams, KeyPair};
+    use rustls::{ServerConfig, ServerConnection, StreamOwned, pki_types::PrivatePkcs8KeyDer};
+
+    use super::{
+        EXPORTED_KEY_LABEL, EXPORTED_KEY_SIZE, PEER_INFO_DEVICE_GUID, PEER_INFO_RSA_PUBLIC_KEY,
+        PeerInfo, exchange, pair,
+    };
+    use crate::{
+        Result,
+        message_devices::{models::ADBRsaKey, tcp::pairing::spake2::Spake2Role},
+        tcp::{KnownDevices, TlsOptions},
+    };
+
+    /// Stand-in for the pairing server of `adbd`, returning information sent by client.
+    fn spawn_pairing_server(
+        code: &'static str,
+        key_pair: KeyPair,
+    ) -> (SocketAddr, JoinHandle<Result<PeerInfo>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind listener");
+        let address = listener.local_addr().expect("cannot get listener address");
+
+        let handle = std::thread::spawn(move || {
+            let certificate = CertificateParams::default().self_signed(&key_pair)?;
+            let config = ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(
+                    vec![certificate.der().clone()],
+                    PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into(),
+                )?;
+
+            let (socket, _) = listener.accept()?;
+            let mut stream = StreamOwned::new(ServerConnection::new(Arc::new(config))?, socket);
+            while stream.conn.is_handshaking() {
+                stream.conn.complete_io(&mut stream.sock)?;
+            }
+
+            let mut password = code.as_bytes().to_vec();
+            password.extend_from_slice(&stream.conn.export_keying_material(
+                [0u8; EXPORTED_KEY_SIZE],
+                EXPORTED_KEY_LABEL,
+                None,
+            )?);
+
+            let server_info = PeerInfo {
+                info_type: PEER_INFO_DEVICE_GUID,
+                data: b"adb-stand-in-guid".to_vec(),
+            };
+            exchange(&mut stream, Spake2Role::Bob, &password, &server_info)
+        });
+
+        (address, handle)
+    }
+
+    fn private_key_path() -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "adb_client_pairing_test_{}.pem",
+            std::process::id()
+        ));
+        std::fs::write(&path, ADBRsaKey::TEST_PRIVATE_KEY).expect("cannot write private key");
+        path
+    }
+
+    #[test]
+    fn test_pairing_with_stand_in_server() {
+        let private_key_path = private_key_path();
+        let known_devices = KnownDevices::new(private_key_path.with_extension("known_devices"));
+        let tls_options = TlsOptions::default().known_devices(known_devices.clone());
+
+        let (address, server) =
+            spawn_pairing_server("123456", KeyPair::generate().expect("cannot generate key"));
+        known_devices
+            .pin("adb-stand-in-guid", "previous")
+            .expect("cannot pin certificate");
+        assert_eq!(
+            pair(address, "123456", &private_key_path, &tls_options).expect("cannot pair"),
+            "adb-stand-in-guid"
+        );
+        // Pairing certificate is not the one presented once connected
+        assert_eq!(known_devices.list().unwrap(), Vec::new());
+        let client_info = server
+            .join()
+            .expect("server panicked")
+            .expect("server failed");
+        let public_key = ADBRsaKey::new_from_pkcs8(ADBRsaKey::TEST_PRIVATE_KEY)
+            .and_then(|key| key.android_pubkey_encode())
+            .expect("cannot encode public key");
+        assert_eq!(
+            client_info,
+            PeerInfo {
+                info_type: PEER_INFO_RSA_PUBLIC_KEY,
+                data: public_key.into_bytes(),
+            }
+        );
+
+        let (address, server) =
+            spawn_pairing_server("123456", KeyPair::generate().expect("cannot generate key"));
+        known_devices
+            .pin("adb-stand-in-guid", "kept")
+            .expect("cannot pin certificate");
+        assert!(pair(address, "654321", &private_key_path, &tls_options).is_err());
+        assert!(server.join().expect("server panicked").is_err());
+        assert_eq!(
+            known_devices.fingerprint("adb-stand-in-guid").unwrap(),
+            Some("kept".into())
+        );
+
+        let _ = std::fs::remove_file(known_devices.path());
+        let _ = std::fs::remove_file(private_key_path);
+    }
+}
@@ -0,0 +1,299 @@
+//! SPAKE2 over edwards25519, compatible with `BoringSSL` implementation used by `adbd`.
+
+use curve25519_dalek::{EdwardsPoint, Scalar, edwards::CompressedEdwardsY};
+use ring::{
+    digest::{Context, SHA512, digest},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::{Result, RustADBError};
+
+/// Encoding of point `M`, used to mask messages of [`Spake2Role::Alice`].
+const POINT_M: [u8; 32] = [
+    0x5a, 0xda, 0x7e, 0x4b, 0xf6, 0xdd, 0xd9, 0xad, 0xb6, 0x62, 0x6d, 0x32, 0x13, 0x1c, 0x6b, 0x5c,
+    0x51, 0xa1, 0xe3, 0x47, 0xa3, 0x47, 0x8f, 0x53, 0xcf, 0xcf, 0x44, 0x1b, 0x88, 0xee, 0xd1, 0x2e,
+];
+
+/// Encoding of point `N`, used to mask messages of [`Spake2Role::Bob`].
+const POINT_N: [u8; 32] = [
+    0x10, 0xe3, 0xdf, 0x0a, 0xe3, 0x7d, 0x8e, 0x7a, 0x99, 0xb5, 0xfe, 0x74, 0xb4, 0x46, 0x72, 0x10,
+    0x3d, 0xbd, 0xdc, 0xbd, 0x06, 0xaf, 0x68, 0x0d, 0x71, 0x32, 0x9a, 0x11, 0x69, 0x3b, 0xc7, 0x78,
+];
+
+/// Size of exchanged SPAKE2 messages.
+pub(crate) const SPAKE2_MESSAGE_SIZE: usize = 32;
+/// Size of keys agreed on by SPAKE2.
+pub(crate) const SPAKE2_KEY_SIZE: usize = 64;
+
+/// Side of the SPAKE2 exchange.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Spake2Role {
+    /// Initiating side, i.e. pairing client.
+    Alice,
+    /// Responding side, i.e. pairing server.
+    #[cfg_attr(not(test), expect(dead_code))]
+    Bob,
+}
+
+/// Mask point `bytes`, multiplied by the cofactor (eight).
+///
+/// Mask points are not in the prime-order subgroup, so multiplying them by eight clears their small-order component.
+fn mask_point(bytes: [u8; 32]) -> Result<EdwardsPoint> {
+    CompressedEdwardsY(bytes)
+        .decompress()
+        .map(|point| point.mul_by_cofactor())
+        .ok_or_else(|| RustADBError::PairingError("invalid SPAKE2 mask point".into()))
+}
+
+/// Hash `data` prefixed by its length, as done by `BoringSSL`.
+fn update_with_length_prefix(context: &mut Context, data: &[u8]) {
+    context.update(&(data.len() as u64).to_le_bytes());
+    context.update(data);
+}
+
+/// State of one side of a SPAKE2 exchange.
+///
+/// `BoringSSL` multiplies points by scalars which are multiples of eight, lower than eight times the group order,
+/// to clear the cofactor. Multiplying a point by such scalar `8 * s` is done here by multiplying eight times the point by `s`,
+/// keeping every scalar reduced modulo group order, as required by constant-time arithmetic.
+#[derive(Debug)]
+pub(crate) struct Spake2 {
+    role: Spake2Role,
+    my_name: Vec<u8>,
+    their_name: Vec<u8>,
+    /// Private key, divided by eight.
+    private_key: Scalar,
+    /// Password scalar, divided by eight.
+    password_scalar: Scalar,
+    password_hash: [u8; 64],
+    my_message: [u8; SPAKE2_MESSAGE_SIZE],
+}
+
+impl Spake2 {
+    /// Start an exchange as `role`, proving knowledge of `password`.
+    pub(crate) fn new(
+        role: Spake2Role,
+        my_name: &[u8],
+        their_name: &[u8],
+        password: &[u8],
+    ) -> Result<Self> {
+        let mut random = [0u8; 64];
+        SystemRandom::new()
+            .fill(&mut random)
+            .map_err(|_| RustADBError::PairingError("cannot generate random bytes".into()))?;
+
+        Self::new_with_random(role, my_name, their_name, password, &random)
+    }
+
+    fn new_with_random(
+        role: Spake2Role,
+        my_name: &[u8],
+        their_name: &[u8],
+        password: &[u8],
+        random: &[u8; 64],
+    ) -> Result<Self> {
+        // `BoringSSL` private key is eight times this one
+        let private_key = Scalar::from_bytes_mod_order_wide(random);
+
+        let mut password_hash = [0u8; 64];
+        password_hash.copy_from_slice(digest(&SHA512, password).as_ref());
+
+        // `BoringSSL` adds multiples of group order to reduced password hash, until it is a multiple of eight
+        let password_scalar =
+            Scalar::from_bytes_mod_order_wide(&password_hash) * Scalar::from(8u8).invert();
+
+        let my_mask = match role {
+            Spake2Role::Alice => mask_point(POINT_M)?,
+            Spake2Role::Bob => mask_point(POINT_N)?,
+        };
+        let my_message = (EdwardsPoint::mul_base(&private_key).mul_by_cofactor()
+            + my_mask * password_scalar)
+            .compress()
+            .to_bytes();
+
+        Ok(Self {
+            role,
+            my_name: my_name.to_vec(),
+            their_name: their_name.to_vec(),
+            private_key,
+            password_scalar,
+            password_hash,
+            my_message,
+        })
+    }
+
+    /// Message to send to peer.
+    pub(crate) const fn message(&self) -> &[u8; SPAKE2_MESSAGE_SIZE] {
+        &self.my_message
+    }
+
+    /// Process `their_message` received from peer, and return the agreed key.
+    ///
+    /// Both sides only get the same key if they used the same password.
+    pub(crate) fn process_message(&self, their_message: &[u8]) -> Result<[u8; SPAKE2_KEY_SIZE]> {
+        let their_message: [u8; SPAKE2_MESSAGE_SIZE] = their_message.try_into().map_err(|_| {
+            RustADBError::PairingError(format!(
+                "invalid SPAKE2 message size: {}",
+                their_message.len()
+            ))
+        })?;
+        let their_point = CompressedEdwardsY(their_message)
+            .decompress()
+            .ok_or_else(|| {
+                RustADBError::PairingError("SPAKE2 message is not a curve point".into())
+            })?;
+
+        let their_mask = match self.role {
+            Spake2Role::Alice => mask_point(POINT_N)?,
+            Spake2Role::Bob => mask_point(POINT_M)?,
+        };
+        // Multiplying by the cofactor also clears any small-order component of peer's point
+        let shared = ((their_point - their_mask * self.password_scalar).mul_by_cofactor()
+            * self.private_key)
+            .compress()
+            .to_bytes();
+
+        let mut context = Context::new(&SHA512);
+        match self.role {
+            Spake2Role::Alice => {
+                update_with_length_prefix(&mut context, &self.my_name);
+                update_with_length_prefix(&mut context, &self.their_name);
+                update_with_length_prefix(&mut context, &self.my_message);
+                update_with_length_prefix(&mut context, &their_message);
+            }
+            Spake2Role::Bob => {
+                update_with_length_prefix(&mut context, &self.their_name);
+                update_with_length_prefix(&mut context, &self.my_name);
+                update_with_length_prefix(&mut context, &their_message);
+                update_with_length_prefix(&mut context, &self.my_message);
+            }
+        }
+        update_with_length_prefix(&mut context, &shared);
+        update_with_length_prefix(&mut context, &self.password_hash);
+
+        let mut key = [0u8; SPAKE2_KEY_SIZE];
+        key.copy_from_slice(context.finish().as_ref());
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use super::{Spake2, Spake2Role};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+    }
+
+    /// Run an exchange, returning messages and keys of both sides.
+    fn exchange(
+        alice: (&[u8], &[u8], &[u8], u8),
+        bob: (&[u8], &[u8], &[u8], u8),
+    ) -> (Spake2, Spake2, [u8; 64], [u8; 64]) {
+        let (my_name, their_name, password, random) = alice;
+        let alice = Spake2::new_with_random(
+            Spake2Role::Alice,
+            my_name,
+            their_name,
+            password,
+            &[random; 64],
+        )
+        .expect("cannot start exchange");
+        let (my_name, their_name, password, random) = bob;
+        let bob = Spake2::new_with_random(
+            Spake2Role::Bob,
+            my_name,
+            their_name,
+            password,
+            &[random; 64],
+        )
+        .expect("cannot start exchange");
+
+        let alice_key = alice
+            .process_message(bob.message())
+            .expect("cannot process message");
+        let bob_key = bob
+            .process_message(alice.message())
+            .expect("cannot process message");
+        (alice, bob, alice_key, bob_key)
+    }
+
+    /// Known answers, produced by `SPAKE2_generate_msg` and `SPAKE2_process_msg` of `BoringSSL` (commit `b9232f9`),
+    /// with `RAND_bytes` filling its output with given byte.
+    #[test]
+    fn test_spake2_boringssl_vectors() {
+        let (alice, bob, alice_key, bob_key) = exchange(
+            (b"alice", b"bob", b"password", 0x11),
+            (b"bob", b"alice", b"password", 0x22),
+        );
+        assert_eq!(
+            hex(alice.message()),
+            "33e77af6fba2e5cc2e97eafcbb42a6e1d9442c86772847d5c0ee6ea1543a7b4a"
+        );
+        assert_eq!(
+            hex(bob.message()),
+            "55831d9789de372228f0528f6e4157390cd1ab05e87e375a432d32d0158fd25c"
+        );
+        assert_eq!(alice_key, bob_key);
+        assert_eq!(
+            hex(&alice_key),
+            "54d81b5515e29285288bec90cfa657dcf399e93408c7c3346e1b21694f3c707f\
+             e11c52786da9d0723755b33bb2619b1925168e8cb5441397b839b0bce5cb1b9c"
+        );
+
+        let (client, server, client_key, server_key) = exchange(
+            (b"adb pair client\0", b"adb pair server\0", b"123456", 0x33),
+            (b"adb pair server\0", b"adb pair client\0", b"123456", 0x44),
+        );
+        assert_eq!(
+            hex(client.message()),
+            "6bdb5620c44ba55efc34bf432a627be648faa84b7886a26d9a56c534d3c388bb"
+        );
+        assert_eq!(
+            hex(server.message()),
+            "cccc229d42c23d3951b168806954a588e4b0fb928283d5a48296338dcef0947e"
+        );
+        assert_eq!(client_key, server_key);
+        assert_eq!(
+            hex(&client_key),
+            "523da983a556b9d3e640a9e943b20c4aceb1318fe97457d035c2973dd048c741\
+             703d1bdae4bfad4a0c28d82f18a03660d8eb6b539580672f80dee3dab68104ef"
+        );
+    }
+
+    /// Same cases as `spake25519_test.cc` of `BoringSSL`.
+    #[test]
+    fn test_spake2_mismatches() {
+        let (.., alice_key, bob_key) = exchange(
+            (b"alice", b"bob", b"password", 0x11),
+            (b"bob", b"alice", b"wrong password", 0x22),
+        );
+        assert_ne!(alice_key, bob_key, "key matched for unequal passwords");
+
+        let (.., alice_key, bob_key) = exchange(
+            (b"alice", b"charlie", b"password", 0x11),
+            (b"bob", b"charlie", b"password", 0x22),
+        );
+        assert_ne!(alice_key, bob_key, "key matched for unequal names");
+
+        let (alice, bob, alice_key, _) = exchange(
+            (b"alice", b"bob", b"password", 0x11),
+            (b"bob", b"alice", b"password", 0x22),
+        );
+        for bit in 0..8 * alice.message().len() {
+            let mut corrupted = *alice.message();
+            corrupted[bit / 8] ^= 1 << (bit % 8);
+            if let Ok(bob_key) = bob.process_message(&corrupted) {
+                assert_ne!(
+                    alice_key, bob_key,
+                    "key matched after corrupting message bit {bit}"
+                );
+            }
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use rcgen::{CertificateParams, KeyPair, PKCS_RSA_SHA256};
 use rustls::{
-    ClientConfig, ClientConnection, KeyLogFile, SignatureScheme, StreamOwned,
-    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
-    pki_types::{CertificateDer, PrivatePkcs8KeyDer, pem::PemObject},
+    ClientConfig, ClientConnection, KeyLogFile,
+    client::danger::ServerCertVerifier,
+    pki_types::{CertificateDer, PrivatePkcs8KeyDer},
 };
 
 use crate::{
@@ -10,76 +10,172 @@ use crate::{
     adb_transport::ADBTransport,
     message_devices::{
         adb_message_transport::ADBMessageTransport,
-        adb_transport_message::{ADBTransportMessage, ADBTransportMessageHeader},
-        message_commands::MessageCommand,
+        adb_transport_message::{ADBTransportMessage, ADBTransportMessageHeader, ChecksumPolicy},
+        models::ADBRsaKey,
     },
+    models::{TimeoutPhase, timeout_error},
+    tcp::{KnownDevices, TlsOptions, device_certificate_verifier::DeviceCertificateVerifier},
 };
 use std::{
-    fs::read_to_string,
-    io::{Read, Write},
+    io::{ErrorKind, Read, Write},
     net::{Shutdown, SocketAddr, TcpStream},
-    path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, OnceLock},
     time::Duration,
 };
 
+/// Maximum amount of raw bytes read from socket at once when TLS is enabled.
+///
+/// Staying under `rustls` received plaintext limit avoids filling its buffers.
+const TLS_READ_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Connection to the device, allowing to read and write concurrently.
+///
+/// Lock order, when several are needed, is `reader` -> `writer` -> `tls`.
 #[derive(Debug)]
-enum CurrentConnection {
-    Tcp(TcpStream),
-    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+struct CurrentConnection {
+    /// Socket handle used to change options and shutdown connection.
+    socket: TcpStream,
+    /// Socket handle used for reading, serializing readers.
+    reader: Mutex<TcpStream>,
+    /// Socket handle used for writing, serializing writers.
+    writer: Mutex<TcpStream>,
+    /// TLS state, set once connection has been upgraded.
+    tls: OnceLock<Mutex<ClientConnection>>,
+    /// Payload checksums usage, negotiated for this connection.
+    checksum: ChecksumPolicy,
 }
 
 impl CurrentConnection {
-    fn set_read_timeout(&self, read_timeout: Duration) -> Result<()> {
-        match self {
-            Self::Tcp(tcp_stream) => Ok(tcp_stream.set_read_timeout(Some(read_timeout))?),
-            Self::Tls(stream_owned) => {
-                Ok(stream_owned.sock.set_read_timeout(Some(read_timeout))?)
+    fn new(stream: TcpStream) -> Result<Self> {
+        Ok(Self {
+            reader: Mutex::new(stream.try_clone()?),
+            writer: Mutex::new(stream.try_clone()?),
+            socket: stream,
+            tls: OnceLock::new(),
+            checksum: ChecksumPolicy::default(),
+        })
+    }
+
+    /// Read available data into `buf`, decrypting it if needed.
+    fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut socket = self.reader.lock().map_err(poisoned)?;
+
+        let Some(tls) = self.tls.get() else {
+            return socket.read(buf);
+        };
+
+        loop {
+            match tls.lock().map_err(poisoned)?.reader().read(buf) {
+                Ok(read) => return Ok(read),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
             }
+
+            // No plaintext available: read raw data without holding TLS state, to let writers progress.
+            let mut raw = [0u8; TLS_READ_CHUNK_SIZE];
+            let read = socket.read(&mut raw)?;
+
+            {
+                let mut conn = tls.lock().map_err(poisoned)?;
+                let mut raw = &raw[..read];
+                loop {
+                    // Reading an empty slice notifies rustls of EOF
+                    conn.read_tls(&mut raw)?;
+                    conn.process_new_packets()
+                        .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+                    if raw.is_empty() {
+                        break;
+                    }
+                }
+            }
+
+            // Processing may have produced messages to send back (alerts, key updates...)
+            Self::flush_tls(&mut *self.writer.lock().map_err(poisoned)?, tls)?;
         }
     }
 
-    fn set_write_timeout(&self, write_timeout: Duration) -> Result<()> {
-        match self {
-            Self::Tcp(tcp_stream) => Ok(tcp_stream.set_write_timeout(Some(write_timeout))?),
-            Self::Tls(stream_owned) => {
-                Ok(stream_owned.sock.set_write_timeout(Some(write_timeout))?)
+    /// Write the whole `buf`, encrypting it if needed.
+    fn write_all(&self, buf: &[u8]) -> std::io::Result<()> {
+        let mut socket = self.writer.lock().map_err(poisoned)?;
+
+        match self.tls.get() {
+            None => socket.write_all(buf)?,
+            Some(tls) => {
+                tls.lock().map_err(poisoned)?.writer().write_all(buf)?;
+                Self::flush_tls(&mut socket, tls)?;
             }
         }
+
+        socket.flush()
     }
-}
 
-impl Read for CurrentConnection {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        match self {
-            Self::Tcp(tcp_stream) => tcp_stream.read(buf),
-            Self::Tls(tls_conn) => tls_conn.read(buf),
+    fn flush_tls(socket: &mut TcpStream, tls: &Mutex<ClientConnection>) -> std::io::Result<()> {
+        let mut conn = tls.lock().map_err(poisoned)?;
+        while conn.wants_write() {
+            conn.write_tls(socket)?;
         }
+        Ok(())
     }
-}
 
-impl Write for CurrentConnection {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        match self {
-            Self::Tcp(tcp_stream) => tcp_stream.write(buf),
-            Self::Tls(tls_conn) => tls_conn.write(buf),
+    fn shutdown(&self) {
+        if let Some(tls) = self.tls.get()
+            && let Ok(mut socket) = self.writer.try_lock()
+            && let Ok(mut conn) = tls.lock()
+        {
+            conn.send_close_notify();
+            while conn.wants_write() {
+                if conn.write_tls(&mut *socket).is_err() {
+                    break;
+                }
+            }
         }
+
+        let _ = self.socket.shutdown(Shutdown::Both);
     }
+}
+
+fn poisoned<E>(_: E) -> std::io::Error {
+    std::io::Error::other("connection lock poisoned")
+}
+
+/// Whether `error` reports that an operation did not complete in time.
+fn is_timeout(error: &std::io::Error) -> bool {
+    matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        match self {
-            Self::Tcp(tcp_stream) => tcp_stream.flush(),
-            Self::Tls(tls_conn) => tls_conn.flush(),
+/// Fill `buf` from `connection`.
+///
+/// A timeout is only reported if nothing has been read yet and `message_started` is false,
+/// a message is never left partially read.
+fn read_exact(
+    connection: &CurrentConnection,
+    buf: &mut [u8],
+    mut message_started: bool,
+) -> std::io::Result<()> {
+    let mut total_read = 0;
+    while total_read < buf.len() {
+        match connection.read(&mut buf[total_read..]) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(read) => {
+                total_read += read;
+                message_started = true;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) if is_timeout(&e) && message_started => {}
+            Err(e) => return Err(e),
         }
     }
+    Ok(())
 }
 
-/// Transport running on USB
+/// Transport running on TCP
 #[derive(Clone, Debug)]
 pub struct TcpTransport {
     address: SocketAddr,
-    current_connection: Option<Arc<Mutex<CurrentConnection>>>,
-    private_key_path: PathBuf,
+    current_connection: Option<Arc<CurrentConnection>>,
+    private_key: ADBRsaKey,
+    tls_options: TlsOptions,
+    connect_timeout: Option<Duration>,
 }
 
 fn certificate_from_pk(key_pair: &KeyPair) -> Result<Vec<CertificateDer<'static>>> {
@@ -88,47 +184,141 @@ fn certificate_from_pk(key_pair: &KeyPair) -> Result<Vec<CertificateDer<'static>
     Ok(vec![certificate.der().to_owned()])
 }
 
+/// TLS configuration authenticating with `private_key`, and checking device certificate with `verifier`.
+pub(super) fn tls_client_config(
+    private_key: &ADBRsaKey,
+    verifier: Arc<dyn ServerCertVerifier>,
+    tls_options: &TlsOptions,
+) -> Result<ClientConfig> {
+    let private_key = PrivatePkcs8KeyDer::from(private_key.to_pkcs8_der()?);
+    let key_pair = KeyPair::from_pkcs8_der_and_sign_algo(&private_key, &PKCS_RSA_SHA256)?;
+    let certificate = certificate_from_pk(&key_pair)?;
+
+    let mut client_config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_client_auth_cert(certificate, private_key.into())?;
+
+    if tls_options.key_log_enabled() {
+        log::warn!("TLS key logging enabled, secrets are written to SSLKEYLOGFILE");
+        client_config.key_log = Arc::new(KeyLogFile::new());
+    }
+
+    Ok(client_config)
+}
+
+/// Check of the certificate presented by device during a TLS handshake, against the one pinned for its identity.
+pub(super) struct CertificatePinning {
+    /// Device identity and store pinning its certificate, if identity is set in TLS options.
+    device: Option<(String, KnownDevices)>,
+    pinned_fingerprint: Option<String>,
+    verifier: Arc<DeviceCertificateVerifier>,
+}
+
+impl CertificatePinning {
+    pub(super) fn new(address: SocketAddr, tls_options: &TlsOptions) -> Result<Self> {
+        let device = if let Some(device_id) = tls_options.device_id_value() {
+            Some((device_id.to_string(), tls_options.known_devices_store()?))
+        } else {
+            log::warn!(
+                "no identity set for device {address}, its certificate is neither checked nor pinned"
+            );
+            None
+        };
+        let pinned_fingerprint = match &device {
+            Some((device_id, known_devices)) => known_devices.fingerprint(device_id)?,
+            None => None,
+        };
+        let verifier = Arc::new(DeviceCertificateVerifier::new(pinned_fingerprint.clone()));
+
+        Ok(Self {
+            device,
+            pinned_fingerprint,
+            verifier,
+        })
+    }
+
+    /// Verifier to use for the handshake.
+    pub(super) fn verifier(&self) -> Arc<dyn ServerCertVerifier> {
+        self.verifier.clone()
+    }
+
+    /// Turn handshake `error` into [`RustADBError::DeviceCertificateMismatch`] if device certificate has been rejected.
+    pub(super) fn handshake_error<E: Into<RustADBError>>(&self, error: E) -> RustADBError {
+        match (&self.device, self.verifier.rejected_fingerprint()) {
+            (Some((device_id, _)), Some(fingerprint)) => {
+                RustADBError::DeviceCertificateMismatch(device_id.clone(), fingerprint)
+            }
+            _ => error.into(),
+        }
+    }
+
+    /// Pin certificate presented by device once handshake succeeded, if it is seen for the first time.
+    pub(super) fn finish(&self) -> Result<()> {
+        if self.pinned_fingerprint.is_none()
+            && let Some((device_id, known_devices)) = &self.device
+            && let Some(fingerprint) = self.verifier.presented_fingerprint()
+        {
+            log::warn!(
+                "trusting certificate of device {device_id} seen for the first time, pinning its fingerprint {fingerprint}"
+            );
+            known_devices.pin(device_id, &fingerprint)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl TcpTransport {
-    /// Instantiate a new [`TcpTransport`] using a given private key
-    pub fn new<A: Into<SocketAddr>, P: AsRef<Path>>(address: A, private_key_path: P) -> Self {
+    /// Instantiate a new [`TcpTransport`] using a given private key and TLS options
+    pub fn new<A: Into<SocketAddr>>(
+        address: A,
+        private_key: ADBRsaKey,
+        tls_options: TlsOptions,
+    ) -> Self {
         Self {
             address: address.into(),
             current_connection: None,
-            private_key_path: private_key_path.as_ref().to_path_buf(),
+            private_key,
+            tls_options,
+            connect_timeout: None,
         }
     }
 
-    fn get_current_connection(&self) -> Result<Arc<Mutex<CurrentConnection>>> {
+    fn get_current_connection(&self) -> Result<Arc<CurrentConnection>> {
         self.current_connection
             .as_ref()
             .ok_or(RustADBError::IOError(std::io::Error::new(
-                std::io::ErrorKind::NotConnected,
+                ErrorKind::NotConnected,
                 "not connected",
             )))
             .cloned()
     }
+
+    fn tls_client_config(&self, verifier: Arc<dyn ServerCertVerifier>) -> Result<ClientConfig> {
+        tls_client_config(&self.private_key, verifier, &self.tls_options)
+    }
 }
 
 impl ADBTransport for TcpTransport {
     fn connect(&mut self) -> Result<()> {
-        let stream = TcpStream::connect(self.address)?;
-        self.current_connection = Some(Arc::new(Mutex::new(CurrentConnection::Tcp(stream))));
+        let stream = match self.connect_timeout {
+            Some(timeout) => {
+                TcpStream::connect_timeout(&self.address, timeout).map_err(|e| match e.kind() {
+                    ErrorKind::TimedOut => timeout_error(TimeoutPhase::Connect),
+                    _ => e,
+                })?
+            }
+            None => TcpStream::connect(self.address)?,
+        };
+        self.current_connection = Some(Arc::new(CurrentConnection::new(stream)?));
         Ok(())
     }
 
     fn disconnect(&mut self) -> Result<()> {
         log::debug!("disconnecting...");
         if let Some(current_connection) = &self.current_connection {
-            let mut lock = current_connection.lock()?;
-            match &mut *lock {
-                CurrentConnection::Tcp(tcp_stream) => {
-                    let _ = tcp_stream.shutdown(Shutdown::Both);
-                }
-                CurrentConnection::Tls(tls_conn) => {
-                    tls_conn.conn.send_close_notify();
-                    let _ = tls_conn.sock.shutdown(Shutdown::Both);
-                }
-            }
+            current_connection.shutdown();
         }
 
         Ok(())
@@ -136,47 +326,35 @@ impl ADBTransport for TcpTransport {
 }
 
 impl ADBMessageTransport for TcpTransport {
+    fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_timeout = timeout;
+    }
+
+    fn set_protocol_version(&mut self, version: u32) {
+        if let Some(current_connection) = &self.current_connection {
+            current_connection.checksum.set_protocol_version(version);
+        }
+    }
+
     fn read_message_with_timeout(
         &mut self,
         read_timeout: std::time::Duration,
     ) -> Result<ADBTransportMessage> {
-        let raw_connection_lock = self.get_current_connection()?;
-        let mut raw_connection = raw_connection_lock.lock()?;
+        let raw_connection = self.get_current_connection()?;
 
-        raw_connection.set_read_timeout(read_timeout)?;
+        raw_connection.socket.set_read_timeout(Some(read_timeout))?;
 
         let mut data = [0; 24];
-        let mut total_read = 0;
-        loop {
-            total_read += raw_connection.read(&mut data[total_read..])?;
-            if total_read == data.len() {
-                break;
-            }
-        }
+        read_exact(&raw_connection, &mut data, false)?;
 
         let header = ADBTransportMessageHeader::try_from(data)?;
 
         if header.data_length() != 0 {
             let mut msg_data = vec![0_u8; header.data_length() as usize];
-            let mut total_read = 0;
-            loop {
-                total_read += raw_connection.read(&mut msg_data[total_read..])?;
-                if total_read == msg_data.capacity() {
-                    break;
-                }
-            }
-            // raw_connection is not used anymore, let's drop it
-            drop(raw_connection);
+            read_exact(&raw_connection, &mut msg_data, true)?;
 
             let message = ADBTransportMessage::from_header_and_payload(header, msg_data);
-
-            // Check message integrity
-            if !message.check_message_integrity() {
-                return Err(RustADBError::InvalidIntegrity(
-                    ADBTransportMessageHeader::compute_crc32(message.payload()),
-                    message.header().data_crc32(),
-                ));
-            }
+            raw_connection.checksum.check_incoming(&message)?;
 
             return Ok(message);
         }
@@ -186,35 +364,19 @@ impl ADBMessageTransport for TcpTransport {
 
     fn write_message_with_timeout(
         &mut self,
-        message: ADBTransportMessage,
+        mut message: ADBTransportMessage,
         write_timeout: Duration,
     ) -> Result<()> {
-        let message_bytes = message.header().as_bytes();
-        let raw_connection_lock = self.get_current_connection()?;
-        let mut raw_connection = raw_connection_lock.lock()?;
-        raw_connection.set_write_timeout(write_timeout)?;
-
-        let mut total_written = 0;
-        loop {
-            total_written += raw_connection.write(&message_bytes[total_written..])?;
-            if total_written == message_bytes.len() {
-                raw_connection.flush()?;
-                break;
-            }
-        }
+        let raw_connection = self.get_current_connection()?;
+        raw_connection.checksum.prepare_outgoing(&mut message);
+        raw_connection
+            .socket
+            .set_write_timeout(Some(write_timeout))?;
 
-        let payload = message.into_payload();
-        if !payload.is_empty() {
-            let mut total_written = 0;
-            loop {
-                total_written += raw_connection.write(&payload[total_written..])?;
-                if total_written == payload.len() {
-                    raw_connection.flush()?;
-                    drop(raw_connection);
-                    break;
-                }
-            }
-        }
+        // Header and payload are written at once, so that messages from concurrent writers cannot interleave
+        let mut message_bytes = message.header().as_bytes();
+        message_bytes.extend_from_slice(message.payload());
+        raw_connection.write_all(&message_bytes)?;
 
         Ok(())
     }
@@ -226,106 +388,147 @@ impl ADBMessageTransport for TcpTransport {
             ));
         };
 
+        if current_connection.tls.get().is_some() {
+            return Err(RustADBError::UpgradeError(
+                "cannot upgrade a TLS connection...".into(),
+            ));
+        }
+
+        let pinning = CertificatePinning::new(self.address, &self.tls_options)?;
+
+        let rc_config = Arc::new(self.tls_client_config(pinning.verifier())?);
+        let mut conn = ClientConnection::new(rc_config, self.address.ip().into())?;
+
         {
-            let mut current_conn_locked = current_connection.lock()?;
-            match &*current_conn_locked {
-                CurrentConnection::Tcp(tcp_stream) => {
-                    // TODO: Check if we cannot be more precise
-                    let pk_content = read_to_string(&self.private_key_path)?;
-
-                    let key_pair =
-                        KeyPair::from_pkcs8_pem_and_sign_algo(&pk_content, &PKCS_RSA_SHA256)?;
-
-                    let certificate = certificate_from_pk(&key_pair)?;
-                    let private_key = PrivatePkcs8KeyDer::from_pem_file(&self.private_key_path)?;
-
-                    let mut client_config = ClientConfig::builder()
-                        .dangerous()
-                        .with_custom_certificate_verifier(Arc::new(NoCertificateVerification {}))
-                        .with_client_auth_cert(certificate, private_key.into())?;
-
-                    client_config.key_log = Arc::new(KeyLogFile::new());
-
-                    let rc_config = Arc::new(client_config);
-                    let server_name = self.address.ip().into();
-                    let conn = ClientConnection::new(rc_config, server_name)?;
-                    let owned = tcp_stream.try_clone()?;
-                    let client = StreamOwned::new(conn, owned);
-
-                    // Update current connection state to now use TLS protocol
-                    *current_conn_locked = CurrentConnection::Tls(Box::new(client));
-                    drop(current_conn_locked);
-                }
-                CurrentConnection::Tls(_) => {
-                    return Err(RustADBError::UpgradeError(
-                        "cannot upgrade a TLS connection...".into(),
-                    ));
+            // No one else can use the connection during handshake
+            let mut socket = current_connection.writer.lock()?;
+            while conn.is_handshaking() {
+                if let Err(e) = conn.complete_io(&mut *socket) {
+                    return Err(pinning.handshake_error(e));
                 }
             }
         }
 
-        let message = self.read_message()?;
-        match message.header().command() {
-            MessageCommand::Cnxn => {
-                let device_infos = String::from_utf8(message.into_payload())?;
-                log::debug!("received device info: {device_infos}");
-                Ok(())
-            }
-            c => Err(RustADBError::ADBRequestFailed(format!(
-                "Wrong command received {c}"
-            ))),
+        pinning.finish()?;
+
+        // Update current connection state to now use TLS protocol
+        if current_connection.tls.set(Mutex::new(conn)).is_err() {
+            return Err(RustADBError::UpgradeError(
+                "connection has already been upgraded...".into(),
+            ));
         }
-    }
-}
 
-#[derive(Debug)]
-struct NoCertificateVerification;
-
-impl ServerCertVerifier for NoCertificateVerification {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &rustls::pki_types::CertificateDer<'_>,
-        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
-        _server_name: &rustls::pki_types::ServerName<'_>,
-        _ocsp_response: &[u8],
-        _now: rustls::pki_types::UnixTime,
-    ) -> std::result::Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
-        Ok(ServerCertVerified::assertion())
+        Ok(())
     }
+}
 
-    fn verify_tls12_signature(
-        &self,
-        _message: &[u8],
-        _cert: &rustls::pki_types::CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
-    ) -> std::result::Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        Ok(HandshakeSignatureValid::assertion())
-    }
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{SocketAddr, TcpListener},
+        sync::Arc,
+        thread::JoinHandle,
+    };
+
+    use rcgen::{CertificateParams, KeyPair};
+    use rustls::{ServerConfig, ServerConnection, pki_types::PrivatePkcs8KeyDer};
+
+    use super::TcpTransport;
+    use crate::{
+        Result, RustADBError,
+        adb_transport::ADBTransport,
+        message_devices::{adb_message_transport::ADBMessageTransport, models::ADBRsaKey},
+        tcp::{KnownDevices, TlsOptions},
+    };
+
+    /// Stand-in for a device accepting a single TLS connection on `listener` with given key.
+    fn spawn_tls_device(
+        listener: &TcpListener,
+        key_pair: KeyPair,
+    ) -> (SocketAddr, JoinHandle<Result<()>>) {
+        let listener = listener.try_clone().expect("cannot clone listener");
+        let address = listener.local_addr().expect("cannot get listener address");
+
+        let handle = std::thread::spawn(move || {
+            let certificate = CertificateParams::default().self_signed(&key_pair)?;
+            let config = ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(
+                    vec![certificate.der().clone()],
+                    PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into(),
+                )?;
+
+            let (mut socket, _) = listener.accept()?;
+            let mut conn = ServerConnection::new(Arc::new(config))?;
+            while conn.is_handshaking() {
+                conn.complete_io(&mut socket)?;
+            }
+            Ok(())
+        });
 
-    fn verify_tls13_signature(
-        &self,
-        _message: &[u8],
-        _cert: &rustls::pki_types::CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
-    ) -> std::result::Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        Ok(HandshakeSignatureValid::assertion())
+        (address, handle)
     }
 
-    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-        vec![
-            SignatureScheme::RSA_PKCS1_SHA1,
-            SignatureScheme::ECDSA_SHA1_Legacy,
-            SignatureScheme::RSA_PKCS1_SHA256,
-            SignatureScheme::ECDSA_NISTP256_SHA256,
-            SignatureScheme::RSA_PKCS1_SHA384,
-            SignatureScheme::ECDSA_NISTP384_SHA384,
-            SignatureScheme::RSA_PKCS1_SHA512,
-            SignatureScheme::ECDSA_NISTP521_SHA512,
-            SignatureScheme::RSA_PSS_SHA256,
-            SignatureScheme::RSA_PSS_SHA384,
-            SignatureScheme::RSA_PSS_SHA512,
-            SignatureScheme::ED25519,
-            SignatureScheme::ED448,
-        ]
+    #[test]
+    fn test_device_certificate_pinning() {
+        let private_key =
+            ADBRsaKey::new_from_pkcs8(ADBRsaKey::TEST_PRIVATE_KEY).expect("cannot load key");
+        let known_devices = KnownDevices::new(std::env::temp_dir().join(format!(
+            "adb_client_tls_test_{}.known_devices",
+            std::process::id()
+        )));
+        let device_id = "adb-stand-in-guid";
+        let tls_options = TlsOptions::default()
+            .known_devices(known_devices.clone())
+            .device_id(device_id);
+        let device_key = KeyPair::generate().expect("cannot generate key");
+        let device_key_pem = device_key.serialize_pem();
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind listener");
+        let other_listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind listener");
+
+        let upgrade = |listener: &TcpListener, key_pair: KeyPair, tls_options: &TlsOptions| {
+            let (address, device) = spawn_tls_device(listener, key_pair);
+            let mut transport =
+                TcpTransport::new(address, private_key.clone(), tls_options.clone());
+            transport.connect().expect("cannot connect");
+            let result = transport.upgrade_connection();
+            let _ = transport.disconnect();
+            let _ = device.join().expect("device panicked");
+            result
+        };
+        let device_key = || KeyPair::from_pem(&device_key_pem).unwrap();
+
+        // Certificate is pinned on first connection, and accepted afterwards even if device port changed
+        upgrade(&listener, device_key(), &tls_options).expect("cannot upgrade");
+        let fingerprint = known_devices
+            .fingerprint(device_id)
+            .unwrap()
+            .expect("certificate not pinned");
+        upgrade(&other_listener, device_key(), &tls_options).expect("cannot upgrade");
+
+        // Another device claiming the same identity is rejected
+        match upgrade(&listener, KeyPair::generate().unwrap(), &tls_options) {
+            Err(RustADBError::DeviceCertificateMismatch(device, presented)) => {
+                assert_eq!(device, device_id);
+                assert_ne!(presented, fingerprint);
+            }
+            other => panic!("unexpected upgrade result: {other:?}"),
+        }
+        assert_eq!(
+            known_devices.fingerprint(device_id).unwrap(),
+            Some(fingerprint.clone())
+        );
+
+        // Without identity, certificate is neither checked nor pinned
+        let anonymous_options = TlsOptions::default().known_devices(known_devices.clone());
+        upgrade(&listener, KeyPair::generate().unwrap(), &anonymous_options)
+            .expect("cannot upgrade");
+        assert_eq!(
+            known_devices.list().unwrap(),
+            vec![(device_id.to_string(), fingerprint)]
+        );
+
+        let _ = std::fs::remove_file(known_devices.path());
     }
 }
@@ -0,0 +1,54 @@
+use crate::{Result, tcp::KnownDevices};
+
+/// Options of TLS connections established with devices reached over TCP.
+///
+/// Options are set using builder methods, e.g. `TlsOptions::default().key_log(true)`.
+#[derive(Clone, Debug, Default)]
+pub struct TlsOptions {
+    known_devices: Option<KnownDevices>,
+    device_id: Option<String>,
+    key_log: bool,
+}
+
+impl TlsOptions {
+    /// Pin device certificates in given store, instead of the default one.
+    #[must_use]
+    pub fn known_devices(mut self, known_devices: KnownDevices) -> Self {
+        self.known_devices = Some(known_devices);
+        self
+    }
+
+    /// Identity of device, under which its certificate is pinned in known devices, see [`KnownDevices`].
+    ///
+    /// Use the GUID returned by pairing, or the instance name of the device advertised over mDNS.
+    /// If not set, device certificate is neither checked nor pinned.
+    #[must_use]
+    pub fn device_id<S: Into<String>>(mut self, device_id: S) -> Self {
+        self.device_id = Some(device_id.into());
+        self
+    }
+
+    /// Log TLS secrets to the file named by `SSLKEYLOGFILE` environment variable, to decrypt captured traffic.
+    ///
+    /// Only meant for debugging, as anyone reading this file can decrypt exchanged data.
+    #[must_use]
+    pub const fn key_log(mut self, key_log: bool) -> Self {
+        self.key_log = key_log;
+        self
+    }
+
+    pub(crate) fn known_devices_store(&self) -> Result<KnownDevices> {
+        match &self.known_devices {
+            Some(known_devices) => Ok(known_devices.clone()),
+            None => KnownDevices::new_default(),
+        }
+    }
+
+    pub(crate) fn device_id_value(&self) -> Option<&str> {
+        self.device_id.as_deref()
+    }
+
+    pub(crate) const fn key_log_enabled(&self) -> bool {
+        self.key_log
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+    net::TcpStream,
+    sync::{
+        Arc,
+        mpsc::{Receiver, Sender, channel},
+    },
+};
+
+use rand::RngExt;
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use crate::{
+    Result, RustADBError,
+    message_devices::{
+        adb_transport_message::{
+            ADBTransportMessage, ADBTransportMessageHeader, AUTH_RSAPUBLICKEY, AUTH_SIGNATURE,
+            AUTH_TOKEN,
+        },
+        message_commands::MessageCommand,
+        models::{
+            ADB_VERSION, DELAYED_ACK_FEATURE, INITIAL_DELAYED_ACK_BYTES, verify_android_signature,
+        },
+        test_support::{
+            fake_device::FakeDeviceState,
+            fake_listener::{POLL_INTERVAL, lock},
+            fake_services::{FakeService, OpenedService, ServiceStream},
+        },
+    },
+    models::DeviceBanner,
+};
+
+/// Maximum payload size advertised by device, as done by recent `adbd` versions.
+const DEVICE_MAX_PAYLOAD: u32 = 256 * 1024;
+/// Version of TLS upgrade sent in `STLS` messages.
+const STLS_VERSION: u32 = 0x0100_0000;
+/// Size of authentication tokens sent to host.
+const AUTH_TOKEN_SIZE: usize = 20;
+
+/// Byte stream carrying messages, upgraded to TLS if device requires it.
+trait Stream: Read + Write + Send {}
+
+impl<S: Read + Write + Send> Stream for S {}
+
+/// Request sent by a service thread to the connection it runs on.
+enum Outgoing {
+    /// Send data on session with given local id.
+    Write(u32, Vec<u8>),
+    /// Close session with given local id.
+    Close(u32),
+    /// Drop whole connection.
+    Disconnect,
+}
+
+/// Session opened by host, as seen by connection thread.
+struct Route {
+    remote_id: u32,
+    input: Sender<Vec<u8>>,
+    /// Acknowledgements of data sent to host, as amount of bytes when delayed ACK is used.
+    acks: Sender<u32>,
+}
+
+/// Connection of a host to a [`FakeDevice`](super::FakeDevice), serving its messages on a single thread.
+///
+/// Sessions opened by host are served on their own threads, sending their data back through a channel.
+pub(crate) struct FakeConnection {
+    socket: TcpStream,
+    stream: Box<dyn Stream>,
+    state: Arc<FakeDeviceState>,
+    /// Bytes received and not parsed as messages yet.
+    buffer: Vec<u8>,
+    max_payload: usize,
+    delayed_ack: bool,
+    routes: HashMap<u32, Route>,
+    next_local_id: u32,
+    outgoing: (Sender<Outgoing>, Receiver<Outgoing>),
+}
+
+impl FakeConnection {
+    pub(crate) fn new(socket: TcpStream, state: Arc<FakeDeviceState>) -> Result<Self> {
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        socket.set_nodelay(true)?;
+
+        Ok(Self {
+            stream: Box::new(socket.try_clone()?),
+            socket,
+            state,
+            buffer: Vec::new(),
+            max_payload: DEVICE_MAX_PAYLOAD as usize,
+            delayed_ack: false,
+            routes: HashMap::new(),
+            next_local_id: 1,
+            outgoing: channel(),
+        })
+    }
+
+    /// Serve host until it disconnects, or device is stopped.
+    pub(crate) fn serve(mut self) -> Result<()> {
+        self.handshake()?;
+
+        let device = &self.state.device;
+        let banner = format!(
+            "device::ro.product.name={model};ro.product.model={model};ro.product.device={model};features={features}",
+            model = device.model,
+            features = device.features.join(",")
+        );
+        self.send(
+            MessageCommand::Cnxn,
+            ADB_VERSION,
+            DEVICE_MAX_PAYLOAD,
+            banner.as_bytes(),
+        )?;
+
+        while !self.state.is_stopped() {
+            if let Some(message) = self.poll_message()?
+                && !self.handle_message(message)?
+            {
+                return Ok(());
+            }
+
+            if !self.flush_outgoing()? {
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait for host `CNXN`, then upgrade connection to TLS or authenticate host as configured.
+    fn handshake(&mut self) -> Result<()> {
+        let connect = self.wait_message()?;
+        connect.assert_command(MessageCommand::Cnxn)?;
+
+        self.max_payload = connect.header().arg1().min(DEVICE_MAX_PAYLOAD) as usize;
+        let host_banner = String::from_utf8_lossy(connect.payload()).parse::<DeviceBanner>();
+        self.delayed_ack = connect.header().arg0() >= ADB_VERSION
+            && self
+                .state
+                .device
+                .features
+                .iter()
+                .any(|f| f == DELAYED_ACK_FEATURE)
+            && host_banner.is_ok_and(|banner| banner.has_feature(DELAYED_ACK_FEATURE));
+
+        if let Some(tls_config) = self.state.tls_config.clone() {
+            self.send(MessageCommand::Stls, STLS_VERSION, 0, &[])?;
+            self.wait_message()?.assert_command(MessageCommand::Stls)?;
+            self.upgrade(tls_config)
+        } else if self.state.device.require_auth {
+            self.authenticate()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Switch connection to TLS, device acting as server.
+    fn upgrade(&mut self, config: Arc<ServerConfig>) -> Result<()> {
+        let mut socket = self.socket.try_clone()?;
+        let mut conn = ServerConnection::new(config)?;
+
+        // Host may already have started its handshake
+        let mut received = self.buffer.as_slice();
+        while !received.is_empty() {
+            conn.read_tls(&mut received)?;
+        }
+        self.buffer.clear();
+
+        while conn.is_handshaking() {
+            if self.state.is_stopped() {
+                return Err(std::io::Error::from(ErrorKind::ConnectionAborted).into());
+            }
+            match conn.complete_io(&mut socket) {
+                Ok(_) => {}
+                Err(e) if is_timeout(&e) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.stream = Box::new(StreamOwned::new(conn, socket));
+        Ok(())
+    }
+
+    /// Ask host to sign tokens until one of its keys is trusted, or user allows its public key.
+    fn authenticate(&mut self) -> Result<()> {
+        let mut token = self.send_auth_token()?;
+        loop {
+            let message = self.wait_message()?;
+            message.assert_command(MessageCommand::Auth)?;
+
+            match message.header().arg0() {
+                AUTH_SIGNATURE => {
+                    let trusted = lock(&self.state.trusted_keys)
+                        .iter()
+                        .any(|key| verify_android_signature(key, &token, message.payload()));
+                    if trusted {
+                        return Ok(());
+                    }
+                    token = self.send_auth_token()?;
+                }
+                AUTH_RSAPUBLICKEY => {
+                    if !self.state.device.accept_unknown_keys {
+                        // User never answers, host has to give up
+                        loop {
+                            self.wait_message()?;
+                        }
+                    }
+
+                    let public_key = String::from_utf8_lossy(message.payload());
+                    lock(&self.state.trusted_keys)
+                        .push(public_key.trim_end_matches('\0').to_string());
+                    return Ok(());
+                }
+                v => {
+                    return Err(RustADBError::ADBRequestFailed(format!(
+                        "unexpected AUTH message type {v}"
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Send a new random token for host to sign, returning it.
+    fn send_auth_token(&mut self) -> Result<[u8; AUTH_TOKEN_SIZE]> {
+        let token: [u8; AUTH_TOKEN_SIZE] = rand::rng().random();
+        self.send(MessageCommand::Auth, AUTH_TOKEN, 0, &token)?;
+        Ok(token)
+    }
+
+    /// Handle `message` sent by host, returning whether connection should be kept.
+    fn handle_message(&mut self, message: ADBTransportMessage) -> Result<bool> {
+        let (arg0, arg1) = (message.header().arg0(), message.header().arg1());
+        match message.header().command() {
+            MessageCommand::Open => {
+                let service = String::from_utf8_lossy(message.payload())
+                    .trim_end_matches('\0')
+                    .to_string();
+                return self.open(&service, arg0, arg1);
+            }
+            MessageCommand::Write => {
+                let Some(route) = self.routes.get(&arg1) else {
+                    return Ok(true);
+                };
+                let remote_id = route.remote_id;
+                let payload = message.into_payload();
+                let acked_bytes = if self.delayed_ack {
+                    u32::try_from(payload.len())?.to_le_bytes().to_vec()
+                } else {
+                    Vec::new()
+                };
+                // Service may not read its input
+                let _ = route.input.send(payload);
+                self.send(MessageCommand::Okay, arg1, remote_id, &acked_bytes)?;
+            }
+            MessageCommand::Okay => {
+                if let Some(route) = self.routes.get(&arg1) {
+                    let acked_bytes = message
+                        .payload()
+                        .as_slice()
+                        .try_into()
+                        .map(u32::from_le_bytes)
+                        .unwrap_or_default();
+                    let _ = route.acks.send(acked_bytes);
+                }
+            }
+            MessageCommand::Clse => {
+                self.routes.remove(&arg1);
+            }
+            c => log::debug!("fake device ignoring {c} message"),
+        }
+
+        Ok(true)
+    }
+
+    /// Open `service` requested by host with id `remote_id`, injecting pending fault if any.
+    fn open(&mut self, service: &str, remote_id: u32, receive_window: u32) -> Result<bool> {
+        let service = match FakeService::open(service, &self.state) {
+            OpenedService::Accepted(service) => service,
+            OpenedService::Refused => {
+                self.send(MessageCommand::Clse, 0, remote_id, &[])?;
+                return Ok(true);
+            }
+            OpenedService::Disconnected => return Ok(false),
+        };
+
+        let local_id = self.next_local_id;
+        self.next_local_id = self.next_local_id.wrapping_add(1).max(1);
+
+        let (input_sender, input) = channel();
+        let (ack_sender, acks) = channel();
+        self.routes.insert(
+            local_id,
+            Route {
+                remote_id,
+                input: input_sender,
+                acks: ack_sender,
+            },
+        );
+
+        let send_window = if self.delayed_ack {
+            INITIAL_DELAYED_ACK_BYTES.to_le_bytes().to_vec()
+        } else {
+            Vec::new()
+        };
+        self.send(MessageCommand::Okay, local_id, remote_id, &send_window)?;
+
+        let session = FakeSession {
+            local_id,
+            outgoing: self.outgoing.0.clone(),
+            input,
+            pending: Vec::new(),
+            acks,
+            send_window: self.delayed_ack.then_some(i64::from(receive_window)),
+            max_payload: self.max_payload,
+        };
+        let state = self.state.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = service.run(session, &state) {
+                log::debug!("fake device service ended: {e}");
+            }
+        });
+
+        Ok(true)
+    }
+
+    /// Send data queued by services, returning whether connection should be kept.
+    fn flush_outgoing(&mut self) -> Result<bool> {
+        while let Ok(outgoing) = self.outgoing.1.try_recv() {
+            match outgoing {
+                Outgoing::Write(local_id, data) => {
+                    if let Some(remote_id) = self.routes.get(&local_id).map(|r| r.remote_id) {
+                        self.send(MessageCommand::Write, local_id, remote_id, &data)?;
+                    }
+                }
+                Outgoing::Close(local_id) => {
+                    if let Some(route) = self.routes.remove(&local_id) {
+                        self.send(MessageCommand::Clse, local_id, route.remote_id, &[])?;
+                    }
+                }
+                Outgoing::Disconnect => return Ok(false),
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn send(&mut self, command: MessageCommand, arg0: u32, arg1: u32, data: &[u8]) -> Result<()> {
+        let mut message = ADBTransportMessage::try_new(command, arg0, arg1, data)?;
+        message.compute_checksum();
+
+        self.stream.write_all(&message.header().as_bytes())?;
+        self.stream.write_all(message.payload())?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Wait for next message sent by host, until device is stopped.
+    fn wait_message(&mut self) -> Result<ADBTransportMessage> {
+        while !self.state.is_stopped() {
+            if let Some(message) = self.poll_message()? {
+                return Ok(message);
+            }
+        }
+
+        Err(std::io::Error::from(ErrorKind::ConnectionAborted).into())
+    }
+
+    /// Next message sent by host, or `None` if none has been fully received yet.
+    fn poll_message(&mut self) -> Result<Option<ADBTransportMessage>> {
+        let mut chunk = [0_u8; 16 * 1024];
+        loop {
+            if let Some(message) = self.parse_message()? {
+                return Ok(Some(message));
+            }
+
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if is_timeout(&e) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn parse_message(&mut self) -> Result<Option<ADBTransportMessage>> {
+        let Some(header) = self.buffer.first_chunk::<24>() else {
+            return Ok(None);
+        };
+        let header = ADBTransportMessageHeader::try_from(*header)?;
+
+        let end = 24 + header.data_length() as usize;
+        if self.buffer.len() < end {
+            return Ok(None);
+        }
+        let payload = self.buffer[24..end].to_vec();
+        self.buffer.drain(..end);
+
+        Ok(Some(ADBTransportMessage::from_header_and_payload(
+            header, payload,
+        )))
+    }
+}
+
+fn is_timeout(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+    )
+}
+
+/// Session opened by host, read and written by the service it requested.
+///
+/// Closed once dropped.
+pub(crate) struct FakeSession {
+    local_id: u32,
+    outgoing: Sender<Outgoing>,
+    input: Receiver<Vec<u8>>,
+    /// Data received from host and not read yet.
+    pending: Vec<u8>,
+    acks: Receiver<u32>,
+    /// Amount of bytes host still accepts, when delayed ACK is used.
+    send_window: Option<i64>,
+    max_payload: usize,
+}
+
+impl FakeSession {
+    fn wait_ack(&self) -> std::io::Result<u32> {
+        self.acks.recv().map_err(|_| ErrorKind::BrokenPipe.into())
+    }
+}
+
+impl ServiceStream for FakeSession {
+    /// Close session, then drop connection it runs on.
+    fn disconnect(self) {
+        let _ = self.outgoing.send(Outgoing::Close(self.local_id));
+        let _ = self.outgoing.send(Outgoing::Disconnect);
+    }
+}
+
+impl Read for FakeSession {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pending.is_empty() {
+            match self.input.recv() {
+                Ok(data) => self.pending = data,
+                // Host closed session
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for FakeSession {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for chunk in buf.chunks(self.max_payload) {
+            if let Some(mut window) = self.send_window {
+                while let Ok(acked_bytes) = self.acks.try_recv() {
+                    window += i64::from(acked_bytes);
+                }
+                while window <= 0 {
+                    window += i64::from(self.wait_ack()?);
+                }
+                self.send_window =
+                    Some(window - i64::try_from(chunk.len()).map_err(std::io::Error::other)?);
+            }
+
+            self.outgoing
+                .send(Outgoing::Write(self.local_id, chunk.to_vec()))
+                .map_err(|_| std::io::Error::from(ErrorKind::BrokenPipe))?;
+
+            if self.send_window.is_none() {
+                self.wait_ack()?;
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for FakeSession {
+    fn drop(&mut self) {
+        let _ = self.outgoing.send(Outgoing::Close(self.local_id));
+    }
+}
@@ -0,0 +1,457 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, TcpStream},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rcgen::{CertificateParams, KeyPair};
+use rustls::{ServerConfig, pki_types::PrivatePkcs8KeyDer};
+
+use crate::{
+    Result,
+    message_devices::{
+        models::ADBRsaKey,
+        test_support::{
+            fake_connection::FakeConnection,
+            fake_filesystem::FakeFileSystem,
+            fake_listener::{FakeConnections, FakeListener, listen, lock},
+        },
+    },
+};
+
+/// Exit code of a shell command that cannot be found.
+const COMMAND_NOT_FOUND: u8 = 127;
+
+/// Output of a command run by a [`FakeDevice`], using shell or exec services.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FakeCommand {
+    pub(crate) stdout: Vec<u8>,
+    pub(crate) stderr: Vec<u8>,
+    pub(crate) exit_code: u8,
+}
+
+impl FakeCommand {
+    /// Command writing `stdout` on its standard output, and exiting successfully.
+    #[must_use]
+    pub fn new(stdout: impl Into<Vec<u8>>) -> Self {
+        Self {
+            stdout: stdout.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Content written by command on its error output.
+    #[must_use]
+    pub fn stderr(mut self, stderr: impl Into<Vec<u8>>) -> Self {
+        self.stderr = stderr.into();
+        self
+    }
+
+    /// Exit code of command, only reported by shell v2.
+    #[must_use]
+    pub const fn exit_code(mut self, exit_code: u8) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    /// Output of a command that has not been scripted, as reported by device shell.
+    pub(crate) fn not_found(command: &str) -> Self {
+        Self::default()
+            .stderr(format!(
+                "/system/bin/sh: {command}: inaccessible or not found\n"
+            ))
+            .exit_code(COMMAND_NOT_FOUND)
+    }
+}
+
+/// Fault injected by a [`FakeDevice`] when a service is opened.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FakeFault {
+    /// Service is refused, device closing it instead of accepting it.
+    Refuse,
+    /// Service is accepted, but device never answers.
+    Hang,
+    /// Connection drops before service is accepted, as when `adbd` restarts.
+    Disconnect,
+    /// Every file transfer request fails with given message.
+    SyncFail(String),
+}
+
+/// Scriptable fake `adbd`, serving the ADB protocol on a local TCP port to test code using this crate without any device.
+///
+/// Device is configured using builder methods, then started using [`FakeDevice::start`].
+/// Shell and exec services answer scripted commands, and file transfers use an in-memory file system.
+#[derive(Clone, Debug)]
+pub struct FakeDevice {
+    pub(crate) model: String,
+    pub(crate) features: Vec<String>,
+    pub(crate) require_auth: bool,
+    trusted_keys: Vec<ADBRsaKey>,
+    pub(crate) accept_unknown_keys: bool,
+    tls: bool,
+    pub(crate) commands: HashMap<String, FakeCommand>,
+    filesystem: FakeFileSystem,
+    pub(crate) framebuffer: Option<(u32, u32, Vec<u8>)>,
+    pub(crate) echo_ports: Vec<u16>,
+    faults: Vec<(String, FakeFault)>,
+}
+
+impl Default for FakeDevice {
+    fn default() -> Self {
+        Self {
+            model: "Fake_Device".to_string(),
+            features: [
+                "shell_v2",
+                "cmd",
+                "stat_v2",
+                "ls_v2",
+                "sendrecv_v2",
+                "delayed_ack",
+            ]
+            .map(ToString::to_string)
+            .to_vec(),
+            require_auth: false,
+            trusted_keys: Vec::new(),
+            accept_unknown_keys: false,
+            tls: false,
+            commands: HashMap::new(),
+            filesystem: FakeFileSystem::default(),
+            framebuffer: None,
+            echo_ports: Vec::new(),
+            faults: Vec::new(),
+        }
+    }
+}
+
+impl FakeDevice {
+    /// Model advertised by device.
+    #[must_use]
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Features advertised by device, replacing default ones.
+    ///
+    /// Defaults to `shell_v2`, `cmd`, `stat_v2`, `ls_v2`, `sendrecv_v2` and `delayed_ack`. Removing one of them makes device
+    /// use older version of matching protocol. Compressed file transfers are not supported.
+    #[must_use]
+    pub fn features<S: Into<String>>(mut self, features: impl IntoIterator<Item = S>) -> Self {
+        self.features = features.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Require host to authenticate using one of the keys trusted by device.
+    #[must_use]
+    pub const fn require_auth(mut self) -> Self {
+        self.require_auth = true;
+        self
+    }
+
+    /// Trust `key`, as if its public key had been added to device `adb_keys`.
+    #[must_use]
+    pub fn trusted_key(mut self, key: &ADBRsaKey) -> Self {
+        self.trusted_keys.push(key.clone());
+        self
+    }
+
+    /// Whether user allows debugging with keys sent by host that device does not trust yet.
+    ///
+    /// Defaults to `false`, user never answering.
+    #[must_use]
+    pub const fn accept_unknown_keys(mut self, accept_unknown_keys: bool) -> Self {
+        self.accept_unknown_keys = accept_unknown_keys;
+        self
+    }
+
+    /// Switch connections to TLS, as devices using wireless debugging do, instead of authenticating.
+    #[must_use]
+    pub const fn tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Answer `command` with `output`, when run using shell or exec services.
+    ///
+    /// Besides `mkdir -p`, `rm -rf` and `readlink` run on the in-memory file system, other commands fail, as if they could not be found.
+    #[must_use]
+    pub fn command(mut self, command: impl Into<String>, output: FakeCommand) -> Self {
+        self.commands.insert(command.into(), output);
+        self
+    }
+
+    /// Add regular file at absolute `path`, along with its missing parent directories.
+    #[must_use]
+    pub fn file(mut self, path: &str, content: impl Into<Vec<u8>>) -> Self {
+        self.filesystem
+            .add_file(path, content.into(), 0o644, unix_time());
+        self
+    }
+
+    /// Add directory at absolute `path`, along with its missing parents.
+    #[must_use]
+    pub fn directory(mut self, path: &str) -> Self {
+        self.filesystem.add_directory(path, unix_time());
+        self
+    }
+
+    /// Screen content returned by framebuffer service, as RGBA pixels.
+    ///
+    /// Framebuffer service is refused if not set.
+    #[must_use]
+    pub fn framebuffer(mut self, width: u32, height: u32, rgba: Vec<u8>) -> Self {
+        self.framebuffer = Some((width, height, rgba));
+        self
+    }
+
+    /// Run an echo server on device `port`, sending back whatever `tcp:<port>` services receive, e.g. through forwarded ports.
+    ///
+    /// Services connecting to other ports are refused.
+    #[must_use]
+    pub fn echo(mut self, port: u16) -> Self {
+        self.echo_ports.push(port);
+        self
+    }
+
+    /// Inject `fault` when next service whose name starts with `service` is opened, e.g. `sync:` or `shell`.
+    #[must_use]
+    pub fn fault(mut self, service: impl Into<String>, fault: FakeFault) -> Self {
+        self.faults.push((service.into(), fault));
+        self
+    }
+
+    /// Start serving device on a free port of loopback interface, until returned handle is dropped.
+    pub fn start(self) -> Result<FakeDeviceHandle> {
+        let state = Arc::new(FakeDeviceState::new(self)?);
+        let address = listen(&state)?;
+
+        Ok(FakeDeviceHandle { address, state })
+    }
+}
+
+/// State of a running [`FakeDevice`], shared between its connections.
+#[derive(Debug)]
+pub(crate) struct FakeDeviceState {
+    pub(crate) device: FakeDevice,
+    pub(crate) filesystem: Mutex<FakeFileSystem>,
+    faults: Mutex<Vec<(String, FakeFault)>>,
+    /// Public keys trusted by device, encoded as in `adb_keys` file.
+    pub(crate) trusted_keys: Mutex<Vec<String>>,
+    opened_services: Mutex<Vec<String>>,
+    connections: FakeConnections,
+    pub(crate) tls_config: Option<Arc<ServerConfig>>,
+}
+
+impl FakeDeviceState {
+    pub(crate) fn new(mut device: FakeDevice) -> Result<Self> {
+        let tls_config = if device.tls {
+            Some(Arc::new(tls_server_config()?))
+        } else {
+            None
+        };
+        let trusted_keys = device
+            .trusted_keys
+            .iter()
+            .map(ADBRsaKey::android_pubkey_encode)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            filesystem: Mutex::new(std::mem::take(&mut device.filesystem)),
+            faults: Mutex::new(std::mem::take(&mut device.faults)),
+            trusted_keys: Mutex::new(trusted_keys),
+            opened_services: Mutex::default(),
+            connections: FakeConnections::default(),
+            tls_config,
+            device,
+        })
+    }
+
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.connections.is_stopped()
+    }
+
+    /// Record that `service` has been opened, returning fault to inject if any.
+    pub(crate) fn open_service(&self, service: &str) -> Option<FakeFault> {
+        lock(&self.opened_services).push(service.to_string());
+
+        let mut faults = lock(&self.faults);
+        let index = faults
+            .iter()
+            .position(|(prefix, _)| service.starts_with(prefix.as_str()))?;
+        Some(faults.remove(index).1)
+    }
+
+    /// Output of `command`, as scripted or run by a builtin.
+    pub(crate) fn run_command(&self, command: &str) -> FakeCommand {
+        self.device
+            .commands
+            .get(command)
+            .cloned()
+            .or_else(|| self.run_builtin(command))
+            .unwrap_or_else(|| FakeCommand::not_found(command))
+    }
+
+    /// Output of `command` if it is one of the file system commands used by directory transfers.
+    fn run_builtin(&self, command: &str) -> Option<FakeCommand> {
+        let words = shell_words(command)?;
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let mut filesystem = lock(&self.filesystem);
+        match words.as_slice() {
+            ["mkdir", "-p", paths @ ..] => {
+                for path in paths {
+                    filesystem.add_directory(path, unix_time());
+                }
+                Some(FakeCommand::default())
+            }
+            ["rm", "-rf", paths @ ..] => {
+                for path in paths {
+                    filesystem.remove(path);
+                }
+                Some(FakeCommand::default())
+            }
+            ["readlink", path] => Some(
+                match filesystem.get(path).filter(|entry| entry.is_symlink()) {
+                    Some(entry) => {
+                        let mut target = entry.content.clone();
+                        target.push(b'\n');
+                        FakeCommand::new(target)
+                    }
+                    None => FakeCommand::default().exit_code(1),
+                },
+            ),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn file(&self, path: &str) -> Option<Vec<u8>> {
+        lock(&self.filesystem)
+            .get(path)
+            .filter(|entry| !entry.is_directory())
+            .map(|entry| entry.content.clone())
+    }
+
+    pub(crate) fn add_file(&self, path: &str, content: Vec<u8>) {
+        lock(&self.filesystem).add_file(path, content, 0o644, unix_time());
+    }
+
+    pub(crate) fn inject_fault(&self, service: String, fault: FakeFault) {
+        lock(&self.faults).push((service, fault));
+    }
+
+    pub(crate) fn opened_services(&self) -> Vec<String> {
+        lock(&self.opened_services).clone()
+    }
+}
+
+impl FakeListener for FakeDeviceState {
+    fn connections(&self) -> &FakeConnections {
+        &self.connections
+    }
+
+    fn serve(self: Arc<Self>, socket: TcpStream) -> Result<()> {
+        FakeConnection::new(socket, self)?.serve()
+    }
+}
+
+/// Handle on a running [`FakeDevice`], stopping it once dropped.
+#[derive(Debug)]
+pub struct FakeDeviceHandle {
+    address: SocketAddr,
+    state: Arc<FakeDeviceState>,
+}
+
+impl FakeDeviceHandle {
+    /// Address to connect to, e.g. using [`ADBTcpDevice`](crate::tcp::ADBTcpDevice).
+    #[must_use]
+    pub const fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Content of regular file at `path`, e.g. after it has been pushed.
+    #[must_use]
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        self.state.file(path)
+    }
+
+    /// Add or replace regular file at `path` while device is running.
+    pub fn add_file(&self, path: &str, content: impl Into<Vec<u8>>) {
+        self.state.add_file(path, content.into());
+    }
+
+    /// Inject `fault` when next service whose name starts with `service` is opened.
+    pub fn inject_fault(&self, service: impl Into<String>, fault: FakeFault) {
+        self.state.inject_fault(service.into(), fault);
+    }
+
+    /// Every service opened so far, in order, e.g. `shell,v2,raw:id` or `sync:`.
+    #[must_use]
+    pub fn opened_services(&self) -> Vec<String> {
+        self.state.opened_services()
+    }
+
+    /// Drop every established connection, as when `adbd` restarts. Device keeps accepting new ones.
+    pub fn disconnect(&self) {
+        self.state.connections.disconnect();
+    }
+
+    /// Number of connections accepted so far, including reconnections.
+    #[must_use]
+    pub fn accepted_connections(&self) -> u64 {
+        self.state.connections.accepted()
+    }
+}
+
+impl Drop for FakeDeviceHandle {
+    fn drop(&mut self) {
+        self.state.connections.stop();
+    }
+}
+
+/// TLS configuration of device, using a newly generated certificate.
+fn tls_server_config() -> Result<ServerConfig> {
+    let key_pair = KeyPair::generate()?;
+    let certificate = CertificateParams::default().self_signed(&key_pair)?;
+
+    Ok(ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![certificate.der().clone()],
+            PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into(),
+        )?)
+}
+
+/// Current time, in seconds since epoch.
+/// Words of shell `command`, unquoted as `sh` does, or `None` if a quote is not closed.
+fn shell_words(command: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => words.extend(word.take()),
+            '\\' => word.get_or_insert_default().push(chars.next()?),
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+
+    Some(words)
+}
+
+pub(crate) fn unix_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|elapsed| u32::try_from(elapsed.as_secs()).ok())
+        .unwrap_or_default()
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+/// Mask of file type bits.
+pub(crate) const S_IFMT: u32 = 0o170_000;
+/// File type bits of a directory.
+const S_IFDIR: u32 = 0o040_000;
+/// File type bits of a regular file.
+const S_IFREG: u32 = 0o100_000;
+/// File type bits of a symbolic link.
+pub(crate) const S_IFLNK: u32 = 0o120_000;
+/// Permission bits of a mode.
+const PERMISSIONS_MASK: u32 = 0o7777;
+
+/// Entry of a [`FakeFileSystem`].
+#[derive(Clone, Debug)]
+pub(crate) struct FakeEntry {
+    /// File type and permissions, as returned by `stat`.
+    pub(crate) mode: u32,
+    /// Last modification time, in seconds since epoch.
+    pub(crate) mtime: u32,
+    /// Content of a regular file, or target of a symbolic link, empty for directories.
+    pub(crate) content: Vec<u8>,
+}
+
+impl FakeEntry {
+    pub(crate) const fn is_directory(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub(crate) const fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+}
+
+/// In-memory file system served by a fake device, indexed by absolute path.
+#[derive(Clone, Debug)]
+pub(crate) struct FakeFileSystem {
+    entries: BTreeMap<String, FakeEntry>,
+}
+
+impl Default for FakeFileSystem {
+    fn default() -> Self {
+        let mut entries = BTreeMap::new();
+        entries.insert(
+            "/".to_string(),
+            FakeEntry {
+                mode: S_IFDIR | 0o755,
+                mtime: 0,
+                content: Vec::new(),
+            },
+        );
+        Self { entries }
+    }
+}
+
+impl FakeFileSystem {
+    /// Create directory `path`, along with its missing parents.
+    pub(crate) fn add_directory(&mut self, path: &str, mtime: u32) {
+        let path = normalize(path);
+        if let Some(parent) = parent(&path) {
+            self.add_directory(&parent, mtime);
+        }
+
+        self.entries.entry(path).or_insert(FakeEntry {
+            mode: S_IFDIR | 0o755,
+            mtime,
+            content: Vec::new(),
+        });
+    }
+
+    /// Write regular file `path` with permissions of `mode`, creating its missing parents.
+    ///
+    /// A symbolic link pointing to `content` is created instead if `mode` says so, as `adbd` does.
+    pub(crate) fn add_file(&mut self, path: &str, content: Vec<u8>, mode: u32, mtime: u32) {
+        let path = normalize(path);
+        if let Some(parent) = parent(&path) {
+            self.add_directory(&parent, mtime);
+        }
+
+        let file_type = if mode & S_IFMT == S_IFLNK {
+            S_IFLNK
+        } else {
+            S_IFREG
+        };
+        self.entries.insert(
+            path,
+            FakeEntry {
+                mode: file_type | (mode & PERMISSIONS_MASK),
+                mtime,
+                content,
+            },
+        );
+    }
+
+    /// Remove entry `path`, along with its content if it is a directory.
+    pub(crate) fn remove(&mut self, path: &str) {
+        let path = normalize(path);
+        let prefix = format!("{path}/");
+        self.entries
+            .retain(|entry, _| *entry != path && !entry.starts_with(&prefix));
+    }
+
+    pub(crate) fn get(&self, path: &str) -> Option<&FakeEntry> {
+        self.entries.get(&normalize(path))
+    }
+
+    /// Entries of directory `path` with their names, or `None` if it is not a directory.
+    pub(crate) fn list(&self, path: &str) -> Option<Vec<(String, &FakeEntry)>> {
+        let path = normalize(path);
+        if !self.entries.get(&path)?.is_directory() {
+            return None;
+        }
+
+        let prefix = if path == "/" {
+            path
+        } else {
+            format!("{path}/")
+        };
+        Some(
+            self.entries
+                .range(prefix.clone()..)
+                .take_while(|(child, _)| child.starts_with(&prefix))
+                .filter_map(|(child, entry)| {
+                    let name = &child[prefix.len()..];
+                    (!name.is_empty() && !name.contains('/')).then(|| (name.to_string(), entry))
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Absolute path without trailing separators.
+fn normalize(path: &str) -> String {
+    let path = path.trim_end_matches('/');
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{path}")
+    }
+}
+
+/// Parent directory of normalized `path`, `None` for root.
+fn parent(path: &str) -> Option<String> {
+    if path == "/" {
+        return None;
+    }
+
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => Some("/".to_string()),
+        Some((parent, _)) => Some(parent.to_string()),
+    }
+}
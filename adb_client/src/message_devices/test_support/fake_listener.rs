@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::Result;
+
+/// Interval at which background threads check whether fake peer has been stopped.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Connections accepted by a fake device or server, shut down once it is stopped.
+#[derive(Debug, Default)]
+pub(crate) struct FakeConnections {
+    sockets: Mutex<HashMap<u64, TcpStream>>,
+    next_id: AtomicU64,
+    stopped: AtomicBool,
+}
+
+impl FakeConnections {
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+
+    /// Stop accepting connections, and drop established ones.
+    pub(crate) fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+        self.disconnect();
+    }
+
+    /// Number of connections accepted so far.
+    pub(crate) fn accepted(&self) -> u64 {
+        self.next_id.load(Ordering::Relaxed)
+    }
+
+    /// Drop every established connection.
+    pub(crate) fn disconnect(&self) {
+        for socket in lock(&self.sockets).values() {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Fake peer accepting connections on a local port.
+pub(crate) trait FakeListener: Send + Sync + 'static {
+    fn connections(&self) -> &FakeConnections;
+
+    /// Serve `socket` until peer disconnects, or listener is stopped.
+    fn serve(self: Arc<Self>, socket: TcpStream) -> Result<()>;
+}
+
+/// Listen on a free port of loopback interface until `listener` is stopped, serving each connection on its own thread.
+pub(crate) fn listen<L: FakeListener>(listener: &Arc<L>) -> Result<SocketAddr> {
+    let tcp_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    tcp_listener.set_nonblocking(true)?;
+    let address = tcp_listener.local_addr()?;
+
+    let listener = listener.clone();
+    std::thread::spawn(move || accept_connections(&tcp_listener, &listener));
+
+    Ok(address)
+}
+
+fn accept_connections<L: FakeListener>(tcp_listener: &TcpListener, listener: &Arc<L>) {
+    let connections = listener.connections();
+    while !connections.is_stopped() {
+        let socket = match tcp_listener.accept() {
+            Ok((socket, _)) => socket,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Err(e) => {
+                log::error!("fake peer cannot accept connections anymore: {e}");
+                return;
+            }
+        };
+
+        let id = connections.next_id.fetch_add(1, Ordering::Relaxed);
+        let listener = listener.clone();
+        std::thread::spawn(move || {
+            let result = socket
+                .set_nonblocking(false)
+                .and_then(|()| socket.try_clone());
+            let result = result.map_err(Into::into).and_then(|registered| {
+                lock(&listener.connections().sockets).insert(id, registered);
+                listener.clone().serve(socket)
+            });
+            if let Err(e) = result {
+                log::debug!("fake peer connection ended: {e}");
+            }
+
+            if let Some(socket) = lock(&listener.connections().sockets).remove(&id) {
+                let _ = socket.shutdown(Shutdown::Both);
+            }
+        });
+    }
+}
+
+/// Lock `mutex`, even if a thread panicked while holding it.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,370 @@
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, SocketAddr, SocketAddrV4, TcpStream},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    Result, RustADBError,
+    message_devices::test_support::{
+        fake_device::{FakeDevice, FakeDeviceState, FakeFault},
+        fake_listener::{FakeConnections, FakeListener, listen, lock},
+        fake_services::{FakeService, OpenedService, ServiceStream},
+    },
+    server::DeviceState,
+};
+
+/// Version reported by `host:version` requests.
+const ADB_SERVER_VERSION: u32 = 41;
+
+/// Scriptable fake ADB server, serving the smart socket protocol on a local TCP port to test code using [`ADBServer`](crate::server::ADBServer) without any `adb` binary.
+///
+/// Each of its devices is a [`FakeDevice`], answering device services (shell, sync, ...) once selected by a transport request.
+/// As server runs on loopback interface, use [`ADBServer::set_auto_start`](crate::server::ADBServer::set_auto_start) to prevent `adb` from being started when connecting to it.
+#[derive(Clone, Debug, Default)]
+pub struct FakeServer {
+    devices: Vec<(String, DeviceState, FakeDevice)>,
+}
+
+impl FakeServer {
+    /// Add an online `device`, listed with `serial` identifier.
+    #[must_use]
+    pub fn device(self, serial: impl Into<String>, device: FakeDevice) -> Self {
+        self.device_with_state(serial, DeviceState::Device, device)
+    }
+
+    /// Add a `device` listed with `serial` identifier and given `state`, e.g. [`DeviceState::Unauthorized`].
+    ///
+    /// Transport requests to a device not in [`DeviceState::Device`] state fail, as with a real server.
+    #[must_use]
+    pub fn device_with_state(
+        mut self,
+        serial: impl Into<String>,
+        state: DeviceState,
+        device: FakeDevice,
+    ) -> Self {
+        self.devices.push((serial.into(), state, device));
+        self
+    }
+
+    /// Start serving on a free port of loopback interface, until returned handle is dropped.
+    pub fn start(self) -> Result<FakeServerHandle> {
+        let devices = self
+            .devices
+            .into_iter()
+            .zip(1..)
+            .map(|((serial, state, device), transport_id)| {
+                Ok(FakeServedDevice {
+                    serial,
+                    state,
+                    transport_id,
+                    device: FakeDeviceState::new(device)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let state = Arc::new(FakeServerState {
+            devices,
+            requests: Mutex::default(),
+            connections: FakeConnections::default(),
+        });
+
+        let address = match listen(&state)? {
+            SocketAddr::V4(address) => address,
+            SocketAddr::V6(_) => unreachable!("fake server listens on IPv4 loopback"),
+        };
+
+        Ok(FakeServerHandle { address, state })
+    }
+}
+
+#[derive(Debug)]
+struct FakeServedDevice {
+    serial: String,
+    state: DeviceState,
+    transport_id: u32,
+    device: FakeDeviceState,
+}
+
+/// State of a running [`FakeServer`], shared between its connections.
+#[derive(Debug)]
+struct FakeServerState {
+    devices: Vec<FakeServedDevice>,
+    requests: Mutex<Vec<String>>,
+    connections: FakeConnections,
+}
+
+impl FakeServerState {
+    fn device(&self, serial: &str) -> &FakeDeviceState {
+        let Some(served) = self.devices.iter().find(|device| device.serial == serial) else {
+            panic!("fake server has no device '{serial}'");
+        };
+        &served.device
+    }
+
+    /// Handle `request` sent on a connection using `transport`, returning whether connection goes on.
+    fn handle_request(
+        &self,
+        request: &str,
+        socket: &mut TcpStream,
+        transport: &mut Option<usize>,
+    ) -> Result<bool> {
+        match request {
+            "host:version" => okay(socket, format!("{ADB_SERVER_VERSION:04x}").as_bytes())?,
+            "host:kill" => {
+                socket.write_all(b"OKAY")?;
+                self.connections.stop();
+                return Ok(false);
+            }
+            "host:devices" => okay(socket, self.list_devices(false).as_bytes())?,
+            "host:devices-l" => okay(socket, self.list_devices(true).as_bytes())?,
+            "host:track-devices" => {
+                socket.write_all(b"OKAY")?;
+                write_hex_length_prefixed(socket, self.list_devices(false).as_bytes())?;
+                // Device list never changes, so wait for host to close connection
+                std::io::copy(socket, &mut std::io::sink())?;
+                return Ok(false);
+            }
+            "host:features" => {
+                let selected = transport.map_or_else(|| self.select(|_| true, None), Ok);
+                match selected {
+                    Ok(index) => {
+                        let features = self.devices[index].device.device.features.join(",");
+                        okay(socket, features.as_bytes())?;
+                    }
+                    Err(error) => return fail(socket, &error),
+                }
+            }
+            "host:transport-any" => {
+                return self.select_transport(socket, transport, |_| true, None);
+            }
+            _ => {
+                if let Some(serial) = request.strip_prefix("host:transport:") {
+                    let missing = format!("device '{serial}' not found");
+                    return self.select_transport(
+                        socket,
+                        transport,
+                        |device| device.serial == serial,
+                        Some(missing),
+                    );
+                }
+                if let Some(id) = request.strip_prefix("host:transport-id:") {
+                    let missing = format!("no device with transport id '{id}'");
+                    let id = id.parse().ok();
+                    return self.select_transport(
+                        socket,
+                        transport,
+                        |device| Some(device.transport_id) == id,
+                        Some(missing),
+                    );
+                }
+
+                return match transport {
+                    Some(index) if !request.starts_with("host") => {
+                        self.open_service(request, socket, *index)?;
+                        Ok(false)
+                    }
+                    _ => fail(socket, "unknown host service"),
+                };
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Device list, one device per line, with details when `long` is set.
+    fn list_devices(&self, long: bool) -> String {
+        self.devices
+            .iter()
+            .map(|served| {
+                if long {
+                    let model = &served.device.device.model;
+                    format!(
+                        "{}\t{} product:{model} model:{model} device:{model} transport_id:{}\n",
+                        served.serial, served.state, served.transport_id
+                    )
+                } else {
+                    format!("{}\t{}\n", served.serial, served.state)
+                }
+            })
+            .collect()
+    }
+
+    /// Index of the only device matching `filter`, or error message reported by a real server.
+    ///
+    /// `missing` is reported when no device matches, instead of a generic message.
+    fn select(
+        &self,
+        filter: impl Fn(&FakeServedDevice) -> bool,
+        missing: Option<String>,
+    ) -> std::result::Result<usize, String> {
+        let mut matching = self
+            .devices
+            .iter()
+            .enumerate()
+            .filter(|(_, device)| filter(device));
+        match (matching.next(), matching.next()) {
+            (None, _) => Err(missing.unwrap_or_else(|| "no devices/emulators found".to_string())),
+            (Some(_), Some(_)) => Err("more than one device/emulator".to_string()),
+            (Some((_, device)), None) if device.state != DeviceState::Device => {
+                Err(format!("device {}", device.state))
+            }
+            (Some((index, _)), None) => Ok(index),
+        }
+    }
+
+    fn select_transport(
+        &self,
+        socket: &mut TcpStream,
+        transport: &mut Option<usize>,
+        filter: impl Fn(&FakeServedDevice) -> bool,
+        missing: Option<String>,
+    ) -> Result<bool> {
+        match self.select(filter, missing) {
+            Ok(index) => {
+                *transport = Some(index);
+                socket.write_all(b"OKAY")?;
+                Ok(true)
+            }
+            Err(error) => fail(socket, &error),
+        }
+    }
+
+    /// Open device `service` on selected device, then serve it on `socket` until it ends.
+    fn open_service(&self, service: &str, socket: &mut TcpStream, index: usize) -> Result<()> {
+        let device = &self.devices[index].device;
+        match FakeService::open(service, device) {
+            OpenedService::Accepted(service) => {
+                socket.write_all(b"OKAY")?;
+                service.run(socket.try_clone()?, device)
+            }
+            OpenedService::Refused => fail(socket, "closed").map(|_| ()),
+            OpenedService::Disconnected => Ok(()),
+        }
+    }
+}
+
+impl FakeListener for FakeServerState {
+    fn connections(&self) -> &FakeConnections {
+        &self.connections
+    }
+
+    fn serve(self: Arc<Self>, mut socket: TcpStream) -> Result<()> {
+        let mut transport = None;
+        while let Some(request) = read_request(&mut socket)? {
+            lock(&self.requests).push(request.clone());
+            if !self.handle_request(&request, &mut socket, &mut transport)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ServiceStream for TcpStream {
+    fn disconnect(self) {
+        let _ = self.shutdown(Shutdown::Both);
+    }
+}
+
+/// Read next request sent by host, or `None` once it closed connection.
+fn read_request(socket: &mut TcpStream) -> Result<Option<String>> {
+    let mut length = [0_u8; 4];
+    match socket.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let length = usize::from_str_radix(std::str::from_utf8(&length)?, 16)
+        .map_err(|_| RustADBError::ConversionError)?;
+    let mut request = vec![0_u8; length];
+    socket.read_exact(&mut request)?;
+    Ok(Some(String::from_utf8(request)?))
+}
+
+fn write_hex_length_prefixed(socket: &mut TcpStream, body: &[u8]) -> Result<()> {
+    socket.write_all(format!("{:04x}", body.len()).as_bytes())?;
+    socket.write_all(body)?;
+    Ok(())
+}
+
+/// Answer `OKAY`, followed by `body`.
+fn okay(socket: &mut TcpStream, body: &[u8]) -> Result<()> {
+    socket.write_all(b"OKAY")?;
+    write_hex_length_prefixed(socket, body)
+}
+
+/// Answer `FAIL` with `message`, letting connection go on.
+fn fail(socket: &mut TcpStream, message: &str) -> Result<bool> {
+    socket.write_all(b"FAIL")?;
+    write_hex_length_prefixed(socket, message.as_bytes())?;
+    Ok(true)
+}
+
+/// Handle on a running [`FakeServer`], stopping it once dropped.
+#[derive(Debug)]
+pub struct FakeServerHandle {
+    address: SocketAddrV4,
+    state: Arc<FakeServerState>,
+}
+
+impl FakeServerHandle {
+    /// Address to connect to, e.g. using [`ADBServer::new`](crate::server::ADBServer::new).
+    #[must_use]
+    pub const fn address(&self) -> SocketAddrV4 {
+        self.address
+    }
+
+    /// Content of regular file at `path` on device `serial`, e.g. after it has been pushed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if server has no device `serial`.
+    #[must_use]
+    pub fn file(&self, serial: &str, path: &str) -> Option<Vec<u8>> {
+        self.state.device(serial).file(path)
+    }
+
+    /// Add or replace regular file at `path` on device `serial` while server is running.
+    ///
+    /// # Panics
+    ///
+    /// Panics if server has no device `serial`.
+    pub fn add_file(&self, serial: &str, path: &str, content: impl Into<Vec<u8>>) {
+        self.state.device(serial).add_file(path, content.into());
+    }
+
+    /// Inject `fault` when next service whose name starts with `service` is opened on device `serial`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if server has no device `serial`.
+    pub fn inject_fault(&self, serial: &str, service: impl Into<String>, fault: FakeFault) {
+        self.state
+            .device(serial)
+            .inject_fault(service.into(), fault);
+    }
+
+    /// Every service opened so far on device `serial`, in order, e.g. `shell,v2,raw:id` or `sync:`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if server has no device `serial`.
+    #[must_use]
+    pub fn opened_services(&self, serial: &str) -> Vec<String> {
+        self.state.device(serial).opened_services()
+    }
+
+    /// Every request received so far, in order, e.g. `host:devices` or `host:transport:emulator-5554`.
+    #[must_use]
+    pub fn requests(&self) -> Vec<String> {
+        lock(&self.state.requests).clone()
+    }
+}
+
+impl Drop for FakeServerHandle {
+    fn drop(&mut self) {
+        self.state.connections.stop();
+    }
+}
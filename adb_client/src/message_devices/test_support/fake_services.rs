@@ -0,0 +1,471 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::{
+    AdbStatResponse, Result,
+    file_sync::SYNC_DATA_MAX,
+    message_devices::test_support::{
+        fake_device::{FakeCommand, FakeDevice, FakeDeviceState, FakeFault, unix_time},
+        fake_filesystem::{FakeEntry, S_IFLNK, S_IFMT},
+        fake_listener::lock,
+    },
+    models::ShellChannel,
+};
+
+/// `errno` reported by stat requests on missing files.
+const ENOENT: u32 = 2;
+/// Size of a version 1 directory listing end, without its identifier.
+const DONE_V1_SIZE: usize = 16;
+/// Size of a version 2 directory listing end, without its identifier.
+const DONE_V2_SIZE: usize = 72;
+
+/// Stream a service is served on, either a device session or a host server socket.
+pub(crate) trait ServiceStream: Read + Write {
+    /// Drop connection to device, as when `adbd` restarts.
+    fn disconnect(self);
+}
+
+/// Outcome of a request to open a service.
+pub(crate) enum OpenedService {
+    Accepted(FakeService),
+    /// Device does not serve requested service, or refuses it.
+    Refused,
+    /// Device drops connection instead of answering.
+    Disconnected,
+}
+
+/// Service requested by host when opening a session.
+pub(crate) enum FakeService {
+    /// Shell command, interactive if empty.
+    Shell {
+        command: String,
+        v2: bool,
+    },
+    Exec(String),
+    /// File transfers, each of them failing with given error if any.
+    Sync(Option<String>),
+    Framebuffer,
+    /// Connection to an echo server on device.
+    Echo,
+    Root,
+    Reboot,
+    /// Service that never answers.
+    Hang,
+}
+
+impl FakeService {
+    /// Open `service` on device, applying first fault injected on it if any.
+    pub(crate) fn open(service: &str, state: &FakeDeviceState) -> OpenedService {
+        let fault = state.open_service(service);
+        match (fault, Self::parse(service, &state.device)) {
+            (Some(FakeFault::Disconnect), _) => OpenedService::Disconnected,
+            (Some(FakeFault::Refuse), _) | (_, None) => OpenedService::Refused,
+            (Some(FakeFault::SyncFail(error)), Some(Self::Sync(_))) => {
+                OpenedService::Accepted(Self::Sync(Some(error)))
+            }
+            (Some(FakeFault::Hang), Some(_)) => OpenedService::Accepted(Self::Hang),
+            (_, Some(service)) => OpenedService::Accepted(service),
+        }
+    }
+
+    /// Parse requested `service` string, `None` if device does not serve it.
+    fn parse(service: &str, device: &FakeDevice) -> Option<Self> {
+        if let Some(shell) = service.strip_prefix("shell") {
+            let (options, command) = shell.split_once(':')?;
+            return Some(Self::Shell {
+                command: command.to_string(),
+                v2: options.split(',').any(|option| option == "v2"),
+            });
+        }
+        if let Some(command) = service.strip_prefix("exec:") {
+            return Some(Self::Exec(command.to_string()));
+        }
+        if service.starts_with("reboot:") {
+            return Some(Self::Reboot);
+        }
+        if let Some(port) = service.strip_prefix("tcp:") {
+            let port = port.parse().ok()?;
+            return device.echo_ports.contains(&port).then_some(Self::Echo);
+        }
+
+        match service {
+            "sync:" => Some(Self::Sync(None)),
+            "framebuffer:" if device.framebuffer.is_some() => Some(Self::Framebuffer),
+            "root:" => Some(Self::Root),
+            _ => None,
+        }
+    }
+
+    /// Serve this service on `session`, which is closed on return.
+    pub(crate) fn run<S: ServiceStream>(
+        self,
+        mut session: S,
+        state: &FakeDeviceState,
+    ) -> Result<()> {
+        match self {
+            Self::Shell { command, v2 } if command.is_empty() => {
+                interactive_shell(&mut session, state, v2)
+            }
+            Self::Shell { command, v2 } => {
+                let output = state.run_command(&command);
+                write_output(&mut session, &output, v2)?;
+                if v2 {
+                    session.write_all(&ShellChannel::ExitStatus.packet(&[output.exit_code])?)?;
+                }
+                Ok(())
+            }
+            Self::Exec(command) => {
+                session.write_all(&state.run_command(&command).stdout)?;
+                Ok(())
+            }
+            Self::Sync(error) => FakeSync {
+                session: &mut session,
+                state,
+                error,
+            }
+            .serve(),
+            Self::Framebuffer => {
+                if let Some((width, height, rgba)) = &state.device.framebuffer {
+                    session.write_all(&framebuffer(*width, *height, rgba)?)?;
+                }
+                Ok(())
+            }
+            Self::Echo => {
+                let mut buffer = [0_u8; 4096];
+                loop {
+                    match session.read(&mut buffer)? {
+                        0 => return Ok(()),
+                        n => session.write_all(&buffer[..n])?,
+                    }
+                }
+            }
+            Self::Root => {
+                session.write_all(b"restarting adbd as root\n")?;
+                session.disconnect();
+                Ok(())
+            }
+            Self::Reboot => {
+                session.disconnect();
+                Ok(())
+            }
+            Self::Hang => {
+                std::io::copy(&mut session, &mut std::io::sink())?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Run each line written by host as a scripted command, until it exits or closes its input.
+fn interactive_shell(
+    session: &mut impl ServiceStream,
+    state: &FakeDeviceState,
+    v2: bool,
+) -> Result<()> {
+    let mut line = Vec::new();
+    let mut buffer = [0_u8; 4096];
+    let mut exit_code = 0;
+    'input: loop {
+        let input = if v2 {
+            let mut header = [0_u8; ShellChannel::HEADER_SIZE];
+            if !read_exact_or_eof(session, &mut header)? {
+                break;
+            }
+            let mut payload = vec![
+                0_u8;
+                u32::from_le_bytes([header[1], header[2], header[3], header[4]])
+                    as usize
+            ];
+            session.read_exact(&mut payload)?;
+
+            match ShellChannel::try_from(header[0])? {
+                ShellChannel::Stdin => payload,
+                ShellChannel::CloseStdin => break,
+                _ => continue,
+            }
+        } else {
+            match session.read(&mut buffer)? {
+                0 => break,
+                n => buffer[..n].to_vec(),
+            }
+        };
+
+        line.extend_from_slice(&input);
+        while let Some(end) = line.iter().position(|&b| b == b'\n') {
+            let command_line: Vec<u8> = line.drain(..=end).collect();
+            let command = String::from_utf8_lossy(&command_line);
+            match command.trim() {
+                "exit" => break 'input,
+                "" => {}
+                command => {
+                    let output = state.run_command(command);
+                    write_output(session, &output, v2)?;
+                    exit_code = output.exit_code;
+                }
+            }
+        }
+    }
+
+    if v2 {
+        // Like `sh`, exiting with status of last command
+        session.write_all(&ShellChannel::ExitStatus.packet(&[exit_code])?)?;
+    }
+    Ok(())
+}
+
+/// Write output of a command, using shell v2 packets if `v2` is set.
+fn write_output(session: &mut impl Write, output: &FakeCommand, v2: bool) -> Result<()> {
+    for (channel, data) in [
+        (ShellChannel::Stdout, &output.stdout),
+        (ShellChannel::Stderr, &output.stderr),
+    ] {
+        if data.is_empty() {
+            continue;
+        }
+        if v2 {
+            session.write_all(&channel.packet(data)?)?;
+        } else {
+            session.write_all(data)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fill `buf`, returning `false` if host closed session before sending anything.
+fn read_exact_or_eof(session: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    match session.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Framebuffer version 2 header, followed by `rgba` pixels.
+fn framebuffer(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>> {
+    let header = [
+        2,  // version
+        32, // bits per pixel
+        0,  // color space
+        u32::try_from(rgba.len())?,
+        width,
+        height,
+        0,  // red offset
+        8,  // red length
+        16, // blue offset
+        8,  // blue length
+        8,  // green offset
+        8,  // green length
+        24, // alpha offset
+        8,  // alpha length
+    ];
+
+    let mut framebuffer: Vec<u8> = header.iter().flat_map(|v| v.to_le_bytes()).collect();
+    framebuffer.extend_from_slice(rgba);
+    Ok(framebuffer)
+}
+
+/// Synchronization session, serving requests from device file system.
+struct FakeSync<'a, S> {
+    session: &'a mut S,
+    state: &'a FakeDeviceState,
+    /// Error every file transfer fails with, if any.
+    error: Option<String>,
+}
+
+impl<S: ServiceStream> FakeSync<'_, S> {
+    fn serve(mut self) -> Result<()> {
+        loop {
+            let mut request = [0_u8; 8];
+            if !read_exact_or_eof(self.session, &mut request)? {
+                return Ok(());
+            }
+            let (id, length) = request.split_at(4);
+            if id == b"QUIT" {
+                return Ok(());
+            }
+
+            let mut path = vec![0_u8; u32::from_le_bytes(length.try_into()?) as usize];
+            self.session.read_exact(&mut path)?;
+            let path = String::from_utf8_lossy(&path).to_string();
+
+            let response = match id {
+                b"STAT" => self.stat_v1(&path),
+                b"LST2" | b"STA2" => {
+                    let mut response = id.to_vec();
+                    response.extend_from_slice(&stat_v2(self.entry(&path).as_ref()));
+                    response
+                }
+                b"LIST" => self.list(&path, false),
+                b"LIS2" => self.list(&path, true),
+                b"SEND" => {
+                    let (path, mode) = path.rsplit_once(',').unwrap_or((&path, "0644"));
+                    let mode = u32::from_str_radix(mode, 8).unwrap_or(0o644);
+                    self.receive(path, mode, None)?
+                }
+                b"SND2" => {
+                    let mut setup = [0_u8; 12];
+                    self.session.read_exact(&mut setup)?;
+                    let mode = u32::from_le_bytes(setup[4..8].try_into()?);
+                    let flags = u32::from_le_bytes(setup[8..12].try_into()?);
+                    let error = (flags != 0).then(|| "compression is not supported".to_string());
+                    self.receive(&path, mode, error)?
+                }
+                b"RECV" => self.send(&path, None),
+                b"RCV2" => {
+                    let mut setup = [0_u8; 8];
+                    self.session.read_exact(&mut setup)?;
+                    let flags = u32::from_le_bytes(setup[4..8].try_into()?);
+                    let error = (flags != 0).then(|| "compression is not supported".to_string());
+                    self.send(&path, error)
+                }
+                _ => {
+                    self.session.write_all(&fail("unknown sync request"))?;
+                    return Ok(());
+                }
+            };
+
+            self.session.write_all(&response)?;
+        }
+    }
+
+    fn entry(&self, path: &str) -> Option<FakeEntry> {
+        lock(&self.state.filesystem).get(path).cloned()
+    }
+
+    fn stat_v1(&self, path: &str) -> Vec<u8> {
+        let mut response = b"STAT".to_vec();
+        let (mode, size, mtime) = self
+            .entry(path)
+            .map(|entry| (entry.mode, entry.content.len(), entry.mtime))
+            .unwrap_or_default();
+        for value in [mode, u32::try_from(size).unwrap_or(u32::MAX), mtime] {
+            response.extend_from_slice(&value.to_le_bytes());
+        }
+        response
+    }
+
+    /// Entries of directory `path`, followed by an empty `DONE` entry.
+    fn list(&self, path: &str, v2: bool) -> Vec<u8> {
+        let mut response = Vec::new();
+        let filesystem = lock(&self.state.filesystem);
+        for (name, entry) in filesystem.list(path).unwrap_or_default() {
+            if v2 {
+                response.extend_from_slice(b"DNT2");
+                response.extend_from_slice(&stat_v2(Some(entry)));
+            } else {
+                response.extend_from_slice(b"DENT");
+                let size = u32::try_from(entry.content.len()).unwrap_or(u32::MAX);
+                for value in [entry.mode, size, entry.mtime] {
+                    response.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            response
+                .extend_from_slice(&u32::try_from(name.len()).unwrap_or_default().to_le_bytes());
+            response.extend_from_slice(name.as_bytes());
+        }
+
+        response.extend_from_slice(b"DONE");
+        response.resize(
+            response.len() + if v2 { DONE_V2_SIZE } else { DONE_V1_SIZE },
+            0,
+        );
+        response
+    }
+
+    /// Receive content of file `path` from host, then store it unless transfer fails.
+    fn receive(&mut self, path: &str, mode: u32, error: Option<String>) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+        let mtime = loop {
+            let mut packet = [0_u8; 8];
+            self.session.read_exact(&mut packet)?;
+            let (id, value) = packet.split_at(4);
+            let value = u32::from_le_bytes(value.try_into()?);
+            match id {
+                b"DATA" => {
+                    let start = content.len();
+                    content.resize(start + value as usize, 0);
+                    self.session.read_exact(&mut content[start..])?;
+                }
+                b"DONE" => break value,
+                _ => return Ok(fail("expected DATA or DONE")),
+            }
+        };
+
+        if let Some(error) = error.or_else(|| self.error.clone()) {
+            return Ok(fail(&error));
+        }
+        if self.entry(path).is_some_and(|entry| entry.is_directory()) {
+            return Ok(fail("Is a directory"));
+        }
+
+        // Like `adbd`, sent modification time is not applied to symlinks
+        let mtime = if mode & S_IFMT == S_IFLNK {
+            unix_time()
+        } else {
+            mtime
+        };
+        lock(&self.state.filesystem).add_file(path, content, mode, mtime);
+        let mut response = b"OKAY".to_vec();
+        response.extend_from_slice(&0_u32.to_le_bytes());
+        Ok(response)
+    }
+
+    /// Send content of file `path` to host as `DATA` packets, followed by `DONE`.
+    fn send(&self, path: &str, error: Option<String>) -> Vec<u8> {
+        if let Some(error) = error.or_else(|| self.error.clone()) {
+            return fail(&error);
+        }
+        let entry = match self.entry(path) {
+            None => return fail("No such file or directory"),
+            Some(entry) if entry.is_directory() => return fail("Is a directory"),
+            Some(entry) => entry,
+        };
+
+        let mut response = Vec::with_capacity(entry.content.len() + 16);
+        for chunk in entry.content.chunks(SYNC_DATA_MAX) {
+            response.extend_from_slice(b"DATA");
+            response
+                .extend_from_slice(&u32::try_from(chunk.len()).unwrap_or_default().to_le_bytes());
+            response.extend_from_slice(chunk);
+        }
+        response.extend_from_slice(b"DONE");
+        response.extend_from_slice(&unix_time().to_le_bytes());
+        response
+    }
+}
+
+/// Version 2 stat of `entry`, reporting `ENOENT` if it is missing.
+fn stat_v2(entry: Option<&FakeEntry>) -> Vec<u8> {
+    let mut stat = Vec::with_capacity(AdbStatResponse::V2_SIZE);
+    let Some(entry) = entry else {
+        stat.extend_from_slice(&ENOENT.to_le_bytes());
+        stat.resize(AdbStatResponse::V2_SIZE, 0);
+        return stat;
+    };
+
+    let mtime = i64::from(entry.mtime).to_le_bytes();
+    stat.extend_from_slice(&0_u32.to_le_bytes()); // error
+    stat.extend_from_slice(&0_u64.to_le_bytes()); // dev
+    stat.extend_from_slice(&0_u64.to_le_bytes()); // inode
+    stat.extend_from_slice(&entry.mode.to_le_bytes());
+    stat.extend_from_slice(&1_u32.to_le_bytes()); // nlink
+    stat.extend_from_slice(&0_u32.to_le_bytes()); // uid
+    stat.extend_from_slice(&0_u32.to_le_bytes()); // gid
+    stat.extend_from_slice(&(entry.content.len() as u64).to_le_bytes());
+    stat.extend_from_slice(&mtime); // access time
+    stat.extend_from_slice(&mtime);
+    stat.extend_from_slice(&mtime); // change time
+    stat
+}
+
+/// `FAIL` response carrying `message`.
+fn fail(message: &str) -> Vec<u8> {
+    let mut response = b"FAIL".to_vec();
+    response.extend_from_slice(
+        &u32::try_from(message.len())
+            .unwrap_or_default()
+            .to_le_bytes(),
+    );
+    response.extend_from_slice(message.as_bytes());
+    response
+}
@@ -0,0 +1,581 @@
+#![doc = include_str!("./README.md")]
+
+mod fake_connection;
+mod fake_device;
+mod fake_filesystem;
+mod fake_listener;
+mod fake_server;
+mod fake_services;
+
+pub use fake_device::{FakeCommand, FakeDevice, FakeDeviceHandle, FakeFault};
+pub use fake_server::{FakeServer, FakeServerHandle};
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{Ipv4Addr, TcpStream},
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{FakeCommand, FakeDevice, FakeDeviceHandle, FakeFault, FakeServer};
+    use crate::{
+        ADBDeviceExt, ADBListItemType, ForwardRule, PullOptions, RustADBError, SyncAction,
+        SyncDirOptions, TimeoutPhase, Timeouts, TransferEntryKind,
+        message_devices::models::{ADBRsaKey, AuthOptions},
+        server::{ADBServer, DeviceState},
+        tcp::{ADBTcpDevice, KnownDevices, TlsOptions},
+    };
+
+    /// Path of a private key file holding test key, unique to `test`.
+    fn private_key_path(test: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("adb_client_fake_{test}_{}.pem", std::process::id()));
+        std::fs::write(&path, ADBRsaKey::TEST_PRIVATE_KEY).expect("cannot write private key");
+        path
+    }
+
+    fn connect(fake: &FakeDeviceHandle, test: &str) -> crate::Result<ADBTcpDevice> {
+        let private_key_path = private_key_path(test);
+        let device = ADBTcpDevice::new_with_options(
+            fake.address(),
+            Some(private_key_path.clone()),
+            &AuthOptions::default().authorization_timeout(Some(Duration::from_millis(200))),
+            TlsOptions::default(),
+        );
+        // Keys are loaded once device is created
+        let _ = std::fs::remove_file(private_key_path);
+        device
+    }
+
+    fn shell(device: &mut ADBTcpDevice, command: &str) -> (Vec<u8>, Vec<u8>, Option<u8>) {
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let exit_code = device
+            .shell_command(&command, Some(&mut stdout), Some(&mut stderr))
+            .expect("cannot run command");
+        (stdout, stderr, exit_code)
+    }
+
+    #[test]
+    fn test_fake_shell() {
+        let fake = FakeDevice::default()
+            .command("id -u", FakeCommand::new("2000\n"))
+            .command(
+                "false",
+                FakeCommand::default().stderr("failed\n").exit_code(1),
+            )
+            .start()
+            .unwrap();
+        let mut device = connect(&fake, "shell").unwrap();
+
+        assert_eq!(
+            shell(&mut device, "id -u"),
+            (b"2000\n".to_vec(), Vec::new(), Some(0))
+        );
+        assert_eq!(
+            shell(&mut device, "false"),
+            (Vec::new(), b"failed\n".to_vec(), Some(1))
+        );
+        assert_eq!(shell(&mut device, "unknown").2, Some(127));
+
+        // Without shell v2, only output is reported
+        let fake = FakeDevice::default()
+            .features(["cmd"])
+            .command("id -u", FakeCommand::new("2000\n"))
+            .start()
+            .unwrap();
+        let mut device = connect(&fake, "shell_v1").unwrap();
+        assert_eq!(
+            shell(&mut device, "id -u"),
+            (b"2000\n".to_vec(), Vec::new(), None)
+        );
+        assert_eq!(fake.opened_services(), ["shell:id -u"]);
+    }
+
+    /// Output shared with the thread writing it.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fake_interactive_shell() {
+        let fake = FakeDevice::default()
+            .command("id -u", FakeCommand::new("2000\n"))
+            .command(
+                "false",
+                FakeCommand::default().stderr("failed\n").exit_code(1),
+            )
+            .start()
+            .unwrap();
+        let mut device = connect(&fake, "interactive_shell").unwrap();
+
+        // Whole output is written once shell returns, input being over
+        let output = SharedOutput::default();
+        let exit_status = device
+            .shell(&mut b"id -u\nfalse\n".as_slice(), Box::new(output.clone()))
+            .unwrap();
+        assert_eq!(exit_status, Some(1));
+        assert_eq!(*output.0.lock().unwrap(), b"2000\nfailed\n");
+
+        let exit_status = device
+            .shell(&mut b"id -u\nexit\n".as_slice(), Box::new(output.clone()))
+            .unwrap();
+        assert_eq!(exit_status, Some(0));
+    }
+
+    #[test]
+    fn test_fake_file_transfers() {
+        let content: Vec<u8> = (0..=u8::MAX).cycle().take(200 * 1024).collect();
+
+        for (test, features) in [
+            ("sync_v2", FakeDevice::default().features),
+            ("sync_v1", Vec::new()),
+        ] {
+            let fake = FakeDevice::default()
+                .features(features)
+                .directory("/sdcard/Download")
+                .start()
+                .unwrap();
+            let mut device = connect(&fake, test).unwrap();
+
+            device
+                .push(&mut content.as_slice(), &"/sdcard/file.bin")
+                .unwrap();
+            assert_eq!(fake.file("/sdcard/file.bin").as_ref(), Some(&content));
+
+            let stat = device.stat(&"/sdcard/file.bin").unwrap();
+            assert_eq!(stat.file_size, content.len() as u64);
+            assert_eq!(stat.file_perm & 0o170_000, 0o100_000);
+
+            let mut names: Vec<_> = device
+                .list(&"/sdcard")
+                .unwrap()
+                .into_iter()
+                .map(|item| match item {
+                    ADBListItemType::File(item) => format!("file {}", item.name),
+                    ADBListItemType::Directory(item) => format!("dir {}", item.name),
+                    other => panic!("unexpected entry {other:?}"),
+                })
+                .collect();
+            names.sort();
+            assert_eq!(names, ["dir Download", "file file.bin"]);
+
+            let mut pulled = Vec::new();
+            device.pull(&"/sdcard/file.bin", &mut pulled).unwrap();
+            assert_eq!(pulled, content);
+            assert!(device.pull(&"/sdcard/missing", &mut Vec::new()).is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fake_directory_transfers() {
+        let local = std::env::temp_dir().join(format!(
+            "adb_client_fake_directory_transfers_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&local);
+        let (source, destination) = (local.join("source"), local.join("destination"));
+        std::fs::create_dir_all(source.join("sub/nested")).unwrap();
+        std::fs::create_dir_all(source.join("empty")).unwrap();
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+        std::fs::write(source.join("sub/nested/b.txt"), "b").unwrap();
+        std::fs::write(source.join("conflict.txt"), "conflict").unwrap();
+        std::os::unix::fs::symlink("a.txt", source.join("link")).unwrap();
+
+        // A directory stands where a file is pushed
+        let fake = FakeDevice::default()
+            .directory("/sdcard/tree/conflict.txt")
+            .start()
+            .unwrap();
+        let mut device = connect(&fake, "directory_transfers").unwrap();
+
+        let mut report: Vec<_> = device
+            .push_dir(&source, &"/sdcard/tree")
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.remote_path, entry.kind, entry.result.is_ok()))
+            .collect();
+        report.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            report,
+            [
+                (
+                    "/sdcard/tree".to_string(),
+                    TransferEntryKind::Directory,
+                    true
+                ),
+                (
+                    "/sdcard/tree/a.txt".to_string(),
+                    TransferEntryKind::File,
+                    true
+                ),
+                (
+                    "/sdcard/tree/conflict.txt".to_string(),
+                    TransferEntryKind::File,
+                    false
+                ),
+                (
+                    "/sdcard/tree/empty".to_string(),
+                    TransferEntryKind::Directory,
+                    true
+                ),
+                (
+                    "/sdcard/tree/link".to_string(),
+                    TransferEntryKind::Symlink,
+                    true
+                ),
+                (
+                    "/sdcard/tree/sub".to_string(),
+                    TransferEntryKind::Directory,
+                    true
+                ),
+                (
+                    "/sdcard/tree/sub/nested".to_string(),
+                    TransferEntryKind::Directory,
+                    true
+                ),
+                (
+                    "/sdcard/tree/sub/nested/b.txt".to_string(),
+                    TransferEntryKind::File,
+                    true
+                ),
+            ]
+        );
+        assert_eq!(fake.file("/sdcard/tree/a.txt"), Some(b"a".to_vec()));
+        assert_eq!(
+            fake.file("/sdcard/tree/sub/nested/b.txt"),
+            Some(b"b".to_vec())
+        );
+        assert_eq!(fake.file("/sdcard/tree/link"), Some(b"a.txt".to_vec()));
+        let empty = device.stat(&"/sdcard/tree/empty").unwrap();
+        assert_eq!(empty.file_perm & 0o170_000, 0o040_000);
+
+        // Pulling again replaces what a previous pull left
+        for _ in 0..2 {
+            let report = device
+                .pull_dir(&"/sdcard/tree", &destination, PullOptions::default())
+                .unwrap();
+            assert_eq!(report.len(), 8);
+            assert!(report.iter().all(|entry| entry.result.is_ok()));
+            assert_eq!(
+                std::fs::read_link(destination.join("link")).unwrap(),
+                PathBuf::from("a.txt")
+            );
+            assert_eq!(std::fs::read(destination.join("a.txt")).unwrap(), b"a");
+            assert_eq!(
+                std::fs::read(destination.join("sub/nested/b.txt")).unwrap(),
+                b"b"
+            );
+            assert!(destination.join("empty").is_dir());
+            assert!(destination.join("conflict.txt").is_dir());
+        }
+
+        let _ = std::fs::remove_dir_all(&local);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fake_sync_dir() {
+        let local =
+            std::env::temp_dir().join(format!("adb_client_fake_sync_dir_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&local);
+        std::fs::create_dir_all(local.join("sub")).unwrap();
+        std::fs::write(local.join("a.txt"), "a").unwrap();
+        std::fs::write(local.join("sub/b.txt"), "b").unwrap();
+        std::os::unix::fs::symlink("a.txt", local.join("link")).unwrap();
+        // Symlinks created on device then get another modification time than local ones
+        std::thread::sleep(Duration::from_millis(1100));
+
+        let fake = FakeDevice::default()
+            .file("/sdcard/sync/extra.txt", "extra")
+            .start()
+            .unwrap();
+        let mut device = connect(&fake, "sync_dir").unwrap();
+        let mut sync = |delete, dry_run| {
+            let mut report: Vec<_> = device
+                .sync_dir(&local, &"/sdcard/sync", SyncDirOptions { delete, dry_run })
+                .unwrap()
+                .into_iter()
+                .map(|entry| {
+                    assert!(entry.result.is_ok(), "{entry:?}");
+                    (entry.action, entry.remote_path)
+                })
+                .collect();
+            report.sort_by(|a, b| a.1.cmp(&b.1));
+            report
+        };
+
+        let planned = [
+            (SyncAction::Push, "/sdcard/sync/a.txt".to_string()),
+            (SyncAction::Delete, "/sdcard/sync/extra.txt".to_string()),
+            (SyncAction::Push, "/sdcard/sync/link".to_string()),
+            (SyncAction::CreateDirectory, "/sdcard/sync/sub".to_string()),
+            (SyncAction::Push, "/sdcard/sync/sub/b.txt".to_string()),
+        ];
+        assert_eq!(sync(true, true), planned);
+        assert_eq!(fake.file("/sdcard/sync/a.txt"), None);
+        assert_eq!(fake.file("/sdcard/sync/extra.txt"), Some(b"extra".to_vec()));
+
+        let mut pushed = planned.to_vec();
+        pushed.remove(1);
+        assert_eq!(sync(false, false), pushed);
+        assert_eq!(fake.file("/sdcard/sync/a.txt"), Some(b"a".to_vec()));
+        assert_eq!(fake.file("/sdcard/sync/sub/b.txt"), Some(b"b".to_vec()));
+        assert_eq!(fake.file("/sdcard/sync/link"), Some(b"a.txt".to_vec()));
+
+        // Unchanged entries, symlink included, are skipped
+        assert_eq!(sync(false, false), []);
+
+        assert_eq!(
+            sync(true, false),
+            [(SyncAction::Delete, "/sdcard/sync/extra.txt".to_string())]
+        );
+        assert_eq!(fake.file("/sdcard/sync/extra.txt"), None);
+        assert_eq!(sync(true, false), []);
+
+        let _ = std::fs::remove_dir_all(&local);
+    }
+
+    #[test]
+    fn test_fake_forward() {
+        let fake = FakeDevice::default().echo(5000).start().unwrap();
+        let mut device = connect(&fake, "forward").unwrap();
+
+        let local = device.forward("tcp:5000".to_string(), "tcp:0").unwrap();
+        let port: u16 = local.strip_prefix("tcp:").unwrap().parse().unwrap();
+        assert_ne!(port, 0);
+        assert_eq!(
+            device.forward_list().unwrap(),
+            [ForwardRule {
+                local: local.clone(),
+                remote: "tcp:5000".to_string(),
+            }]
+        );
+
+        // Each accepted connection gets its own session
+        for message in [b"hello".as_slice(), b"world"] {
+            let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream.write_all(message).unwrap();
+            let mut echoed = vec![0_u8; message.len()];
+            stream.read_exact(&mut echoed).unwrap();
+            assert_eq!(echoed, message);
+        }
+
+        device.forward_remove(&local).unwrap();
+        assert_eq!(device.forward_list().unwrap(), []);
+        assert!(device.forward_remove(&local).is_err());
+    }
+
+    #[test]
+    fn test_fake_authentication() {
+        let key = ADBRsaKey::new_from_pem(ADBRsaKey::TEST_PRIVATE_KEY).unwrap();
+
+        let fake = FakeDevice::default()
+            .require_auth()
+            .trusted_key(&key)
+            .start()
+            .unwrap();
+        connect(&fake, "auth_trusted").expect("trusted key rejected");
+
+        let fake = FakeDevice::default().require_auth().start().unwrap();
+        match connect(&fake, "auth_unknown") {
+            Err(RustADBError::Unauthorized(fingerprint)) => {
+                assert_eq!(fingerprint, key.fingerprint().unwrap());
+            }
+            other => panic!("unexpected connection result: {:?}", other.map(|_| ())),
+        }
+
+        let fake = FakeDevice::default()
+            .require_auth()
+            .accept_unknown_keys(true)
+            .start()
+            .unwrap();
+        connect(&fake, "auth_accepted").expect("key not accepted");
+    }
+
+    #[test]
+    fn test_fake_tls() {
+        let fake = FakeDevice::default()
+            .tls(true)
+            .command("echo ok", FakeCommand::new("ok\n"))
+            .start()
+            .unwrap();
+        let private_key_path = private_key_path("tls");
+        let known_devices = KnownDevices::new(private_key_path.with_extension("known_devices"));
+
+        let mut device = ADBTcpDevice::new_with_options(
+            fake.address(),
+            Some(private_key_path.clone()),
+            &AuthOptions::default(),
+            TlsOptions::default()
+                .known_devices(known_devices.clone())
+                .device_id("adb-fake-tls"),
+        )
+        .unwrap();
+        assert_eq!(shell(&mut device, "echo ok").0, b"ok\n");
+        assert!(known_devices.fingerprint("adb-fake-tls").unwrap().is_some());
+
+        let _ = std::fs::remove_file(known_devices.path());
+        let _ = std::fs::remove_file(private_key_path);
+    }
+
+    #[cfg(feature = "framebuffer")]
+    #[test]
+    fn test_fake_framebuffer() {
+        let rgba: Vec<u8> = (0..4 * 3 * 2).collect();
+        let fake = FakeDevice::default()
+            .framebuffer(3, 2, rgba.clone())
+            .start()
+            .unwrap();
+        let mut device = connect(&fake, "framebuffer").unwrap();
+
+        let image = device.framebuffer_inner().unwrap();
+        assert_eq!(image.dimensions(), (3, 2));
+        assert_eq!(image.into_raw(), rgba);
+    }
+
+    #[test]
+    fn test_fake_faults() {
+        let fake = FakeDevice::default()
+            .file("/sdcard/file.txt", "content")
+            .fault("sync:", FakeFault::SyncFail("Read-only file system".into()))
+            .start()
+            .unwrap();
+        let mut device = connect(&fake, "faults").unwrap();
+
+        match device.push(&mut b"data".as_slice(), &"/sdcard/other.txt") {
+            Err(RustADBError::ADBRequestFailed(error)) => {
+                assert_eq!(error, "Read-only file system");
+            }
+            other => panic!("unexpected push result: {other:?}"),
+        }
+        assert_eq!(fake.file("/sdcard/other.txt"), None);
+
+        fake.inject_fault("shell", FakeFault::Refuse);
+        assert!(device.shell_command(&"id", None, None).is_err());
+
+        // Device drops connection, stat being retried once reconnected
+        fake.inject_fault("sync:", FakeFault::Disconnect);
+        assert_eq!(device.stat(&"/sdcard/file.txt").unwrap().file_size, 7);
+
+        fake.inject_fault("shell", FakeFault::Hang);
+        device.set_timeouts(Timeouts::none().operation(Some(Duration::from_millis(200))));
+        match device.shell_command(&"id", None, None) {
+            Err(RustADBError::Timeout(phase)) => assert_eq!(phase, TimeoutPhase::Operation),
+            other => panic!("unexpected shell result: {other:?}"),
+        }
+
+        assert_eq!(
+            fake.opened_services()
+                .iter()
+                .filter(|service| *service == "sync:")
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_fake_clones_share_reconnection() {
+        let fake = FakeDevice::default()
+            .command("echo ok", FakeCommand::new("ok\n"))
+            .start()
+            .unwrap();
+        let mut device = connect(&fake, "clones").unwrap();
+        let mut clone = device.clone();
+
+        fake.disconnect();
+        assert_eq!(shell(&mut device, "echo ok").0, b"ok\n");
+        assert_eq!(shell(&mut clone, "echo ok").0, b"ok\n");
+        assert_eq!(fake.accepted_connections(), 2);
+    }
+
+    #[test]
+    fn test_fake_server() {
+        let fake = FakeServer::default()
+            .device(
+                "emulator-5554",
+                FakeDevice::default()
+                    .model("Pixel_7")
+                    .command("id -u", FakeCommand::new("2000\n"))
+                    .command("false", FakeCommand::default().exit_code(1))
+                    .directory("/sdcard"),
+            )
+            .device_with_state("R5CT0000", DeviceState::Unauthorized, FakeDevice::default())
+            .start()
+            .unwrap();
+        let mut server = ADBServer::new(fake.address());
+        server.set_auto_start(false);
+
+        assert_eq!(server.version().unwrap().to_string(), "1.0.41");
+        let devices: Vec<_> = server
+            .devices()
+            .unwrap()
+            .into_iter()
+            .map(|device| (device.identifier, device.state))
+            .collect();
+        assert_eq!(
+            devices,
+            [
+                ("emulator-5554".to_string(), DeviceState::Device),
+                ("R5CT0000".to_string(), DeviceState::Unauthorized)
+            ]
+        );
+        let devices_long = server.devices_long().unwrap();
+        assert_eq!(devices_long[0].model, "Pixel_7");
+        assert_eq!(devices_long[1].transport_id, 2);
+
+        let mut device = server.get_device_by_name("emulator-5554").unwrap();
+        let mut stdout = Vec::new();
+        assert_eq!(
+            device
+                .shell_command(&"id -u", Some(&mut stdout), None)
+                .unwrap(),
+            Some(0)
+        );
+        assert_eq!(stdout, b"2000\n");
+        assert_eq!(device.shell_command(&"false", None, None).unwrap(), Some(1));
+
+        device
+            .push(&mut b"content".as_slice(), "/sdcard/file.txt")
+            .unwrap();
+        assert_eq!(
+            fake.file("emulator-5554", "/sdcard/file.txt").as_deref(),
+            Some(b"content".as_slice())
+        );
+        assert_eq!(device.stat("/sdcard/file.txt").unwrap().file_size, 7);
+        fake.add_file("emulator-5554", "/sdcard/other.txt", "other");
+        let mut pulled = Vec::new();
+        device.pull(&"/sdcard/other.txt", &mut pulled).unwrap();
+        assert_eq!(pulled, b"other");
+
+        fake.inject_fault("emulator-5554", "shell", FakeFault::Refuse);
+        assert!(device.shell_command(&"id -u", None, None).is_err());
+
+        let mut unauthorized = server.get_device_by_name("R5CT0000").unwrap();
+        match unauthorized.shell_command(&"id -u", None, None) {
+            Err(RustADBError::ADBRequestFailed(error)) => assert_eq!(error, "device unauthorized"),
+            other => panic!("unexpected shell result: {other:?}"),
+        }
+        assert!(fake.opened_services("R5CT0000").is_empty());
+        assert!(
+            fake.requests()
+                .contains(&"host:transport:emulator-5554".to_string())
+        );
+    }
+}
@@ -2,19 +2,25 @@ use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
 
 use crate::ADBDeviceExt;
 use crate::ADBListItemType;
 use crate::Result;
 use crate::RustADBError;
-use crate::message_devices::adb_message_device::ADBMessageDevice;
-use crate::models::RemountInfo;
+use crate::message_devices::adb_message_device::{ADBMessageDevice, load_private_keys};
+use crate::message_devices::models::{AuthOptions, ReconnectPolicy};
+use crate::models::{
+    CancellationToken, DeviceBanner, ForwardRule, InstallOptions, ProgressObserver, PushOptions,
+    RemountInfo, ShellWindowSize, SyncCompression, Timeouts,
+};
 use crate::usb::usb_transport::USBTransport;
 use crate::usb::utils;
 use crate::utils::get_default_adb_key_path;
 
 /// Represent a device reached and available over USB.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ADBUSBDevice {
     inner: ADBMessageDevice<USBTransport>,
     vendor_id: u16,
@@ -33,7 +39,32 @@ impl ADBUSBDevice {
         product_id: u16,
         private_key_path: P,
     ) -> Result<Self> {
-        Self::new_from_transport_inner(USBTransport::new(vendor_id, product_id)?, private_key_path)
+        Self::new_from_transport_inner(
+            USBTransport::new(vendor_id, product_id)?,
+            private_key_path,
+            &AuthOptions::default(),
+        )
+    }
+
+    /// Instantiate a new [`ADBUSBDevice`] using given authentication options, and an optional private key path.
+    ///
+    /// `auth_options` tell how long to wait for user to allow USB debugging on device, and who to notify meanwhile.
+    pub fn new_with_options(
+        vendor_id: u16,
+        product_id: u16,
+        private_key_path: Option<PathBuf>,
+        auth_options: &AuthOptions,
+    ) -> Result<Self> {
+        let private_key_path = match private_key_path {
+            Some(private_key_path) => private_key_path,
+            None => get_default_adb_key_path()?,
+        };
+
+        Self::new_from_transport_inner(
+            USBTransport::new(vendor_id, product_id)?,
+            &private_key_path,
+            auth_options,
+        )
     }
 
     /// Instantiate a new [`ADBUSBDevice`] from a [`USBTransport`] and an optional private key path.
@@ -46,18 +77,23 @@ impl ADBUSBDevice {
             None => get_default_adb_key_path()?,
         };
 
-        Self::new_from_transport_inner(transport, &private_key_path)
+        Self::new_from_transport_inner(transport, &private_key_path, &AuthOptions::default())
     }
 
     fn new_from_transport_inner<P: AsRef<Path>>(
         transport: USBTransport,
         private_key_path: P,
+        auth_options: &AuthOptions,
     ) -> Result<Self> {
         let vendor_id = transport.vendor_id()?;
         let product_id = transport.product_id()?;
 
         Ok(Self {
-            inner: ADBMessageDevice::new(transport, private_key_path)?,
+            inner: ADBMessageDevice::new_with_private_keys(
+                transport,
+                load_private_keys(private_key_path, auth_options)?,
+                auth_options,
+            )?,
             vendor_id,
             product_id,
         })
@@ -90,17 +126,143 @@ impl ADBUSBDevice {
     ///
     /// Returns an error if multiple devices are connected or if none can be detected.
     pub fn autodetect_with_custom_private_key(private_key_path: PathBuf) -> Result<Self> {
+        Self::autodetect_with_options(Some(private_key_path), &AuthOptions::default())
+    }
+
+    /// Autodetect connected ADB devices and establish a connection with the first device found using given authentication options,
+    /// and an optional private key path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if multiple devices are connected or if none can be detected.
+    pub fn autodetect_with_options(
+        private_key_path: Option<PathBuf>,
+        auth_options: &AuthOptions,
+    ) -> Result<Self> {
         match utils::get_single_connected_adb_device()? {
-            Some(device_info) => Self::new_with_custom_private_key(
+            Some(device_info) => Self::new_with_options(
                 device_info.vendor_id,
                 device_info.product_id,
                 private_key_path,
+                auth_options,
             ),
             _ => Err(RustADBError::DeviceNotFound(
                 "cannot find USB devices matching the signature of an ADB device".into(),
             )),
         }
     }
+
+    /// Starts an interactive shell session on the device, forwarding each size received from `window_sizes` to the remote terminal.
+    /// Input data is read from reader and write to writer.
+    ///
+    /// Window sizes are only forwarded, and shell exit status returned, if device supports shell v2.
+    pub fn shell_with_window_size(
+        &mut self,
+        reader: &mut dyn Read,
+        writer: Box<dyn Write + Send>,
+        window_sizes: Receiver<ShellWindowSize>,
+    ) -> Result<Option<u8>> {
+        self.inner
+            .shell_with_window_size(reader, writer, Some(window_sizes))
+    }
+
+    /// Banner sent by device when connection has been established, describing it and its features.
+    #[must_use]
+    pub const fn banner(&self) -> &DeviceBanner {
+        self.inner.banner()
+    }
+
+    /// Set compression used by file transfers, when device supports sync version 2.
+    ///
+    /// Defaults to [`SyncCompression::Any`], using the best algorithm supported by both device and this build.
+    pub const fn set_sync_compression(&mut self, compression: SyncCompression) {
+        self.inner.set_sync_compression(compression);
+    }
+
+    /// Set policy applied when connection to device drops, e.g. after [`root`](ADBDeviceExt::root) restarted `adbd`.
+    ///
+    /// Defaults to reconnecting for up to 30 seconds before next operation, see [`ReconnectPolicy`].
+    pub const fn set_reconnect_policy(&mut self, reconnect_policy: ReconnectPolicy) {
+        self.inner.set_reconnect_policy(reconnect_policy);
+    }
+
+    /// Set default timeouts applied to each operation run on this device, and when connecting again to it.
+    ///
+    /// Connection defaults to a 10 seconds timeout, handshake included, operations waiting as long as needed. See [`Timeouts`].
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.inner.set_timeouts(timeouts);
+    }
+
+    /// Run `operation` on this device using `timeouts` instead of its defaults, restored afterwards.
+    pub fn with_timeouts<R>(
+        &mut self,
+        timeouts: Timeouts,
+        operation: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        let defaults = self.inner.timeouts();
+        self.inner.set_timeouts(timeouts);
+        let result = operation(self);
+        self.inner.set_timeouts(defaults);
+        result
+    }
+
+    /// Wait for device to be reachable for at most `timeout`, connecting to it again if connection dropped, e.g. after a reboot.
+    ///
+    /// Returns immediately if connection is still up.
+    pub fn wait_for_device(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.wait_for_device(timeout)
+    }
+
+    /// Forward connections accepted on `local` to `remote` service on device, e.g. `tcp:8080` or `localabstract:name`.
+    ///
+    /// Only `tcp:<port>` local endpoints are supported, listening on localhost. Port 0 picks a free port.
+    /// Returns the local endpoint actually used. Forwarding stops once this device is dropped.
+    pub fn forward(&mut self, remote: String, local: &str) -> Result<String> {
+        self.inner.forward(remote, local)
+    }
+
+    /// Remove a previously applied forward rule by its local endpoint.
+    pub fn forward_remove(&mut self, local: &str) -> Result<()> {
+        self.inner.forward_remove(local)
+    }
+
+    /// Remove all previously applied forward rules
+    pub fn forward_remove_all(&mut self) -> Result<()> {
+        self.inner.forward_remove_all()
+    }
+
+    /// List active forward rules
+    pub fn forward_list(&self) -> Result<Vec<ForwardRule>> {
+        self.inner.forward_list()
+    }
+
+    /// Ask device to forward connections accepted on `remote` to `local` endpoint on this host, e.g. `tcp:8080`.
+    ///
+    /// Only `tcp:<port>` local endpoints are supported. Remote port 0 lets device pick a free port.
+    /// Returns the remote endpoint actually used.
+    pub fn reverse(&mut self, remote: String, local: &str) -> Result<String> {
+        self.inner.reverse(remote, local)
+    }
+
+    /// Remove a previously applied reverse rule by its remote endpoint.
+    pub fn reverse_remove(&mut self, remote: &str) -> Result<()> {
+        self.inner.reverse_remove(remote)
+    }
+
+    /// Remove all previously applied reverse rules
+    pub fn reverse_remove_all(&mut self) -> Result<()> {
+        self.inner.reverse_remove_all()
+    }
+
+    /// List reverse rules applied from this host
+    pub fn reverse_list(&self) -> Result<Vec<ForwardRule>> {
+        self.inner.reverse_list()
+    }
+
+    /// Underlying message device, e.g. to be served by an [`ADBHostServer`](crate::host_server::ADBHostServer).
+    pub(crate) fn into_inner(self) -> ADBMessageDevice<USBTransport> {
+        self.inner
+    }
 }
 
 impl ADBDeviceExt for ADBUSBDevice {
@@ -115,8 +277,12 @@ impl ADBDeviceExt for ADBUSBDevice {
     }
 
     #[inline]
-    fn shell<'a>(&mut self, reader: &mut dyn Read, writer: Box<dyn Write + Send>) -> Result<()> {
-        self.inner.shell(reader, writer)
+    fn shell<'a>(
+        &mut self,
+        reader: &mut dyn Read,
+        writer: Box<dyn Write + Send>,
+    ) -> Result<Option<u8>> {
+        self.inner.shell_with_window_size(reader, writer, None)
     }
 
     #[inline]
@@ -130,8 +296,13 @@ impl ADBDeviceExt for ADBUSBDevice {
     }
 
     #[inline]
-    fn push(&mut self, stream: &mut dyn Read, path: &dyn AsRef<str>) -> Result<()> {
-        self.inner.push(stream, path)
+    fn push_with_options(
+        &mut self,
+        stream: &mut dyn Read,
+        path: &dyn AsRef<str>,
+        options: PushOptions,
+    ) -> Result<()> {
+        self.inner.push_with_options(stream, path, options)
     }
 
     #[inline]
@@ -150,8 +321,16 @@ impl ADBDeviceExt for ADBUSBDevice {
     }
 
     #[inline]
-    fn install(&mut self, apk_path: &dyn AsRef<Path>, user: Option<&str>) -> Result<()> {
-        self.inner.install(apk_path, user)
+    fn install_from_reader_with_progress(
+        &mut self,
+        apk: &mut dyn Read,
+        size: u64,
+        options: &InstallOptions,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        self.inner
+            .install_from_reader_with_progress(apk, size, options, observer, cancellation)
     }
 
     #[inline]
@@ -189,4 +368,9 @@ impl ADBDeviceExt for ADBUSBDevice {
     ) -> Result<()> {
         self.inner.exec(command, reader, writer)
     }
+
+    #[inline]
+    fn exec_with_input(&mut self, command: &str, input: &mut dyn Read) -> Result<Vec<u8>> {
+        self.inner.exec_with_input(command, input)
+    }
 }
@@ -1,4 +1,7 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use rusb::{
     Context, Device, DeviceHandle, Direction, TransferType, UsbContext,
@@ -10,9 +13,10 @@ use crate::{
     adb_transport::ADBTransport,
     message_devices::{
         adb_message_transport::ADBMessageTransport,
-        adb_transport_message::{ADBTransportMessage, ADBTransportMessageHeader},
+        adb_transport_message::{ADBTransportMessage, ADBTransportMessageHeader, ChecksumPolicy},
         message_commands::MessageCommand,
     },
+    models::{TimeoutPhase, timeout_error},
 };
 
 #[derive(Clone, Debug)]
@@ -29,6 +33,12 @@ pub struct USBTransport {
     handle: Option<Arc<DeviceHandle<Context>>>,
     read_endpoint: Option<Endpoint>,
     write_endpoint: Option<Endpoint>,
+    /// Serializes writers, so that messages from concurrent sessions cannot interleave
+    write_lock: Option<Arc<Mutex<()>>>,
+    /// Payload checksums usage, negotiated for current connection.
+    checksum: Option<ChecksumPolicy>,
+    /// Serial number of device, read on first connection to find it again once enumerated again.
+    serial_number: Option<String>,
 }
 
 impl USBTransport {
@@ -60,6 +70,9 @@ impl USBTransport {
             handle: None,
             read_endpoint: None,
             write_endpoint: None,
+            write_lock: None,
+            checksum: None,
+            serial_number: None,
         }
     }
 
@@ -100,6 +113,31 @@ impl USBTransport {
             )))
     }
 
+    /// Find device currently enumerated with the same vendor id, product id and serial number as ours.
+    fn find_same_device(&self) -> Result<Option<Device<Context>>> {
+        let descriptor = self.device.device_descriptor()?;
+        for device in self.device.context().devices()?.iter() {
+            let Ok(candidate) = device.device_descriptor() else {
+                continue;
+            };
+            if candidate.vendor_id() != descriptor.vendor_id()
+                || candidate.product_id() != descriptor.product_id()
+            {
+                continue;
+            }
+
+            let serial_number = device
+                .open()
+                .and_then(|handle| handle.read_serial_number_string_ascii(&candidate))
+                .ok();
+            if self.serial_number.is_none() || serial_number == self.serial_number {
+                return Ok(Some(device));
+            }
+        }
+
+        Ok(None)
+    }
+
     fn configure_endpoint(handle: &DeviceHandle<Context>, endpoint: &Endpoint) -> Result<()> {
         match handle.claim_interface(endpoint.iface) {
             Ok(()) => Ok(()),
@@ -159,11 +197,11 @@ impl USBTransport {
         let handle = self.get_raw_connection()?;
         let max_packet_size = endpoint.max_packet_size;
 
+        // Whole buffer is submitted at once, libusb splits it into packets
         let mut offset = 0;
         let data_len = data.len();
         while offset < data_len {
-            let end = (offset + max_packet_size).min(data_len);
-            let write_amount = handle.write_bulk(endpoint.address, &data[offset..end], timeout)?;
+            let write_amount = handle.write_bulk(endpoint.address, &data[offset..], timeout)?;
             offset += write_amount;
 
             log::trace!("wrote chunk of size {write_amount} - {offset}/{data_len}");
@@ -176,11 +214,47 @@ impl USBTransport {
 
         Ok(())
     }
+
+    /// Fill `buf` from read endpoint, waiting at most `timeout` for each transfer, `None` waiting forever.
+    ///
+    /// First transfer only waits `poll_timeout` if set, a timeout being reported as is since nothing has been read yet.
+    /// Data of a timed out transfer is lost, hence any later timeout is an idle read timeout, leaving the message partially read.
+    fn read_bulk_data(
+        &self,
+        buf: &mut [u8],
+        mut poll_timeout: Option<Duration>,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let endpoint = self.get_read_endpoint()?;
+        let handle = self.get_raw_connection()?;
+
+        let mut offset = 0;
+        while offset < buf.len() {
+            let polling = poll_timeout.is_some();
+            // `libusb` waits forever when given a zero timeout
+            let transfer_timeout = poll_timeout.take().or(timeout).unwrap_or(Duration::ZERO);
+            match handle.read_bulk(endpoint.address, &mut buf[offset..], transfer_timeout) {
+                Ok(read) => offset += read,
+                Err(rusb::Error::Timeout) if polling => return Err(rusb::Error::Timeout.into()),
+                Err(rusb::Error::Timeout) => return Err(timeout_error(TimeoutPhase::Read).into()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl ADBTransport for USBTransport {
     fn connect(&mut self) -> crate::Result<()> {
         let device = self.device.open()?;
+        if self.serial_number.is_none() {
+            self.serial_number = self
+                .device
+                .device_descriptor()
+                .and_then(|descriptor| device.read_serial_number_string_ascii(&descriptor))
+                .ok();
+        }
 
         let (read_endpoint, write_endpoint) = Self::find_endpoints(&device)?;
 
@@ -193,6 +267,8 @@ impl ADBTransport for USBTransport {
         self.write_endpoint = Some(write_endpoint);
 
         self.handle = Some(Arc::new(device));
+        self.write_lock = Some(Arc::new(Mutex::new(())));
+        self.checksum = Some(ChecksumPolicy::default());
 
         Ok(())
     }
@@ -223,11 +299,38 @@ impl ADBTransport for USBTransport {
 }
 
 impl ADBMessageTransport for USBTransport {
+    fn reconnect(&mut self) -> Result<()> {
+        // Device is enumerated again once adbd restarted, e.g. as root
+        if let Some(device) = self.find_same_device()? {
+            self.device = device;
+        }
+
+        self.connect()
+    }
+
+    fn set_protocol_version(&mut self, version: u32) {
+        if let Some(checksum) = &self.checksum {
+            checksum.set_protocol_version(version);
+        }
+    }
+
     fn write_message_with_timeout(
         &mut self,
-        message: ADBTransportMessage,
+        mut message: ADBTransportMessage,
         timeout: Duration,
     ) -> Result<()> {
+        let write_lock =
+            self.write_lock
+                .clone()
+                .ok_or(RustADBError::IOError(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "not connected",
+                )))?;
+        let _write_guard = write_lock.lock()?;
+
+        if let Some(checksum) = &self.checksum {
+            checksum.prepare_outgoing(&mut message);
+        }
         let message_bytes = message.header().as_bytes();
         self.write_bulk_data(&message_bytes, timeout)?;
 
@@ -243,38 +346,27 @@ impl ADBMessageTransport for USBTransport {
     }
 
     fn read_message_with_timeout(&mut self, timeout: Duration) -> Result<ADBTransportMessage> {
-        let endpoint = self.get_read_endpoint()?;
-        let handle = self.get_raw_connection()?;
-        let max_packet_size = endpoint.max_packet_size;
+        self.poll_message(timeout, Some(timeout))
+    }
 
+    fn poll_message(
+        &mut self,
+        poll_timeout: Duration,
+        read_timeout: Option<Duration>,
+    ) -> Result<ADBTransportMessage> {
         let mut data = [0u8; 24];
-        let mut offset = 0;
-        while offset < data.len() {
-            let end = (offset + max_packet_size).min(data.len());
-            let chunk = &mut data[offset..end];
-            offset += handle.read_bulk(endpoint.address, chunk, timeout)?;
-        }
+        self.read_bulk_data(&mut data, Some(poll_timeout), read_timeout)?;
 
         let header = ADBTransportMessageHeader::try_from(data)?;
         log::trace!("received header {header:?}");
 
         if header.data_length() != 0 {
             let mut msg_data = vec![0_u8; header.data_length() as usize];
-            let mut offset = 0;
-            while offset < msg_data.len() {
-                let end = (offset + max_packet_size).min(msg_data.len());
-                let chunk = &mut msg_data[offset..end];
-                offset += handle.read_bulk(endpoint.address, chunk, timeout)?;
-            }
+            self.read_bulk_data(&mut msg_data, None, read_timeout)?;
 
             let message = ADBTransportMessage::from_header_and_payload(header, msg_data);
-
-            // Check message integrity
-            if !message.check_message_integrity() {
-                return Err(RustADBError::InvalidIntegrity(
-                    ADBTransportMessageHeader::compute_crc32(message.payload()),
-                    message.header().data_crc32(),
-                ));
+            if let Some(checksum) = &self.checksum {
+                checksum.check_incoming(&message)?;
             }
 
             return Ok(message);
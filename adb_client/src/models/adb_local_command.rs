@@ -1,11 +1,13 @@
 use std::fmt::Display;
 
 use crate::RebootType;
+use crate::models::InstallOptions;
 
 /// ADB commands that relates to an actual device.
 pub enum ADBLocalCommand {
     ShellCommand(String, Vec<String>),
     Shell,
+    ShellV2,
     Exec(String),
     Sync,
     Reboot(RebootType),
@@ -20,10 +22,12 @@ pub enum ADBLocalCommand {
     DisableVerity,
     EnableVerity,
     Uninstall(String, Option<String>),
-    Install(u64, Option<String>),
+    Install(u64, InstallOptions),
     TcpIp(u16),
     Usb,
     Root,
+    /// Raw service name, such as `tcp:8080` or `localabstract:name`
+    Service(String),
 
     #[cfg(feature = "framebuffer")]
     FrameBuffer,
@@ -47,6 +51,10 @@ impl Display for ADBLocalCommand {
                 Ok(term) => write!(f, "shell,TERM={term},raw:"),
                 Err(_) => write!(f, "shell,raw:"),
             },
+            Self::ShellV2 => match std::env::var("TERM") {
+                Ok(term) => write!(f, "shell,v2,TERM={term},pty:"),
+                Err(_) => write!(f, "shell,v2,pty:"),
+            },
             Self::Exec(command) => write!(f, "exec:{command}"),
             Self::Reboot(reboot_type) => {
                 write!(f, "reboot:{reboot_type}")
@@ -58,12 +66,12 @@ impl Display for ADBLocalCommand {
                 }
                 write!(f, " {package}")
             }
-            Self::Install(size, user) => {
-                write!(f, "exec:cmd package 'install'")?;
-                if let Some(user) = user {
-                    write!(f, " --user {user}")?;
-                }
-                write!(f, " -S {size}")
+            Self::Install(size, options) => {
+                write!(
+                    f,
+                    "exec:cmd package 'install'{} -S {size}",
+                    options.to_args()
+                )
             }
             Self::Forward(remote, local) => {
                 write!(f, "host:forward:{local};{remote}")
@@ -84,6 +92,7 @@ impl Display for ADBLocalCommand {
             }
             Self::Usb => write!(f, "usb:"),
             Self::Root => write!(f, "root:"),
+            Self::Service(service) => write!(f, "{service}"),
 
             #[cfg(feature = "framebuffer")]
             Self::FrameBuffer => write!(f, "framebuffer:"),
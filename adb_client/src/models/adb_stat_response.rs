@@ -1,61 +1,158 @@
 use byteorder::ByteOrder;
-use chrono::{DateTime, Utc};
-use std::{
-    fmt::Display,
-    time::{Duration, UNIX_EPOCH},
-};
+use chrono::DateTime;
+use std::fmt::Display;
 
 use crate::{BinaryDecodable, RustADBError};
 use byteorder::LittleEndian;
 
 /// Represents a `stat` response
+///
+/// Extended metadata is only available when device supports sync `stat` version 2.
 #[derive(Debug)]
 pub struct AdbStatResponse {
     /// File permissions
     pub file_perm: u32,
     /// File size, in bytes
-    pub file_size: u32,
+    pub file_size: u64,
     /// File modification time
-    pub mod_time: u32,
+    pub mod_time: i64,
+    /// ID of device containing file
+    pub dev: Option<u64>,
+    /// Inode number
+    pub inode: Option<u64>,
+    /// Number of hard links
+    pub nlink: Option<u32>,
+    /// User ID of owner
+    pub uid: Option<u32>,
+    /// Group ID of owner
+    pub gid: Option<u32>,
+    /// File last access time
+    pub access_time: Option<i64>,
+    /// File last status change time
+    pub change_time: Option<i64>,
 }
 
-impl BinaryDecodable for AdbStatResponse {
-    fn decode(bytes: &[u8]) -> crate::Result<Self> {
-        if bytes.len() != std::mem::size_of::<Self>() {
+impl AdbStatResponse {
+    /// Size of a version 1 response, without its leading identifier.
+    pub(crate) const V1_SIZE: usize = 12;
+    /// Size of a version 2 response, without its leading identifier.
+    pub(crate) const V2_SIZE: usize = 68;
+
+    /// Decode a version 2 (`STA2` / `LST2`) response, without its leading identifier.
+    ///
+    /// As with version 1, a file that cannot be stat'ed is reported with all fields set to 0.
+    pub(crate) fn decode_v2(bytes: &[u8]) -> crate::Result<Self> {
+        if bytes.len() != Self::V2_SIZE {
             return Err(RustADBError::ConversionError);
         }
 
+        let error = LittleEndian::read_u32(&bytes[0..4]);
+        if error != 0 {
+            log::debug!("device could not stat file: errno {error}");
+            return Ok(Self::from([0; Self::V1_SIZE]));
+        }
+
         Ok(Self {
-            file_perm: LittleEndian::read_u32(&bytes[0..4]),
-            file_size: LittleEndian::read_u32(&bytes[4..8]),
-            mod_time: LittleEndian::read_u32(&bytes[8..]),
+            dev: Some(LittleEndian::read_u64(&bytes[4..12])),
+            inode: Some(LittleEndian::read_u64(&bytes[12..20])),
+            file_perm: LittleEndian::read_u32(&bytes[20..24]),
+            nlink: Some(LittleEndian::read_u32(&bytes[24..28])),
+            uid: Some(LittleEndian::read_u32(&bytes[28..32])),
+            gid: Some(LittleEndian::read_u32(&bytes[32..36])),
+            file_size: LittleEndian::read_u64(&bytes[36..44]),
+            access_time: Some(LittleEndian::read_i64(&bytes[44..52])),
+            mod_time: LittleEndian::read_i64(&bytes[52..60]),
+            change_time: Some(LittleEndian::read_i64(&bytes[60..68])),
         })
     }
 }
 
+impl BinaryDecodable for AdbStatResponse {
+    fn decode(bytes: &[u8]) -> crate::Result<Self> {
+        let bytes: [u8; Self::V1_SIZE] = bytes
+            .try_into()
+            .map_err(|_| RustADBError::ConversionError)?;
+
+        Ok(bytes.into())
+    }
+}
+
 impl From<[u8; 12]> for AdbStatResponse {
     fn from(value: [u8; 12]) -> Self {
         Self {
             file_perm: LittleEndian::read_u32(&value[0..4]),
-            file_size: LittleEndian::read_u32(&value[4..8]),
-            mod_time: LittleEndian::read_u32(&value[8..]),
+            file_size: LittleEndian::read_u32(&value[4..8]).into(),
+            mod_time: LittleEndian::read_u32(&value[8..]).into(),
+            dev: None,
+            inode: None,
+            nlink: None,
+            uid: None,
+            gid: None,
+            access_time: None,
+            change_time: None,
         }
     }
 }
 
 impl Display for AdbStatResponse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let d = UNIX_EPOCH + Duration::from_secs(self.mod_time.into());
-        // Create DateTime from SystemTime
-        let datetime = DateTime::<Utc>::from(d);
+        let format_time = |timestamp: i64| {
+            DateTime::from_timestamp(timestamp, 0).map_or_else(
+                || timestamp.to_string(),
+                |datetime| datetime.format("%Y-%m-%d %H:%M:%S.%f %Z").to_string(),
+            )
+        };
 
         writeln!(f, "File permissions: {}", self.file_perm)?;
         writeln!(f, "File size: {} bytes", self.file_size)?;
-        write!(
-            f,
-            "Modification time: {}",
-            datetime.format("%Y-%m-%d %H:%M:%S.%f %Z")
-        )?;
+        write!(f, "Modification time: {}", format_time(self.mod_time))?;
+
+        if let (Some(uid), Some(gid)) = (self.uid, self.gid) {
+            write!(f, "\nOwner: uid={uid} gid={gid}")?;
+        }
+        if let (Some(dev), Some(inode), Some(nlink)) = (self.dev, self.inode, self.nlink) {
+            write!(f, "\nDevice: {dev} Inode: {inode} Links: {nlink}")?;
+        }
+        if let Some(access_time) = self.access_time {
+            write!(f, "\nAccess time: {}", format_time(access_time))?;
+        }
+        if let Some(change_time) = self.change_time {
+            write!(f, "\nChange time: {}", format_time(change_time))?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_v2() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0_u32.to_le_bytes()); // error
+        bytes.extend_from_slice(&66_u64.to_le_bytes()); // dev
+        bytes.extend_from_slice(&1234_u64.to_le_bytes()); // inode
+        bytes.extend_from_slice(&0o100_644_u32.to_le_bytes()); // mode
+        bytes.extend_from_slice(&1_u32.to_le_bytes()); // nlink
+        bytes.extend_from_slice(&1000_u32.to_le_bytes()); // uid
+        bytes.extend_from_slice(&1001_u32.to_le_bytes()); // gid
+        bytes.extend_from_slice(&(5_u64 << 32).to_le_bytes()); // size
+        bytes.extend_from_slice(&1_700_000_000_i64.to_le_bytes()); // atime
+        bytes.extend_from_slice(&1_700_000_001_i64.to_le_bytes()); // mtime
+        bytes.extend_from_slice(&1_700_000_002_i64.to_le_bytes()); // ctime
+
+        let response = AdbStatResponse::decode_v2(&bytes).expect("cannot decode");
+        assert_eq!(response.file_perm, 0o100_644);
+        assert_eq!(response.file_size, 5 << 32);
+        assert_eq!(response.mod_time, 1_700_000_001);
+        assert_eq!(response.inode, Some(1234));
+        assert_eq!((response.uid, response.gid), (Some(1000), Some(1001)));
+
+        // Errors are reported as missing files
+        bytes[0] = 2;
+        let response = AdbStatResponse::decode_v2(&bytes).expect("cannot decode");
+        assert_eq!(response.file_perm, 0);
+        assert_eq!(response.uid, None);
+    }
+}
@@ -0,0 +1,105 @@
+use std::str::FromStr;
+
+use crate::{HostFeatures, RustADBError};
+
+/// Banner sent by device in its `CNXN` message, e.g. `device::ro.product.name=x;ro.product.model=y;features=shell_v2,cmd`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeviceBanner {
+    /// Connection type, e.g. `device`, `recovery`, `bootloader`, `sideload` or `rescue`
+    pub connection_type: String,
+    /// Product name (`ro.product.name`)
+    pub product_name: Option<String>,
+    /// Product model (`ro.product.model`)
+    pub model: Option<String>,
+    /// Device name (`ro.product.device`)
+    pub device: Option<String>,
+    /// Every feature advertised by device, including ones not known by [`HostFeatures`]
+    pub features: Vec<String>,
+}
+
+impl DeviceBanner {
+    /// Whether device advertised `feature`, e.g. `shell_v2` or `abb_exec`.
+    #[must_use]
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Advertised features known by this crate.
+    #[must_use]
+    pub fn host_features(&self) -> Vec<HostFeatures> {
+        self.features
+            .iter()
+            .filter_map(|feature| HostFeatures::try_from(feature.as_bytes()).ok())
+            .collect()
+    }
+}
+
+impl FromStr for DeviceBanner {
+    type Err = RustADBError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // Banner is formatted as `<connection_type>:<serial>:<properties>`, serial being usually empty
+        let mut parts = s.trim_end_matches('\0').splitn(3, ':');
+        let (Some(connection_type), Some(_serial)) = (parts.next(), parts.next()) else {
+            return Err(RustADBError::UnknownResponseType(format!(
+                "invalid device banner: {s}"
+            )));
+        };
+
+        let mut banner = Self {
+            connection_type: connection_type.to_string(),
+            ..Self::default()
+        };
+        for property in parts.next().unwrap_or_default().split(';') {
+            let Some((key, value)) = property.split_once('=') else {
+                continue;
+            };
+            match key {
+                "ro.product.name" => banner.product_name = Some(value.to_string()),
+                "ro.product.model" => banner.model = Some(value.to_string()),
+                "ro.product.device" => banner.device = Some(value.to_string()),
+                "features" => {
+                    banner.features = value
+                        .split(',')
+                        .filter(|feature| !feature.is_empty())
+                        .map(ToString::to_string)
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+
+        Ok(banner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::DeviceBanner;
+    use crate::HostFeatures;
+
+    #[test]
+    fn test_parse_device_banner() {
+        let banner = DeviceBanner::from_str(
+            "device::ro.product.name=sdk_phone;ro.product.model=Pixel;ro.product.device=emu64;features=shell_v2,abb_exec,cmd\0",
+        )
+        .unwrap();
+
+        assert_eq!(banner.connection_type, "device");
+        assert_eq!(banner.product_name.as_deref(), Some("sdk_phone"));
+        assert_eq!(banner.model.as_deref(), Some("Pixel"));
+        assert_eq!(banner.device.as_deref(), Some("emu64"));
+        assert!(banner.has_feature("abb_exec"));
+        assert_eq!(
+            banner.host_features(),
+            vec![HostFeatures::ShellV2, HostFeatures::Cmd]
+        );
+
+        let banner = DeviceBanner::from_str("recovery::").unwrap();
+        assert_eq!(banner.connection_type, "recovery");
+        assert!(banner.features.is_empty());
+        assert!(DeviceBanner::from_str("garbage").is_err());
+    }
+}
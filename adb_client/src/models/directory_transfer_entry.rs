@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use crate::Result;
+
+/// Kind of entry handled by a directory transfer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransferEntryKind {
+    /// Regular file, whose content is transferred
+    File,
+    /// Directory, created on destination side
+    Directory,
+    /// Symbolic link, recreated on destination side
+    Symlink,
+}
+
+/// Outcome of the transfer of a single entry by `push_dir` or `pull_dir`.
+#[derive(Debug)]
+pub struct DirectoryTransferEntry {
+    /// Path of the entry on local side
+    pub local_path: PathBuf,
+    /// Path of the entry on the device
+    pub remote_path: String,
+    /// Kind of the entry
+    pub kind: TransferEntryKind,
+    /// Whether the entry has been successfully transferred
+    pub result: Result<()>,
+}
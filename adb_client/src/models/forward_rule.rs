@@ -0,0 +1,16 @@
+use std::fmt::Display;
+
+/// A forwarding rule, as set up by `forward` or `reverse`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ForwardRule {
+    /// Endpoint on this host, e.g. `tcp:8080`
+    pub local: String,
+    /// Remote endpoint on device, e.g. `tcp:8080` or `localabstract:name`
+    pub remote: String,
+}
+
+impl Display for ForwardRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.local, self.remote)
+    }
+}
@@ -1,12 +1,26 @@
 use std::fmt::Display;
 
 /// Available host features.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum HostFeatures {
     /// Shell version 2.
     ShellV2,
     /// Command.
     Cmd,
+    /// Sync `stat` version 2, with 64-bit sizes and extended metadata.
+    StatV2,
+    /// Sync `list` version 2, with 64-bit sizes and extended metadata.
+    LsV2,
+    /// Sync `send` and `recv` version 2.
+    SendRecvV2,
+    /// Sync version 2 transfers can be compressed using Brotli.
+    SendRecvV2Brotli,
+    /// Sync version 2 transfers can be compressed using LZ4.
+    SendRecvV2Lz4,
+    /// Sync version 2 transfers can be compressed using Zstandard.
+    SendRecvV2Zstd,
+    /// Sync version 2 `send` can be run without writing anything on device.
+    SendRecvV2DryRunSend,
 }
 
 impl Display for HostFeatures {
@@ -14,6 +28,13 @@ impl Display for HostFeatures {
         match self {
             Self::ShellV2 => write!(f, "ShellV2"),
             Self::Cmd => write!(f, "Cmd"),
+            Self::StatV2 => write!(f, "StatV2"),
+            Self::LsV2 => write!(f, "LsV2"),
+            Self::SendRecvV2 => write!(f, "SendRecvV2"),
+            Self::SendRecvV2Brotli => write!(f, "SendRecvV2Brotli"),
+            Self::SendRecvV2Lz4 => write!(f, "SendRecvV2Lz4"),
+            Self::SendRecvV2Zstd => write!(f, "SendRecvV2Zstd"),
+            Self::SendRecvV2DryRunSend => write!(f, "SendRecvV2DryRunSend"),
         }
     }
 }
@@ -25,6 +46,13 @@ impl TryFrom<&[u8]> for HostFeatures {
         match value {
             b"shell_v2" => Ok(Self::ShellV2),
             b"cmd" => Ok(Self::Cmd),
+            b"stat_v2" => Ok(Self::StatV2),
+            b"ls_v2" => Ok(Self::LsV2),
+            b"sendrecv_v2" => Ok(Self::SendRecvV2),
+            b"sendrecv_v2_brotli" => Ok(Self::SendRecvV2Brotli),
+            b"sendrecv_v2_lz4" => Ok(Self::SendRecvV2Lz4),
+            b"sendrecv_v2_zstd" => Ok(Self::SendRecvV2Zstd),
+            b"sendrecv_v2_dry_run_send" => Ok(Self::SendRecvV2DryRunSend),
             _ => Err(format!("Unknown value {value:?}")),
         }
     }
@@ -0,0 +1,142 @@
+use std::fmt::Display;
+
+/// Reason of an installation failure, as reported by the package manager in `Failure [REASON: details]` messages.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InstallFailureReason {
+    /// Package is already installed.
+    AlreadyExists,
+    /// APK file is invalid.
+    InvalidApk,
+    /// Not enough storage left on device.
+    InsufficientStorage,
+    /// A package with the same name is already installed.
+    DuplicatePackage,
+    /// Installed package has a different signature than the new one.
+    UpdateIncompatible,
+    /// A required shared library is missing on device.
+    MissingSharedLibrary,
+    /// Package requires a newer SDK than the one of device.
+    OlderSdk,
+    /// Package requires an older SDK than the one of device.
+    NewerSdk,
+    /// Package is marked as test-only.
+    TestOnly,
+    /// Package native code does not match any ABI of device.
+    NoMatchingAbis,
+    /// Package uses a feature device does not have.
+    MissingFeature,
+    /// Installed package has a newer version code.
+    VersionDowngrade,
+    /// A required split APK is missing.
+    MissingSplit,
+    /// A content provider authority is already used by another package.
+    ConflictingProvider,
+    /// Package verification failed.
+    VerificationFailure,
+    /// User is restricted from installing apps.
+    UserRestricted,
+    /// Installation has been aborted.
+    Aborted,
+    /// APK has no certificates.
+    NoCertificates,
+    /// APK entries are signed with inconsistent certificates.
+    InconsistentCertificates,
+    /// Package manager encountered an internal error.
+    InternalError,
+    /// Any other reason, holding its raw code, e.g. `INSTALL_FAILED_DEXOPT`.
+    Other(String),
+    /// Output of package manager did not contain any reason.
+    Unknown,
+}
+
+impl InstallFailureReason {
+    /// Extract failure reason from package manager `output`, e.g. `Failure [INSTALL_FAILED_ALREADY_EXISTS: details]`.
+    pub(crate) fn from_output(output: &str) -> Self {
+        let Some(code) = output
+            .split_once('[')
+            .map(|(_, reason)| reason)
+            .and_then(|reason| reason.split([':', ']', ' ']).next())
+            .filter(|code| !code.is_empty())
+        else {
+            return Self::Unknown;
+        };
+
+        match code {
+            "INSTALL_FAILED_ALREADY_EXISTS" => Self::AlreadyExists,
+            "INSTALL_FAILED_INVALID_APK" => Self::InvalidApk,
+            "INSTALL_FAILED_INSUFFICIENT_STORAGE" => Self::InsufficientStorage,
+            "INSTALL_FAILED_DUPLICATE_PACKAGE" => Self::DuplicatePackage,
+            "INSTALL_FAILED_UPDATE_INCOMPATIBLE" => Self::UpdateIncompatible,
+            "INSTALL_FAILED_MISSING_SHARED_LIBRARY" => Self::MissingSharedLibrary,
+            "INSTALL_FAILED_OLDER_SDK" => Self::OlderSdk,
+            "INSTALL_FAILED_NEWER_SDK" => Self::NewerSdk,
+            "INSTALL_FAILED_TEST_ONLY" => Self::TestOnly,
+            "INSTALL_FAILED_NO_MATCHING_ABIS" | "INSTALL_FAILED_CPU_ABI_INCOMPATIBLE" => {
+                Self::NoMatchingAbis
+            }
+            "INSTALL_FAILED_MISSING_FEATURE" => Self::MissingFeature,
+            "INSTALL_FAILED_VERSION_DOWNGRADE" => Self::VersionDowngrade,
+            "INSTALL_FAILED_MISSING_SPLIT" => Self::MissingSplit,
+            "INSTALL_FAILED_CONFLICTING_PROVIDER" => Self::ConflictingProvider,
+            "INSTALL_FAILED_VERIFICATION_FAILURE" => Self::VerificationFailure,
+            "INSTALL_FAILED_USER_RESTRICTED" => Self::UserRestricted,
+            "INSTALL_FAILED_ABORTED" => Self::Aborted,
+            "INSTALL_PARSE_FAILED_NO_CERTIFICATES" => Self::NoCertificates,
+            "INSTALL_PARSE_FAILED_INCONSISTENT_CERTIFICATES" => Self::InconsistentCertificates,
+            "INSTALL_FAILED_INTERNAL_ERROR" => Self::InternalError,
+            code => Self::Other(code.to_string()),
+        }
+    }
+}
+
+impl Display for InstallFailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlreadyExists => write!(f, "package already exists"),
+            Self::InvalidApk => write!(f, "invalid APK"),
+            Self::InsufficientStorage => write!(f, "insufficient storage"),
+            Self::DuplicatePackage => write!(f, "duplicate package"),
+            Self::UpdateIncompatible => write!(f, "update incompatible with installed package"),
+            Self::MissingSharedLibrary => write!(f, "missing shared library"),
+            Self::OlderSdk => write!(f, "device SDK is too old"),
+            Self::NewerSdk => write!(f, "device SDK is too new"),
+            Self::TestOnly => write!(f, "test-only package"),
+            Self::NoMatchingAbis => write!(f, "no matching ABIs"),
+            Self::MissingFeature => write!(f, "missing feature"),
+            Self::VersionDowngrade => write!(f, "version downgrade"),
+            Self::MissingSplit => write!(f, "missing split"),
+            Self::ConflictingProvider => write!(f, "conflicting provider"),
+            Self::VerificationFailure => write!(f, "verification failure"),
+            Self::UserRestricted => write!(f, "user restricted"),
+            Self::Aborted => write!(f, "aborted"),
+            Self::NoCertificates => write!(f, "no certificates"),
+            Self::InconsistentCertificates => write!(f, "inconsistent certificates"),
+            Self::InternalError => write!(f, "internal error"),
+            Self::Other(code) => write!(f, "{code}"),
+            Self::Unknown => write!(f, "unknown reason"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InstallFailureReason;
+
+    #[test]
+    fn test_install_failure_reason_from_output() {
+        assert_eq!(
+            InstallFailureReason::from_output(
+                "Failure [INSTALL_FAILED_ALREADY_EXISTS: Attempt to re-install com.example without first uninstalling.]\n"
+            ),
+            InstallFailureReason::AlreadyExists
+        );
+        assert_eq!(
+            InstallFailureReason::from_output("Failure [INSTALL_FAILED_DEXOPT]"),
+            InstallFailureReason::Other("INSTALL_FAILED_DEXOPT".to_string())
+        );
+        assert_eq!(
+            InstallFailureReason::from_output("Error: java.lang.IllegalArgumentException"),
+            InstallFailureReason::Unknown
+        );
+    }
+}
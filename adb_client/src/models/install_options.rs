@@ -0,0 +1,128 @@
+use std::fmt::Write;
+
+use crate::utils::shell_quote;
+
+/// Options used when installing packages on the device.
+///
+/// Options are set using builder methods, e.g. `InstallOptions::default().replace(true).user("0")`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[expect(clippy::struct_excessive_bools)]
+pub struct InstallOptions {
+    user: Option<String>,
+    replace: bool,
+    allow_downgrade: bool,
+    grant_all_permissions: bool,
+    allow_test_only: bool,
+    abi: Option<String>,
+    instant: bool,
+    dont_kill: bool,
+}
+
+impl InstallOptions {
+    /// Install packages for given user id, instead of the current user.
+    #[must_use]
+    pub fn user<S: Into<String>>(mut self, user: S) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Replace an already installed package, keeping its data.
+    #[must_use]
+    pub const fn replace(mut self, replace: bool) -> Self {
+        self.replace = replace;
+        self
+    }
+
+    /// Allow installing a package with a lower version code than the installed one.
+    #[must_use]
+    pub const fn allow_downgrade(mut self, allow_downgrade: bool) -> Self {
+        self.allow_downgrade = allow_downgrade;
+        self
+    }
+
+    /// Grant all runtime permissions listed in package manifest.
+    #[must_use]
+    pub const fn grant_all_permissions(mut self, grant_all_permissions: bool) -> Self {
+        self.grant_all_permissions = grant_all_permissions;
+        self
+    }
+
+    /// Allow installing packages marked as test-only.
+    #[must_use]
+    pub const fn allow_test_only(mut self, allow_test_only: bool) -> Self {
+        self.allow_test_only = allow_test_only;
+        self
+    }
+
+    /// Override the ABI used for package native code, e.g. `arm64-v8a`.
+    #[must_use]
+    pub fn abi<S: Into<String>>(mut self, abi: S) -> Self {
+        self.abi = Some(abi.into());
+        self
+    }
+
+    /// Install packages as instant apps.
+    #[must_use]
+    pub const fn instant(mut self, instant: bool) -> Self {
+        self.instant = instant;
+        self
+    }
+
+    /// Do not kill the running application when updating it, e.g. when adding a split.
+    #[must_use]
+    pub const fn dont_kill(mut self, dont_kill: bool) -> Self {
+        self.dont_kill = dont_kill;
+        self
+    }
+
+    /// Arguments of `pm install` and `cmd package install` commands matching these options.
+    pub(crate) fn to_args(&self) -> String {
+        let mut args = String::new();
+        if let Some(user) = &self.user {
+            let _ = write!(args, " --user {}", shell_quote(user));
+        }
+        for (enabled, flag) in [
+            (self.replace, " -r"),
+            (self.allow_downgrade, " -d"),
+            (self.grant_all_permissions, " -g"),
+            (self.allow_test_only, " -t"),
+        ] {
+            if enabled {
+                args.push_str(flag);
+            }
+        }
+        if let Some(abi) = &self.abi {
+            let _ = write!(args, " --abi {}", shell_quote(abi));
+        }
+        if self.instant {
+            args.push_str(" --instant");
+        }
+        if self.dont_kill {
+            args.push_str(" --dont-kill");
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InstallOptions;
+
+    #[test]
+    fn test_install_options_to_args() {
+        assert_eq!(InstallOptions::default().to_args(), "");
+        assert_eq!(
+            InstallOptions::default()
+                .user("10")
+                .replace(true)
+                .allow_downgrade(true)
+                .grant_all_permissions(true)
+                .allow_test_only(true)
+                .abi("arm64-v8a")
+                .instant(true)
+                .dont_kill(true)
+                .to_args(),
+            " --user '10' -r -d -g -t --abi 'arm64-v8a' --instant --dont-kill"
+        );
+    }
+}
@@ -4,11 +4,25 @@ mod adb_local_command;
 mod adb_request_status;
 mod adb_stat_extended_response;
 mod adb_stat_response;
+mod device_banner;
+mod directory_transfer_entry;
+mod forward_rule;
 mod host_features;
+mod install_failure_reason;
+mod install_options;
 mod list_info;
+mod pull_options;
+mod push_options;
 mod reboot_type;
 mod remount_info;
+mod shell_channel;
+mod shell_window_size;
+mod sync_action;
 mod sync_command;
+mod sync_compression;
+mod sync_dir_options;
+mod timeouts;
+mod transfer_progress;
 
 #[cfg(feature = "framebuffer")]
 mod framebuffer_info;
@@ -19,11 +33,27 @@ pub use adb_local_command::ADBLocalCommand;
 pub use adb_request_status::AdbRequestStatus;
 pub use adb_stat_extended_response::{ADBStatExtendedResponse, ADBStatMapping};
 pub use adb_stat_response::AdbStatResponse;
+pub use device_banner::DeviceBanner;
+pub use directory_transfer_entry::{DirectoryTransferEntry, TransferEntryKind};
+pub use forward_rule::ForwardRule;
 pub use host_features::HostFeatures;
+pub use install_failure_reason::InstallFailureReason;
+pub use install_options::InstallOptions;
 pub use list_info::{ADBListItem, ADBListItemType};
+pub use pull_options::PullOptions;
+pub use push_options::PushOptions;
 pub use reboot_type::RebootType;
 pub use remount_info::RemountInfo;
+pub(crate) use shell_channel::ShellChannel;
+pub use shell_window_size::ShellWindowSize;
+pub use sync_action::{SyncAction, SyncActionEntry};
 pub use sync_command::SyncCommand;
+pub use sync_compression::SyncCompression;
+pub use sync_dir_options::SyncDirOptions;
+pub(crate) use timeouts::{Deadline, TimedOut, timeout_error};
+pub use timeouts::{TimeoutPhase, Timeouts};
+pub use transfer_progress::{CancellationToken, ProgressObserver};
+pub(crate) use transfer_progress::{ProgressReader, ProgressWriter};
 
 #[cfg(feature = "framebuffer")]
 pub use framebuffer_info::{FrameBufferInfoV1, FrameBufferInfoV2};
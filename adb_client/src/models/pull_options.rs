@@ -0,0 +1,57 @@
+use std::{
+    fs::File,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{Result, models::AdbStatResponse};
+
+/// Options used when pulling a file from the device into a local file.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PullOptions {
+    /// Apply remote file permissions to the local file.
+    ///
+    /// Only the read-only flag can be applied on non-unix platforms.
+    pub preserve_permissions: bool,
+    /// Apply remote file modification time to the local file.
+    pub preserve_timestamps: bool,
+}
+
+impl PullOptions {
+    /// Whether remote file metadata is needed to apply these options.
+    pub(crate) const fn needs_metadata(self) -> bool {
+        self.preserve_permissions || self.preserve_timestamps
+    }
+
+    /// Apply remote file metadata, as returned by `stat`, to the local `file`.
+    pub(crate) fn apply(self, file: &File, stat: &AdbStatResponse) -> Result<()> {
+        if self.preserve_timestamps {
+            let offset = Duration::from_secs(stat.mod_time.unsigned_abs());
+            let mtime = if stat.mod_time >= 0 {
+                UNIX_EPOCH.checked_add(offset)
+            } else {
+                UNIX_EPOCH.checked_sub(offset)
+            };
+
+            match mtime {
+                Some(mtime) => file.set_modified(mtime)?,
+                None => log::warn!("cannot represent modification time {}", stat.mod_time),
+            }
+        }
+
+        if self.preserve_permissions {
+            let mut permissions = file.metadata()?.permissions();
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                permissions.set_mode(stat.file_perm & 0o7777);
+            }
+            #[cfg(not(unix))]
+            permissions.set_readonly(stat.file_perm & 0o222 == 0);
+
+            file.set_permissions(permissions)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    fs::Metadata,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Result, RustADBError};
+
+/// Default permissions of pushed files.
+const DEFAULT_PUSH_MODE: u32 = 0o777;
+
+/// Options used when pushing a file to the device.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PushOptions {
+    /// Permissions given to the remote file. Defaults to `0o777`.
+    pub mode: u32,
+    /// Modification time given to the remote file. Defaults to current time if `None`.
+    pub mtime: Option<SystemTime>,
+}
+
+impl Default for PushOptions {
+    fn default() -> Self {
+        Self {
+            mode: DEFAULT_PUSH_MODE,
+            mtime: None,
+        }
+    }
+}
+
+impl PushOptions {
+    /// Instantiate options forwarding permissions and modification time of a local file.
+    ///
+    /// Permissions are only forwarded on unix platforms.
+    #[must_use]
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.permissions().mode() & 0o7777
+        };
+        #[cfg(not(unix))]
+        let mode = DEFAULT_PUSH_MODE;
+
+        Self {
+            mode,
+            mtime: metadata.modified().ok(),
+        }
+    }
+
+    /// Modification time to send to the device, in seconds since UNIX epoch.
+    pub(crate) fn remote_mtime(&self) -> Result<u32> {
+        let Ok(mtime) = self
+            .mtime
+            .unwrap_or_else(SystemTime::now)
+            .duration_since(UNIX_EPOCH)
+        else {
+            return Err(RustADBError::ADBRequestFailed(
+                "SystemTime before UNIX EPOCH!".into(),
+            ));
+        };
+
+        Ok(u32::try_from(mtime.as_secs())?)
+    }
+}
@@ -0,0 +1,79 @@
+use std::io::ErrorKind;
+
+use crate::Result;
+
+/// Shell v2 protocol packet identifiers, reference:
+/// <https://android.googlesource.com/platform/packages/modules/adb/+/refs/heads/main/shell_protocol.h>
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ShellChannel {
+    Stdin,
+    Stdout,
+    Stderr,
+    ExitStatus,
+    CloseStdin,
+    WindowSizeChange,
+}
+
+impl ShellChannel {
+    /// Size of a packet header: 1 byte of channel and 4 bytes of payload size
+    pub(crate) const HEADER_SIZE: usize = 5;
+
+    /// Build a packet sending `payload` on this channel.
+    pub(crate) fn packet(self, payload: &[u8]) -> Result<Vec<u8>> {
+        let mut packet = Vec::with_capacity(Self::HEADER_SIZE + payload.len());
+        packet.push(u8::from(self));
+        packet.extend_from_slice(&u32::try_from(payload.len())?.to_le_bytes());
+        packet.extend_from_slice(payload);
+        Ok(packet)
+    }
+}
+
+impl From<ShellChannel> for u8 {
+    fn from(value: ShellChannel) -> Self {
+        match value {
+            ShellChannel::Stdin => 0,
+            ShellChannel::Stdout => 1,
+            ShellChannel::Stderr => 2,
+            ShellChannel::ExitStatus => 3,
+            ShellChannel::CloseStdin => 4,
+            ShellChannel::WindowSizeChange => 5,
+        }
+    }
+}
+
+impl TryFrom<u8> for ShellChannel {
+    type Error = std::io::Error;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Stdin),
+            1 => Ok(Self::Stdout),
+            2 => Ok(Self::Stderr),
+            3 => Ok(Self::ExitStatus),
+            4 => Ok(Self::CloseStdin),
+            5 => Ok(Self::WindowSizeChange),
+            _ => Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Invalid channel",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShellChannel;
+
+    #[test]
+    fn test_packet_encoding() {
+        let packet = ShellChannel::Stdin
+            .packet(b"ls\n")
+            .expect("cannot encode packet");
+        assert_eq!(packet, [0, 3, 0, 0, 0, b'l', b's', b'\n']);
+
+        let packet = ShellChannel::CloseStdin
+            .packet(&[])
+            .expect("cannot encode packet");
+        assert_eq!(packet, [4, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt::Display;
+
+/// Size of the terminal attached to an interactive shell.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ShellWindowSize {
+    /// Number of rows, in characters
+    pub rows: u16,
+    /// Number of columns, in characters
+    pub cols: u16,
+    /// Width, in pixels
+    pub x_pixels: u16,
+    /// Height, in pixels
+    pub y_pixels: u16,
+}
+
+impl ShellWindowSize {
+    /// Instantiate a new [`ShellWindowSize`] from its dimensions in characters
+    #[must_use]
+    pub const fn new(rows: u16, cols: u16) -> Self {
+        Self {
+            rows,
+            cols,
+            x_pixels: 0,
+            y_pixels: 0,
+        }
+    }
+}
+
+impl Display for ShellWindowSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Format expected by adbd in window size change packets
+        write!(
+            f,
+            "{}x{},{}x{}",
+            self.rows, self.cols, self.x_pixels, self.y_pixels
+        )
+    }
+}
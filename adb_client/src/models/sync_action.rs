@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use crate::{Result, models::TransferEntryKind};
+
+/// Action taken on a remote entry by `sync_dir`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncAction {
+    /// Remote directory is missing and gets created
+    CreateDirectory,
+    /// Remote entry is missing or differs by size or modification time, and gets pushed
+    Push,
+    /// Remote entry does not exist locally anymore, and gets deleted
+    Delete,
+}
+
+/// Outcome of a single action performed by `sync_dir`.
+#[derive(Debug)]
+pub struct SyncActionEntry {
+    /// Action taken
+    pub action: SyncAction,
+    /// Path of the entry on local side, if it exists locally
+    pub local_path: Option<PathBuf>,
+    /// Path of the entry on the device
+    pub remote_path: String,
+    /// Kind of the entry
+    pub kind: TransferEntryKind,
+    /// Whether the action has been successfully performed.
+    ///
+    /// Always `Ok` during a dry run, as no action is performed.
+    pub result: Result<()>,
+}
@@ -1,23 +1,45 @@
 use std::fmt::Display;
 
+/// Requests and packet identifiers of the file synchronization protocol.
 pub enum SyncCommand {
     /// List files in a folder
     List,
+    /// List files in a folder, with extended metadata (v2)
+    List2,
     /// Receive a file from the device
     Recv,
+    /// Receive a file from the device, optionally compressed (v2)
+    Recv2,
     /// Send a file to the device
     Send,
+    /// Send a file to the device, optionally compressed (v2)
+    Send2,
     // Stat a file
     Stat,
+    /// Stat a file, without following symlinks (v2)
+    LStat2,
+    /// Chunk of file content
+    Data,
+    /// End of file content or of a listing
+    Done,
+    /// End synchronization session
+    Quit,
 }
 
 impl Display for SyncCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::List => write!(f, "LIST"),
+            Self::List2 => write!(f, "LIS2"),
             Self::Recv => write!(f, "RECV"),
+            Self::Recv2 => write!(f, "RCV2"),
             Self::Send => write!(f, "SEND"),
+            Self::Send2 => write!(f, "SND2"),
             Self::Stat => write!(f, "STAT"),
+            Self::LStat2 => write!(f, "LST2"),
+            Self::Data => write!(f, "DATA"),
+            Self::Done => write!(f, "DONE"),
+            Self::Quit => write!(f, "QUIT"),
         }
     }
 }
@@ -0,0 +1,29 @@
+use std::fmt::Display;
+
+/// Compression algorithm used for file transfers, when device supports sync version 2.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SyncCompression {
+    /// Best algorithm supported by both device and this build, or none
+    #[default]
+    Any,
+    /// Transfer files without compression
+    None,
+    /// Brotli compression, requires `brotli` feature
+    Brotli,
+    /// LZ4 compression, requires `lz4` feature
+    Lz4,
+    /// Zstandard compression, requires `zstd` feature
+    Zstd,
+}
+
+impl Display for SyncCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::None => write!(f, "none"),
+            Self::Brotli => write!(f, "brotli"),
+            Self::Lz4 => write!(f, "lz4"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+/// Options used when synchronizing a local directory to the device.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SyncDirOptions {
+    /// Delete remote entries that do not exist in local directory anymore.
+    pub delete: bool,
+    /// Only compute and report planned actions, without applying any of them.
+    pub dry_run: bool,
+}
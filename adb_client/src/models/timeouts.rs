@@ -0,0 +1,242 @@
+use std::{
+    fmt::Display,
+    io::{self, ErrorKind},
+    net::TcpStream,
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+/// Default time given to establish a connection.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Phase of an operation that did not complete in time, reported by [`RustADBError::Timeout`](crate::RustADBError::Timeout).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    /// Connection to device or server, handshake included, was not established in time.
+    Connect,
+    /// No data has been received for longer than idle read timeout.
+    Read,
+    /// Operation did not complete before its deadline.
+    Operation,
+}
+
+impl Display for TimeoutPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connect => write!(f, "connect"),
+            Self::Read => write!(f, "idle read"),
+            Self::Operation => write!(f, "operation deadline"),
+        }
+    }
+}
+
+/// Timeouts applied to operations run on a device or server.
+///
+/// Timeouts are set using builder methods, e.g. `Timeouts::default().read(Some(Duration::from_secs(5)))`.
+/// They also apply to interactive sessions, such as shells, which may need to run without them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeouts {
+    connect: Option<Duration>,
+    read: Option<Duration>,
+    operation: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Timeouts {
+    pub(crate) const DEFAULT: Self = Self::none().connect(Some(DEFAULT_CONNECT_TIMEOUT));
+
+    /// No timeout at all, operations waiting as long as needed.
+    #[must_use]
+    pub const fn none() -> Self {
+        Self {
+            connect: None,
+            read: None,
+            operation: None,
+        }
+    }
+
+    /// Time given to establish a connection, `None` waiting as long as operating system allows.
+    ///
+    /// Defaults to 10 seconds.
+    #[must_use]
+    pub const fn connect(mut self, connect: Option<Duration>) -> Self {
+        self.connect = connect;
+        self
+    }
+
+    /// Longest time without receiving any data, `None` waiting forever.
+    ///
+    /// Defaults to `None`.
+    #[must_use]
+    pub const fn read(mut self, read: Option<Duration>) -> Self {
+        self.read = read;
+        self
+    }
+
+    /// Time given to each request sent to device or server to complete, e.g. a command or a file transfer, `None` waiting forever.
+    ///
+    /// Defaults to `None`.
+    #[must_use]
+    pub const fn operation(mut self, operation: Option<Duration>) -> Self {
+        self.operation = operation;
+        self
+    }
+
+    pub(crate) const fn connect_value(&self) -> Option<Duration> {
+        self.connect
+    }
+
+    pub(crate) const fn read_value(&self) -> Option<Duration> {
+        self.read
+    }
+}
+
+/// Error carried by [`io::Error`] once a timeout has been reached, converted into [`RustADBError::Timeout`](crate::RustADBError::Timeout).
+#[derive(Debug)]
+pub(crate) struct TimedOut(pub TimeoutPhase);
+
+impl Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} timeout reached", self.0)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Error reporting that `phase` did not complete in time.
+pub(crate) fn timeout_error(phase: TimeoutPhase) -> io::Error {
+    io::Error::new(ErrorKind::TimedOut, TimedOut(phase))
+}
+
+/// Idle read timeout and deadline of an operation started at creation.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Deadline {
+    read: Option<Duration>,
+    end: Option<Instant>,
+}
+
+impl Deadline {
+    /// Operation not bounded by any timeout, until next one starts.
+    pub(crate) const UNBOUNDED: Self = Self {
+        read: None,
+        end: None,
+    };
+
+    /// Start an operation bounded by `timeouts`.
+    pub(crate) fn start(timeouts: &Timeouts) -> Self {
+        Self {
+            read: timeouts.read,
+            end: timeouts
+                .operation
+                .map(|operation| Instant::now() + operation),
+        }
+    }
+
+    /// Longest time to wait for next data, and phase to report if none arrives meanwhile.
+    ///
+    /// Fails if operation deadline has already been reached.
+    fn next_wait(&self) -> io::Result<Option<(Duration, TimeoutPhase)>> {
+        let remaining = match self.end {
+            Some(end) => {
+                let remaining = end.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(timeout_error(TimeoutPhase::Operation));
+                }
+                Some(remaining)
+            }
+            None => None,
+        };
+
+        Ok(match (self.read, remaining) {
+            (Some(read), Some(remaining)) if read < remaining => Some((read, TimeoutPhase::Read)),
+            (_, Some(remaining)) => Some((remaining, TimeoutPhase::Operation)),
+            (Some(read), None) => Some((read, TimeoutPhase::Read)),
+            (None, None) => None,
+        })
+    }
+
+    /// Receive next item sent on `receiver`, `disconnected` being returned if its sender is gone.
+    pub(crate) fn recv<T, E: From<io::Error>>(
+        &self,
+        receiver: &Receiver<T>,
+        disconnected: impl FnOnce() -> E,
+    ) -> Result<T, E> {
+        match self.next_wait()? {
+            None => receiver.recv().map_err(|_| disconnected()),
+            Some((wait, phase)) => receiver.recv_timeout(wait).map_err(|e| match e {
+                RecvTimeoutError::Timeout => timeout_error(phase).into(),
+                RecvTimeoutError::Disconnected => disconnected(),
+            }),
+        }
+    }
+
+    /// Run `read` on `stream`, socket read timeout being set so that it does not wait for longer than allowed.
+    pub(crate) fn read<R>(
+        &self,
+        stream: &TcpStream,
+        read: impl FnOnce() -> io::Result<R>,
+    ) -> io::Result<R> {
+        let wait = self.next_wait()?;
+        // Socket read timeout is left as is when only idle timeout is used, as it has been set on connection
+        if self.end.is_some() {
+            stream.set_read_timeout(wait.map(|(wait, _)| wait))?;
+        }
+
+        read().map_err(|e| match (e.kind(), wait) {
+            (ErrorKind::WouldBlock | ErrorKind::TimedOut, Some((_, phase))) => timeout_error(phase),
+            _ => e,
+        })
+    }
+
+    /// Idle read timeout, to be set on sockets when connecting.
+    pub(crate) const fn read_timeout(&self) -> Option<Duration> {
+        self.read
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::channel, time::Duration};
+
+    use super::{Deadline, TimeoutPhase, Timeouts};
+    use crate::RustADBError;
+
+    fn phase(result: &Result<(), RustADBError>) -> Option<TimeoutPhase> {
+        match result {
+            Err(RustADBError::Timeout(phase)) => Some(*phase),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_deadline_reports_phase() {
+        let (_sender, receiver) = channel::<()>();
+        let disconnected = || RustADBError::ConversionError;
+
+        let idle = Deadline::start(&Timeouts::none().read(Some(Duration::from_millis(10))));
+        assert_eq!(
+            phase(&idle.recv(&receiver, disconnected)),
+            Some(TimeoutPhase::Read)
+        );
+
+        let bounded = Deadline::start(
+            &Timeouts::none()
+                .read(Some(Duration::from_secs(60)))
+                .operation(Some(Duration::from_millis(10))),
+        );
+        assert_eq!(
+            phase(&bounded.recv(&receiver, disconnected)),
+            Some(TimeoutPhase::Operation)
+        );
+        // Deadline stays reached
+        assert_eq!(
+            phase(&bounded.recv(&receiver, disconnected)),
+            Some(TimeoutPhase::Operation)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    io::{Read, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use crate::{Result, RustADBError};
+
+/// Observer notified while data is being transferred to or from the device.
+///
+/// Implemented for any `FnMut(u64, Option<u64>)` closure.
+pub trait ProgressObserver {
+    /// Called each time some data has been transferred, with the total amount of bytes already transferred
+    /// and the size of the whole transfer, if known.
+    fn on_progress(&mut self, transferred: u64, total: Option<u64>);
+}
+
+impl<F: FnMut(u64, Option<u64>)> ProgressObserver for F {
+    fn on_progress(&mut self, transferred: u64, total: Option<u64>) {
+        self(transferred, total);
+    }
+}
+
+/// Token used to cancel a running transfer, possibly from another thread.
+///
+/// Clones share the same state: cancelling one of them cancels all transfers using any of them.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new token, not cancelled yet.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation of transfers using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether cancellation has been requested.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn check(&self) -> std::io::Result<()> {
+        if self.is_cancelled() {
+            return Err(std::io::Error::other(RustADBError::Cancelled));
+        }
+        Ok(())
+    }
+
+    /// Turn any error of `result` into [`RustADBError::Cancelled`] if cancellation has been requested,
+    /// as it is the root cause of the failure.
+    pub(crate) fn map_result<R>(&self, result: Result<R>) -> Result<R> {
+        result.map_err(|e| {
+            if self.is_cancelled() {
+                RustADBError::Cancelled
+            } else {
+                e
+            }
+        })
+    }
+}
+
+/// Counts transferred bytes and checks for cancellation around a reader or a writer.
+struct ProgressTracker<'a> {
+    observer: &'a mut dyn ProgressObserver,
+    cancellation: &'a CancellationToken,
+    transferred: u64,
+    total: Option<u64>,
+}
+
+impl<'a> ProgressTracker<'a> {
+    fn new(
+        observer: &'a mut dyn ProgressObserver,
+        cancellation: &'a CancellationToken,
+        total: Option<u64>,
+    ) -> Self {
+        observer.on_progress(0, total);
+        Self {
+            observer,
+            cancellation,
+            transferred: 0,
+            total,
+        }
+    }
+
+    fn advance(&mut self, amount: usize) {
+        if amount > 0 {
+            self.transferred += amount as u64;
+            self.observer.on_progress(self.transferred, self.total);
+        }
+    }
+}
+
+/// [`Read`] implementation reporting progress of data read from `inner`, and failing once cancelled.
+pub(crate) struct ProgressReader<'a, R: Read + ?Sized> {
+    inner: &'a mut R,
+    tracker: ProgressTracker<'a>,
+}
+
+impl<'a, R: Read + ?Sized> ProgressReader<'a, R> {
+    pub(crate) fn new(
+        inner: &'a mut R,
+        total: Option<u64>,
+        observer: &'a mut dyn ProgressObserver,
+        cancellation: &'a CancellationToken,
+    ) -> Self {
+        Self {
+            inner,
+            tracker: ProgressTracker::new(observer, cancellation, total),
+        }
+    }
+}
+
+impl<R: Read + ?Sized> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.tracker.cancellation.check()?;
+        let amount = self.inner.read(buf)?;
+        self.tracker.advance(amount);
+        Ok(amount)
+    }
+}
+
+/// [`Write`] implementation reporting progress of data written to `inner`, and failing once cancelled.
+pub(crate) struct ProgressWriter<'a, W: Write + ?Sized> {
+    inner: &'a mut W,
+    tracker: ProgressTracker<'a>,
+}
+
+impl<'a, W: Write + ?Sized> ProgressWriter<'a, W> {
+    pub(crate) fn new(
+        inner: &'a mut W,
+        total: Option<u64>,
+        observer: &'a mut dyn ProgressObserver,
+        cancellation: &'a CancellationToken,
+    ) -> Self {
+        Self {
+            inner,
+            tracker: ProgressTracker::new(observer, cancellation, total),
+        }
+    }
+}
+
+impl<W: Write + ?Sized> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.tracker.cancellation.check()?;
+        let amount = self.inner.write(buf)?;
+        self.tracker.advance(amount);
+        Ok(amount)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::{CancellationToken, ProgressReader};
+
+    #[test]
+    fn test_progress_reader_reports_and_cancels() {
+        let data = vec![0u8; 10];
+        let mut input = data.as_slice();
+        let mut reported = Vec::new();
+        let mut observer = |transferred, total| reported.push((transferred, total));
+        let cancellation = CancellationToken::new();
+
+        {
+            let mut reader =
+                ProgressReader::new(&mut input, Some(10), &mut observer, &cancellation);
+            let mut buf = [0u8; 4];
+            assert_eq!(reader.read(&mut buf).unwrap(), 4);
+            cancellation.cancel();
+            assert!(reader.read(&mut buf).is_err());
+        }
+
+        assert_eq!(reported, vec![(0, Some(10)), (4, Some(10))]);
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    ADBDeviceExt, Result, RustADBError,
+    models::{
+        CancellationToken, InstallFailureReason, InstallOptions, ProgressObserver, ProgressReader,
+    },
+    utils::{check_extension_is_apk, shell_quote},
+};
+
+/// Directory where APKs are pushed before being installed with `pm install`.
+const PM_INSTALL_DIRECTORY: &str = "/data/local/tmp";
+
+/// Check package manager `output`, turning any failure it reports into [`RustADBError::InstallFailed`].
+///
+/// Status may be preceded by other lines, e.g. `pkg: /data/local/tmp/app.apk` printed by older `pm install`.
+pub(crate) fn check_install_output(output: &[u8]) -> Result<()> {
+    let output = String::from_utf8_lossy(output);
+    let mut lines = output.lines().map(str::trim);
+    if lines.clone().any(|line| line.starts_with("Success")) {
+        return Ok(());
+    }
+
+    let status = lines
+        .find(|line| line.starts_with("Failure"))
+        .unwrap_or_else(|| output.trim());
+    Err(RustADBError::InstallFailed(
+        InstallFailureReason::from_output(status),
+        status.to_string(),
+    ))
+}
+
+/// Install APK file at `path`.
+pub(crate) fn install_file<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    path: &Path,
+    options: &InstallOptions,
+    observer: &mut dyn ProgressObserver,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    check_extension_is_apk(path)?;
+    let mut apk = File::open(path)?;
+    let size = apk.metadata()?.len();
+
+    device.install_from_reader_with_progress(&mut apk, size, options, observer, cancellation)?;
+
+    log::info!("APK file {} successfully installed", path.display());
+    Ok(())
+}
+
+/// Install an APK of `size` bytes read from `apk` by pushing it into a temporary file and running `pm install`.
+///
+/// Used for devices lacking the `cmd` feature, which cannot stream APKs to the package manager.
+pub(crate) fn install_with_pm<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    apk: &mut dyn Read,
+    size: u64,
+    options: &InstallOptions,
+    observer: &mut dyn ProgressObserver,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    let remote_path = temporary_apk_path();
+
+    log::debug!("device does not support cmd, installing from {remote_path}");
+    let mut reader = ProgressReader::new(apk, Some(size), observer, cancellation);
+    let result = cancellation
+        .map_result(device.push(&mut reader, &remote_path))
+        .and_then(|()| {
+            let mut output = Vec::new();
+            device.shell_command(
+                &pm_install_command(options, &remote_path),
+                Some(&mut output),
+                None,
+            )?;
+            check_install_output(&output)
+        });
+
+    // Temporary file is removed whatever the installation outcome
+    let remove_command = format!("rm -f {}", shell_quote(&remote_path));
+    if let Err(e) = device.shell_command(&remove_command, None, None) {
+        log::warn!("cannot remove {remote_path}: {e}");
+    }
+
+    result
+}
+
+/// Unique path of a temporary APK pushed on device before installing it with `pm install`.
+pub(crate) fn temporary_apk_path() -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!(
+        "{PM_INSTALL_DIRECTORY}/adb_client_{}_{timestamp}.apk",
+        std::process::id()
+    )
+}
+
+/// Shell command installing APK at `remote_path` on device with `pm install`.
+pub(crate) fn pm_install_command(options: &InstallOptions, remote_path: &str) -> String {
+    format!(
+        "pm install{} {}",
+        options.to_args(),
+        shell_quote(remote_path)
+    )
+}
+
+/// Run `cmd package` with `arguments`, and check its output.
+fn run_package_command<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    arguments: &str,
+) -> Result<String> {
+    let output =
+        device.exec_with_input(&format!("cmd package {arguments}"), &mut std::io::empty())?;
+    check_install_output(&output)?;
+
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Create an install session with given `arguments`, returning its id.
+fn create_session<D: ADBDeviceExt + ?Sized>(device: &mut D, arguments: &str) -> Result<u32> {
+    // e.g. "Success: created install session [1234]"
+    let output = run_package_command(device, &format!("install-create{arguments}"))?;
+    output
+        .rsplit_once('[')
+        .and_then(|(_, id)| id.strip_suffix(']'))
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| {
+            RustADBError::ADBRequestFailed(format!("cannot read install session id in '{output}'"))
+        })
+}
+
+/// Total size of APK files at `paths`, after checking their extension.
+fn total_size(paths: &[&Path]) -> Result<u64> {
+    paths.iter().try_fold(0, |total, path| {
+        check_extension_is_apk(path)?;
+        Ok(total + std::fs::metadata(path)?.len())
+    })
+}
+
+/// Upload every APK of `paths` into install session `session`.
+fn write_apks<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    session: u32,
+    paths: &[&Path],
+) -> Result<()> {
+    for (index, path) in paths.iter().enumerate() {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        // Split names must be unique inside a session
+        let name = format!(
+            "{index}_{}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+
+        log::debug!("writing {} into install session {session}", path.display());
+        let output = device.exec_with_input(
+            &format!(
+                "cmd package install-write -S {size} {session} {} -",
+                shell_quote(&name)
+            ),
+            &mut file,
+        )?;
+        check_install_output(&output)?;
+    }
+
+    Ok(())
+}
+
+/// Abandon install `sessions`, ignoring errors as they may already be gone.
+fn abandon_sessions<D: ADBDeviceExt + ?Sized>(device: &mut D, sessions: &[u32]) {
+    for session in sessions {
+        if let Err(e) = run_package_command(device, &format!("install-abandon {session}")) {
+            log::debug!("cannot abandon install session {session}: {e}");
+        }
+    }
+}
+
+/// Install a single package made of APKs at `paths`, e.g. a base APK and its splits.
+pub(crate) fn install_multiple<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    paths: &[&Path],
+    options: &InstallOptions,
+) -> Result<()> {
+    let size = total_size(paths)?;
+    let session = create_session(device, &format!("{} -S {size}", options.to_args()))?;
+
+    let result = write_apks(device, session, paths)
+        .and_then(|()| run_package_command(device, &format!("install-commit {session}")));
+    if let Err(e) = result {
+        abandon_sessions(device, &[session]);
+        return Err(e);
+    }
+
+    log::info!("{} APK files successfully installed", paths.len());
+    Ok(())
+}
+
+/// Atomically install several `packages`, each of them being made of one or more APKs.
+pub(crate) fn install_multi_package<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    packages: &[&[&Path]],
+    options: &InstallOptions,
+) -> Result<()> {
+    let sizes = packages
+        .iter()
+        .map(|paths| total_size(paths))
+        .collect::<Result<Vec<u64>>>()?;
+
+    let parent = create_session(device, &format!(" --multi-package{}", options.to_args()))?;
+    let mut children = Vec::with_capacity(packages.len());
+
+    if let Err(e) =
+        fill_multi_package_session(device, parent, packages, &sizes, options, &mut children)
+    {
+        // Parent session is abandoned last, once it does not reference any child anymore
+        children.push(parent);
+        abandon_sessions(device, &children);
+        return Err(e);
+    }
+
+    log::info!("{} packages successfully installed", packages.len());
+    Ok(())
+}
+
+/// Create a child session of `parent` for each package, upload its APKs, and commit `parent`.
+///
+/// Created child sessions are pushed into `children`, for them to be abandoned on failure.
+fn fill_multi_package_session<D: ADBDeviceExt + ?Sized>(
+    device: &mut D,
+    parent: u32,
+    packages: &[&[&Path]],
+    sizes: &[u64],
+    options: &InstallOptions,
+    children: &mut Vec<u32>,
+) -> Result<()> {
+    for (paths, size) in packages.iter().zip(sizes) {
+        let child = create_session(device, &format!("{} -S {size}", options.to_args()))?;
+        children.push(child);
+        write_apks(device, child, paths)?;
+    }
+
+    let children: Vec<String> = children.iter().map(ToString::to_string).collect();
+    run_package_command(
+        device,
+        &format!("install-add-session {parent} {}", children.join(" ")),
+    )?;
+    run_package_command(device, &format!("install-commit {parent}"))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_install_output;
+    use crate::{RustADBError, models::InstallFailureReason};
+
+    #[test]
+    fn test_check_install_output() {
+        assert!(check_install_output(b"Success\n").is_ok());
+        assert!(check_install_output(b"\tpkg: /data/local/tmp/app.apk\nSuccess\n").is_ok());
+
+        let Err(RustADBError::InstallFailed(reason, message)) = check_install_output(
+            b"\tpkg: /data/local/tmp/app.apk\nFailure [INSTALL_FAILED_OLDER_SDK]\n",
+        ) else {
+            panic!("installation failure expected");
+        };
+        assert_eq!(reason, InstallFailureReason::OlderSdk);
+        assert_eq!(message, "Failure [INSTALL_FAILED_OLDER_SDK]");
+    }
+}
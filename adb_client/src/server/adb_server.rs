@@ -1,13 +1,14 @@
 use crate::ADBTransport;
 use crate::Result;
 use crate::RustADBError;
+use crate::models::Timeouts;
 use crate::server::tcp_server_transport::TCPServerTransport;
 use std::collections::HashMap;
 use std::net::SocketAddrV4;
 use std::process::Command;
 
 /// Represents an ADB Server
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ADBServer {
     /// Internal [`TcpStream`], lazily initialized
     pub(crate) transport: Option<TCPServerTransport>,
@@ -18,6 +19,23 @@ pub struct ADBServer {
     /// Path to adb binary
     /// If not set, will use adb from PATH
     pub(crate) adb_path: Option<String>,
+    /// Timeouts applied to requests sent to server, and inherited by devices it returns
+    pub(crate) timeouts: Timeouts,
+    /// Whether `adb start-server` is run before connecting to a local server
+    auto_start: bool,
+}
+
+impl Default for ADBServer {
+    fn default() -> Self {
+        Self {
+            transport: None,
+            socket_addr: None,
+            envs: HashMap::new(),
+            adb_path: None,
+            timeouts: Timeouts::default(),
+            auto_start: true,
+        }
+    }
 }
 
 impl ADBServer {
@@ -29,6 +47,8 @@ impl ADBServer {
             socket_addr: Some(address),
             envs: HashMap::new(),
             adb_path: None,
+            timeouts: Timeouts::default(),
+            auto_start: true,
         }
     }
 
@@ -40,6 +60,8 @@ impl ADBServer {
             socket_addr: Some(address),
             envs: HashMap::new(),
             adb_path,
+            timeouts: Timeouts::default(),
+            auto_start: true,
         }
     }
 
@@ -49,6 +71,20 @@ impl ADBServer {
         self.socket_addr
     }
 
+    /// Set timeouts applied to requests sent to server, e.g. when listing devices.
+    ///
+    /// Devices returned afterwards, e.g. by [`get_device`](Self::get_device), use them as their default timeouts.
+    pub const fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Set whether `adb start-server` is run before connecting to a server listening on a local address. Defaults to `true`.
+    ///
+    /// Disable it when server is already running, or is not a real `adb` server, e.g. a fake one used in tests.
+    pub const fn set_auto_start(&mut self, auto_start: bool) {
+        self.auto_start = auto_start;
+    }
+
     /// Start an instance of `adb-server`
     pub fn start(envs: &HashMap<String, String>, adb_path: &Option<String>) {
         // ADB Server is local, we start it if not already running
@@ -100,10 +136,11 @@ impl ADBServer {
             TCPServerTransport::default()
         };
 
-        if is_local_ip {
+        if is_local_ip && self.auto_start {
             Self::start(&self.envs, &self.adb_path);
         }
 
+        transport.set_timeouts(self.timeouts);
         transport.connect()?;
         self.transport = Some(transport);
 
@@ -0,0 +1,243 @@
+use std::net::SocketAddrV4;
+
+use tokio::process::Command;
+
+use crate::models::{ADBCommand, ADBHostCommand};
+use crate::server::async_tcp_server_transport::AsyncTCPServerTransport;
+use crate::server::{AdbVersion, DeviceLong, DeviceShort, TCPServerTransport};
+use crate::server_device::AsyncADBServerDevice;
+use crate::{Result, RustADBError};
+
+/// Represents an ADB Server, from an asynchronous runtime.
+///
+/// Asynchronous counterpart of [`ADBServer`](super::ADBServer). Each request uses its own connection,
+/// so that a single instance can be shared between tasks.
+#[derive(Debug, Clone)]
+pub struct AsyncADBServer {
+    /// Address to connect to
+    socket_addr: Option<SocketAddrV4>,
+    /// Path to adb binary
+    /// If not set, will use adb from PATH
+    adb_path: Option<String>,
+    /// Whether `adb start-server` is run before connecting to a local server
+    auto_start: bool,
+}
+
+impl Default for AsyncADBServer {
+    fn default() -> Self {
+        Self {
+            socket_addr: None,
+            adb_path: None,
+            auto_start: true,
+        }
+    }
+}
+
+impl AsyncADBServer {
+    /// Instantiates a new [`AsyncADBServer`]
+    #[must_use]
+    pub const fn new(address: SocketAddrV4) -> Self {
+        Self {
+            socket_addr: Some(address),
+            adb_path: None,
+            auto_start: true,
+        }
+    }
+
+    /// Instantiates a new [`AsyncADBServer`] with a custom adb path
+    #[must_use]
+    pub const fn new_from_path(address: SocketAddrV4, adb_path: Option<String>) -> Self {
+        Self {
+            socket_addr: Some(address),
+            adb_path,
+            auto_start: true,
+        }
+    }
+
+    /// Get the server address
+    #[must_use]
+    pub const fn socket_addr(&self) -> Option<SocketAddrV4> {
+        self.socket_addr
+    }
+
+    /// Set whether `adb start-server` is run before connecting to a server listening on a local address. Defaults to `true`.
+    ///
+    /// Disable it when server is already running, or is not a real `adb` server, e.g. a fake one used in tests.
+    pub const fn set_auto_start(&mut self, auto_start: bool) {
+        self.auto_start = auto_start;
+    }
+
+    /// Start an instance of `adb-server`
+    pub async fn start(adb_path: Option<&str>) {
+        // ADB Server is local, we start it if not already running
+        let mut command = Command::new(adb_path.unwrap_or("adb"));
+        command.arg("start-server");
+
+        #[cfg(target_os = "windows")]
+        {
+            // Do not show a prompt on Windows
+            command.creation_flags(0x08000000);
+        }
+
+        match command.status().await {
+            Ok(_) => {}
+            Err(e) => log::error!("error while starting adb server: {e}"),
+        }
+    }
+
+    /// Connect to server, starting it first if it is local.
+    async fn connect(&self) -> Result<AsyncTCPServerTransport> {
+        let socket_addr = TCPServerTransport::new_or_default(self.socket_addr).get_socketaddr();
+        let ip = socket_addr.ip();
+        if self.auto_start && (ip.is_loopback() || ip.is_unspecified()) {
+            Self::start(self.adb_path.as_deref()).await;
+        }
+
+        AsyncTCPServerTransport::connect(socket_addr).await
+    }
+
+    /// Gets server's internal version number.
+    pub async fn version(&self) -> Result<AdbVersion> {
+        let version = self
+            .connect()
+            .await?
+            .proxy_connection(&ADBCommand::Host(ADBHostCommand::Version), true)
+            .await?;
+        AdbVersion::try_from(version)
+    }
+
+    /// Gets a list of connected devices.
+    pub async fn devices(&self) -> Result<Vec<DeviceShort>> {
+        let devices = self
+            .connect()
+            .await?
+            .proxy_connection(&ADBCommand::Host(ADBHostCommand::Devices), true)
+            .await?;
+
+        devices
+            .split(|x| x.eq(&b'\n'))
+            .take_while(|device| !device.is_empty())
+            .map(|device| DeviceShort::try_from(device.to_vec()))
+            .collect()
+    }
+
+    /// Gets an extended list of connected devices including the device paths in the state.
+    pub async fn devices_long(&self) -> Result<Vec<DeviceLong>> {
+        let devices_long = self
+            .connect()
+            .await?
+            .proxy_connection(&ADBCommand::Host(ADBHostCommand::DevicesLong), true)
+            .await?;
+
+        devices_long
+            .split(|x| x.eq(&b'\n'))
+            .take_while(|device| !device.is_empty())
+            .map(DeviceLong::try_from)
+            .collect()
+    }
+
+    /// Get a device, assuming that only this device is connected.
+    pub async fn get_device(&self) -> Result<AsyncADBServerDevice> {
+        let mut devices = self.devices().await?.into_iter();
+        match devices.next() {
+            Some(device) => match devices.next() {
+                Some(_) => Err(RustADBError::DeviceNotFound(
+                    "too many devices connected".to_string(),
+                )),
+                None => Ok(AsyncADBServerDevice::new(
+                    device.identifier,
+                    self.socket_addr,
+                )),
+            },
+            None => Err(RustADBError::DeviceNotFound(
+                "no device connected".to_string(),
+            )),
+        }
+    }
+
+    /// Get a device matching the given name, if existing.
+    /// - There is no device connected => Error
+    /// - There is a single device connected => Ok
+    /// - There are more than 1 device connected => Error
+    pub async fn get_device_by_name(&self, name: &str) -> Result<AsyncADBServerDevice> {
+        let nb_devices = self
+            .devices()
+            .await?
+            .into_iter()
+            .filter(|d| d.identifier.as_str() == name)
+            .count();
+        if nb_devices == 1 {
+            Ok(AsyncADBServerDevice::new(
+                name.to_string(),
+                self.socket_addr,
+            ))
+        } else {
+            Err(RustADBError::DeviceNotFound(format!(
+                "could not find device {name}"
+            )))
+        }
+    }
+
+    /// Get a device matching the given transport id (as returned by `adb devices -l`).
+    ///
+    /// Transport ids are reassigned on device reconnect or server restart, so callers should re-query rather than caching the id.
+    pub async fn get_device_by_transport_id(
+        &self,
+        transport_id: u32,
+    ) -> Result<AsyncADBServerDevice> {
+        let nb_devices = self
+            .devices_long()
+            .await?
+            .into_iter()
+            .filter(|d| d.transport_id == transport_id)
+            .count();
+        if nb_devices == 1 {
+            Ok(AsyncADBServerDevice::new_with_transport_id(
+                transport_id,
+                self.socket_addr,
+            ))
+        } else {
+            Err(RustADBError::DeviceNotFound(format!(
+                "could not find device with transport id {transport_id}"
+            )))
+        }
+    }
+
+    /// Connect device over tcp with address and port
+    pub async fn connect_device(&self, address: SocketAddrV4) -> Result<()> {
+        let response = self
+            .connect()
+            .await?
+            .proxy_connection(&ADBCommand::Host(ADBHostCommand::Connect(address)), true)
+            .await?;
+        match String::from_utf8(response) {
+            Ok(s) if s.starts_with("connected to") => Ok(()),
+            Ok(s) if s.starts_with("already connected to") => Ok(()),
+            Ok(s) => Err(RustADBError::ADBRequestFailed(s)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Disconnect device connected over tcp with address and port
+    pub async fn disconnect_device(&self, address: SocketAddrV4) -> Result<()> {
+        let response = self
+            .connect()
+            .await?
+            .proxy_connection(&ADBCommand::Host(ADBHostCommand::Disconnect(address)), true)
+            .await?;
+        match String::from_utf8(response) {
+            Ok(s) if s.starts_with("disconnected") => Ok(()),
+            Ok(s) => Err(RustADBError::ADBRequestFailed(s)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Asks the ADB server to quit immediately.
+    pub async fn kill(&self) -> Result<()> {
+        self.connect()
+            .await?
+            .proxy_connection(&ADBCommand::Host(ADBHostCommand::Kill), false)
+            .await
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,89 @@
+use std::net::SocketAddrV4;
+use std::str::FromStr;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::models::{ADBCommand, AdbRequestStatus};
+use crate::{Result, RustADBError};
+
+/// Asynchronous connection to an ADB server, counterpart of [`TCPServerTransport`](super::TCPServerTransport).
+#[derive(Debug)]
+pub(crate) struct AsyncTCPServerTransport {
+    tcp_stream: TcpStream,
+}
+
+impl AsyncTCPServerTransport {
+    /// Connect to ADB server listening on `socket_addr`.
+    pub(crate) async fn connect(socket_addr: SocketAddrV4) -> Result<Self> {
+        let tcp_stream = TcpStream::connect(socket_addr).await?;
+        tcp_stream.set_nodelay(true)?;
+        log::trace!("Successfully connected to {socket_addr}");
+
+        Ok(Self { tcp_stream })
+    }
+
+    /// Send `adb_command`, and read its hexadecimal length prefixed response if `with_response` is set.
+    pub(crate) async fn proxy_connection(
+        &mut self,
+        adb_command: &ADBCommand,
+        with_response: bool,
+    ) -> Result<Vec<u8>> {
+        self.send_adb_request(adb_command).await?;
+
+        if with_response {
+            self.read_hex_body().await
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// Send the given [`ADBCommand`] to ADB server, and checks that the request has been taken in consideration.
+    /// If an error occurred, a [`RustADBError`] is returned with the response error string.
+    pub(crate) async fn send_adb_request(&mut self, command: &ADBCommand) -> Result<()> {
+        let adb_command_string = command.to_string();
+        let adb_request = format!("{:04x}{}", adb_command_string.len(), adb_command_string);
+
+        self.tcp_stream.write_all(adb_request.as_bytes()).await?;
+
+        self.read_adb_response().await
+    }
+
+    /// Read a response from ADB server
+    async fn read_adb_response(&mut self) -> Result<()> {
+        // Reads returned status code from ADB server
+        let mut request_status = [0; 4];
+        self.tcp_stream.read_exact(&mut request_status).await?;
+
+        match AdbRequestStatus::from_str(std::str::from_utf8(request_status.as_ref())?)? {
+            AdbRequestStatus::Fail => {
+                // We can keep reading to get further details
+                let body = self.read_hex_body().await?;
+                Err(RustADBError::ADBRequestFailed(String::from_utf8(body)?))
+            }
+            AdbRequestStatus::Okay => Ok(()),
+        }
+    }
+
+    /// Read a body prefixed by its length, as 4 hexadecimal digits.
+    async fn read_hex_body(&mut self) -> Result<Vec<u8>> {
+        let mut length_buffer = [0; 4];
+        self.tcp_stream.read_exact(&mut length_buffer).await?;
+        let length = u32::from_str_radix(std::str::from_utf8(&length_buffer)?, 16)?;
+
+        let mut body = vec![
+            0;
+            length
+                .try_into()
+                .map_err(|_| RustADBError::ConversionError)?
+        ];
+        self.tcp_stream.read_exact(&mut body).await?;
+
+        Ok(body)
+    }
+
+    /// Give back underlying connection, e.g. once switched to a device service.
+    pub(crate) fn into_stream(self) -> TcpStream {
+        self.tcp_stream
+    }
+}
@@ -53,7 +53,10 @@ impl ADBServer {
                 Some(_) => Err(RustADBError::DeviceNotFound(
                     "too many devices connected".to_string(),
                 )),
-                None => Ok(ADBServerDevice::new(device.identifier, self.socket_addr)),
+                None => Ok(self.with_default_timeouts(ADBServerDevice::new(
+                    device.identifier,
+                    self.socket_addr,
+                ))),
             },
             None => Err(RustADBError::DeviceNotFound(
                 "no device connected".to_string(),
@@ -72,7 +75,12 @@ impl ADBServer {
             .filter(|d| d.identifier.as_str() == name)
             .count();
         if nb_devices == 1 {
-            Ok(ADBServerDevice::new(name.to_string(), self.socket_addr))
+            Ok(
+                self.with_default_timeouts(ADBServerDevice::new(
+                    name.to_string(),
+                    self.socket_addr,
+                )),
+            )
         } else {
             Err(RustADBError::DeviceNotFound(format!(
                 "could not find device {name}"
@@ -92,10 +100,12 @@ impl ADBServer {
             .filter(|d| d.transport_id == transport_id)
             .count();
         if nb_devices == 1 {
-            Ok(ADBServerDevice::new_with_transport_id(
-                transport_id,
-                self.socket_addr,
-            ))
+            Ok(
+                self.with_default_timeouts(ADBServerDevice::new_with_transport_id(
+                    transport_id,
+                    self.socket_addr,
+                )),
+            )
         } else {
             Err(RustADBError::DeviceNotFound(format!(
                 "could not find device with transport id {transport_id}"
@@ -103,6 +113,12 @@ impl ADBServer {
         }
     }
 
+    /// Make `device` inherit timeouts set on this server.
+    const fn with_default_timeouts(&self, mut device: ADBServerDevice) -> ADBServerDevice {
+        device.set_timeouts(self.timeouts);
+        device
+    }
+
     /// Tracks new devices showing up.
     pub fn track_devices(&mut self, callback: impl Fn(DeviceShort) -> Result<()>) -> Result<()> {
         self.connect()?
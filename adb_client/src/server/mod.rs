@@ -1,10 +1,18 @@
 #![doc = include_str!("./README.md")]
 
 mod adb_server;
+#[cfg(feature = "tokio")]
+mod async_adb_server;
+#[cfg(feature = "tokio")]
+pub(crate) mod async_tcp_server_transport;
 mod commands;
 mod models;
 mod tcp_server_transport;
 
 pub use adb_server::ADBServer;
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub use async_adb_server::AsyncADBServer;
 pub use models::*;
+pub(crate) use tcp_server_transport::ServerConnection;
 pub use tcp_server_transport::TCPServerTransport;
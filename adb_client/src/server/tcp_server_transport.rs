@@ -1,11 +1,11 @@
 use std::io::{Error, ErrorKind, Read, Write};
-use std::net::{Ipv4Addr, SocketAddrV4, TcpStream};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream};
 use std::str::FromStr;
 
-use byteorder::{ByteOrder, LittleEndian};
-
 use crate::ADBTransport;
-use crate::models::{ADBCommand, AdbRequestStatus, SyncCommand};
+use crate::models::{
+    ADBCommand, AdbRequestStatus, Deadline, TimeoutPhase, Timeouts, timeout_error,
+};
 use crate::{Result, RustADBError};
 
 const DEFAULT_SERVER_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
@@ -16,6 +16,39 @@ const DEFAULT_SERVER_PORT: u16 = 5037;
 pub struct TCPServerTransport {
     socket_addr: SocketAddrV4,
     tcp_stream: Option<TcpStream>,
+    timeouts: Timeouts,
+    deadline: Deadline,
+}
+
+/// Connection to ADB server, reads being bounded by timeouts of the running request.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ServerConnection<'a> {
+    stream: &'a TcpStream,
+    deadline: &'a Deadline,
+}
+
+impl ServerConnection<'_> {
+    /// Clone underlying stream, e.g. to read it from another thread. Operation deadline does not apply to clones.
+    pub(crate) fn try_clone(&self) -> std::io::Result<TcpStream> {
+        self.stream.try_clone()
+    }
+}
+
+impl Read for ServerConnection<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut stream = self.stream;
+        self.deadline.read(self.stream, || stream.read(buf))
+    }
+}
+
+impl Write for ServerConnection<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
 }
 
 impl Default for TCPServerTransport {
@@ -31,6 +64,8 @@ impl TCPServerTransport {
         Self {
             socket_addr,
             tcp_stream: None,
+            timeouts: Timeouts::DEFAULT,
+            deadline: Deadline::UNBOUNDED,
         }
     }
 
@@ -46,6 +81,15 @@ impl TCPServerTransport {
         self.socket_addr
     }
 
+    /// Set timeouts applied from next connection on.
+    pub(crate) const fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    pub(crate) const fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
     pub(crate) fn proxy_connection(
         &self,
         adb_command: &ADBCommand,
@@ -71,9 +115,13 @@ impl TCPServerTransport {
         }
     }
 
-    pub(crate) fn get_raw_connection(&self) -> Result<&TcpStream> {
+    pub(crate) fn get_raw_connection(&self) -> Result<ServerConnection<'_>> {
         self.tcp_stream
             .as_ref()
+            .map(|stream| ServerConnection {
+                stream,
+                deadline: &self.deadline,
+            })
             .ok_or(RustADBError::IOError(Error::new(
                 ErrorKind::NotConnected,
                 "not connected",
@@ -89,21 +137,6 @@ impl TCPServerTransport {
         )?)
     }
 
-    /// Send the given [`SyncCommand`] to ADB server, and checks that the request has been taken in consideration.
-    pub(crate) fn send_sync_request(&self, command: &SyncCommand) -> Result<()> {
-        // First 4 bytes are the name of the command we want to send
-        // (e.g. "SEND", "RECV", "STAT", "LIST")
-        Ok(self
-            .get_raw_connection()?
-            .write_all(command.to_string().as_bytes())?)
-    }
-
-    /// Gets the body length from a `LittleEndian` value
-    pub(crate) fn get_body_length(&self) -> Result<u32> {
-        let length_buffer = self.read_body_length()?;
-        Ok(LittleEndian::read_u32(&length_buffer))
-    }
-
     /// Read 4 bytes representing body length
     fn read_body_length(&self) -> Result<[u8; 4]> {
         let mut length_buffer = [0; 4];
@@ -167,8 +200,18 @@ impl ADBTransport for TCPServerTransport {
             // Ignoring underlying error, we will recreate a new connection
             let _ = previous.shutdown(std::net::Shutdown::Both);
         }
-        let tcp_stream = TcpStream::connect(self.socket_addr)?;
+        let tcp_stream = match self.timeouts.connect_value() {
+            Some(timeout) => TcpStream::connect_timeout(&SocketAddr::V4(self.socket_addr), timeout)
+                .map_err(|e| match e.kind() {
+                    ErrorKind::TimedOut => timeout_error(TimeoutPhase::Connect),
+                    _ => e,
+                })?,
+            None => TcpStream::connect(self.socket_addr)?,
+        };
         tcp_stream.set_nodelay(true)?;
+        // Each request to server runs on its own connection, its deadline starting now
+        self.deadline = Deadline::start(&self.timeouts);
+        tcp_stream.set_read_timeout(self.deadline.read_timeout())?;
         self.tcp_stream = Some(tcp_stream);
         log::trace!("Successfully connected to {}", self.socket_addr);
 
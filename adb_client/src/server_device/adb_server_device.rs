@@ -1,6 +1,6 @@
 use crate::{
     ADBTransport, Result,
-    models::{ADBCommand, ADBHostCommand},
+    models::{ADBCommand, ADBHostCommand, SyncCompression, Timeouts},
     server::TCPServerTransport,
 };
 use std::net::SocketAddrV4;
@@ -21,6 +21,8 @@ pub struct ADBServerDevice {
     pub transport_id: Option<u32>,
     /// Internal [`TCPServerTransport`]
     pub(crate) transport: TCPServerTransport,
+    /// Compression used by file transfers
+    sync_compression: SyncCompression,
 }
 
 impl ADBServerDevice {
@@ -33,6 +35,7 @@ impl ADBServerDevice {
             identifier: Some(identifier),
             transport_id: None,
             transport,
+            sync_compression: SyncCompression::default(),
         }
     }
 
@@ -49,6 +52,7 @@ impl ADBServerDevice {
             identifier: None,
             transport_id: Some(transport_id),
             transport,
+            sync_compression: SyncCompression::default(),
         }
     }
 
@@ -61,9 +65,41 @@ impl ADBServerDevice {
             identifier: None,
             transport_id: None,
             transport,
+            sync_compression: SyncCompression::default(),
         }
     }
 
+    /// Set compression used by file transfers, when device supports sync version 2.
+    ///
+    /// Defaults to [`SyncCompression::Any`], using the best algorithm supported by both device and this build.
+    pub const fn set_sync_compression(&mut self, compression: SyncCompression) {
+        self.sync_compression = compression;
+    }
+
+    /// Set default timeouts applied to each operation run on this device.
+    ///
+    /// Connection to server defaults to a 10 seconds timeout, operations waiting as long as needed. See [`Timeouts`].
+    pub const fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.transport.set_timeouts(timeouts);
+    }
+
+    /// Run `operation` on this device using `timeouts` instead of its defaults, restored afterwards.
+    pub fn with_timeouts<R>(
+        &mut self,
+        timeouts: Timeouts,
+        operation: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        let defaults = self.transport.timeouts();
+        self.transport.set_timeouts(timeouts);
+        let result = operation(self);
+        self.transport.set_timeouts(defaults);
+        result
+    }
+
+    pub(crate) const fn sync_compression(&self) -> SyncCompression {
+        self.sync_compression
+    }
+
     /// Connect to underlying transport
     pub(crate) fn connect(&mut self) -> Result<&mut TCPServerTransport> {
         self.transport.connect()?;
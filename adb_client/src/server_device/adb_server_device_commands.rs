@@ -1,42 +1,19 @@
-use std::{
-    io::{ErrorKind, Read, Write},
-    path::Path,
-};
+use std::io::{ErrorKind, Read, Write};
 
 use byteorder::ReadBytesExt;
 
 use crate::{
     ADBDeviceExt, ADBListItemType, Result, RustADBError,
-    models::{ADBCommand, ADBLocalCommand, AdbStatResponse, HostFeatures, RemountInfo},
+    models::{
+        ADBCommand, ADBLocalCommand, AdbStatResponse, CancellationToken, HostFeatures,
+        InstallOptions, ProgressObserver, PushOptions, RemountInfo, ShellChannel,
+    },
 };
 
 use super::ADBServerDevice;
 
 const BUFFER_SIZE: usize = 65535;
 
-#[derive(Eq, PartialEq)]
-enum ShellChannel {
-    Stdout,
-    Stderr,
-    ExitStatus,
-}
-
-impl TryFrom<u8> for ShellChannel {
-    type Error = std::io::Error;
-
-    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
-        match value {
-            1 => Ok(Self::Stdout),
-            2 => Ok(Self::Stderr),
-            3 => Ok(Self::ExitStatus),
-            _ => Err(std::io::Error::new(
-                ErrorKind::InvalidData,
-                "Invalid channel",
-            )),
-        }
-    }
-}
-
 impl ADBDeviceExt for ADBServerDevice {
     fn shell_command(
         &mut self,
@@ -76,8 +53,28 @@ impl ADBDeviceExt for ADBServerDevice {
         )
     }
 
-    fn shell(&mut self, reader: &mut dyn Read, writer: Box<dyn Write + Send>) -> Result<()> {
-        self.bidirectional_session(&ADBCommand::Local(ADBLocalCommand::Shell), reader, writer)
+    fn exec_with_input(&mut self, command: &str, input: &mut dyn Read) -> Result<Vec<u8>> {
+        self.set_serial_transport()?;
+        self.transport
+            .send_adb_request(&ADBCommand::Local(ADBLocalCommand::Exec(
+                command.to_owned(),
+            )))?;
+
+        let mut raw_connection = self.transport.get_raw_connection()?;
+        std::io::copy(input, &mut raw_connection)?;
+
+        let mut output = Vec::new();
+        raw_connection.read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    fn shell(
+        &mut self,
+        reader: &mut dyn Read,
+        writer: Box<dyn Write + Send>,
+    ) -> Result<Option<u8>> {
+        self.bidirectional_session(&ADBCommand::Local(ADBLocalCommand::Shell), reader, writer)?;
+        Ok(None)
     }
 
     fn pull(&mut self, source: &dyn AsRef<str>, mut output: &mut dyn Write) -> Result<()> {
@@ -92,12 +89,24 @@ impl ADBDeviceExt for ADBServerDevice {
         self.root()
     }
 
-    fn push(&mut self, stream: &mut dyn Read, path: &dyn AsRef<str>) -> Result<()> {
-        self.push(stream, path)
+    fn push_with_options(
+        &mut self,
+        stream: &mut dyn Read,
+        path: &dyn AsRef<str>,
+        options: PushOptions,
+    ) -> Result<()> {
+        self.push_with_options(stream, path, options)
     }
 
-    fn install(&mut self, apk_path: &dyn AsRef<Path>, user: Option<&str>) -> Result<()> {
-        self.install(apk_path, user)
+    fn install_from_reader_with_progress(
+        &mut self,
+        apk: &mut dyn Read,
+        size: u64,
+        options: &InstallOptions,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        self.install_from_reader_with_progress(apk, size, options, observer, cancellation)
     }
 
     fn uninstall(&mut self, package: &dyn AsRef<str>, user: Option<&str>) -> Result<()> {
@@ -193,7 +202,8 @@ impl ADBServerDevice {
             let mut pckt_metadata = vec![0; 5];
             if let Err(err) = input.read_exact(&mut pckt_metadata) {
                 match err.kind() {
-                    ErrorKind::UnexpectedEof | ErrorKind::BrokenPipe => return Ok(None),
+                    // Stream ends once command exited, after its exit status has been sent
+                    ErrorKind::UnexpectedEof | ErrorKind::BrokenPipe => return Ok(exit),
                     _ => return Err(RustADBError::IOError(err)),
                 }
             }
@@ -233,7 +243,7 @@ impl ADBServerDevice {
                                             writer.write_all(&buffer[..size])?;
                                         }
                                     }
-                                    ShellChannel::ExitStatus => {
+                                    _ => {
                                         // unreachable
                                     }
                                 }
@@ -261,6 +271,11 @@ impl ADBServerDevice {
                         },
                     }
                 }
+                c => {
+                    return Err(RustADBError::ADBShellV2ParseError(format!(
+                        "Unexpected packet received on channel {c:?}"
+                    )));
+                }
             }
         }
     }
@@ -0,0 +1,100 @@
+use std::net::SocketAddrV4;
+
+use tokio::net::TcpStream;
+
+use crate::models::{ADBCommand, ADBHostCommand, ADBLocalCommand, HostFeatures};
+use crate::server::TCPServerTransport;
+use crate::server::async_tcp_server_transport::AsyncTCPServerTransport;
+use crate::{AsyncADBDeviceExt, Result};
+
+/// Represents a device connected to the ADB server, from an asynchronous runtime.
+///
+/// Asynchronous counterpart of [`ADBServerDevice`](super::ADBServerDevice), features being provided by [`AsyncADBDeviceExt`].
+/// Each service is opened on its own connection to the server.
+#[derive(Debug, Clone)]
+pub struct AsyncADBServerDevice {
+    /// Unique device identifier.
+    pub identifier: Option<String>,
+    /// Optional transport id assigned by the ADB server.
+    ///
+    /// When set, this takes precedence over `identifier` for transport selection.
+    /// See [`ADBServerDevice::transport_id`](super::ADBServerDevice::transport_id).
+    pub transport_id: Option<u32>,
+    /// Address of the ADB server
+    server_addr: SocketAddrV4,
+}
+
+impl AsyncADBServerDevice {
+    /// Instantiates a new [`AsyncADBServerDevice`], knowing its ADB identifier (as returned by `adb devices` command).
+    #[must_use]
+    pub fn new(identifier: String, server_addr: Option<SocketAddrV4>) -> Self {
+        Self {
+            identifier: Some(identifier),
+            transport_id: None,
+            server_addr: TCPServerTransport::new_or_default(server_addr).get_socketaddr(),
+        }
+    }
+
+    /// Instantiates a new [`AsyncADBServerDevice`] selected by its transport id (as returned by `adb devices -l`).
+    #[must_use]
+    pub fn new_with_transport_id(transport_id: u32, server_addr: Option<SocketAddrV4>) -> Self {
+        Self {
+            identifier: None,
+            transport_id: Some(transport_id),
+            server_addr: TCPServerTransport::new_or_default(server_addr).get_socketaddr(),
+        }
+    }
+
+    /// Instantiates a new [`AsyncADBServerDevice`], assuming only one is currently connected.
+    #[must_use]
+    pub fn autodetect(server_addr: Option<SocketAddrV4>) -> Self {
+        Self {
+            identifier: None,
+            transport_id: None,
+            server_addr: TCPServerTransport::new_or_default(server_addr).get_socketaddr(),
+        }
+    }
+
+    /// Open a new connection to the server, switched to the configured device transport.
+    async fn connect_transport(&self) -> Result<AsyncTCPServerTransport> {
+        let cmd = if let Some(id) = self.transport_id {
+            ADBHostCommand::TransportId(id)
+        } else if let Some(serial) = self.identifier.clone() {
+            ADBHostCommand::TransportSerial(serial)
+        } else {
+            ADBHostCommand::TransportAny
+        };
+
+        let mut transport = AsyncTCPServerTransport::connect(self.server_addr).await?;
+        transport.send_adb_request(&ADBCommand::Host(cmd)).await?;
+        Ok(transport)
+    }
+}
+
+impl AsyncADBDeviceExt for AsyncADBServerDevice {
+    type Stream = TcpStream;
+
+    async fn open_service(&mut self, service: &str) -> Result<TcpStream> {
+        let mut transport = self.connect_transport().await?;
+        transport
+            .send_adb_request(&ADBCommand::Local(ADBLocalCommand::Service(
+                service.to_string(),
+            )))
+            .await?;
+
+        Ok(transport.into_stream())
+    }
+
+    async fn host_features(&mut self) -> Result<Vec<HostFeatures>> {
+        let features = self
+            .connect_transport()
+            .await?
+            .proxy_connection(&ADBCommand::Host(ADBHostCommand::HostFeatures), true)
+            .await?;
+
+        Ok(features
+            .split(|x| x.eq(&b','))
+            .filter_map(|v| HostFeatures::try_from(v).ok())
+            .collect())
+    }
+}
@@ -1,47 +1,67 @@
-use std::{fs::File, io::Read, path::Path};
+use std::{io::Read, path::Path};
 
 use crate::{
-    Result,
-    models::{ADBCommand, ADBLocalCommand},
+    ADBDeviceExt, ADBTransport, Result,
+    models::{
+        ADBCommand, ADBLocalCommand, CancellationToken, HostFeatures, InstallOptions,
+        ProgressObserver, ProgressReader,
+    },
+    package_install::{check_install_output, install_with_pm},
     server_device::ADBServerDevice,
-    utils::check_extension_is_apk,
 };
 
 impl ADBServerDevice {
     /// Install an APK on device
     pub fn install<P: AsRef<Path>>(&mut self, apk_path: P, user: Option<&str>) -> Result<()> {
-        let mut apk_file = File::open(&apk_path)?;
+        ADBDeviceExt::install(self, &apk_path, user)
+    }
 
-        check_extension_is_apk(&apk_path)?;
+    /// Install an APK on device, reporting upload progress to `observer` and aborting if `cancellation` is cancelled.
+    pub fn install_with_progress<P: AsRef<Path>>(
+        &mut self,
+        apk_path: P,
+        user: Option<&str>,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        ADBDeviceExt::install_with_progress(self, &apk_path, user, observer, cancellation)
+    }
 
-        let file_size = apk_file.metadata()?.len();
+    pub(crate) fn install_from_reader_with_progress(
+        &mut self,
+        apk: &mut dyn Read,
+        size: u64,
+        options: &InstallOptions,
+        observer: &mut dyn ProgressObserver,
+        cancellation: &CancellationToken,
+    ) -> Result<()> {
+        if !self
+            .host_features()
+            .is_ok_and(|features| features.contains(&HostFeatures::Cmd))
+        {
+            return install_with_pm(self, apk, size, options, observer, cancellation);
+        }
 
         self.set_serial_transport()?;
 
         self.transport
             .send_adb_request(&ADBCommand::Local(ADBLocalCommand::Install(
-                file_size,
-                user.map(ToString::to_string),
+                size,
+                options.clone(),
             )))?;
 
         let mut raw_connection = self.transport.get_raw_connection()?;
 
-        std::io::copy(&mut apk_file, &mut raw_connection)?;
-
-        let mut data = [0; 1024];
-        let read_amount = self.transport.get_raw_connection()?.read(&mut data)?;
-
-        match &data[0..read_amount] {
-            b"Success\n" => {
-                log::info!(
-                    "APK file {} successfully installed",
-                    apk_path.as_ref().display()
-                );
-                Ok(())
-            }
-            d => Err(crate::RustADBError::ADBRequestFailed(String::from_utf8(
-                d.to_vec(),
-            )?)),
+        let mut reader = ProgressReader::new(apk, Some(size), observer, cancellation);
+        if let Err(e) = std::io::copy(&mut reader, &mut raw_connection) {
+            // Closing connection aborts installation on device side
+            let _ = self.transport.disconnect();
+            return cancellation.map_result(Err(e.into()));
         }
+
+        // Status may be split over several reads, server closes connection once done
+        let mut output = Vec::new();
+        raw_connection.read_to_end(&mut output)?;
+        check_install_output(&output)
     }
 }
@@ -11,6 +11,7 @@ mod reverse;
 mod root;
 mod send;
 mod stat;
+mod sync;
 mod tcpip;
 mod transport;
 mod uninstall;
@@ -0,0 +1,37 @@
+use crate::{
+    ADBTransport, Result,
+    file_sync::ADBSyncClient,
+    models::{ADBCommand, ADBLocalCommand},
+    server::ServerConnection,
+    server_device::ADBServerDevice,
+};
+
+impl ADBServerDevice {
+    /// Run `operation` after switching device connection to SYNC mode, ending it properly afterwards.
+    pub(crate) fn with_sync_client<R>(
+        &mut self,
+        operation: impl FnOnce(&mut ADBSyncClient<ServerConnection<'_>>) -> Result<R>,
+    ) -> Result<R> {
+        // Sync v2 requests are only used if both server and device support them
+        let features = self.host_features().unwrap_or_default();
+
+        self.set_serial_transport()?;
+
+        // Set device in SYNC mode
+        self.transport
+            .send_adb_request(&ADBCommand::Local(ADBLocalCommand::Sync))?;
+
+        let mut client = ADBSyncClient::new(self.transport.get_raw_connection()?, &features);
+        match operation(&mut client) {
+            Ok(output) => {
+                client.quit()?;
+                Ok(output)
+            }
+            Err(e) => {
+                // Connection may be left in the middle of a transfer, closing it makes device discard it
+                let _ = self.transport.disconnect();
+                Err(e)
+            }
+        }
+    }
+}
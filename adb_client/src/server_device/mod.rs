@@ -2,6 +2,11 @@
 
 mod adb_server_device;
 mod adb_server_device_commands;
+#[cfg(feature = "tokio")]
+mod async_adb_server_device;
 mod commands;
 
 pub use adb_server_device::ADBServerDevice;
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub use async_adb_server_device::AsyncADBServerDevice;
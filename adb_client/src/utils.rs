@@ -20,16 +20,30 @@ pub fn check_extension_is_apk<P: AsRef<Path>>(path: P) -> Result<()> {
     Ok(())
 }
 
-/// Get the default path to the ADB key file.
+/// Get the default directory holding ADB configuration, such as keys.
 /// First checks for the presence of the environment variable `ANDROID_USER_HOME`, defaulting to the user's home directory.
-pub fn get_default_adb_key_path() -> Result<PathBuf> {
+fn get_default_android_directory() -> Result<PathBuf> {
     let android_user_home = std::env::var("ANDROID_USER_HOME")
         .ok()
         .map(|android_user_home| PathBuf::from(android_user_home).join("android"));
     let default_dot_android = std::env::home_dir().map(|home| home.join(".android"));
 
-    Ok(android_user_home
+    android_user_home
         .or(default_dot_android)
-        .ok_or(RustADBError::NoHomeDirectory)?
-        .join("adbkey"))
+        .ok_or(RustADBError::NoHomeDirectory)
+}
+
+/// Get the default path to the ADB key file.
+pub fn get_default_adb_key_path() -> Result<PathBuf> {
+    Ok(get_default_android_directory()?.join("adbkey"))
+}
+
+/// Get the default path to the file storing certificates of devices reached over TLS.
+pub fn get_default_known_devices_path() -> Result<PathBuf> {
+    Ok(get_default_android_directory()?.join("adb_client_known_devices"))
+}
+
+/// Quote `argument` so that it is passed as a single word to device shell.
+pub fn shell_quote(argument: &str) -> String {
+    format!("'{}'", argument.replace('\'', r"'\''"))
 }
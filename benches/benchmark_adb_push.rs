@@ -1,4 +1,6 @@
 use adb_client::server::ADBServer;
+#[cfg(feature = "usb")]
+use adb_client::{ADBDeviceExt, usb::ADBUSBDevice};
 use anyhow::Result;
 use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
 use rand::{RngExt, rng};
@@ -38,6 +40,20 @@ fn bench_adb_client_push() -> Result<()> {
     Ok(device.push(f, REMOTE_TEST_FILE_PATH)?)
 }
 
+/// Use `adb_client` crate to push a file on a device directly connected over USB
+#[cfg(feature = "usb")]
+fn bench_adb_client_usb_push(device: &mut ADBUSBDevice) -> Result<()> {
+    let mut f = File::open(LOCAL_TEST_FILE_PATH)?;
+    Ok(device.push(&mut f, &REMOTE_TEST_FILE_PATH)?)
+}
+
+/// Stop `adb` server, as it would otherwise hold the USB device
+#[cfg(feature = "usb")]
+fn kill_adb_server() -> Result<()> {
+    Command::new("adb").arg("kill-server").output()?;
+    Ok(())
+}
+
 /// Use standard `adb` command ti push a file on device
 fn bench_adb_push_command() -> Result<()> {
     let output = Command::new("adb")
@@ -52,7 +68,8 @@ fn bench_adb_push_command() -> Result<()> {
     Ok(())
 }
 
-/// benchmarking `adb push INPUT DEST` and `adb_client` `ADBServerDevice.push(INPUT, DEST)`
+/// benchmarking `adb push INPUT DEST` and `adb_client` `ADBServerDevice.push(INPUT, DEST)`,
+/// along with `ADBUSBDevice.push(INPUT, DEST)` when `usb` feature is enabled
 fn benchmark_adb_push(c: &mut Criterion) {
     for (file_size, sample_size) in [
         (10 * 1024 * 1024, 100),  // 10MB -> 100 iterations
@@ -78,6 +95,18 @@ fn benchmark_adb_push(c: &mut Criterion) {
             });
         });
 
+        #[cfg(feature = "usb")]
+        {
+            kill_adb_server().expect("Cannot stop adb server");
+            let mut device = ADBUSBDevice::autodetect().expect("Cannot find USB device");
+            group.bench_function(BenchmarkId::new("adb_client", "usb_push"), |b| {
+                b.iter(|| {
+                    bench_adb_client_usb_push(&mut device)
+                        .expect("Error while benchmarking adb_client USB push");
+                });
+            });
+        }
+
         group.finish();
     }
 }